@@ -1,5 +1,7 @@
 use criterion::{Criterion, criterion_main, criterion_group};
 use ray_tracer::{parse_scene, render, default_dims};
+use ray_tracer::scene::generators::{random_spheres, RandomSceneConfig};
+use std::sync::Arc;
 
 fn bench_sphere(c: &mut Criterion) {
     let dimensions = default_dims();
@@ -9,9 +11,23 @@ fn bench_sphere(c: &mut Criterion) {
     let (scene, camera) = parse_scene(scene_path, dimensions).unwrap();
 
     c.bench_function("spheres", |b| b.iter(|| 
-        render(scene.clone(), camera.clone(), dimensions, samples, max_depth)
+        render(scene.clone(), camera, dimensions, samples, max_depth)
     ));
 }
 
-criterion_group!(benches, bench_sphere);
-criterion_main!(benches);
\ No newline at end of file
+// About 500 objects, small so each iteration is quick.
+fn bench_random_spheres(c: &mut Criterion) {
+    let dimensions = (160, 90);
+    let (scene, camera) = random_spheres(RandomSceneConfig { seed: 1, grid_extent: 11, dimensions, ..Default::default() });
+    let scene = Arc::new(scene);
+
+    let mut group = c.benchmark_group("random spheres");
+    group.sample_size(10);
+    group.bench_function("500 objects", |b| b.iter(||
+        render(scene.clone(), camera, dimensions, 1, 10)
+    ));
+    group.finish();
+}
+
+criterion_group!(benches, bench_sphere, bench_random_spheres);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+use std::sync::Arc;
+use ray_tracer::*;
+use ray_tracer::scene::generators::{random_spheres, RandomSceneConfig};
+
+fn main() {
+    let dimensions = (1920, 1080);
+    let (scene, camera) = random_spheres(RandomSceneConfig { seed: 1, dimensions, ..Default::default() });
+    let image = render(Arc::new(scene), camera, dimensions, 100, 50);
+    write_to_file("renders/random_spheres", image, OutputFormat::PNG, dimensions).unwrap();
+}
@@ -0,0 +1,107 @@
+use std::fmt::Debug;
+
+use crate::{Point3, Vec3};
+use crate::noise::Perlin;
+
+// Tilts surface normals to fake small bumps without changing the geometry.
+pub trait NormalMap: Send + Sync + Debug {
+
+    // Takes a point in object space and the world normal there, and returns
+    // the bumped normal. Materials normalise and keep it facing the eye.
+    fn perturb(&self, point: &Point3, normal: &Vec3) -> Vec3;
+}
+
+// Tilts the normal away from the slope, the part of it along the surface.
+fn tilt(normal: &Vec3, slope: Vec3) -> Vec3 {
+    normal - (slope - normal * normal.dot(&slope))
+}
+
+// Ripples along each axis, like orange peel, or brushed metal when scaled
+// flat along the brushing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waves {
+    pub amplitude: f64,
+    pub frequency: f64,
+}
+
+impl Waves {
+    pub fn new(amplitude: f64, frequency: f64) -> Self {
+        Self { amplitude, frequency }
+    }
+}
+
+impl NormalMap for Waves {
+    // Slope of amplitude / frequency * (sin(fx) + sin(fy) + sin(fz)).
+    fn perturb(&self, point: &Point3, normal: &Vec3) -> Vec3 {
+        let slope = point.coords.map(|v| (v * self.frequency).cos()) * self.amplitude;
+        tilt(normal, slope)
+    }
+}
+
+// Irregular bumps from Perlin noise, scale times smaller than object space.
+#[derive(Debug, Clone)]
+pub struct NoiseBump {
+    pub amplitude: f64,
+    pub scale:     f64,
+    perlin:        Perlin,
+}
+
+impl NoiseBump {
+    pub fn new(amplitude: f64, scale: f64, seed: u64) -> Self {
+        Self { amplitude, scale, perlin: Perlin::new(seed) }
+    }
+}
+
+impl NormalMap for NoiseBump {
+    fn perturb(&self, point: &Point3, normal: &Vec3) -> Vec3 {
+        let slope = self.perlin.vector(&(point * self.scale), 1, 0.5);
+        tilt(normal, slope * self.amplitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use rand::{SeedableRng, rngs::StdRng};
+    use crate::{Material, Object};
+    use crate::math::*;
+    use crate::object::Plane;
+    use crate::ray::Ray;
+
+    #[test]
+    fn test_bumped_normals() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for bump in [Arc::new(Waves::new(3.0, 10.0)) as Arc<dyn NormalMap>, Arc::new(NoiseBump::new(3.0, 4.0, 1))] {
+            let material = Material { normal_perturbation: Some(bump), ..Default::default() };
+            for _ in 0..500 {
+                let point = Point3::from(rand_vec_range(&mut rng, -5.0, 5.0));
+                let normal = rand_unit_vec(&mut rng).into_inner();
+                // Eye anywhere on the normal's side, down to grazing.
+                let mut eye = rand_unit_vec(&mut rng).into_inner();
+                if eye.dot(&normal) < 0.0 {
+                    eye = -eye;
+                }
+                let bumped = material.perturb_normal(&point, &normal, &eye);
+                assert!(fuzzy_eq_f64(bumped.norm(), 1.0));
+                assert!(bumped.dot(&eye) >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_waves_on_plane() {
+        let ray = |x: f64| Ray::new(Point3::new(x, 1.0, 0.0), -Vec3::y());
+        let flat = Plane::new(Material { normal_perturbation: Some(Arc::new(Waves::new(0.0, 10.0))), ..Default::default() });
+        let wavy = Plane::new(Material { normal_perturbation: Some(Arc::new(Waves::new(0.1, 10.0))), ..Default::default() });
+        assert!(fuzzy_eq_vec(&flat.hit(&ray(0.3), 0.0, f64::INFINITY).unwrap()[0].normal, &Vec3::y()));
+
+        // Tilted along x by the x ripple, hits and reflections follow.
+        let hit = &wavy.hit(&ray(0.0), 0.0, f64::INFINITY).unwrap()[0];
+        assert!(fuzzy_eq_vec(&hit.normal, &Vec3::new(-0.1, 1.0, -0.1).normalize()));
+        assert!(fuzzy_eq_vec(&hit.reflect, &reflect(&-Vec3::y(), &hit.normal)));
+        assert!(fuzzy_eq_f64(hit.t, 1.0));
+        let hit = &wavy.hit(&ray(std::f64::consts::PI / 10.0), 0.0, f64::INFINITY).unwrap()[0];
+        assert!(hit.normal.x > 0.0);
+    }
+}
@@ -1,8 +1,21 @@
+use std::f64::consts::PI;
 use anyhow::Context;
 use rand::{Rng, rngs::ThreadRng};
 use crate::transform::Transformable;
 use crate::{Point3, Vec3, Matrix4, Translation};
 use crate::ray::Ray;
+use crate::math::rand_in_unit_disk;
+
+// How pixels map to directions out of the camera.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // Equirectangular, all the way round and from straight down to straight
+    // up, ahead in the middle. Meant for a 2:1 image, field of view and
+    // aperture are ignored.
+    Panorama,
+}
 
 #[derive(Default, Debug, Clone, Copy)]
 pub struct Camera {
@@ -12,6 +25,12 @@ pub struct Camera {
     half_height:        f64,
     pixel_size:         f64,
     lens_radius:        f64,
+    // Distance to the plane that is sharp.
+    focus_dist:         f64,
+    // Times the shutter opens and closes, rays are cast between them.
+    shutter:            (f64, f64),
+    projection:         Projection,
+    dimensions:         (u32, u32),
 }
 
 impl Camera {
@@ -23,6 +42,7 @@ impl Camera {
         vert_fov:       f64, // Vertical field of view in degrees.
         dimensions:     (u32, u32),
         aperture:       f64,
+        focus_dist:     f64, // Distance to the plane in focus, usually look_at's.
     ) -> Self {
         
         let transform = Camera::view_matrix(look_from, look_at, view_up);
@@ -45,28 +65,77 @@ impl Camera {
             half_height,
             pixel_size: (half_width * 2.0) / dimensions.0 as f64,
             lens_radius: aperture / 2.0,
+            focus_dist,
+            shutter: (0.0, 0.0),
+            projection: Projection::Perspective,
+            dimensions,
         }
     }
 
-    pub fn get_ray(&self, x: u32, y: u32, rng: Option<&mut ThreadRng>) -> Ray {
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.shutter = (open, close);
+    }
+
+    pub fn focus_dist(&self) -> f64 {
+        self.focus_dist
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    // Ray through a uniformly random point in pixel (x, y) given an rng, for
+    // anti-aliasing, or through its centre without one.
+    pub fn get_ray(&self, x: u32, y: u32, mut rng: Option<&mut ThreadRng>) -> Ray {
         
-        let (a, b) = if let Some(rng) = rng {
-            (rng.gen::<f64>(), rng.gen::<f64>())
+        let (a, b, c) = if let Some(rng) = &mut rng {
+            (rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>())
         } else {
-            (0.5, 0.5)
+            (0.5, 0.5, 0.5)
         };
 
+        let time = self.shutter.0 + c * (self.shutter.1 - self.shutter.0);
+        if self.projection == Projection::Panorama {
+            return self.panorama_ray(x as f64 + a, y as f64 + b).with_time(time).as_primary();
+        }
+
         let offset_x = (x as f64 + a) * self.pixel_size;
         let offset_y = (y as f64 + b) * self.pixel_size;
 
         let world_x = self.half_width - offset_x;
         let world_y = self.half_height - offset_y;
 
-        let pixel = self.inverse.transform_point(&Point3::new(world_x, world_y, -1.0));
+        // Thin lens, rays from anywhere on it meet again on the focal plane.
+        // Without an rng they all start at its centre, as for a pinhole.
+        let lens = match rng {
+            Some(rng) if self.lens_radius > 0.0 => rand_in_unit_disk(rng) * self.lens_radius,
+            _ => Vec3::zeros(),
+        };
+        let focus = Point3::new(world_x, world_y, -1.0) * self.focus_dist;
+        let origin = self.inverse.transform_point(&Point3::from(lens));
+        let direction = (self.inverse.transform_point(&focus) - origin).normalize();
+        Ray::new(origin, direction).with_time(time).as_primary()
+    }
+
+    // Longitude across and latitude down the image, from the camera's centre.
+    // Left to right turns the same way as perspective images, and as
+    // environment backgrounds are read, so renders can be used as them.
+    fn panorama_ray(&self, x: f64, y: f64) -> Ray {
+        let longitude = (x / self.dimensions.0 as f64 - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - y / self.dimensions.1 as f64) * PI;
+        // Camera space, looking down -z with +x to the left.
+        let direction = Vec3::new(
+            -longitude.sin() * latitude.cos(),
+            latitude.sin(),
+            -longitude.cos() * latitude.cos(),
+        );
         let origin = self.inverse.transform_point(&Point3::origin());
-        let direction = (pixel - origin).normalize();
+        Ray::new(origin, self.inverse.transform_vector(&direction).normalize())
+    }
 
-        Ray::new(origin, direction)
+    // Ray through the centre of pixel (x, y).
+    pub fn ray_for_pixel(&self, x: u32, y: u32) -> Ray {
+        self.get_ray(x, y, None)
     }
 
     pub fn view_matrix(from: Point3, to: Point3, up: Vec3) -> Matrix4 {
@@ -131,7 +200,8 @@ mod tests {
             Vec3::new(0.0, 1.0, 0.0),
             90.0, 
             (200, 125), 
-            0.0);
+            0.0,
+            1.0);
         assert!(fuzzy_eq_f64(camera.pixel_size, 0.01));
     
         let camera = Camera::new(
@@ -140,7 +210,8 @@ mod tests {
             Vec3::new(0.0, 1.0, 0.0),
             90.0, 
             (125, 200), 
-            0.0);
+            0.0,
+            1.0);
         assert!(fuzzy_eq_f64(camera.pixel_size, 0.01));
     }
 
@@ -153,7 +224,8 @@ mod tests {
             Vec3::new(0.0, 1.0, 0.0),
             90.0, 
             (201, 101), 
-            0.0
+            0.0,
+            1.0,
         );
 
         // Center of canvas.
@@ -173,4 +245,97 @@ mod tests {
         assert_eq!(ray3.origin, Point3::new(0.0, 2.0, -5.0));
         assert!(fuzzy_eq_vec(&ray3.direction, &Vec3::new(2.0_f64.sqrt() / 2.0, 0.0, -2.0_f64.sqrt() / 2.0)));
     }
+
+    #[test]
+    fn test_shutter() {
+        let mut camera = Camera::new(Point3::origin(), Point3::new(0.0, 0.0, -1.0), Vec3::y(), 90.0, (10, 10), 0.0, 1.0);
+        assert_eq!(camera.get_ray(5, 5, None).time, 0.0);
+
+        camera.set_shutter(0.25, 0.75);
+        assert_eq!(camera.ray_for_pixel(5, 5).time, 0.5);
+        let mut rng = rand::thread_rng();
+        let times: Vec<f64> = (0..200).map(|_| camera.get_ray(5, 5, Some(&mut rng)).time).collect();
+        assert!(times.iter().all(|t| (0.25..=0.75).contains(t)));
+        assert!(times.iter().any(|&t| t < 0.4) && times.iter().any(|&t| t > 0.6));
+    }
+
+    #[test]
+    fn test_depth_of_field() {
+        let eye = Point3::new(1.0, 2.0, 0.0);
+        let pinhole = Camera::new(eye, Point3::new(1.0, 2.0, -6.0), Vec3::y(), 60.0, (21, 21), 0.0, 6.0);
+        let camera = Camera::new(eye, Point3::new(1.0, 2.0, -6.0), Vec3::y(), 60.0, (21, 21), 2.0, 6.0);
+        // Without an rng it's a pinhole, whatever the aperture.
+        let (ray, expected) = (camera.ray_for_pixel(3, 17), pinhole.ray_for_pixel(3, 17));
+        assert_eq!((ray.origin, ray.direction), (expected.origin, expected.direction));
+
+        // Rays start across the lens and meet again, within the pixel, on the
+        // focal plane. Further back they spread out.
+        let mut rng = rand::thread_rng();
+        let rays: Vec<Ray> = (0..200).map(|_| camera.get_ray(3, 17, Some(&mut rng))).collect();
+        let at_depth = |ray: &Ray, depth: f64| ray.at(-depth / ray.direction.z);
+        let spread = |depth: f64| {
+            let centre = at_depth(&expected, depth);
+            rays.iter().map(|ray| (at_depth(ray, depth) - centre).norm()).fold(0.0, f64::max)
+        };
+        assert!(rays.iter().all(|ray| fuzzy_eq_f64(ray.origin.z, 0.0) && (ray.origin - eye).norm() <= 1.0));
+        assert!(rays.iter().any(|ray| (ray.origin - eye).norm() > 0.5));
+        assert!(spread(6.0) <= camera.pixel_size * 6.0, "{}", spread(6.0));
+        assert!(spread(12.0) > 0.5, "{}", spread(12.0));
+    }
+
+    #[test]
+    fn test_panorama() {
+        let eye = Point3::new(1.0, 2.0, 3.0);
+        let mut camera = Camera::new(eye, Point3::new(1.0, 2.0, 8.0), Vec3::y(), 60.0, (40, 20), 1.0, 5.0);
+        camera.set_projection(Projection::Panorama);
+        let direction = |x: f64, y: f64| camera.panorama_ray(x, y).direction;
+
+        // Ahead in the middle, behind at the edges, up and down at the top and bottom.
+        assert!(fuzzy_eq_vec(&direction(20.0, 10.0), &Vec3::z()));
+        assert!(fuzzy_eq_vec(&direction(0.0, 10.0), &-Vec3::z()));
+        assert!(fuzzy_eq_vec(&direction(40.0, 10.0), &-Vec3::z()));
+        assert!(fuzzy_eq_vec(&direction(13.0, 0.0), &Vec3::y()));
+        assert!(fuzzy_eq_vec(&direction(27.0, 20.0), &-Vec3::y()));
+        // Right of the middle turns the same way as a perspective camera's.
+        let perspective = Camera::new(eye, Point3::new(1.0, 2.0, 8.0), Vec3::y(), 60.0, (40, 20), 0.0, 5.0);
+        assert!(fuzzy_eq_vec(&direction(30.0, 10.0), &Vec3::x()));
+        assert!(perspective.ray_for_pixel(39, 10).direction.x > 0.0);
+        assert!(fuzzy_eq_vec(&direction(20.0, 5.0), &Vec3::new(0.0, 1.0, 1.0).normalize()));
+
+        // No lens, whatever the aperture.
+        let mut rng = rand::thread_rng();
+        assert!((0..50).all(|_| camera.get_ray(7, 3, Some(&mut rng)).origin == eye));
+    }
+
+    #[test]
+    fn test_panorama_as_environment() {
+        use std::sync::Arc;
+        use crate::{Colour, Material, Scene, Background};
+        use crate::object::Sphere;
+        use crate::pattern::{Texture, Checkers};
+        use crate::transform::Transformable;
+
+        // A patterned room around the camera, rendered to a panorama and read
+        // back as an environment, looks the same every way from the middle.
+        let mut scene = Scene::default();
+        let walls = Checkers::new(Colour::new(1.0, 0.2, 0.1), Colour::new(0.1, 0.3, 1.0));
+        let mut room = Sphere::new(Material { pattern: Some(Arc::new(walls)), ambient: 1.0, diffuse: 0.0, specular: 0.0, ..Default::default() });
+        room.scale_uniform(10.0);
+        scene.push(Box::new(room));
+
+        let (width, height) = (64, 32);
+        let mut camera = Camera::new(Point3::origin(), Point3::new(0.0, 0.0, 1.0), Vec3::y(), 60.0, (width, height), 0.0, 1.0);
+        camera.set_projection(Projection::Panorama);
+        let image = image::Rgb32FImage::from_fn(width, height, |x, y| {
+            let colour: Vec3 = scene.colour_at(&camera.ray_for_pixel(x, y), 1).into();
+            image::Rgb([colour.x as f32, colour.y as f32, colour.z as f32])
+        });
+        let sky = Background::Environment { map: Arc::new(Texture::from_linear(&image, Default::default())), rotation: 0.0 };
+
+        for (x, y) in (0..width).flat_map(|x| (0..height).map(move |y| (x, y))) {
+            let ray = camera.ray_for_pixel(x, y);
+            let (seen, expected): (Vec3, Vec3) = (sky.colour(&ray.direction).into(), scene.colour_at(&ray, 1).into());
+            assert!((seen - expected).norm() < 1e-5, "{} {}: {} {}", x, y, seen, expected);
+        }
+    }
 }
\ No newline at end of file
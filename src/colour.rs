@@ -1,3 +1,4 @@
+use std::fmt;
 use std::ops::{Mul, Add, AddAssign, Sub};
 use rand::Rng;
 use crate::Vec3;
@@ -41,6 +42,40 @@ impl Colour {
         }
     }
 
+    // Approximate colour of a black body at the given temperature in Kelvin,
+    // valid for 1000K - 40000K. 6600K is roughly white.
+    pub fn from_temperature(kelvin: f64) -> Self {
+        let t = kelvin / 100.0;
+        let r = if t <= 66.0 {
+            255.0
+        } else {
+            329.698727446 * (t - 60.0).powf(-0.1332047592)
+        };
+        let g = if t <= 66.0 {
+            99.4708025861 * t.ln() - 161.1195681661
+        } else {
+            288.1221695283 * (t - 60.0).powf(-0.0755148492)
+        };
+        let b = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.5177312231 * (t - 10.0).ln() - 305.0447927307
+        };
+
+        Self {
+            r: r.clamp(0.0, 255.0) / 255.0,
+            g: g.clamp(0.0, 255.0) / 255.0,
+            b: b.clamp(0.0, 255.0) / 255.0,
+        }
+    }
+
+    // Applies f to each channel.
+    pub fn map(self, f: impl Fn(f64) -> f64) -> Self {
+        Self { r: f(self.r), g: f(self.g), b: f(self.b) }
+    }
+
     pub fn gamma_correct(&mut self, samples: u32) {
         let scale = 1.0 / (samples as f64);
         self.r = (self.r * scale).sqrt();
@@ -49,6 +84,12 @@ impl Colour {
     }
 }
 
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:.4}, {:.4}, {:.4}]", self.r, self.g, self.b)
+    }
+}
+
 impl From<Colour> for Vec3 {
     fn from(colour: Colour) -> Vec3 {
         Vec3::new(colour.r, colour.g, colour.b)
@@ -138,4 +179,22 @@ impl Sub<Colour> for Colour {
 pub fn fuzzy_eq_colour(a: Colour, b: Colour) -> bool {
     use crate::math::fuzzy_eq_f64; 
     fuzzy_eq_f64(a.r, b.r) && fuzzy_eq_f64(a.g, b.g) && fuzzy_eq_f64(a.b, b.b)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_temperature() {
+        assert!(fuzzy_eq_colour(Colour::from_temperature(6600.0), WHITE));
+
+        let candle = Colour::from_temperature(1900.0);
+        assert_eq!(candle.r, 1.0);
+        assert!(candle.g < 0.6);
+        assert_eq!(candle.b, 0.0);
+
+        let sky = Colour::from_temperature(12000.0);
+        assert!(sky.b > sky.r);
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::{Camera, Colour, ObjectId, Point3, Scene, Vec3};
+use crate::ray::Ray;
+use crate::render::{RenderSettings, TraceContext};
+
+// Caps on exported ray paths, to keep files a sane size.
+pub const DUMP_MAX_DEPTH: u32 = 8;
+pub const DUMP_MAX_SEGMENTS: usize = 256;
+// Length of the segment drawn for a ray that escapes the scene.
+pub const MISS_LENGTH: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayKind {
+    Primary,
+    Reflect,
+    Refract,
+}
+
+// A single step in the life of a traced ray.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    // Number of bounces since the primary ray.
+    pub bounce: usize,
+    pub kind:   TraceKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceKind {
+    Ray {
+        kind:      RayKind,
+        origin:    Point3,
+        direction: Vec3,
+    },
+    Hit {
+        obj_id: ObjectId,
+        name:   Option<String>,
+        t:      f64,
+        point:  Point3,
+        normal: Vec3,
+    },
+    Miss {
+        background: Colour,
+    },
+    Shadow {
+        light:    usize,
+        origin:   Point3,
+        target:   Point3,
+        shadowed: bool,
+    },
+    TotalInternalReflection,
+    // Colour contributions summed at this level.
+    Shade {
+        surface:   Colour,
+        reflected: Colour,
+        refracted: Colour,
+        schlick:   Option<f64>,
+        total:     Colour,
+    },
+}
+
+// Collects events as Scene::colour_at_traced walks the ray tree.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    max_depth: usize,
+    events:    Vec<TraceEvent>,
+}
+
+impl Recorder {
+    pub(crate) fn new(max_depth: usize) -> Self {
+        Self { max_depth, events: Vec::new() }
+    }
+
+    // Depth is the remaining bounce budget, as passed through colour_at.
+    pub(crate) fn record(&mut self, depth: usize, kind: TraceKind) {
+        self.events.push(TraceEvent { bounce: self.max_depth - depth, kind });
+    }
+
+    pub(crate) fn record_ray(&mut self, depth: usize, kind: RayKind, ray: &Ray) {
+        self.record(depth, TraceKind::Ray { kind, origin: ray.origin, direction: ray.direction });
+    }
+
+    pub(crate) fn into_events(self) -> Vec<TraceEvent> {
+        self.events
+    }
+}
+
+// Traces the centre sample of pixel (x, y) and returns every event along the way.
+pub fn debug_pixel(
+    scene:    &Scene,
+    camera:   &Camera,
+    settings: &RenderSettings,
+    x:        u32,
+    y:        u32,
+) -> Vec<TraceEvent> {
+    let max_depth = settings.max_depth as usize;
+    let mut recorder = Recorder::new(max_depth);
+    let ray = camera.ray_for_pixel(x, y);
+
+    recorder.record_ray(max_depth, RayKind::Primary, &ray);
+    let mut ctx = TraceContext { recorder: Some(&mut recorder), ..Default::default() };
+    scene.colour_at_traced(&ray, max_depth, &mut ctx);
+    recorder.into_events()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentKind {
+    Primary,
+    Shadow,
+    Reflect,
+    Refract,
+}
+
+impl From<RayKind> for SegmentKind {
+    fn from(kind: RayKind) -> Self {
+        match kind {
+            RayKind::Primary => SegmentKind::Primary,
+            RayKind::Reflect => SegmentKind::Reflect,
+            RayKind::Refract => SegmentKind::Refract,
+        }
+    }
+}
+
+// One straight piece of a traced path, in world space.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RaySegment {
+    pub origin: [f64; 3],
+    pub end:    [f64; 3],
+    pub r#type: SegmentKind,
+    pub depth:  usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PixelPath {
+    pub x:        u32,
+    pub y:        u32,
+    pub segments: Vec<RaySegment>,
+}
+
+// Turns a trace into line segments; rays that miss are drawn MISS_LENGTH long.
+pub fn ray_segments(events: &[TraceEvent]) -> Vec<RaySegment> {
+    let mut segments = Vec::new();
+    let mut pending: Option<(RayKind, Point3, Vec3)> = None;
+
+    for event in events {
+        let segment = match (&event.kind, pending.take()) {
+            (TraceKind::Ray { kind, origin, direction }, _) => {
+                pending = Some((*kind, *origin, *direction));
+                None
+            },
+            (TraceKind::Hit { point, .. }, Some((kind, origin, _))) => {
+                Some((kind.into(), origin, *point))
+            },
+            (TraceKind::Miss { .. }, Some((kind, origin, direction))) => {
+                Some((kind.into(), origin, origin + direction.normalize() * MISS_LENGTH))
+            },
+            (TraceKind::Shadow { origin, target, .. }, _) => {
+                Some((SegmentKind::Shadow, *origin, *target))
+            },
+            _ => None,
+        };
+
+        if let Some((kind, origin, end)) = segment {
+            segments.push(RaySegment {
+                origin: [origin.x, origin.y, origin.z],
+                end:    [end.x, end.y, end.z],
+                r#type: kind,
+                depth:  event.bounce,
+            });
+        }
+        if segments.len() >= DUMP_MAX_SEGMENTS {
+            break;
+        }
+    }
+    segments
+}
+
+// Traces each pixel with a capped depth and collects its ray segments.
+pub fn dump_rays(
+    scene:    &Scene,
+    camera:   &Camera,
+    settings: &RenderSettings,
+    pixels:   &[(u32, u32)],
+) -> Vec<PixelPath> {
+    let settings = RenderSettings {
+        max_depth: settings.max_depth.min(DUMP_MAX_DEPTH),
+        ..settings.clone()
+    };
+    pixels.iter()
+        .map(|&(x, y)| PixelPath {
+            x,
+            y,
+            segments: ray_segments(&debug_pixel(scene, camera, &settings, x, y)),
+        })
+        .collect()
+}
+
+fn fmt_xyz(x: f64, y: f64, z: f64) -> String {
+    format!("({:.4}, {:.4}, {:.4})", x, y, z)
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", "  ".repeat(self.bounce))?;
+        match &self.kind {
+            TraceKind::Ray { kind, origin, direction } => write!(
+                f, "{:?} ray from {} towards {}", 
+                kind, fmt_xyz(origin.x, origin.y, origin.z), fmt_xyz(direction.x, direction.y, direction.z),
+            ),
+            TraceKind::Hit { obj_id, name, t, point, normal } => write!(
+                f, "hit object {}{} at t = {:.4}, point {}, normal {}",
+                obj_id, name.as_ref().map(|name| format!(" \"{}\"", name)).unwrap_or_default(), t, fmt_xyz(point.x, point.y, point.z), fmt_xyz(normal.x, normal.y, normal.z),
+            ),
+            TraceKind::Miss { background } => write!(f, "miss, background {}", background),
+            TraceKind::Shadow { light, shadowed, .. } => write!(
+                f, "light {}: {}", light, if *shadowed { "shadowed" } else { "visible" },
+            ),
+            TraceKind::TotalInternalReflection => write!(f, "total internal reflection"),
+            TraceKind::Shade { surface, reflected, refracted, schlick, total } => {
+                write!(f, "surface {} + reflected {} + refracted {}", surface, reflected, refracted)?;
+                if let Some(schlick) = schlick {
+                    write!(f, " (schlick {:.4})", schlick)?;
+                }
+                write!(f, " = {}", total)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Material, Light};
+    use crate::object::{Cone, Plane};
+    use crate::transform::Transformable;
+
+    #[test]
+    fn test_debug_pixel() {
+        // A reflective floor under an empty sky.
+        let mut scene = Scene::default();
+        scene.push(Box::new(Plane::new(Material {
+            reflect: 0.5,
+            ..Default::default()
+        })));
+        scene.lights.push(Light::new(Point3::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+
+        let settings = RenderSettings { dimensions: (11, 11), samples: 1, max_depth: 5, ..Default::default() };
+        let camera = Camera::new(
+            Point3::new(0.0, 1.0, -5.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            60.0,
+            settings.dimensions,
+            0.0,
+            5.0,
+        );
+
+        let events = debug_pixel(&scene, &camera, &settings, 5, 5);
+        let kinds: Vec<&TraceKind> = events.iter().map(|e| &e.kind).collect();
+        assert_eq!(kinds.len(), 6);
+        assert!(matches!(kinds[0], TraceKind::Ray { kind: RayKind::Primary, .. }));
+        assert!(matches!(kinds[1], TraceKind::Hit { obj_id, .. } if *obj_id == scene.objects[0].id()));
+        assert!(matches!(kinds[2], TraceKind::Shadow { light: 0, shadowed: false, .. }));
+        assert!(matches!(kinds[3], TraceKind::Ray { kind: RayKind::Reflect, .. }));
+        assert!(matches!(kinds[4], TraceKind::Miss { .. }));
+        assert!(matches!(kinds[5], TraceKind::Shade { schlick: None, .. }));
+
+        assert_eq!(events[3].bounce, 1);
+        assert_eq!(events[5].bounce, 0);
+    }
+
+    #[test]
+    fn test_preset_smoke() {
+        // Each preset as a gem over a checkered floor, seen from above. Light
+        // comes in through the flat top and meets the sloping sides at 35
+        // degrees, past the critical angle of diamond but not glass or water.
+        let settings = RenderSettings { dimensions: (24, 24), samples: 1, max_depth: 6, ..Default::default() };
+        let camera = Camera::new(Point3::new(0.0, 5.0, 0.0), Point3::origin(), Vec3::z(), 35.0, settings.dimensions, 0.0, 5.0);
+        let trapped = |material: Material| {
+            let mut scene = Scene::default();
+            let floor = crate::pattern::Checkers::new(Colour::new(1.0, 1.0, 1.0), Colour::new(0.1, 0.1, 0.1));
+            scene.push(Box::new(Plane::new(Material { pattern: Some(std::sync::Arc::new(floor)), ..Default::default() })));
+            // Scaled down so its hit offset is small enough for refracted rays
+            // to see the face they came in through.
+            let mut gem = Cone::new(material, 55.0, 0.0, 1.0, true);
+            gem.translate(0.0, 0.5, 0.0);
+            gem.scale_uniform(0.5);
+            scene.push(Box::new(gem));
+            scene.lights.push(Light::new(Point3::new(-5.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0)));
+
+            let mut pixels = 0;
+            for (x, y) in (0..24).flat_map(|x| (0..24).map(move |y| (x, y))) {
+                let events = debug_pixel(&scene, &camera, &settings, x, y);
+                for event in &events {
+                    if let TraceKind::Shade { total, .. } = event.kind {
+                        assert!(Vec3::from(total).iter().all(|c| c.is_finite() && *c >= 0.0));
+                    }
+                }
+                // Reflected where the light first reaches the sides.
+                if events.iter().any(|e| e.bounce == 1 && matches!(e.kind, TraceKind::TotalInternalReflection)) {
+                    pixels += 1;
+                }
+            }
+            pixels
+        };
+        assert_eq!(trapped(Material::mirror()), 0);
+        assert_eq!(trapped(Material::rubber(Colour::new(0.8, 0.1, 0.1))), 0);
+        let (water, glass, diamond) = (trapped(Material::water()), trapped(Material::glass()), trapped(Material::diamond()));
+        assert!(water <= glass && glass < diamond, "{} {} {}", water, glass, diamond);
+    }
+
+    #[test]
+    fn test_dump_rays_round_trip() {
+        let dimensions = (200, 100);
+        let (scene, camera) = crate::parse_scene("scenes/examples/3spheres.yaml", dimensions).unwrap();
+        let settings = RenderSettings { dimensions, samples: 1, max_depth: 100, ..Default::default() };
+
+        let paths = dump_rays(&scene, &camera, &settings, &[(100, 50), (0, 0)]);
+        let json = serde_json::to_string(&paths).unwrap();
+        let parsed: Vec<PixelPath> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, paths);
+
+        // Centre pixel's primary ray ends on the reflective ball.
+        let primary = &parsed[0].segments[0];
+        assert_eq!(primary.r#type, SegmentKind::Primary);
+        assert_eq!(primary.depth, 0);
+        let end = Point3::new(primary.end[0], primary.end[1], primary.end[2]);
+        assert!(((end - Point3::new(0.0, 4.0, 30.0)).magnitude() - 4.0).abs() < 1e-4);
+
+        assert!(parsed.iter().all(|p| p.segments.iter().all(|s| s.depth <= DUMP_MAX_DEPTH as usize)));
+    }
+}
@@ -0,0 +1,177 @@
+use serde::Deserialize;
+use crate::Vec3;
+use crate::colour::Colour;
+use crate::render::HdrImage;
+
+// Per pixel guide buffers from the first hit of each pixel's first sample.
+// Pixels that miss have a zero normal and infinite depth.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Aovs {
+    pub normals: Vec<Vec<Vec3>>,
+    pub depths:  Vec<Vec<f64>>,
+}
+
+// Edge-avoiding à-trous wavelet filter. Each iteration blurs with a 5x5 kernel
+// spread twice as wide as the last, skipping neighbours with a different
+// normal, depth or colour so edges stay sharp.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Denoise {
+    // How different colours can be and still be averaged together.
+    #[serde(default = "denoise_strength_default")]
+    pub strength:   f64,
+    #[serde(default = "denoise_iterations_default")]
+    pub iterations: u32,
+}
+
+fn denoise_strength_default() -> f64 { 1.0 }
+fn denoise_iterations_default() -> u32 { 3 }
+
+impl Default for Denoise {
+    fn default() -> Self {
+        Self { strength: denoise_strength_default(), iterations: denoise_iterations_default() }
+    }
+}
+
+// B3 spline weights.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+// Sharpness of the normal weight, higher only mixes closely aligned normals.
+const NORMAL_POWER: i32 = 64;
+// Relative depth difference at which neighbours stop being mixed.
+const DEPTH_TOLERANCE: f64 = 0.05;
+
+impl Denoise {
+    pub fn apply(&self, image: &mut HdrImage, aovs: &Aovs) {
+        if self.strength <= 0.0 || image.is_empty() {
+            return;
+        }
+        let (width, height) = (image[0].len() as i64, image.len() as i64);
+
+        // Once the step reaches the image size only the centre pixel is in
+        // reach, so later iterations would change nothing.
+        for i in (0..self.iterations).take_while(|&i| i < 62 && 1i64 << i < width.max(height)) {
+            let step = 1i64 << i;
+            // Later iterations see less noise, so are stricter on colour.
+            let colour_sigma = self.strength / step as f64;
+            let source = image.clone();
+
+            for y in 0..height {
+                for x in 0..width {
+                    let (p, n_p, z_p) = (pixel(&source, x, y), aovs.normals[y as usize][x as usize], aovs.depths[y as usize][x as usize]);
+                    let mut sum = Colour::default();
+                    let mut total = 0.0;
+
+                    for (ky, wy) in KERNEL.iter().enumerate() {
+                        for (kx, wx) in KERNEL.iter().enumerate() {
+                            let qx = x + (kx as i64 - 2) * step;
+                            let qy = y + (ky as i64 - 2) * step;
+                            if qx < 0 || qy < 0 || qx >= width || qy >= height {
+                                continue;
+                            }
+                            let q = pixel(&source, qx, qy);
+                            let n_q = aovs.normals[qy as usize][qx as usize];
+                            let z_q = aovs.depths[qy as usize][qx as usize];
+
+                            let weight = wx * wy
+                                * colour_weight(p, q, colour_sigma)
+                                * normal_weight(n_p, n_q)
+                                * depth_weight(z_p, z_q);
+                            sum += q * weight;
+                            total += weight;
+                        }
+                    }
+                    // The centre always has weight, so total is never 0.
+                    image[y as usize][x as usize] = sum * (1.0 / total);
+                }
+            }
+        }
+    }
+}
+
+fn pixel(image: &HdrImage, x: i64, y: i64) -> Colour {
+    image[y as usize][x as usize]
+}
+
+fn colour_weight(p: Colour, q: Colour, sigma: f64) -> f64 {
+    let diff = Vec3::from(p - q).norm_squared();
+    (-diff / (sigma * sigma)).exp()
+}
+
+fn normal_weight(n_p: Vec3, n_q: Vec3) -> f64 {
+    n_p.dot(&n_q).max(0.0).powi(NORMAL_POWER)
+}
+
+fn depth_weight(z_p: f64, z_q: f64) -> f64 {
+    match (z_p.is_finite(), z_q.is_finite()) {
+        (true, true) => (-(z_p - z_q).abs() / (DEPTH_TOLERANCE * z_p.max(1e-6))).exp(),
+        // Background is only mixed with background.
+        (false, false) => 1.0,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    const SIZE: usize = 32;
+
+    fn flat_aovs(normal: impl Fn(usize) -> Vec3) -> Aovs {
+        Aovs {
+            normals: vec![(0..SIZE).map(normal).collect(); SIZE],
+            depths:  vec![vec![5.0; SIZE]; SIZE],
+        }
+    }
+
+    fn variance(image: &HdrImage) -> f64 {
+        let values: Vec<f64> = image.iter().flatten().map(|&c| Vec3::from(c).x).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+    }
+
+    #[test]
+    fn test_denoise_noise() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut image: HdrImage = (0..SIZE)
+            .map(|_| (0..SIZE).map(|_| {
+                let v = 0.5 + rng.gen_range(-0.2..0.2);
+                Colour::new(v, v, v)
+            }).collect())
+            .collect();
+        let aovs = flat_aovs(|_| Vec3::new(0.0, 0.0, -1.0));
+
+        let before = variance(&image);
+        let noisy = image.clone();
+        Denoise::default().apply(&mut image, &aovs);
+        assert!(variance(&image) < before / 10.0);
+
+        // Steps past the image size change nothing, however many are asked for.
+        let denoised = |iterations| {
+            let mut image = noisy.clone();
+            Denoise { iterations, ..Default::default() }.apply(&mut image, &aovs);
+            image
+        };
+        assert_eq!(denoised(100), denoised(5));
+        assert_eq!(denoised(u32::MAX), denoised(5));
+    }
+
+    #[test]
+    fn test_denoise_edge() {
+        // Left half faces +x, right half faces -z, with a step in colour between.
+        let edge = SIZE / 2;
+        let mut image: HdrImage = vec![(0..SIZE).map(|x| if x < edge { Colour::default() } else { Colour::new(1.0, 1.0, 1.0) }).collect(); SIZE];
+        let aovs = flat_aovs(|x| if x < edge { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 0.0, -1.0) });
+
+        Denoise { strength: 10.0, iterations: 4 }.apply(&mut image, &aovs);
+        for row in &image {
+            assert!(row[..edge - 1].iter().all(|&c| Vec3::from(c).x < 0.01));
+            assert!(row[edge + 1..].iter().all(|&c| Vec3::from(c).x > 0.99));
+        }
+
+        // Without strength nothing changes.
+        let mut image = vec![vec![Colour::new(0.3, 0.3, 0.3); SIZE]; SIZE];
+        Denoise { strength: 0.0, iterations: 3 }.apply(&mut image, &aovs);
+        assert_eq!(image, vec![vec![Colour::new(0.3, 0.3, 0.3); SIZE]; SIZE]);
+    }
+}
@@ -1,16 +1,19 @@
 use std::{sync::Arc, collections::HashMap};
 use crate::{Point3, Vec3, Material, Colour};
+use crate::object::ObjectId;
 
 #[derive(Debug, Default)]
 pub struct Intersection {
     // Intersection ID.
     pub id: usize,
     // The ID of the object that was hit.
-    pub obj_id: usize,
+    pub obj_id: ObjectId,
     // The point at which the ray hit the object.
     pub point: Point3,
     // The normal of the object at the point of incidence.
     pub normal: Vec3,
+    // The normal before the material's bump, facing the same side as normal.
+    pub surface_normal: Vec3,
     // Material will be shared between threads.
     pub material: Arc<Material>,
     // Hit only if t is t_min < t < t_max.
@@ -31,6 +34,13 @@ pub struct Intersection {
     pub exit_idx: f64,
     // Enter index of refraction.
     pub enter_idx: f64,
+    // Time of the ray that made the hit.
+    pub time: f64,
+    // Texture coordinates of the hit, see Object::uv_at. Only worked out for
+    // materials coloured by them.
+    pub uv: Option<(f64, f64)>,
+    // Triangle index and barycentric u, v of a hit on a mesh.
+    pub face: Option<(usize, f64, f64)>,
 }
 
 impl Intersection {
@@ -51,13 +61,13 @@ impl Intersection {
     }
 }
 
-pub fn compute_intersections(hits: &mut Vec<Intersection>) {
+pub fn compute_intersections(hits: &mut [Intersection]) {
     
     hits.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
     hits.iter_mut().enumerate()
         .for_each(|(i, hit)| hit.id = i);
 
-    let r_map: HashMap<usize, f64> = hits.iter()
+    let r_map: HashMap<ObjectId, f64> = hits.iter()
         .map(|i| (i.obj_id, i.material.refractive_index))
         .collect();
     
@@ -67,10 +77,9 @@ pub fn compute_intersections(hits: &mut Vec<Intersection>) {
             
             let mut exit_idx = 1.0;
             let mut enter_idx = 1.0;
-            let mut containers: Vec<usize> = Vec::new();
+            let mut containers: Vec<ObjectId> = Vec::new();
 
-            for i in 0..hits.len() {
-                let other = &hits[i];
+            for other in hits.iter() {
 
                 if other.id == hit.id {
                     if !containers.is_empty() {
@@ -122,6 +131,8 @@ mod tests {
         let mut outer_sphere = Sphere::new(Material {
             colour: Colour::default(),
             pattern: None,
+            normal_perturbation: None,
+            uv_pattern: false,
             ambient: 0.1,
             diffuse: 0.0,
             specular: 0.0,
@@ -129,6 +140,14 @@ mod tests {
             reflect: 0.0,
             refractive_index: 1.5,
             transparency: 1.0,
+            shadow_catcher: false,
+            absorption: Colour::default(),
+            roughness: 0.0,
+            glossy_samples: 1,
+            specular_model: Default::default(),
+            diffuse_roughness: 0.0,
+            fresnel: false,
+            pattern_blend: Default::default(),
         });
         outer_sphere.scale_uniform(2.0);
         let mut inner_sphere1 = Sphere::new(Material::new(
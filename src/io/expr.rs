@@ -0,0 +1,175 @@
+use anyhow::{Result, anyhow, bail};
+
+// Evaluates simple arithmetic used in scene files, e.g. "360 / 7 * 3" or "2 * sin(radians(45))".
+// Supports + - * /, parentheses, the constant pi and a few functions.
+pub fn eval(expr: &str) -> Result<f64> {
+    let mut parser = Parser { chars: expr.chars().collect(), pos: 0 };
+    let value = parser.expr()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        bail!("Unexpected '{}' in expression {:?}", parser.chars[parser.pos], expr);
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos:   usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.peek() != Some(c) {
+            bail!("Expected '{}' in expression", c);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // expr = term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    // term = unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = if op == '*' {
+                value * rhs
+            } else if rhs == 0.0 {
+                bail!("Division by zero in expression");
+            } else {
+                value / rhs
+            };
+        }
+        Ok(value)
+    }
+
+    // unary = ('-' | '+') unary | primary
+    fn unary(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some('-') => { self.pos += 1; Ok(-self.unary()?) },
+            Some('+') => { self.pos += 1; self.unary() },
+            _ => self.primary(),
+        }
+    }
+
+    // primary = number | '(' expr ')' | name | name '(' expr ')'
+    fn primary(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.expr()?;
+                self.expect(')')?;
+                Ok(value)
+            },
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                if self.peek() != Some('(') {
+                    return constant(&name);
+                }
+                self.pos += 1;
+                let arg = self.expr()?;
+                self.expect(')')?;
+                function(&name, arg)
+            },
+            Some(c) => bail!("Unexpected '{}' in expression", c),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        let mut text = self.take_while(|c| c.is_ascii_digit() || c == '.');
+        // Exponent, e.g. 1e-3.
+        if matches!(self.chars.get(self.pos), Some('e' | 'E')) {
+            text.push('e');
+            self.pos += 1;
+            if let Some(sign @ ('+' | '-')) = self.chars.get(self.pos).copied() {
+                text.push(sign);
+                self.pos += 1;
+            }
+            text.push_str(&self.take_while(|c| c.is_ascii_digit()));
+        }
+        text.parse().map_err(|_| anyhow!("Invalid number {}", text))
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|&c| f(c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+}
+
+fn constant(name: &str) -> Result<f64> {
+    match name {
+        "pi" => Ok(std::f64::consts::PI),
+        _ => bail!("Unknown name {} in expression", name),
+    }
+}
+
+// Trigonometry is in radians, like the functions it maps to.
+fn function(name: &str, x: f64) -> Result<f64> {
+    Ok(match name {
+        "sin"     => x.sin(),
+        "cos"     => x.cos(),
+        "tan"     => x.tan(),
+        "sqrt"    => x.sqrt(),
+        "abs"     => x.abs(),
+        "radians" => x.to_radians(),
+        "degrees" => x.to_degrees(),
+        _ => bail!("Unknown function {} in expression", name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::fuzzy_eq_f64;
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("2").unwrap(), 2.0);
+        assert_eq!(eval("1 + 2 * 3").unwrap(), 7.0);
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), 9.0);
+        assert_eq!(eval("10 - 4 - 3").unwrap(), 3.0);
+        assert_eq!(eval("12 / 3 / 2").unwrap(), 2.0);
+        assert_eq!(eval("-2 * -(3 + 1)").unwrap(), 8.0);
+        assert_eq!(eval("1.5e2 + .5").unwrap(), 150.5);
+        assert!(fuzzy_eq_f64(eval("2 * sin(radians(30))").unwrap(), 1.0));
+        assert!(fuzzy_eq_f64(eval("degrees(pi)").unwrap(), 180.0));
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        assert!(eval("1 / 0").unwrap_err().to_string().contains("Division by zero"));
+        assert!(eval("1 / (2 - 2)").is_err());
+        assert!(eval("log(2)").unwrap_err().to_string().contains("log"));
+        assert!(eval("tau").is_err());
+        assert!(eval("1 +").is_err());
+        assert!(eval("(1 + 2").is_err());
+        assert!(eval("1 2").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use crate::{Camera, Colour, Light, Material, Matrix4, Object, Point3, Scene, Vec3};
+use crate::object::{Bounds, Mesh};
+use crate::render::RenderOverrides;
+use crate::transform::Transformable;
+
+// Triangle meshes from a .gltf or .glb file, one per primitive, placed by
+// their nodes' transforms. Materials are plastic in the base colour.
+// Skins, animations, textures and non-triangle primitives are skipped.
+pub fn import_gltf<P: AsRef<Path>>(path: P) -> Result<Vec<Box<dyn Object>>> {
+    let path = path.as_ref();
+    let (document, buffers, _) = gltf::import(path).with_context(|| format!("Could not read glTF {}", path.display()))?;
+
+    if document.skins().len() > 0 {
+        eprintln!("Warning: skipping {} skins in {}", document.skins().len(), path.display());
+    }
+    if document.animations().len() > 0 {
+        eprintln!("Warning: skipping {} animations in {}", document.animations().len(), path.display());
+    }
+
+    let mut objects = vec![];
+    if let Some(scene) = document.default_scene().or_else(|| document.scenes().next()) {
+        for node in scene.nodes() {
+            import_node(&node, Matrix4::identity(), &buffers, &mut objects)?;
+        }
+    }
+    Ok(objects)
+}
+
+fn import_node(
+    node:    &gltf::Node,
+    parent:  Matrix4,
+    buffers: &[gltf::buffer::Data],
+    objects: &mut Vec<Box<dyn Object>>,
+) -> Result<()> {
+    let local = Matrix4::from(node.transform().matrix().map(|column| column.map(f64::from)));
+    let transform = parent * local;
+    if transform.try_inverse().is_none() {
+        eprintln!("Warning: skipping node {} with a flat transform", node.index());
+        return Ok(());
+    }
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                eprintln!("Warning: skipping {:?} primitive in mesh {}", primitive.mode(), mesh.index());
+                continue;
+            }
+            let Some(mut object) = import_primitive(&primitive, buffers)? else {
+                continue;
+            };
+            object.apply_matrix(transform);
+            objects.push(Box::new(object));
+        }
+    }
+    for child in node.children() {
+        import_node(&child, transform, buffers, objects)?;
+    }
+    Ok(())
+}
+
+fn import_primitive(primitive: &gltf::Primitive, buffers: &[gltf::buffer::Data]) -> Result<Option<Mesh>> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let Some(positions) = reader.read_positions() else {
+        return Ok(None);
+    };
+    let vertices: Vec<Point3> = positions.map(|[x, y, z]| Point3::new(x as f64, y as f64, z as f64)).collect();
+    let normals: Vec<Vec3> = reader.read_normals()
+        .map(|normals| normals.map(|[x, y, z]| Vec3::new(x as f64, y as f64, z as f64)).collect())
+        .unwrap_or_default();
+    let indices: Vec<usize> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+        None => (0..vertices.len()).collect(),
+    };
+
+    let smooth = normals.len() == vertices.len();
+    let faces: Vec<_> = indices.chunks_exact(3)
+        .filter(|corners| corners.iter().all(|&i| i < vertices.len()))
+        .map(|corners| {
+            let corners = [corners[0], corners[1], corners[2]];
+            (corners, if smooth { Some(corners) } else { None })
+        })
+        .collect();
+    if faces.is_empty() {
+        return Ok(None);
+    }
+
+    let [r, g, b, _] = primitive.material().pbr_metallic_roughness().base_color_factor();
+    let material = Material::plastic(Colour::new(r as f64, g as f64, b as f64), None);
+    Ok(Some(Mesh::new(material, vertices, normals, &faces)))
+}
+
+// A scene of just the imported objects, looked at from +z so they fill the
+// view, with a light above and to the left of the camera.
+pub(crate) fn gltf_scene(path: &Path, dimensions: (u32, u32)) -> Result<(Arc<Scene>, Camera, RenderOverrides)> {
+    let objects = import_gltf(path)?;
+    let bounds = objects.iter().fold(Bounds::empty(), |bounds, obj| bounds.union(&obj.bounds()));
+    let (centre, radius) = if bounds.is_empty() || !bounds.is_finite() {
+        (Point3::origin(), 1.0)
+    } else {
+        (Point3::from((bounds.min.coords + bounds.max.coords) / 2.0), ((bounds.max - bounds.min).norm() / 2.0).max(1e-6))
+    };
+
+    let vfov: f64 = 45.0;
+    let distance = radius / (vfov.to_radians() / 2.0).sin();
+    let look_from = centre + Vec3::new(0.0, 0.0, distance);
+    let camera = Camera::new(look_from, centre, Vec3::y(), vfov, dimensions, 0.0, (centre - look_from).norm());
+
+    let light = Light::new(centre + Vec3::new(-distance, distance, distance), Colour::new(1.0, 1.0, 1.0));
+    let scene = Scene::new(objects, vec![light], Colour::new(0.0, 0.0, 0.0));
+    Ok((Arc::new(scene), camera, RenderOverrides::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::fuzzy_eq_f64;
+    use crate::ray::Ray;
+
+    // One triangle, (0, 0, 0), (1, 0, 0), (0, 1, 0), red, under a node moved 2 along x.
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "translation": [2, 0, 0], "children": [1] }, { "mesh": 0 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }] }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1] } }],
+        "buffers": [{ "byteLength": 42, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIA" }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ]
+    }"#;
+
+    #[test]
+    fn test_import_gltf() {
+        let path = std::env::temp_dir().join(format!("ray_tracer_gltf_test_{}.gltf", std::process::id()));
+        std::fs::write(&path, TRIANGLE).unwrap();
+        let objects = import_gltf(&path);
+        let scene = gltf_scene(&path, (10, 10));
+        std::fs::remove_file(&path).unwrap();
+
+        let objects = objects.unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].material().colour, Colour::new(1.0, 0.0, 0.0));
+
+        let ray = Ray::new(Point3::new(2.25, 0.25, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hits = objects[0].hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(hits[0].t, 5.0));
+        assert_eq!(objects[0].bounds(), Bounds::new(Point3::new(2.0, 0.0, 0.0), Point3::new(3.0, 1.0, 0.0)));
+
+        // The camera looks at the middle of the triangle.
+        let (scene, camera, _) = scene.unwrap();
+        let ray = camera.ray_for_pixel(5, 5);
+        assert!(scene.trace(&ray).is_some());
+    }
+}
@@ -1,9 +1,16 @@
-use serde::Deserialize;
-use std::{fs::read, path::Path, sync::Arc};
-use anyhow::{Result, Context};
+use serde::{Deserialize, Deserializer};
+use serde::de::{self, Visitor, MapAccess, SeqAccess, EnumAccess, VariantAccess};
+use serde::de::IntoDeserializer;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer, EnumAccessDeserializer};
+use std::{fmt, fs::read, path::{Path, PathBuf}, sync::Arc, collections::HashMap};
+use anyhow::{Result, Context, anyhow, bail};
 use crate::*;
 use crate::pattern::*;
-use crate::object::{Sphere, Plane, Disk, AxisAlignedBoundingBox, Cone, Cylinder};
+use crate::bump::{NormalMap, Waves, NoiseBump};
+use crate::transform::Transformable;
+use super::params::substitute_params;
+use super::expr::eval;
+use crate::object::{Sphere, Plane, Disk, AxisAlignedBoundingBox, Cone, Cylinder, Lathe, Prism, Mesh, ParametricPreset, Csg, CsgOp, Heightfield, SdfObject, SdfShape, Instance, Moving, Lens, Superellipsoid, ObjectId, builders};
 
 #[derive(Deserialize, Debug)]
 pub struct Inputs {
@@ -16,52 +23,168 @@ pub struct Inputs {
     #[serde(default = "lights_default")]
     lights:  Vec<LightInputs>,
 
-    #[serde(default = "background_default")]
-    background: (f64, f64, f64),
+    #[serde(default)]
+    background: BackgroundInput,
+
+    // Scales every material's ambient term.
+    #[serde(default = "colour_default", deserialize_with = "de_colour")]
+    ambient: (f64, f64, f64),
+
+    // Materials that objects can refer to by name.
+    #[serde(default)]
+    materials: HashMap<String, MaterialInputs>,
+
+    // Used by objects without a material.
+    #[serde(default = "material_default", deserialize_with = "de_material")]
+    default_material: MaterialInputs,
+
+    // Transform lists that objects and patterns can !Apply by name.
+    #[serde(default)]
+    transforms: HashMap<String, Vec<TransformationInput>>,
+
+    // Objects that are only built once, and placed with !Instance.
+    #[serde(default)]
+    prototypes: HashMap<String, ObjectInputs>,
+
+    #[serde(default)]
+    render: RenderOverrides,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct CameraInputs {
 
-    #[serde(default = "from_default")]
+    #[serde(default = "from_default", deserialize_with = "de_vector")]
     look_from:  (f64, f64, f64),
     
-    #[serde(default = "at_default")]
+    #[serde(default = "at_default", deserialize_with = "de_vector")]
     look_at:    (f64, f64, f64),
     
-    #[serde(default = "up_default")]
+    #[serde(default = "up_default", deserialize_with = "de_vector")]
     vup:        (f64, f64, f64),
     
-    #[serde(default = "vfov_default")]
+    #[serde(default = "vfov_default", deserialize_with = "de_number")]
     vfov:       f64,
     
-    #[serde(default)]
+    #[serde(default, deserialize_with = "de_number")]
     aperture:   f64,
+
+    // Defaults to the distance to look_at.
+    #[serde(default, deserialize_with = "de_number_opt")]
+    focus_dist: Option<f64>,
+
+    // Recentre the scene on the camera, for scenes far from the origin.
+    #[serde(default)]
+    relative:   bool,
+
+    // Times the shutter is open between, for motion blur.
+    #[serde(default)]
+    shutter:    (f64, f64),
+
+    #[serde(default)]
+    projection: ProjectionInput,
+
+    // Applied after the view, so they move the scene in front of the camera.
+    transform:  Option<Vec<TransformationInput>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum ProjectionInput {
+    #[default]
+    Perspective,
+    Panorama,
+}
+
+impl From<ProjectionInput> for Projection {
+    fn from(projection: ProjectionInput) -> Self {
+        match projection {
+            ProjectionInput::Perspective => Projection::Perspective,
+            ProjectionInput::Panorama => Projection::Panorama,
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
 pub struct ObjectInputs {
     r#type:    ObjectType,
-    #[serde(default = "material_default")]
-    material:  MaterialInputs,
+    // Unique, for looking the object up and in errors.
+    name:      Option<String>,
+    // Falls back to the scene's default_material.
+    #[serde(default, deserialize_with = "de_material_opt")]
+    material:  Option<MaterialInputs>,
     transform: Option<Vec<TransformationInput>>,
+    #[serde(default)]
+    shadow_catcher: bool,
+    // Material for the underside of planes and disks.
+    #[serde(default, deserialize_with = "de_material_opt")]
+    material_back: Option<MaterialInputs>,
+    // Fixed surface offset, instead of one scaled to the object.
+    epsilon: Option<f64>,
+    motion: Option<MotionInputs>,
+    // Whether it blocks light from other objects.
+    #[serde(default = "cast_shadow_default")]
+    cast_shadow: bool,
+    // Hidden from camera rays, but still casts shadows and shows in reflections.
+    #[serde(default = "visible_to_camera_default")]
+    visible_to_camera: bool,
+}
+
+// Translations at time 0 and 1, moved between while the shutter is open.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MotionInputs {
+    #[serde(default, deserialize_with = "de_vector")]
+    start: (f64, f64, f64),
+    #[serde(deserialize_with = "de_vector")]
+    end:   (f64, f64, f64),
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
 pub enum ObjectType {
     Sphere,
-    Plane,
-    Disk,
-    Box,
+    Ellipsoid {
+        #[serde(deserialize_with = "de_vector")]
+        radii: (f64, f64, f64),
+    },
+    Plane(Option<PlaneInputs>),
+    Disk(Option<DiskInputs>),
+    Box(Option<BoxInputs>),
     Cylinder {
+        #[serde(default = "radius_default")]
+        radius: f64,
+        // Taper to a frustum, both default to radius.
+        radius_bottom: Option<f64>,
+        radius_top: Option<f64>,
         #[serde(default = "min_default")]
         min: f64,
         #[serde(default = "max_default")]
         max: f64,
         #[serde(default)]
         closed: bool,
+        #[serde(default)]
+        theta_min: f64,
+        #[serde(default = "theta_max_default")]
+        theta_max: f64,
     },
     Cone {
+        // Half-angle in degrees, or the radius at the wider end, not both. Defaults to 45 degrees.
+        angle:  Option<f64>,
+        radius: Option<f64>,
+        #[serde(default = "min_default")]
+        min: f64,
+        #[serde(default = "max_default")]
+        max: f64,
+        #[serde(default)]
+        closed: bool,
+    },
+    Lathe {
+        // (radius, y) points revolved around the y axis.
+        profile: Vec<(f64, f64)>,
+        #[serde(default)]
+        closed: bool,
+    },
+    Prism {
+        // (x, z) corners of a convex polygon.
+        points: Vec<(f64, f64)>,
         #[serde(default = "min_default")]
         min: f64,
         #[serde(default = "max_default")]
@@ -69,26 +192,222 @@ pub enum ObjectType {
         #[serde(default)]
         closed: bool,
     },
+    Mesh {
+        // Wavefront OBJ or PLY file, relative to the scene file.
+        file: String,
+    },
+    // Tessellated into a mesh, with u around and v across.
+    Parametric {
+        preset:  ParametricPreset,
+        #[serde(default = "u_steps_default")]
+        u_steps: usize,
+        #[serde(default = "v_steps_default")]
+        v_steps: usize,
+    },
+    Heightfield {
+        // Greyscale image, brightness is height over the unit square in x and z.
+        file: String,
+        #[serde(default = "vertical_scale_default")]
+        vertical_scale: f64,
+    },
+    Sdf {
+        shape:       SdfShape,
+        max_steps:   Option<u32>,
+        hit_epsilon: Option<f64>,
+    },
+    // Name of an entry in prototypes.
+    Instance(String),
+    // Glass unless given a material.
+    Lens {
+        r1:        f64,
+        r2:        f64,
+        thickness: f64,
+    },
+    Superellipsoid(Option<SuperellipsoidInputs>),
+    // Spheres and cylinders in a ring, see object::builders::hexagon.
+    Hexagon,
+    Csg {
+        op:    CsgOp,
+        left:  Box<ObjectInputs>,
+        right: Box<ObjectInputs>,
+    },
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
 pub enum MaterialInputs {
-    Glass,
-    Metal {
+    Glass(Option<GlassInputs>),
+    Metal(MetalInputs),
+    Plastic(PlasticInputs),
+    Mirror,
+    Water,
+    Diamond,
+    Matte {
+        #[serde(deserialize_with = "de_colour")]
         colour: (f64, f64, f64),
         pattern: Option<PatternInputs>,
     },
-    Plastic {
+    Rubber {
+        #[serde(deserialize_with = "de_colour")]
         colour: (f64, f64, f64),
         pattern: Option<PatternInputs>,
     },
     Custom(CustomInputs),
+    // A material from the top level materials map.
+    Ref(String),
+    // A copy of a named material with some fields replaced.
+    Override(OverrideInputs),
+}
+
+// Where objects take a material, a plain name is short for !Ref, unless it's
+// one of the tags, like Mirror.
+struct MaterialOrName(MaterialInputs);
+
+const MATERIAL_KINDS: &[&str] = &["Glass", "Metal", "Plastic", "Mirror", "Water", "Diamond", "Matte", "Rubber", "Custom", "Ref", "Override"];
+
+impl<'de> Deserialize<'de> for MaterialOrName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MaterialVisitor;
+
+        impl<'de> Visitor<'de> for MaterialVisitor {
+            type Value = MaterialOrName;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a material, or the name of one in the materials map")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                if MATERIAL_KINDS.contains(&name) {
+                    MaterialInputs::deserialize(name.into_deserializer()).map(MaterialOrName)
+                } else {
+                    Ok(MaterialOrName(MaterialInputs::Ref(name.to_string())))
+                }
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                MaterialInputs::deserialize(EnumAccessDeserializer::new(data)).map(MaterialOrName)
+            }
+        }
+
+        deserializer.deserialize_any(MaterialVisitor)
+    }
+}
+
+fn de_material<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MaterialInputs, D::Error> {
+    MaterialOrName::deserialize(deserializer).map(|m| m.0)
+}
+
+fn de_material_opt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<MaterialInputs>, D::Error> {
+    Option::<MaterialOrName>::deserialize(deserializer).map(|m| m.map(|m| m.0))
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MetalInputs {
+    #[serde(deserialize_with = "de_colour")]
+    colour: (f64, f64, f64),
+    pattern: Option<PatternInputs>,
+    #[serde(alias = "reflect")]
+    reflective: Option<f64>,
+    roughness: Option<f64>,
+    glossy_samples: Option<usize>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PlasticInputs {
+    #[serde(deserialize_with = "de_colour")]
+    colour: (f64, f64, f64),
+    pattern: Option<PatternInputs>,
+    shininess: Option<f64>,
+}
+
+// Optional changes to the glass preset.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GlassInputs {
+    #[serde(alias = "ior", default, deserialize_with = "de_ior_opt")]
+    refractive_index: Option<f64>,
+    // Absorbed per unit travelled inside, for each of red, green and blue.
+    #[serde(default, deserialize_with = "de_colour_opt")]
+    absorption: Option<(f64, f64, f64)>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PlaneInputs {
+    // Only hit from above, so it can't be seen or cast shadows from below.
+    #[serde(default)]
+    one_sided: bool,
+}
+
+// Opposite corners, instead of the cube from -1 to 1.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BoxInputs {
+    #[serde(deserialize_with = "de_vector")]
+    min: (f64, f64, f64),
+    #[serde(deserialize_with = "de_vector")]
+    max: (f64, f64, f64),
+}
+
+// 1, 1 is a sphere, towards 0 a box.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SuperellipsoidInputs {
+    #[serde(default = "exponent_default")]
+    e1: f64,
+    #[serde(default = "exponent_default")]
+    e2: f64,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DiskInputs {
+    // Angles around the y axis in degrees.
+    #[serde(default)]
+    theta_min: f64,
+    #[serde(default = "theta_max_default")]
+    theta_max: f64,
+    #[serde(default)]
+    one_sided: bool,
+    #[serde(default = "radius_default")]
+    radius: f64,
+    // Makes the disk a ring, from 0 to below radius.
+    #[serde(default)]
+    inner_radius: f64,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OverrideInputs {
+    base: String,
+    #[serde(default, deserialize_with = "de_colour_opt")]
+    colour: Option<(f64, f64, f64)>,
+    pattern: Option<PatternInputs>,
+    ambient: Option<f64>,
+    diffuse: Option<f64>,
+    specular: Option<f64>,
+    shininess: Option<f64>,
+    reflective: Option<f64>,
+    transparency: Option<f64>,
+    #[serde(default, deserialize_with = "de_ior_opt")]
+    refractive_index: Option<f64>,
+    #[serde(default, deserialize_with = "de_colour_opt")]
+    absorption: Option<(f64, f64, f64)>,
+    roughness: Option<f64>,
+    glossy_samples: Option<usize>,
+    specular_model: Option<SpecularModelInput>,
+    diffuse_roughness: Option<f64>,
+    fresnel: Option<bool>,
+    bump: Option<BumpInputs>,
+    uv_pattern: Option<bool>,
+    pattern_blend: Option<PatternBlendInput>,
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
 pub struct CustomInputs {
     
-    #[serde(default = "colour_default")]
+    #[serde(default = "colour_default", deserialize_with = "de_colour")]
     colour: (f64, f64, f64),
     
     #[serde(default)]
@@ -112,420 +431,3653 @@ pub struct CustomInputs {
     #[serde(default)]
     transparency: f64,
 
-    #[serde(default = "refractive_default")]
+    #[serde(default = "refractive_default", deserialize_with = "de_ior")]
     refractive_index: f64,
+
+    #[serde(default, deserialize_with = "de_colour")]
+    absorption: (f64, f64, f64),
+
+    #[serde(default)]
+    roughness: f64,
+
+    #[serde(default = "glossy_samples_default")]
+    glossy_samples: usize,
+
+    #[serde(default)]
+    specular_model: SpecularModelInput,
+
+    #[serde(default)]
+    diffuse_roughness: f64,
+
+    #[serde(default)]
+    fresnel: bool,
+
+    #[serde(default)]
+    bump: Option<BumpInputs>,
+
+    #[serde(default)]
+    uv_pattern: bool,
+
+    #[serde(default)]
+    pattern_blend: PatternBlendInput,
+}
+
+// Tagged by a type field, e.g. { type: !Stripes, ... }, with each kind only
+// taking its own fields.
+#[derive(PartialEq, Debug)]
+pub enum PatternInputs {
+    Stripes(StripesInputs),
+    Gradient(GradientInputs),
+    Rings(ColourPairInputs),
+    Checkers(ColourPairInputs),
+    UvCheckers(UvCheckersInputs),
+    Noise(NoiseInputs),
+    Texture(TextureInputs),
+    Blend(BlendInputs),
+    Perturbed(PerturbedInputs),
+    Debug(DebugInputs),
+}
+
+const PATTERN_KINDS: &[&str] = &["Stripes", "Gradient", "Rings", "Checkers", "UvCheckers", "Noise", "Texture", "Blend", "Perturbed", "Debug"];
+
+impl<'de> Deserialize<'de> for PatternInputs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut map = serde_yaml::Mapping::deserialize(deserializer)?;
+        // Written as a tag, !Stripes, or as a plain string.
+        let kind = match map.remove("type") {
+            Some(serde_yaml::Value::Tagged(tagged)) if tagged.value.is_null() => tagged.tag.to_string(),
+            Some(serde_yaml::Value::String(kind)) => kind,
+            Some(_) => return Err(de::Error::custom("pattern type must be a tag, like !Stripes")),
+            None => return Err(de::Error::missing_field("type")),
+        };
+        let kind = kind.trim_start_matches('!');
+        let fields = serde_yaml::Value::Mapping(map);
+        let pattern = match kind {
+            "Stripes"    => StripesInputs::deserialize(fields).map(PatternInputs::Stripes),
+            "Gradient"   => GradientInputs::deserialize(fields).map(PatternInputs::Gradient),
+            "Rings"      => ColourPairInputs::deserialize(fields).map(PatternInputs::Rings),
+            "Checkers"   => ColourPairInputs::deserialize(fields).map(PatternInputs::Checkers),
+            "UvCheckers" => UvCheckersInputs::deserialize(fields).map(PatternInputs::UvCheckers),
+            "Noise"      => NoiseInputs::deserialize(fields).map(PatternInputs::Noise),
+            "Texture"    => TextureInputs::deserialize(fields).map(PatternInputs::Texture),
+            "Blend"      => BlendInputs::deserialize(fields).map(PatternInputs::Blend),
+            "Perturbed"  => PerturbedInputs::deserialize(fields).map(PatternInputs::Perturbed),
+            "Debug"      => DebugInputs::deserialize(fields).map(PatternInputs::Debug),
+            _ => return Err(de::Error::unknown_variant(kind, PATTERN_KINDS)),
+        };
+        pattern.map_err(|e| de::Error::custom(format!("invalid {} pattern: {}", kind, e)))
+    }
+}
+
+impl PatternInputs {
+    fn transform_mut(&mut self) -> &mut Option<Vec<TransformationInput>> {
+        match self {
+            PatternInputs::Stripes(p)    => &mut p.transform,
+            PatternInputs::Gradient(p)   => &mut p.transform,
+            PatternInputs::Rings(p)
+            | PatternInputs::Checkers(p) => &mut p.transform,
+            PatternInputs::UvCheckers(p) => &mut p.transform,
+            PatternInputs::Noise(p)      => &mut p.transform,
+            PatternInputs::Texture(p)    => &mut p.transform,
+            PatternInputs::Blend(p)      => &mut p.transform,
+            PatternInputs::Perturbed(p)  => &mut p.transform,
+            PatternInputs::Debug(p)      => &mut p.transform,
+        }
+    }
+
+    // Patterns nested in this one, in colour slots, blends and perturbed children.
+    fn children_mut(&mut self) -> Vec<&mut PatternInputs> {
+        fn slots<'a>(a: &'a mut ColourOrPatternInput, b: &'a mut ColourOrPatternInput) -> Vec<&'a mut PatternInputs> {
+            [a, b].into_iter().filter_map(|slot| match slot {
+                ColourOrPatternInput::Pattern(child) => Some(&mut **child),
+                ColourOrPatternInput::Colour(_) => None,
+            }).collect()
+        }
+        match self {
+            PatternInputs::Stripes(p)    => slots(&mut p.colour_a, &mut p.colour_b),
+            PatternInputs::Gradient(p)   => slots(&mut p.colour_a, &mut p.colour_b),
+            PatternInputs::Rings(p)
+            | PatternInputs::Checkers(p) => slots(&mut p.colour_a, &mut p.colour_b),
+            PatternInputs::UvCheckers(p) => slots(&mut p.colour_a, &mut p.colour_b),
+            PatternInputs::Noise(p)      => slots(&mut p.colour_a, &mut p.colour_b),
+            PatternInputs::Blend(p)      => {
+                let mask = match &mut p.mode {
+                    BlendModeInput::Mask(mask) => Some(&mut **mask),
+                    _ => None,
+                };
+                [&mut *p.pattern_a, &mut *p.pattern_b].into_iter().chain(mask).collect()
+            },
+            PatternInputs::Perturbed(p)  => vec![&mut *p.child],
+            PatternInputs::Texture(_)
+            | PatternInputs::Debug(_)    => vec![],
+        }
+    }
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
-pub struct PatternInputs {
-    r#type: PatternType,
-    colour_a: (f64, f64, f64),
-    colour_b: (f64, f64, f64),
+#[serde(deny_unknown_fields)]
+pub struct StripesInputs {
+    colour_a:  ColourOrPatternInput,
+    colour_b:  ColourOrPatternInput,
     transform: Option<Vec<TransformationInput>>,
+    #[serde(default = "stripe_width_default")]
+    width:     f64,
+    // Stripes change along it.
+    #[serde(default)]
+    axis:      AxisInput,
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
-pub enum PatternType {
-    Stripes,
-    Gradient,
-    Rings,
-    Checkers,
+#[serde(deny_unknown_fields)]
+pub struct GradientInputs {
+    colour_a:  ColourOrPatternInput,
+    colour_b:  ColourOrPatternInput,
+    transform: Option<Vec<TransformationInput>>,
+    // How the gradient carries on past 1.
+    #[serde(default)]
+    mode:      GradientModeInput,
 }
 
-#[allow(non_camel_case_types)]
+// Rings and checkers.
 #[derive(Deserialize, PartialEq, Debug)]
-pub enum TransformationInput {
-    Translate(f64, f64, f64),
-    Scale(f64, f64, f64),
-    Scale_uniform(f64),
-    Rotate_x(f64),
-    Rotate_y(f64),
-    Rotate_z(f64),
+#[serde(deny_unknown_fields)]
+pub struct ColourPairInputs {
+    colour_a:  ColourOrPatternInput,
+    colour_b:  ColourOrPatternInput,
+    transform: Option<Vec<TransformationInput>>,
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
-struct LightInputs {
-    position: (f64, f64, f64),
-    colour:   (f64, f64, f64),
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UvCheckersInputs {
+    colour_a:  ColourOrPatternInput,
+    colour_b:  ColourOrPatternInput,
+    transform: Option<Vec<TransformationInput>>,
+    // Squares across u and up v.
+    #[serde(default = "uv_checkers_default")]
+    width:     f64,
+    #[serde(default = "uv_checkers_default")]
+    height:    f64,
 }
 
-pub fn parse_scene<P: AsRef<Path>>(path: P, dimensions: (u32, u32)) -> Result<(Arc<Scene>, Camera)> {
-    
-    let content = read(path).context("Failed to read scene file")?;
-    let a: Inputs = serde_yaml::from_slice(&content).context("Failed to parse scene file")?;
-    
-    let camera = Camera::new(
-        Point3::new(a.camera.look_from.0, a.camera.look_from.1, a.camera.look_from.2),
-        Point3::new(a.camera.look_at.0, a.camera.look_at.1, a.camera.look_at.2),
-        Vec3::new(a.camera.vup.0, a.camera.vup.1, a.camera.vup.2),
-        a.camera.vfov,
-        dimensions,
-        a.camera.aperture,
-    );
+// Perlin noise, see pattern::Noise.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NoiseInputs {
+    colour_a:    ColourOrPatternInput,
+    colour_b:    ColourOrPatternInput,
+    transform:   Option<Vec<TransformationInput>>,
+    #[serde(default)]
+    seed:        u64,
+    #[serde(default = "noise_scale_default")]
+    scale:       f64,
+    #[serde(default = "octaves_default")]
+    octaves:     u32,
+    #[serde(default = "persistence_default")]
+    persistence: f64,
+}
 
-    let mut objects: Vec<Box<dyn Object>> = Vec::new();
-    a.objects.into_iter().for_each(|obj| {
-        
-        let material = parse_material(obj.material);
-        let mut object: Box<dyn Object> = match obj.r#type {
-            
-            ObjectType::Sphere => Box::new(Sphere::new(material)),
-            ObjectType::Plane  => Box::new(Plane::new(material)),
-            ObjectType::Disk   => Box::new(Disk::new(material)),
-            ObjectType::Box    => Box::new(AxisAlignedBoundingBox::new(material)),
-
-            ObjectType::Cylinder { min, max, closed } => Box::new(Cylinder::new(material, min, max, closed)),
-            ObjectType::Cone { min, max, closed }     => Box::new(Cone::new(material, min, max, closed)),
-        };
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TextureInputs {
+    file:      String,
+    #[serde(default)]
+    mapping:   MappingInput,
+    transform: Option<Vec<TransformationInput>>,
+}
 
-        if let Some(transformations) = obj.transform {
-            apply_object_transformations(&mut *object, transformations);
-        }
-        objects.push(object);
-    });
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BlendInputs {
+    pattern_a: Box<PatternInputs>,
+    pattern_b: Box<PatternInputs>,
+    // How the two are combined.
+    #[serde(default)]
+    mode:      BlendModeInput,
+    transform: Option<Vec<TransformationInput>>,
+}
 
-    let lights = parse_lights(a.lights);
-    let background = Colour::new(a.background.0, a.background.1, a.background.2);
-    Ok((Arc::new(Scene::new(objects, lights, background)), camera))
+// A child pattern looked up at points moved by noise, which takes the noise fields.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PerturbedInputs {
+    child:       Box<PatternInputs>,
+    #[serde(default)]
+    amount:      f64,
+    transform:   Option<Vec<TransformationInput>>,
+    #[serde(default)]
+    seed:        u64,
+    #[serde(default = "noise_scale_default")]
+    scale:       f64,
+    #[serde(default = "octaves_default")]
+    octaves:     u32,
+    #[serde(default = "persistence_default")]
+    persistence: f64,
 }
 
-fn parse_material(material: MaterialInputs) -> Material {
-    match material {
-        MaterialInputs::Glass => Material::glass(),
-        MaterialInputs::Metal { colour, pattern } => {
-            Material::metal(Colour::new(colour.0, colour.1, colour.1), pattern.map(parse_pattern))
-        }
-        MaterialInputs::Plastic { colour, pattern } => {
-            Material::plastic(Colour::new(colour.0, colour.1, colour.1), pattern.map(parse_pattern))
-        }
-        MaterialInputs::Custom(custom) => parse_custom(custom),
-    }
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DebugInputs {
+    transform: Option<Vec<TransformationInput>>,
 }
 
-// Should be a better way to do this...
-fn parse_custom(material: CustomInputs) -> Material {
-    Material::new(
-        Colour::new(material.colour.0, material.colour.1, material.colour.2),
-        material.pattern.map(parse_pattern),
-        material.ambient,
-        material.diffuse,
-        material.specular,
-        material.shininess,
-        material.reflective,
-        material.transparency,
-        material.refractive_index,
-    )
-}
-
-fn parse_pattern(pattern: PatternInputs) -> Arc<dyn Pattern> {
-
-    let pattern_out: Arc<dyn Pattern> = match pattern.r#type {
-        PatternType::Stripes => {
-            let mut stripes = Stripes::new(
-                Colour::new(pattern.colour_a.0, pattern.colour_a.1, pattern.colour_a.2),
-                Colour::new(pattern.colour_b.0, pattern.colour_b.1, pattern.colour_b.2),
-            );
-            if let Some(transformations) = pattern.transform {
-                apply_pattern_transformations(&mut stripes, transformations);
+// Colours of stripes, gradients, rings and checkers can be patterns too,
+// written as a map with a type like any other pattern.
+#[derive(PartialEq, Debug)]
+pub enum ColourOrPatternInput {
+    Colour((f64, f64, f64)),
+    Pattern(Box<PatternInputs>),
+}
+
+impl<'de> Deserialize<'de> for ColourOrPatternInput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SlotVisitor;
+
+        impl<'de> Visitor<'de> for SlotVisitor {
+            type Value = ColourOrPatternInput;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a colour, or a pattern")
             }
-            Arc::new(stripes)
-        }
-        PatternType::Gradient => {
-            let mut gradient = Gradient::new(
-                Colour::new(pattern.colour_a.0, pattern.colour_a.1, pattern.colour_a.2),
-                Colour::new(pattern.colour_b.0, pattern.colour_b.1, pattern.colour_b.2),
-            );
-            if let Some(transformations) = pattern.transform {
-                apply_pattern_transformations(&mut gradient, transformations);
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Self::Value, E> {
+                parse_colour_str(text).map(ColourOrPatternInput::Colour).map_err(E::custom)
             }
-            Arc::new(gradient)
-        }
-        PatternType::Rings => {
-            let mut rings = Rings::new(
-                Colour::new(pattern.colour_a.0, pattern.colour_a.1, pattern.colour_a.2),
-                Colour::new(pattern.colour_b.0, pattern.colour_b.1, pattern.colour_b.2),
-            );
-            if let Some(transformations) = pattern.transform {
-                apply_pattern_transformations(&mut rings, transformations);
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                <(f64, f64, f64)>::deserialize(SeqAccessDeserializer::new(seq)).map(ColourOrPatternInput::Colour)
             }
-            Arc::new(rings)
-        }
-        PatternType::Checkers => {
-            let mut checkers = Checkers::new(
-                Colour::new(pattern.colour_a.0, pattern.colour_a.1, pattern.colour_a.2),
-                Colour::new(pattern.colour_b.0, pattern.colour_b.1, pattern.colour_b.2),
-            );
-            if let Some(transformations) = pattern.transform {
-                apply_pattern_transformations(&mut checkers, transformations);
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                PatternInputs::deserialize(MapAccessDeserializer::new(map)).map(|p| ColourOrPatternInput::Pattern(Box::new(p)))
             }
-            Arc::new(checkers)
-        },
-    };
-    pattern_out
+        }
+
+        deserializer.deserialize_any(SlotVisitor)
+    }
 }
 
-fn apply_object_transformations(obj: &mut dyn Object, transformations: Vec<TransformationInput>) {
-    transformations.into_iter().for_each(|transformation| {
-        match transformation {
-            TransformationInput::Translate(x, y, z) => {
-                obj.translate(x, y, z);
-            },
-            TransformationInput::Scale(x, y, z) => {
-                obj.scale(x, y, z);
-            },
-            TransformationInput::Scale_uniform(s) => {
-                obj.scale_uniform(s);
-            },
-            TransformationInput::Rotate_x(angle) => {
-                obj.rotate(Axis::X, angle)
-            },
-            TransformationInput::Rotate_y(angle) => {
-                obj.rotate(Axis::Y, angle)
-            },
-            TransformationInput::Rotate_z(angle) => {
-                obj.rotate(Axis::Z, angle)
-            },
-        }
-    });
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum AxisInput {
+    #[default]
+    X,
+    Y,
+    Z,
 }
 
-// When trait upcasting is stable, this can be removed, and the function above can be us`ed instead.
-fn apply_pattern_transformations(pattern: &mut dyn Pattern, transformations: Vec<TransformationInput>) {
-    transformations.into_iter().for_each(|transformation| {
-        match transformation {
-            TransformationInput::Translate(x, y, z) => {
-                pattern.translate(x, y, z);
-            },
-            TransformationInput::Scale(x, y, z) => {
-                pattern.scale(x, y, z);
-            },
-            TransformationInput::Scale_uniform(s) => {
-                pattern.scale_uniform(s);
-            },
-            TransformationInput::Rotate_x(angle) => {
-                pattern.rotate(Axis::X, angle)
-            },
-            TransformationInput::Rotate_y(angle) => {
-                pattern.rotate(Axis::Y, angle)
-            },
-            TransformationInput::Rotate_z(angle) => {
-                pattern.rotate(Axis::Z, angle)
-            },
+impl From<AxisInput> for Axis {
+    fn from(axis: AxisInput) -> Self {
+        match axis {
+            AxisInput::X => Axis::X,
+            AxisInput::Y => Axis::Y,
+            AxisInput::Z => Axis::Z,
         }
-    });
+    }
 }
 
-fn parse_lights(lights: Vec<LightInputs>) -> Vec<Light> {
-    lights.into_iter().map(|light| {
-        Light::new(
-            Point3::new(light.position.0, light.position.1, light.position.2),
-            Colour::new(light.colour.0, light.colour.1, light.colour.2),
-        )
-    }).collect()
+#[derive(Deserialize, PartialEq, Debug, Default)]
+pub enum BlendModeInput {
+    #[default]
+    Average,
+    Multiply,
+    Mask(Box<PatternInputs>),
 }
 
-fn colour_default() -> (f64, f64, f64) {
-    (1.0, 1.0, 1.0)
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum GradientModeInput {
+    Clamp,
+    #[default]
+    Repeat,
+    Mirror,
 }
 
-fn background_default() -> (f64, f64, f64) {
-    (0.0, 0.0, 0.0)
+impl From<GradientModeInput> for GradientMode {
+    fn from(mode: GradientModeInput) -> Self {
+        match mode {
+            GradientModeInput::Clamp  => GradientMode::Clamp,
+            GradientModeInput::Repeat => GradientMode::Repeat,
+            GradientModeInput::Mirror => GradientMode::Mirror,
+        }
+    }
 }
 
-fn camera_default() -> CameraInputs {
-    CameraInputs {
-        look_from: (0.0, 5.0, 0.0),
-        look_at: (0.0, 0.0, 10.0),
-        vup: (0.0, 1.0, 0.0),
-        vfov: 90.0,
-        aperture: 0.0,
-    }
+#[derive(Deserialize, PartialEq, Debug)]
+pub enum BumpInputs {
+    Waves {
+        #[serde(deserialize_with = "de_number")]
+        amplitude: f64,
+        #[serde(deserialize_with = "de_number")]
+        frequency: f64,
+    },
+    Noise {
+        #[serde(deserialize_with = "de_number")]
+        amplitude: f64,
+        #[serde(default = "bump_scale_default", deserialize_with = "de_number")]
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+    },
 }
 
-fn lights_default() -> Vec<LightInputs> {
-    vec![
-        LightInputs {
-            position: (-10.0, 10.0, -10.0),
-            colour: (1.0, 1.0, 1.0),
-        }
-    ]
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum SpecularModelInput {
+    #[default]
+    Phong,
+    BlinnPhong,
 }
 
-fn min_default() -> f64 {
-    -f64::INFINITY
+impl From<SpecularModelInput> for SpecularModel {
+    fn from(model: SpecularModelInput) -> Self {
+        match model {
+            SpecularModelInput::Phong      => SpecularModel::Phong,
+            SpecularModelInput::BlinnPhong => SpecularModel::BlinnPhong,
+        }
+    }
 }
 
-fn max_default() -> f64 {
-    f64::INFINITY
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum PatternBlendInput {
+    #[default]
+    Replace,
+    Multiply,
+    Screen,
+    Overlay,
 }
 
-fn material_default() -> MaterialInputs {
-    MaterialInputs::Custom(CustomInputs {
-        colour: colour_default(),
-        pattern: None,
-        ambient: ambient_default(),
-        diffuse: diffuse_default(),
-        specular: specular_default(),
-        shininess: shininess_default(),
-        reflective: 0.0,
-        transparency: 0.0,
-        refractive_index: refractive_default(),
+impl From<PatternBlendInput> for PatternBlend {
+    fn from(blend: PatternBlendInput) -> Self {
+        match blend {
+            PatternBlendInput::Replace  => PatternBlend::Replace,
+            PatternBlendInput::Multiply => PatternBlend::Multiply,
+            PatternBlendInput::Screen   => PatternBlend::Screen,
+            PatternBlendInput::Overlay  => PatternBlend::Overlay,
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub enum MappingInput {
+    #[default]
+    Spherical,
+    Planar,
+    Cylindrical,
+    Cube,
+}
+
+impl From<MappingInput> for Mapping {
+    fn from(mapping: MappingInput) -> Self {
+        match mapping {
+            MappingInput::Spherical   => Mapping::Spherical,
+            MappingInput::Planar      => Mapping::Planar,
+            MappingInput::Cylindrical => Mapping::Cylindrical,
+            MappingInput::Cube        => Mapping::Cube,
+        }
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub enum TransformationInput {
+    Translate(#[serde(deserialize_with = "de_number")] f64, #[serde(deserialize_with = "de_number")] f64, #[serde(deserialize_with = "de_number")] f64),
+    Scale(#[serde(deserialize_with = "de_number")] f64, #[serde(deserialize_with = "de_number")] f64, #[serde(deserialize_with = "de_number")] f64),
+    Scale_uniform(#[serde(deserialize_with = "de_number")] f64),
+    Rotate_x(#[serde(deserialize_with = "de_number")] f64),
+    Rotate_y(#[serde(deserialize_with = "de_number")] f64),
+    Rotate_z(#[serde(deserialize_with = "de_number")] f64),
+    // Axis x, y, z then the angle in degrees.
+    Rotate_axis(
+        #[serde(deserialize_with = "de_number")] f64,
+        #[serde(deserialize_with = "de_number")] f64,
+        #[serde(deserialize_with = "de_number")] f64,
+        #[serde(deserialize_with = "de_number")] f64,
+    ),
+    // Rotations and scales about a point instead of the origin.
+    Rotate_x_about {
+        #[serde(deserialize_with = "de_number")]
+        angle: f64,
+        #[serde(deserialize_with = "de_vector")]
+        pivot: (f64, f64, f64),
+    },
+    Rotate_y_about {
+        #[serde(deserialize_with = "de_number")]
+        angle: f64,
+        #[serde(deserialize_with = "de_vector")]
+        pivot: (f64, f64, f64),
+    },
+    Rotate_z_about {
+        #[serde(deserialize_with = "de_number")]
+        angle: f64,
+        #[serde(deserialize_with = "de_vector")]
+        pivot: (f64, f64, f64),
+    },
+    Scale_about {
+        #[serde(deserialize_with = "de_vector")]
+        scale: (f64, f64, f64),
+        #[serde(deserialize_with = "de_vector")]
+        pivot: (f64, f64, f64),
+    },
+    // Moves the origin to from and points the y axis at to.
+    Look_at {
+        #[serde(deserialize_with = "de_vector")]
+        from: (f64, f64, f64),
+        #[serde(deserialize_with = "de_vector")]
+        to:   (f64, f64, f64),
+        #[serde(default = "up_default", deserialize_with = "de_vector")]
+        up:   (f64, f64, f64),
+    },
+    // A list from the top level transforms map, expanded in place.
+    Apply(String),
+}
+
+// Plain maps are point lights, other kinds are tagged, e.g. !Directional.
+#[derive(Debug, PartialEq)]
+enum LightInputs {
+    Point(PointLightInputs),
+    Directional(DirectionalLightInputs),
+    Area(AreaLightInputs),
+    Sphere(SphereLightInputs),
+}
+
+const LIGHT_KINDS: &[&str] = &["Point", "Directional", "Area", "SphereLight"];
+
+impl<'de> Deserialize<'de> for LightInputs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LightVisitor;
+
+        impl<'de> Visitor<'de> for LightVisitor {
+            type Value = LightInputs;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a point light, or a light tagged with its kind")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                PointLightInputs::deserialize(MapAccessDeserializer::new(map)).map(LightInputs::Point)
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                let (kind, variant) = data.variant::<String>()?;
+                match kind.as_str() {
+                    "Point" => variant.newtype_variant().map(LightInputs::Point),
+                    "Directional" => variant.newtype_variant().map(LightInputs::Directional),
+                    "Area" => variant.newtype_variant().map(LightInputs::Area),
+                    "SphereLight" => variant.newtype_variant().map(LightInputs::Sphere),
+                    _ => Err(de::Error::unknown_variant(&kind, LIGHT_KINDS)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(LightVisitor)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct PointLightInputs {
+    #[serde(deserialize_with = "de_vector")]
+    position: (f64, f64, f64),
+    #[serde(default = "colour_default", deserialize_with = "de_colour")]
+    colour:   (f64, f64, f64),
+    // Colour temperature in Kelvin, tints the colour.
+    #[serde(default)]
+    temperature: Option<f64>,
+    #[serde(default = "intensity_default")]
+    intensity: f64,
+    #[serde(default = "enabled_default")]
+    enabled: bool,
+    // Constant, linear and quadratic falloff with distance.
+    #[serde(default = "attenuation_default")]
+    attenuation: (f64, f64, f64),
+}
+
+// Sunlight, travelling along direction from infinitely far away.
+#[derive(Deserialize, Debug, PartialEq)]
+struct DirectionalLightInputs {
+    #[serde(deserialize_with = "de_vector")]
+    direction: (f64, f64, f64),
+    #[serde(default = "colour_default", deserialize_with = "de_colour")]
+    colour:   (f64, f64, f64),
+    #[serde(default)]
+    temperature: Option<f64>,
+    #[serde(default = "intensity_default")]
+    intensity: f64,
+    #[serde(default = "enabled_default")]
+    enabled: bool,
+}
+
+// A rectangle from corner along u and v, giving soft shadows.
+#[derive(Deserialize, Debug, PartialEq)]
+struct AreaLightInputs {
+    #[serde(deserialize_with = "de_vector")]
+    corner: (f64, f64, f64),
+    #[serde(deserialize_with = "de_vector")]
+    u:      (f64, f64, f64),
+    #[serde(deserialize_with = "de_vector")]
+    v:      (f64, f64, f64),
+    // Shadow rays per point shaded.
+    #[serde(default = "area_samples_default")]
+    samples: usize,
+    #[serde(default = "colour_default", deserialize_with = "de_colour")]
+    colour:   (f64, f64, f64),
+    #[serde(default)]
+    temperature: Option<f64>,
+    #[serde(default = "intensity_default")]
+    intensity: f64,
+    #[serde(default = "enabled_default")]
+    enabled: bool,
+}
+
+// A glowing ball, giving soft shadows.
+#[derive(Deserialize, Debug, PartialEq)]
+struct SphereLightInputs {
+    #[serde(deserialize_with = "de_vector")]
+    position: (f64, f64, f64),
+    radius:   f64,
+    #[serde(default = "area_samples_default")]
+    samples: usize,
+    #[serde(default = "colour_default", deserialize_with = "de_colour")]
+    colour:   (f64, f64, f64),
+    #[serde(default)]
+    temperature: Option<f64>,
+    #[serde(default = "intensity_default")]
+    intensity: f64,
+    #[serde(default = "enabled_default")]
+    enabled: bool,
+}
+
+// A colour for a flat background, or a tagged kind of background.
+#[derive(Debug, PartialEq)]
+enum BackgroundInput {
+    Flat((f64, f64, f64)),
+    VerticalGradient {
+        top:    (f64, f64, f64),
+        bottom: (f64, f64, f64),
+    },
+    Environment(EnvironmentInputs),
+}
+
+impl Default for BackgroundInput {
+    fn default() -> Self {
+        BackgroundInput::Flat((0.0, 0.0, 0.0))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GradientBackgroundInputs {
+    #[serde(deserialize_with = "de_colour")]
+    top:    (f64, f64, f64),
+    #[serde(deserialize_with = "de_colour")]
+    bottom: (f64, f64, f64),
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentInputs {
+    // Equirectangular image, HDR, EXR or anything else textures can load.
+    file: String,
+    // Degrees about y, like !Rotate_y.
+    #[serde(default, deserialize_with = "de_number")]
+    rotation: f64,
+}
+
+const BACKGROUND_KINDS: &[&str] = &["Flat", "VerticalGradient", "Environment"];
+
+impl<'de> Deserialize<'de> for BackgroundInput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BackgroundVisitor;
+
+        impl<'de> Visitor<'de> for BackgroundVisitor {
+            type Value = BackgroundInput;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a colour, or a background tagged with its kind")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Self::Value, E> {
+                parse_colour_str(text).map(BackgroundInput::Flat).map_err(E::custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                <(f64, f64, f64)>::deserialize(SeqAccessDeserializer::new(seq)).map(BackgroundInput::Flat)
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                let (kind, variant) = data.variant::<String>()?;
+                match kind.as_str() {
+                    "Flat" => variant.newtype_variant::<ColourInput>()?
+                        .try_into()
+                        .map(BackgroundInput::Flat)
+                        .map_err(de::Error::custom),
+                    "VerticalGradient" => {
+                        let gradient: GradientBackgroundInputs = variant.newtype_variant()?;
+                        Ok(BackgroundInput::VerticalGradient { top: gradient.top, bottom: gradient.bottom })
+                    },
+                    "Environment" => variant.newtype_variant().map(BackgroundInput::Environment),
+                    _ => Err(de::Error::unknown_variant(&kind, BACKGROUND_KINDS)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(BackgroundVisitor)
+    }
+}
+
+// Environment images are loaded now, so a missing one fails before rendering.
+fn parse_background(background: BackgroundInput) -> Result<Background> {
+    let colour = |c: (f64, f64, f64)| Colour::new(c.0, c.1, c.2);
+    Ok(match background {
+        BackgroundInput::Flat(c) => Background::Flat(colour(c)),
+        BackgroundInput::VerticalGradient { top, bottom } => Background::VerticalGradient { top: colour(top), bottom: colour(bottom) },
+        BackgroundInput::Environment(environment) => Background::Environment {
+            map: Arc::new(Texture::load(&environment.file, Mapping::Spherical)?),
+            rotation: environment.rotation,
+        },
     })
 }
 
-fn ambient_default() -> f64 {
-    0.1
+// Colours can be written as [r, g, b], "#rrggbb" or a name like "red".
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColourInput {
+    Rgb(f64, f64, f64),
+    Text(String),
 }
 
-fn diffuse_default() -> f64 {
-    0.9
+impl TryFrom<ColourInput> for (f64, f64, f64) {
+    type Error = String;
+
+    fn try_from(colour: ColourInput) -> Result<Self, Self::Error> {
+        match colour {
+            ColourInput::Rgb(r, g, b) => Ok((r, g, b)),
+            ColourInput::Text(text) => parse_colour_str(&text),
+        }
+    }
 }
 
-fn specular_default() -> f64 {
-    0.9
+fn parse_colour_str(text: &str) -> Result<(f64, f64, f64), String> {
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(format!("invalid hex colour \"{}\", expected #rrggbb", text));
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16)
+            .map(|c| c as f64 / 255.0)
+            .map_err(|_| format!("invalid hex colour \"{}\"", text));
+        return Ok((channel(0)?, channel(2)?, channel(4)?));
+    }
+
+    let colour = match text.to_lowercase().as_str() {
+        "red"    => colour::RED,
+        "green"  => colour::GREEN,
+        "blue"   => colour::BLUE,
+        "white"  => colour::WHITE,
+        "black"  => colour::BLACK,
+        "pink"   => colour::PINK,
+        "yellow" => colour::YELLOW,
+        "cyan"   => colour::CYAN,
+        "orange" => colour::ORANGE,
+        _ => return Err(format!("unknown colour \"{}\"", text)),
+    };
+    let rgb: Vec3 = colour.into();
+    Ok((rgb.x, rgb.y, rgb.z))
 }
 
-fn shininess_default() -> f64 {
-    200.0
+// Indices of refraction can be a number or a name from material::ior.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IorInput {
+    Value(f64),
+    Name(String),
 }
 
-fn refractive_default() -> f64 {
-    1.0
+impl TryFrom<IorInput> for f64 {
+    type Error = String;
+
+    fn try_from(ior: IorInput) -> Result<Self, Self::Error> {
+        match ior {
+            IorInput::Value(value) => Ok(value),
+            IorInput::Name(name) => material::ior::from_name(&name).ok_or_else(|| {
+                let names: Vec<&str> = material::ior::NAMED.iter().map(|(n, _)| *n).collect();
+                format!("unknown refractive index \"{}\", expected a number or one of: {}", name, names.join(", "))
+            }),
+        }
+    }
 }
 
-fn from_default() -> (f64, f64, f64) {
-    (0.0, 0.0, 0.0)
+fn de_ior<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    IorInput::deserialize(deserializer)?
+        .try_into()
+        .map_err(serde::de::Error::custom)
 }
 
-fn at_default() -> (f64, f64, f64) {
-    (0.0, 0.0, -1.0)
+fn de_ior_opt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Option::<IorInput>::deserialize(deserializer)?
+        .map(|ior| ior.try_into().map_err(serde::de::Error::custom))
+        .transpose()
 }
 
-fn up_default() -> (f64, f64, f64) {
-    (0.0, 1.0, 0.0)
+// Numbers can also be written as expressions, e.g. "360 / 7".
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberInput {
+    Number(f64),
+    Expr(String),
 }
 
-fn vfov_default() -> f64 {
-    90.0
+impl TryFrom<NumberInput> for f64 {
+    type Error = String;
+
+    fn try_from(number: NumberInput) -> Result<Self, Self::Error> {
+        match number {
+            NumberInput::Number(n) => Ok(n),
+            NumberInput::Expr(expr) => eval(&expr).map_err(|e| e.to_string()),
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn de_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    NumberInput::deserialize(deserializer)?
+        .try_into()
+        .map_err(serde::de::Error::custom)
+}
 
-    // Make sure the test scene file parses without error.
-    #[test]
-    fn test_simple_input() {
-        
-        let yaml = "
-            camera:
-                look_from:  [1.0, 2.0, 3.0]
-                look_at:    [4.0, 5.0, 6.0]
-                vup:        [7.0, 8.0, 9.0]
-                vfov:       90.0
-                aperture:   0.0
-                focus_dist: 1.0
+fn de_number_opt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Option::<NumberInput>::deserialize(deserializer)?
+        .map(|n| n.try_into().map_err(serde::de::Error::custom))
+        .transpose()
+}
 
-            objects:
-                - type: !Sphere
-                  material: !Custom
-                    colour: [1.0, 0.0, 0.0]
-                    ambient: 0.1
-                    diffuse: 0.9
-                    specular: 0.9
-                    shininess: 200.0
-                    reflective: 0.0
-                    transparency: 0.0
-                    refractive_index: 1.0
+fn de_vector<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(f64, f64, f64), D::Error> {
+    let (x, y, z) = <(NumberInput, NumberInput, NumberInput)>::deserialize(deserializer)?;
+    let number = |n: NumberInput| f64::try_from(n).map_err(serde::de::Error::custom);
+    Ok((number(x)?, number(y)?, number(z)?))
+}
 
-                  transform:
-                    - !Translate [0.0, 0.0, -1.0]
-                    - !Scale [0.5, 0.5, 0.5]
+fn de_colour<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(f64, f64, f64), D::Error> {
+    ColourInput::deserialize(deserializer)?
+        .try_into()
+        .map_err(serde::de::Error::custom)
+}
 
-            lights:
-                - position: [0.0, 0.0, -10.0]
-                  colour:   [1.0, 1.0, 1.0]
-        ";
+fn de_colour_opt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<(f64, f64, f64)>, D::Error> {
+    Option::<ColourInput>::deserialize(deserializer)?
+        .map(|c| c.try_into().map_err(serde::de::Error::custom))
+        .transpose()
+}
 
-        let a: Inputs = serde_yaml::from_str(yaml).unwrap();
-        assert_eq!(a.camera.look_from, (1.0, 2.0, 3.0));
-        assert_eq!(a.camera.look_at, (4.0, 5.0, 6.0));
-        assert_eq!(a.camera.vup, (7.0, 8.0, 9.0));
-        assert_eq!(a.camera.vfov, 90.0);
-        assert_eq!(a.camera.aperture, 0.0);
+pub fn parse_scene<P: AsRef<Path>>(path: P, dimensions: (u32, u32)) -> Result<(Arc<Scene>, Camera)> {
+    parse_scene_file(path, dimensions, &[]).map(|(scene, camera, _)| (scene, camera))
+}
 
-        assert_eq!(a.objects.len(), 1);
-        assert_eq!(a.objects[0].r#type, ObjectType::Sphere);
-        assert_eq!(a.objects[0].material, 
-            MaterialInputs::Custom(CustomInputs {
-                colour: (1.0, 0.0, 0.0),
-                pattern: None,
-                ambient: ambient_default(),
-                diffuse: diffuse_default(),
-                specular: specular_default(),
-                shininess: shininess_default(),
-                reflective: 0.0,
-                transparency: 0.0,
-                refractive_index: refractive_default(),
-            }));
-        assert_eq!(a.objects[0].transform, Some(vec![
-            TransformationInput::Translate(0.0, 0.0, -1.0),
-            TransformationInput::Scale(0.5, 0.5, 0.5),
-        ]));
+// Also returns the render settings given in the file.
+// Params are (name, value) pairs overriding the file's params block.
+pub fn parse_scene_file<P: AsRef<Path>>(
+    path: P,
+    dimensions: (u32, u32),
+    params: &[(String, String)],
+) -> Result<(Arc<Scene>, Camera, RenderOverrides)> {
 
-        assert_eq!(a.lights.len(), 1);
-        assert_eq!(a.lights[0].position, (0.0, 0.0, -10.0));
-        assert_eq!(a.lights[0].colour, (1.0, 1.0, 1.0));
+    // Models are rendered on their own, framed by a default camera.
+    let path = path.as_ref();
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glb") || ext.eq_ignore_ascii_case("gltf")) {
+        #[cfg(feature = "gltf")]
+        return super::gltf::gltf_scene(path, dimensions);
+        #[cfg(not(feature = "gltf"))]
+        bail!("{} is a glTF model, which needs the gltf feature", path.display());
     }
 
-    #[test]
-    fn test_input_from_file() {
-        let a: Inputs = serde_yaml::from_slice(&read("scenes/tests/test_input.yaml").unwrap()).unwrap();
-        
-        assert_eq!(a.camera.look_from, (0.0, 0.0, 2.0));
-        assert_eq!(a.camera.vfov, 15.0);
+    let mut a = read_scene_file(path, params)?;
+    // Files the scene loads are relative to its directory.
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for file in input_files(&mut a) {
+        *file = dir.join(&*file).to_string_lossy().into_owned();
+    }
+    build_scene_with_render(a, dimensions)
+}
 
-        let sphere = &a.objects[0];
-        assert_eq!(sphere.r#type, ObjectType::Sphere);
-        assert_eq!(sphere.material, MaterialInputs::Plastic {
-            colour: (1.0, 0.0, 1.0),
-            pattern: Some(
-                PatternInputs {
-                    r#type: PatternType::Stripes,
-                    colour_a: (1.0, 0.0, 1.0),
-                    colour_b: (0.0, 0.0, 1.0),
-                    transform: Some(vec![
-                        TransformationInput::Scale_uniform(0.1),
-                        TransformationInput::Rotate_z(90.0)
-                    ]),
-                }
-            )
-        });
-        assert_eq!(sphere.transform, Some(vec![
-            TransformationInput::Translate(30.0, 30.0, 2.0),
-            TransformationInput::Scale_uniform(4.0),
-        ]));
+// Paths of the files a scene file loads, like meshes and textures, not
+// including the scene itself.
+pub fn scene_files<P: AsRef<Path>>(path: P, params: &[(String, String)]) -> Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glb") || ext.eq_ignore_ascii_case("gltf")) {
+        return Ok(vec![]);
+    }
+    let mut a = read_scene_file(path, params)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(input_files(&mut a).into_iter().map(|file| dir.join(&*file)).collect())
+}
 
-        let cone = &a.objects[1];
-        assert_eq!(cone.r#type, ObjectType::Cone {
-            min: -f64::INFINITY,
-            max: f64::INFINITY,
-            closed: false,
-        });
-        assert_eq!(cone.material, MaterialInputs::Glass);
-        assert_eq!(cone.transform, Some(vec![TransformationInput::Rotate_x(45.0)]));
+fn read_scene_file(path: &Path, params: &[(String, String)]) -> Result<Inputs> {
+    let content = read(path).context("Failed to read scene file")?;
+    read_inputs(&String::from_utf8_lossy(&content), params)
+}
 
-        let boxx = &a.objects[2];
-        assert_eq!(boxx.r#type, ObjectType::Box);
-        assert_eq!(boxx.material, MaterialInputs::Metal {
-            colour: (1.0, 0.5, 1.0),
-            pattern: None,
-        });
+fn read_inputs(content: &str, params: &[(String, String)]) -> Result<Inputs> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(content).context("Failed to parse scene file")?;
+    substitute_params(&mut value, params)?;
+    serde_yaml::from_value(value).context("Failed to parse scene file")
+}
 
-        let lights = &a.lights;
-        assert_eq!(lights[0], LightInputs {
-            position: (-10.0, 30.0, 20.0),
-            colour: (1.0, 1.0, 1.0),
-        });
+fn parse_scene_str(content: &str, dimensions: (u32, u32), params: &[(String, String)]) -> Result<(Arc<Scene>, Camera, RenderOverrides)> {
+    build_scene_with_render(read_inputs(content, params)?, dimensions)
+}
+
+fn build_scene_with_render(mut a: Inputs, dimensions: (u32, u32)) -> Result<(Arc<Scene>, Camera, RenderOverrides)> {
+    let render = std::mem::take(&mut a.render);
+    let (scene, camera) = build_scene(a, dimensions)?;
+    Ok((scene, camera, render))
+}
+
+fn build_scene(mut a: Inputs, dimensions: (u32, u32)) -> Result<(Arc<Scene>, Camera)> {
+    
+    resolve_named_transforms(&mut a)?;
+
+    let look_from = Point3::new(a.camera.look_from.0, a.camera.look_from.1, a.camera.look_from.2);
+    let look_at = Point3::new(a.camera.look_at.0, a.camera.look_at.1, a.camera.look_at.2);
+    let origin = if a.camera.relative { look_from.coords } else { Vec3::zeros() };
+    let focus_dist = a.camera.focus_dist.unwrap_or_else(|| (look_at - look_from).norm());
+    if focus_dist <= 0.0 {
+        bail!("Camera has focus_dist {}, it must be positive", focus_dist);
+    }
+    let mut camera = Camera::new(
+        look_from - origin,
+        look_at - origin,
+        Vec3::new(a.camera.vup.0, a.camera.vup.1, a.camera.vup.2),
+        a.camera.vfov,
+        dimensions,
+        a.camera.aperture,
+        focus_dist,
+    );
+    let (open, close) = a.camera.shutter;
+    if open > close {
+        bail!("Camera shutter closes at {} before it opens at {}", close, open);
+    }
+    camera.set_shutter(open, close);
+    camera.set_projection(a.camera.projection.into());
+    if let Some(transformations) = a.camera.transform {
+        // About the scene's origin, even when it is recentred on the camera.
+        camera.translate(-origin.x, -origin.y, -origin.z);
+        apply_transformations(&mut camera, transformations);
+        camera.translate(origin.x, origin.y, origin.z);
     }
-}
\ No newline at end of file
+
+    let named = parse_named_materials(a.materials)?;
+    let default_material = parse_material(a.default_material, &named)
+        .context("Invalid default material")?;
+
+    // Prototypes can't instance each other.
+    let mut prototypes = HashMap::new();
+    for (name, obj) in a.prototypes {
+        let prototype = parse_object(obj, &format!("\"{}\"", name), &named, &HashMap::new(), &default_material)
+            .with_context(|| format!("Invalid prototype {}", name))?;
+        prototypes.insert(name, Arc::from(prototype));
+    }
+
+    let mut names: Vec<Option<String>> = vec![];
+    for (i, obj) in a.objects.iter().enumerate() {
+        if let Some(name) = &obj.name {
+            if names.contains(&obj.name) {
+                bail!("Object {} is named \"{}\", which is already taken", i, name);
+            }
+        }
+        names.push(obj.name.clone());
+    }
+    let objects = a.objects.into_iter()
+        .enumerate()
+        .map(|(i, obj)| {
+            let label = object_label(i, &obj.name);
+            parse_object(obj, &label, &named, &prototypes, &default_material)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let lights = parse_lights(a.lights)?;
+    let mut scene = Scene::new(objects, lights, parse_background(a.background)?);
+    scene.set_default_material(default_material);
+    if [a.ambient.0, a.ambient.1, a.ambient.2].iter().any(|&v| v < 0.0) {
+        bail!("Scene has invalid ambient {:?}, must not be negative", a.ambient);
+    }
+    scene.ambient_light = Colour::new(a.ambient.0, a.ambient.1, a.ambient.2);
+    let ids: Vec<ObjectId> = scene.objects.iter().map(|obj| obj.id()).collect();
+    for (id, name) in ids.into_iter().zip(names) {
+        if let Some(name) = name {
+            scene.set_name(id, name);
+        }
+    }
+    if a.camera.relative {
+        scene.recentre(look_from);
+    }
+    Ok((Arc::new(scene), camera))
+}
+
+// Index of a top level object, and its name if it has one.
+fn object_label(i: usize, name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("{} \"{}\"", i, name),
+        None => i.to_string(),
+    }
+}
+
+// label names the top level object in errors, see object_label.
+fn parse_object(
+    obj:        ObjectInputs,
+    label:      &str,
+    named:      &HashMap<String, Arc<Material>>,
+    prototypes: &HashMap<String, Arc<dyn Object>>,
+    fallback:   &Arc<Material>,
+) -> Result<Box<dyn Object>> {
+
+    // Instances keep their prototype's material unless given one.
+    let has_material = obj.material.is_some();
+    let mut material = match obj.material {
+        Some(material) => parse_material(material, named)
+            .with_context(|| format!("Invalid material on object {}", label))?,
+        None => Arc::clone(fallback),
+    };
+    if obj.shadow_catcher {
+        Arc::make_mut(&mut material).shadow_catcher = true;
+    }
+    let mut material_back = obj.material_back
+        .map(|back| parse_material(back, named))
+        .transpose()
+        .with_context(|| format!("Invalid back material on object {}", label))?;
+    if let (Some(back), true) = (&mut material_back, obj.shadow_catcher) {
+        Arc::make_mut(back).shadow_catcher = true;
+    }
+    if material_back.is_some() && !matches!(obj.r#type, ObjectType::Plane(_) | ObjectType::Disk(_)) {
+        bail!("Object {} has a back material, which is only supported on planes and disks", label);
+    }
+
+    let mut object: Box<dyn Object> = match obj.r#type {
+        
+        ObjectType::Sphere => Box::new(Sphere::new(material)),
+        ObjectType::Ellipsoid { radii: (rx, ry, rz) } => {
+            if rx <= 0.0 || ry <= 0.0 || rz <= 0.0 {
+                bail!("Ellipsoid {} has radii ({}, {}, {}), they must be positive", label, rx, ry, rz);
+            }
+            Box::new(Sphere::ellipsoid(material, rx, ry, rz))
+        },
+        ObjectType::Plane(inputs) => {
+            let mut plane = Plane::new(material);
+            if let Some(back) = material_back {
+                plane.set_material_back(back);
+            }
+            if let Some(PlaneInputs { one_sided }) = inputs {
+                plane.set_one_sided(one_sided);
+            }
+            Box::new(plane)
+        },
+        ObjectType::Disk(inputs) => {
+            let radius = inputs.as_ref().map_or(1.0, |inputs| inputs.radius);
+            if radius <= 0.0 {
+                bail!("Disk {} has radius {}, it must be positive", label, radius);
+            }
+            let mut disk = Disk::with_radius(material, radius);
+            if let Some(back) = material_back {
+                disk.set_material_back(back);
+            }
+            if let Some(DiskInputs { theta_min, theta_max, one_sided, radius, inner_radius }) = inputs {
+                check_sweep(theta_min, theta_max).with_context(|| format!("Invalid disk {}", label))?;
+                if !(0.0..radius).contains(&inner_radius) {
+                    bail!("Disk {} has inner_radius {}, it must be from 0 to below its radius {}", label, inner_radius, radius);
+                }
+                disk.set_inner_radius(inner_radius);
+                disk.set_sweep(theta_min, theta_max);
+                disk.set_one_sided(one_sided);
+            }
+            Box::new(disk)
+        },
+        ObjectType::Box(None) => Box::new(AxisAlignedBoundingBox::new(material)),
+        ObjectType::Box(Some(BoxInputs { min, max })) => {
+            if min.0 >= max.0 || min.1 >= max.1 || min.2 >= max.2 {
+                bail!("Box {} has min {:?} and max {:?}, min must be below max on every axis", label, min, max);
+            }
+            let min = Point3::new(min.0, min.1, min.2);
+            let max = Point3::new(max.0, max.1, max.2);
+            Box::new(AxisAlignedBoundingBox::from_corners(material, min, max))
+        },
+
+        ObjectType::Cylinder { radius, radius_bottom, radius_top, min, max, closed, theta_min, theta_max } => {
+            check_sweep(theta_min, theta_max).with_context(|| format!("Invalid cylinder {}", label))?;
+            let (bottom, top) = (radius_bottom.unwrap_or(radius), radius_top.unwrap_or(radius));
+            if radius <= 0.0 || bottom < 0.0 || top < 0.0 || bottom.max(top) <= 0.0 {
+                bail!("Cylinder {} needs a positive radius", label);
+            }
+            if bottom != top && !(min.is_finite() && max.is_finite()) {
+                bail!("Cylinder {} has different end radii, so needs a finite min and max", label);
+            }
+            let mut cylinder = Cylinder::new(material, radius, min, max, closed);
+            cylinder.set_radii(bottom, top);
+            cylinder.set_sweep(theta_min, theta_max);
+            Box::new(cylinder)
+        },
+        ObjectType::Cone { angle, radius, min, max, closed } => {
+            let angle = match (angle, radius) {
+                (Some(_), Some(_)) => bail!("Cone {} has both an angle and a radius, give one", label),
+                (Some(angle), None) => angle,
+                (None, Some(radius)) => {
+                    let height = min.abs().max(max.abs());
+                    if !height.is_finite() || height == 0.0 || radius <= 0.0 {
+                        bail!("Cone {} needs a positive radius and finite min and max to set its width by radius", label);
+                    }
+                    (radius / height).atan().to_degrees()
+                },
+                (None, None) => 45.0,
+            };
+            if !(angle > 0.0 && angle < 90.0) {
+                bail!("Cone {} has angle {}, it must be between 0 and 90 degrees", label, angle);
+            }
+            Box::new(Cone::new(material, angle, min, max, closed))
+        },
+        ObjectType::Lathe { profile, closed }     => {
+            if profile.len() < 2 {
+                bail!("Lathe {} needs at least 2 profile points", label);
+            }
+            if profile.iter().any(|&(r, _)| r < 0.0 || r.is_nan()) {
+                bail!("Lathe {} has a negative profile radius", label);
+            }
+            Box::new(Lathe::new(material, &profile, closed))
+        },
+        ObjectType::Prism { points, min, max, closed } => {
+            if !Prism::is_convex(&points) {
+                bail!("Prism {} must have at least 3 points forming a convex polygon", label);
+            }
+            Box::new(Prism::new(material, &points, min, max, closed))
+        },
+        ObjectType::Csg { op, left, right } => {
+            // Children without a material use the csg's.
+            let left = parse_object(*left, label, named, prototypes, &material).with_context(|| format!("Invalid left side of csg {}", label))?;
+            let right = parse_object(*right, label, named, prototypes, &material).with_context(|| format!("Invalid right side of csg {}", label))?;
+            Box::new(Csg::new(material, op, left, right))
+        },
+        ObjectType::Heightfield { file, vertical_scale } => {
+            Box::new(Heightfield::load(material, &file, vertical_scale).with_context(|| format!("Invalid heightfield {}", label))?)
+        },
+        ObjectType::Sdf { shape, max_steps, hit_epsilon } => {
+            if let SdfShape::Gyroid { scale, .. } = shape {
+                if !(scale.is_finite() && scale > 0.0) {
+                    bail!("Sdf {} has gyroid scale {}, it must be positive", label, scale);
+                }
+            }
+            let bounds = shape.bounds();
+            let mut sdf = SdfObject::new(material, shape.build());
+            sdf.bounds = bounds;
+            if let Some(max_steps) = max_steps {
+                sdf.max_steps = max_steps;
+            }
+            if let Some(hit_epsilon) = hit_epsilon {
+                if hit_epsilon <= 0.0 {
+                    bail!("Sdf {} has hit_epsilon {}, it must be positive", label, hit_epsilon);
+                }
+                sdf.hit_epsilon = hit_epsilon;
+            }
+            Box::new(sdf)
+        },
+        ObjectType::Instance(name) => {
+            let prototype = prototypes.get(&name).ok_or_else(|| anyhow!("Object {} is an instance of unknown prototype {}", label, name))?;
+            let mut instance = Instance::new(Arc::clone(prototype));
+            if has_material {
+                instance.set_material(material);
+            } else if obj.shadow_catcher {
+                instance.set_shadow_catcher();
+            }
+            Box::new(instance)
+        },
+        ObjectType::Lens { r1, r2, thickness } => {
+            if r1 <= 0.0 || r2 <= 0.0 || thickness <= 0.0 || thickness >= r1 + r2 {
+                bail!("Lens {} needs positive r1, r2 and thickness, with thickness less than r1 + r2", label);
+            }
+            let material = if has_material { material } else { Arc::new(Material { shadow_catcher: obj.shadow_catcher, ..Material::glass() }) };
+            Box::new(Lens::new(material, r1, r2, thickness))
+        },
+        ObjectType::Superellipsoid(inputs) => {
+            let (e1, e2) = inputs.map_or((1.0, 1.0), |inputs| (inputs.e1, inputs.e2));
+            if !(e1 > 0.0 && e1 <= 2.0 && e2 > 0.0 && e2 <= 2.0) {
+                bail!("Superellipsoid {} has exponents {} and {}, they must be above 0 and at most 2", label, e1, e2);
+            }
+            Box::new(Superellipsoid::new(material, e1, e2))
+        },
+        ObjectType::Parametric { preset, u_steps, v_steps } => {
+            if u_steps == 0 || v_steps == 0 {
+                bail!("Parametric {} needs at least 1 step each way", label);
+            }
+            Box::new(Mesh::from_parametric(|u, v| preset.point(u, v), u_steps, v_steps, material))
+        },
+        ObjectType::Hexagon => builders::hexagon(material),
+        ObjectType::Mesh { file } => Box::new(Mesh::load(material, &file).with_context(|| format!("Invalid mesh {}", label))?),
+    };
+
+    // The list goes on top of any transform the object was built with, like an
+    // ellipsoid's stretch, so it moves them like any other object.
+    if let Some(transformations) = obj.transform {
+        let (own, own_inverse) = (*object.transform(), *object.inverse());
+        object.set_transform(Matrix4::identity());
+        object.set_inverse(Matrix4::identity());
+        apply_transformations(&mut *object, transformations);
+        object.set_transform(object.transform() * own);
+        object.set_inverse(own_inverse * object.inverse());
+    }
+    if let Some(epsilon) = obj.epsilon {
+        if epsilon <= 0.0 || !epsilon.is_finite() {
+            bail!("Object {} has epsilon {}, it must be positive", label, epsilon);
+        }
+        object.set_epsilon(epsilon);
+    }
+    object.set_cast_shadow(obj.cast_shadow);
+    object.set_visible_to_camera(obj.visible_to_camera);
+    if let Some(MotionInputs { start, end }) = obj.motion {
+        object = Box::new(Moving::new(object, Vec3::new(start.0, start.1, start.2), Vec3::new(end.0, end.1, end.2)));
+    }
+    Ok(object)
+}
+
+fn parse_named_materials(materials: HashMap<String, MaterialInputs>) -> Result<HashMap<String, Arc<Material>>> {
+    let empty = HashMap::new();
+    materials.into_iter()
+        .map(|(name, material)| {
+            // Named materials can't refer to each other.
+            let material = parse_material(material, &empty)
+                .with_context(|| format!("Invalid named material \"{}\"", name))?;
+            Ok((name, material))
+        })
+        .collect()
+}
+
+fn lookup_material<'a>(name: &str, named: &'a HashMap<String, Arc<Material>>) -> Result<&'a Arc<Material>> {
+    named.get(name).ok_or_else(|| anyhow!("Unknown material \"{}\"", name))
+}
+
+fn parse_material(material: MaterialInputs, named: &HashMap<String, Arc<Material>>) -> Result<Arc<Material>> {
+    let material = match material {
+        MaterialInputs::Glass(glass) => {
+            let mut material = Material::glass();
+            if let Some(glass) = glass {
+                material.refractive_index = glass.refractive_index.unwrap_or(material.refractive_index);
+                if let Some(absorption) = glass.absorption {
+                    material.absorption = Colour::new(absorption.0, absorption.1, absorption.2);
+                }
+            }
+            material
+        }
+        MaterialInputs::Metal(MetalInputs { colour, pattern, reflective, roughness, glossy_samples }) => {
+            let mut material = Material::metal(Colour::new(colour.0, colour.1, colour.2), pattern.map(parse_pattern).transpose()?);
+            material.reflect = reflective.unwrap_or(material.reflect);
+            material.roughness = roughness.unwrap_or(material.roughness);
+            material.glossy_samples = glossy_samples.unwrap_or(material.glossy_samples);
+            material
+        }
+        MaterialInputs::Plastic(PlasticInputs { colour, pattern, shininess }) => {
+            let mut material = Material::plastic(Colour::new(colour.0, colour.1, colour.2), pattern.map(parse_pattern).transpose()?);
+            material.shininess = shininess.unwrap_or(material.shininess);
+            material
+        }
+        MaterialInputs::Mirror  => Material::mirror(),
+        MaterialInputs::Water   => Material::water(),
+        MaterialInputs::Diamond => Material::diamond(),
+        MaterialInputs::Matte { colour, pattern } => Material {
+            pattern: pattern.map(parse_pattern).transpose()?,
+            ..Material::matte(Colour::new(colour.0, colour.1, colour.2))
+        },
+        MaterialInputs::Rubber { colour, pattern } => Material {
+            pattern: pattern.map(parse_pattern).transpose()?,
+            ..Material::rubber(Colour::new(colour.0, colour.1, colour.2))
+        },
+        MaterialInputs::Custom(custom) => parse_custom(custom)?,
+        MaterialInputs::Ref(name) => return lookup_material(&name, named).cloned(),
+        MaterialInputs::Override(over) => parse_override(over, named)?,
+    };
+    if Vec3::from(material.absorption).min() < 0.0 {
+        bail!("Material has invalid absorption {}, must not be negative", material.absorption);
+    }
+    if material.roughness < 0.0 || material.roughness.is_nan() {
+        bail!("Material has invalid roughness {}, must not be negative", material.roughness);
+    }
+    if material.diffuse_roughness < 0.0 || material.diffuse_roughness.is_nan() {
+        bail!("Material has invalid diffuse_roughness {}, must not be negative", material.diffuse_roughness);
+    }
+    if material.glossy_samples == 0 {
+        bail!("Material has 0 glossy_samples, needs at least 1");
+    }
+    Ok(Arc::new(material))
+}
+
+fn parse_override(over: OverrideInputs, named: &HashMap<String, Arc<Material>>) -> Result<Material> {
+    let mut material = Material::clone(lookup_material(&over.base, named)?);
+    if let Some(colour) = over.colour {
+        material.colour = Colour::new(colour.0, colour.1, colour.2);
+    }
+    if let Some(pattern) = over.pattern {
+        material.pattern = Some(parse_pattern(pattern)?);
+    }
+    material.ambient = over.ambient.unwrap_or(material.ambient);
+    material.diffuse = over.diffuse.unwrap_or(material.diffuse);
+    material.specular = over.specular.unwrap_or(material.specular);
+    material.shininess = over.shininess.unwrap_or(material.shininess);
+    material.reflect = over.reflective.unwrap_or(material.reflect);
+    material.transparency = over.transparency.unwrap_or(material.transparency);
+    material.refractive_index = over.refractive_index.unwrap_or(material.refractive_index);
+    if let Some(absorption) = over.absorption {
+        material.absorption = Colour::new(absorption.0, absorption.1, absorption.2);
+    }
+    material.roughness = over.roughness.unwrap_or(material.roughness);
+    material.glossy_samples = over.glossy_samples.unwrap_or(material.glossy_samples);
+    if let Some(model) = over.specular_model {
+        material.specular_model = model.into();
+    }
+    material.diffuse_roughness = over.diffuse_roughness.unwrap_or(material.diffuse_roughness);
+    material.fresnel = over.fresnel.unwrap_or(material.fresnel);
+    if let Some(bump) = over.bump {
+        material.normal_perturbation = Some(parse_bump(bump)?);
+    }
+    material.uv_pattern = over.uv_pattern.unwrap_or(material.uv_pattern);
+    if let Some(blend) = over.pattern_blend {
+        material.pattern_blend = blend.into();
+    }
+    Ok(material)
+}
+
+// Should be a better way to do this...
+fn parse_custom(material: CustomInputs) -> Result<Material> {
+    Ok(Material {
+        absorption: Colour::new(material.absorption.0, material.absorption.1, material.absorption.2),
+        roughness: material.roughness,
+        glossy_samples: material.glossy_samples,
+        specular_model: material.specular_model.into(),
+        diffuse_roughness: material.diffuse_roughness,
+        fresnel: material.fresnel,
+        normal_perturbation: material.bump.map(parse_bump).transpose()?,
+        uv_pattern: material.uv_pattern,
+        pattern_blend: material.pattern_blend.into(),
+        ..Material::new(
+            Colour::new(material.colour.0, material.colour.1, material.colour.2),
+            material.pattern.map(parse_pattern).transpose()?,
+            material.ambient,
+            material.diffuse,
+            material.specular,
+            material.shininess,
+            material.reflective,
+            material.transparency,
+            material.refractive_index,
+        )
+    })
+}
+
+fn parse_bump(bump: BumpInputs) -> Result<Arc<dyn NormalMap>> {
+    let (amplitude, scale, name) = match bump {
+        BumpInputs::Waves { amplitude, frequency } => (amplitude, frequency, "frequency"),
+        BumpInputs::Noise { amplitude, scale, .. } => (amplitude, scale, "scale"),
+    };
+    if !amplitude.is_finite() {
+        bail!("Bump amplitude {} must be finite", amplitude);
+    }
+    if !(scale.is_finite() && scale > 0.0) {
+        bail!("Bump {} {} must be positive", name, scale);
+    }
+    Ok(match bump {
+        BumpInputs::Waves { amplitude, frequency } => Arc::new(Waves::new(amplitude, frequency)),
+        BumpInputs::Noise { amplitude, scale, seed } => Arc::new(NoiseBump::new(amplitude, scale, seed)),
+    })
+}
+
+fn parse_pattern(mut pattern: PatternInputs) -> Result<Arc<dyn Pattern>> {
+
+    let slot = |input: ColourOrPatternInput, name: &str| -> Result<PatternOrColour> {
+        Ok(match input {
+            ColourOrPatternInput::Colour(c) => Colour::new(c.0, c.1, c.2).into(),
+            ColourOrPatternInput::Pattern(p) => parse_pattern(*p).with_context(|| format!("Invalid {}", name))?.into(),
+        })
+    };
+    // For patterns whose colours can be patterns themselves.
+    let slots = |a: ColourOrPatternInput, b: ColourOrPatternInput| -> Result<(PatternOrColour, PatternOrColour)> {
+        Ok((slot(a, "colour_a")?, slot(b, "colour_b")?))
+    };
+    let colours = |kind: &str, a: ColourOrPatternInput, b: ColourOrPatternInput| -> Result<(Colour, Colour)> {
+        match slots(a, b)? {
+            (PatternOrColour::Colour(a), PatternOrColour::Colour(b)) => Ok((a, b)),
+            _ => bail!("{} pattern colours can't be patterns", kind),
+        }
+    };
+
+    let transform = pattern.transform_mut().take();
+    let mut pattern_out: Box<dyn Pattern> = match pattern {
+        PatternInputs::Stripes(stripes) => {
+            let (a, b) = slots(stripes.colour_a, stripes.colour_b)?;
+            if !(stripes.width.is_finite() && stripes.width > 0.0) {
+                bail!("Stripes width must be positive, not {}", stripes.width);
+            }
+            Box::new(Stripes::new(a, b)
+                .with_axis(stripes.axis.into())
+                .with_width(stripes.width))
+        }
+        PatternInputs::Gradient(gradient) => {
+            let (a, b) = slots(gradient.colour_a, gradient.colour_b)?;
+            Box::new(Gradient::new(a, b).with_mode(gradient.mode.into()))
+        }
+        PatternInputs::Rings(rings) => {
+            let (a, b) = slots(rings.colour_a, rings.colour_b)?;
+            Box::new(Rings::new(a, b))
+        }
+        PatternInputs::Checkers(checkers) => {
+            let (a, b) = slots(checkers.colour_a, checkers.colour_b)?;
+            Box::new(Checkers::new(a, b))
+        },
+        PatternInputs::UvCheckers(checkers) => {
+            let (a, b) = colours("UvCheckers", checkers.colour_a, checkers.colour_b)?;
+            for (name, size) in [("width", checkers.width), ("height", checkers.height)] {
+                if !(size.is_finite() && size > 0.0) {
+                    bail!("UvCheckers {} must be positive, not {}", name, size);
+                }
+            }
+            Box::new(UvCheckers::new(checkers.width, checkers.height, a, b))
+        },
+        PatternInputs::Noise(noise) => {
+            let (a, b) = colours("Noise", noise.colour_a, noise.colour_b)?;
+            Box::new(Noise::new(a, b, noise.seed)
+                .with_scale(noise.scale)
+                .with_octaves(noise.octaves, noise.persistence))
+        },
+        // Loaded now so a missing image fails before rendering starts.
+        PatternInputs::Texture(texture) => Box::new(Texture::load(&texture.file, texture.mapping.into())?),
+        PatternInputs::Blend(blend) => {
+            let mode = match blend.mode {
+                BlendModeInput::Average => BlendMode::Average,
+                BlendModeInput::Multiply => BlendMode::Multiply,
+                BlendModeInput::Mask(mask) => BlendMode::Mask(parse_pattern(*mask).context("Invalid blend mask")?),
+            };
+            let a = parse_pattern(*blend.pattern_a).context("Invalid pattern_a")?;
+            let b = parse_pattern(*blend.pattern_b).context("Invalid pattern_b")?;
+            Box::new(Blend::new(a, b, mode))
+        },
+        PatternInputs::Debug(_) => Box::new(TestPattern::new()),
+        PatternInputs::Perturbed(perturbed) => {
+            let child = parse_pattern(*perturbed.child).context("Invalid child pattern")?;
+            Box::new(Perturbed::new(child, perturbed.amount, perturbed.seed)
+                .with_scale(perturbed.scale)
+                .with_octaves(perturbed.octaves, perturbed.persistence))
+        },
+    };
+    if let Some(transformations) = transform {
+        apply_transformations(&mut *pattern_out, transformations);
+    }
+    Ok(Arc::from(pattern_out))
+}
+
+// Expands every !Apply in the scene, so later stages only see plain transforms.
+fn resolve_named_transforms(a: &mut Inputs) -> Result<()> {
+    let named = std::mem::take(&mut a.transforms);
+
+    // Check every entry, even unused ones, for cycles and unknown names.
+    for name in named.keys() {
+        expand_transforms(vec![TransformationInput::Apply(name.clone())], &named, &mut vec![])?;
+    }
+
+    let resolve = |transform: &mut Option<Vec<TransformationInput>>| -> Result<()> {
+        if let Some(list) = transform.take() {
+            *transform = Some(expand_transforms(list, &named, &mut vec![])?);
+        }
+        Ok(())
+    };
+    resolve(&mut a.camera.transform).context("Invalid transform on the camera")?;
+    let materials = a.materials.values_mut()
+        .chain(std::iter::once(&mut a.default_material));
+    for material in materials {
+        if let Some(pattern) = material_pattern(material) {
+            resolve_pattern_transforms(pattern, &resolve)?;
+        }
+    }
+    for (i, obj) in a.objects.iter_mut().enumerate() {
+        resolve_object_transforms(obj, &resolve).with_context(|| format!("Invalid transform on object {}", object_label(i, &obj.name)))?;
+    }
+    for (name, obj) in a.prototypes.iter_mut() {
+        resolve_object_transforms(obj, &resolve).with_context(|| format!("Invalid transform on prototype {}", name))?;
+    }
+    Ok(())
+}
+
+// Expands the object's transforms and its materials' pattern transforms, and those of csg children.
+fn resolve_object_transforms(obj: &mut ObjectInputs, resolve: &dyn Fn(&mut Option<Vec<TransformationInput>>) -> Result<()>) -> Result<()> {
+    for material in obj.material.iter_mut().chain(obj.material_back.iter_mut()) {
+        if let Some(pattern) = material_pattern(material) {
+            resolve_pattern_transforms(pattern, resolve)?;
+        }
+    }
+    resolve(&mut obj.transform)?;
+    if let ObjectType::Csg { left, right, .. } = &mut obj.r#type {
+        resolve_object_transforms(left, resolve)?;
+        resolve_object_transforms(right, resolve)?;
+    }
+    Ok(())
+}
+
+// Expands the pattern's transforms, and those of patterns nested in it.
+fn resolve_pattern_transforms(pattern: &mut PatternInputs, resolve: &dyn Fn(&mut Option<Vec<TransformationInput>>) -> Result<()>) -> Result<()> {
+    resolve(pattern.transform_mut())?;
+    for child in pattern.children_mut() {
+        resolve_pattern_transforms(child, resolve)?;
+    }
+    Ok(())
+}
+
+fn expand_transforms(
+    transformations: Vec<TransformationInput>,
+    named: &HashMap<String, Vec<TransformationInput>>,
+    stack: &mut Vec<String>,
+) -> Result<Vec<TransformationInput>> {
+    let mut expanded = Vec::with_capacity(transformations.len());
+    for transformation in transformations {
+        match transformation {
+            TransformationInput::Look_at { from, to, .. } if from == to => bail!("Look_at needs from and to to be different"),
+            TransformationInput::Rotate_axis(x, y, z, _) if x == 0.0 && y == 0.0 && z == 0.0 => bail!("Rotate_axis needs an axis that isn't zero"),
+            _ => {},
+        }
+        let TransformationInput::Apply(name) = transformation else {
+            expanded.push(transformation);
+            continue;
+        };
+        if let Some(start) = stack.iter().position(|n| *n == name) {
+            let cycle: Vec<&str> = stack[start..].iter().chain(std::iter::once(&name)).map(String::as_str).collect();
+            bail!("Transform {} refers to itself: {}", name, cycle.join(" -> "));
+        }
+        let list = named.get(&name)
+            .with_context(|| format!("No transform named {}", name))?;
+        stack.push(name);
+        expanded.extend(expand_transforms(list.clone(), named, stack)?);
+        stack.pop();
+    }
+    Ok(expanded)
+}
+
+// Every file the scene loads: meshes, heightfields, textures and the environment map.
+fn input_files(a: &mut Inputs) -> Vec<&mut String> {
+    let mut files = vec![];
+    if let BackgroundInput::Environment(environment) = &mut a.background {
+        files.push(&mut environment.file);
+    }
+    let materials = a.materials.values_mut()
+        .chain(std::iter::once(&mut a.default_material));
+    for material in materials {
+        if let Some(pattern) = material_pattern(material) {
+            pattern_files(pattern, &mut files);
+        }
+    }
+    for obj in a.objects.iter_mut().chain(a.prototypes.values_mut()) {
+        object_files(obj, &mut files);
+    }
+    files
+}
+
+fn object_files<'a>(obj: &'a mut ObjectInputs, files: &mut Vec<&'a mut String>) {
+    for material in obj.material.iter_mut().chain(obj.material_back.iter_mut()) {
+        if let Some(pattern) = material_pattern(material) {
+            pattern_files(pattern, files);
+        }
+    }
+    match &mut obj.r#type {
+        ObjectType::Mesh { file } | ObjectType::Heightfield { file, .. } => files.push(file),
+        ObjectType::Csg { left, right, .. } => {
+            object_files(left, files);
+            object_files(right, files);
+        },
+        _ => {},
+    }
+}
+
+fn pattern_files<'a>(pattern: &'a mut PatternInputs, files: &mut Vec<&'a mut String>) {
+    match pattern {
+        PatternInputs::Texture(texture) => files.push(&mut texture.file),
+        pattern => {
+            for child in pattern.children_mut() {
+                pattern_files(child, files);
+            }
+        },
+    }
+}
+
+fn material_pattern(material: &mut MaterialInputs) -> Option<&mut PatternInputs> {
+    match material {
+        MaterialInputs::Metal(MetalInputs { pattern, .. })
+        | MaterialInputs::Plastic(PlasticInputs { pattern, .. })
+        | MaterialInputs::Matte { pattern, .. }
+        | MaterialInputs::Rubber { pattern, .. } => pattern.as_mut(),
+        MaterialInputs::Custom(custom) => custom.pattern.as_mut(),
+        MaterialInputs::Override(over) => over.pattern.as_mut(),
+        _ => None,
+    }
+}
+
+// For objects, patterns and the camera alike.
+fn apply_transformations<T: Transformable + ?Sized>(target: &mut T, transformations: Vec<TransformationInput>) {
+    transformations.into_iter().for_each(|transformation| {
+        match transformation {
+            TransformationInput::Translate(x, y, z) => {
+                target.translate(x, y, z);
+            },
+            TransformationInput::Scale(x, y, z) => {
+                target.scale(x, y, z);
+            },
+            TransformationInput::Scale_uniform(s) => {
+                target.scale_uniform(s);
+            },
+            TransformationInput::Rotate_x(angle) => {
+                target.rotate(Axis::X, angle)
+            },
+            TransformationInput::Rotate_y(angle) => {
+                target.rotate(Axis::Y, angle)
+            },
+            TransformationInput::Rotate_z(angle) => {
+                target.rotate(Axis::Z, angle)
+            },
+            TransformationInput::Rotate_axis(x, y, z, angle) => {
+                target.rotate_about(Vec3::new(x, y, z), angle)
+            },
+            TransformationInput::Rotate_x_about { angle, pivot } => {
+                target.rotate_about_point(Vec3::x(), angle, Point3::new(pivot.0, pivot.1, pivot.2))
+            },
+            TransformationInput::Rotate_y_about { angle, pivot } => {
+                target.rotate_about_point(Vec3::y(), angle, Point3::new(pivot.0, pivot.1, pivot.2))
+            },
+            TransformationInput::Rotate_z_about { angle, pivot } => {
+                target.rotate_about_point(Vec3::z(), angle, Point3::new(pivot.0, pivot.1, pivot.2))
+            },
+            TransformationInput::Scale_about { scale, pivot } => {
+                target.scale_about_point(scale.0, scale.1, scale.2, Point3::new(pivot.0, pivot.1, pivot.2))
+            },
+            TransformationInput::Look_at { from, to, up } => {
+                target.look_at(Point3::new(from.0, from.1, from.2), Point3::new(to.0, to.1, to.2), Vec3::new(up.0, up.1, up.2))
+            },
+            TransformationInput::Apply(_) => unreachable!("named transforms are resolved before building"),
+        }
+    });
+}
+
+const TEMPERATURE_RANGE: std::ops::RangeInclusive<f64> = 1000.0..=40000.0;
+
+// Light colour is colour * temperature colour, which intensity then scales.
+fn light_colour(i: usize, colour: (f64, f64, f64), temperature: Option<f64>, intensity: f64) -> Result<Colour> {
+    if intensity < 0.0 || intensity.is_nan() {
+        bail!("Light {} has invalid intensity {}, must not be negative", i, intensity);
+    }
+
+    let mut colour = Colour::new(colour.0, colour.1, colour.2);
+    if let Some(kelvin) = temperature {
+        if !TEMPERATURE_RANGE.contains(&kelvin) {
+            bail!(
+                "Light {} has invalid temperature {}K, must be between {}K and {}K",
+                i, kelvin, TEMPERATURE_RANGE.start(), TEMPERATURE_RANGE.end(),
+            );
+        }
+        colour = colour * Colour::from_temperature(kelvin);
+    }
+    Ok(colour)
+}
+
+fn parse_lights(lights: Vec<LightInputs>) -> Result<Vec<Light>> {
+    lights.into_iter().enumerate().map(|(i, light)| match light {
+        LightInputs::Point(light) => {
+            let colour = light_colour(i, light.colour, light.temperature, light.intensity)?;
+            let (constant, linear, quadratic) = light.attenuation;
+            if [constant, linear, quadratic].iter().any(|&c| c < 0.0 || !c.is_finite()) || constant + linear + quadratic == 0.0 {
+                bail!("Light {} has invalid attenuation {:?}, must not be negative or all 0", i, light.attenuation);
+            }
+
+            Ok(Light::new(
+                Point3::new(light.position.0, light.position.1, light.position.2),
+                colour,
+            ).with_attenuation(constant, linear, quadratic).with_intensity(light.intensity).with_enabled(light.enabled))
+        },
+        LightInputs::Directional(light) => {
+            let colour = light_colour(i, light.colour, light.temperature, light.intensity)?;
+            let direction = Vec3::new(light.direction.0, light.direction.1, light.direction.2);
+            if direction.norm() == 0.0 || !direction.norm().is_finite() {
+                bail!("Light {} has invalid direction {:?}, must not be 0", i, light.direction);
+            }
+            Ok(Light::directional(direction, colour).with_intensity(light.intensity).with_enabled(light.enabled))
+        },
+        LightInputs::Area(light) => {
+            let colour = light_colour(i, light.colour, light.temperature, light.intensity)?;
+            let u = Vec3::new(light.u.0, light.u.1, light.u.2);
+            let v = Vec3::new(light.v.0, light.v.1, light.v.2);
+            if u.cross(&v).norm() == 0.0 {
+                bail!("Light {} has edges {:?} and {:?}, which must not be 0 or parallel", i, light.u, light.v);
+            }
+            if light.samples == 0 {
+                bail!("Light {} has 0 samples, needs at least 1", i);
+            }
+            let corner = Point3::new(light.corner.0, light.corner.1, light.corner.2);
+            Ok(Light::area(corner, u, v, light.samples, colour).with_intensity(light.intensity).with_enabled(light.enabled))
+        },
+        LightInputs::Sphere(light) => {
+            let colour = light_colour(i, light.colour, light.temperature, light.intensity)?;
+            if light.radius <= 0.0 || !light.radius.is_finite() {
+                bail!("Light {} has invalid radius {}, must be above 0", i, light.radius);
+            }
+            if light.samples == 0 {
+                bail!("Light {} has 0 samples, needs at least 1", i);
+            }
+            let position = Point3::new(light.position.0, light.position.1, light.position.2);
+            Ok(Light::sphere(position, light.radius, light.samples, colour).with_intensity(light.intensity).with_enabled(light.enabled))
+        },
+    }).collect()
+}
+
+fn colour_default() -> (f64, f64, f64) {
+    (1.0, 1.0, 1.0)
+}
+
+fn cast_shadow_default() -> bool {
+    true
+}
+
+fn visible_to_camera_default() -> bool {
+    true
+}
+
+fn camera_default() -> CameraInputs {
+    CameraInputs {
+        look_from: (0.0, 5.0, 0.0),
+        look_at: (0.0, 0.0, 10.0),
+        vup: (0.0, 1.0, 0.0),
+        vfov: 90.0,
+        aperture: 0.0,
+        focus_dist: None,
+        relative: false,
+        shutter: (0.0, 0.0),
+        projection: ProjectionInput::Perspective,
+        transform: None,
+    }
+}
+
+fn lights_default() -> Vec<LightInputs> {
+    vec![
+        LightInputs::Point(PointLightInputs {
+            position: (-10.0, 10.0, -10.0),
+            colour: (1.0, 1.0, 1.0),
+            temperature: None,
+            intensity: intensity_default(),
+            enabled: true,
+            attenuation: attenuation_default(),
+        })
+    ]
+}
+
+fn intensity_default() -> f64 {
+    1.0
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+fn glossy_samples_default() -> usize {
+    material::GLOSSY_SAMPLES
+}
+
+fn attenuation_default() -> (f64, f64, f64) {
+    (1.0, 0.0, 0.0)
+}
+
+fn area_samples_default() -> usize {
+    16
+}
+
+fn radius_default() -> f64 {
+    1.0
+}
+
+fn exponent_default() -> f64 {
+    1.0
+}
+
+fn min_default() -> f64 {
+    -f64::INFINITY
+}
+
+fn max_default() -> f64 {
+    f64::INFINITY
+}
+
+fn theta_max_default() -> f64 {
+    360.0
+}
+
+fn u_steps_default() -> usize {
+    64
+}
+
+fn v_steps_default() -> usize {
+    16
+}
+
+fn vertical_scale_default() -> f64 {
+    1.0
+}
+
+fn check_sweep(theta_min: f64, theta_max: f64) -> Result<()> {
+    if theta_min >= theta_max || theta_min.is_nan() || theta_max.is_nan() {
+        bail!("theta_min ({}) must be less than theta_max ({})", theta_min, theta_max);
+    }
+    Ok(())
+}
+
+fn material_default() -> MaterialInputs {
+    MaterialInputs::Custom(CustomInputs {
+        colour: colour_default(),
+        pattern: None,
+        ambient: ambient_default(),
+        diffuse: diffuse_default(),
+        specular: specular_default(),
+        shininess: shininess_default(),
+        reflective: 0.0,
+        transparency: 0.0,
+        refractive_index: refractive_default(),
+        absorption: (0.0, 0.0, 0.0),
+        roughness: 0.0,
+        glossy_samples: glossy_samples_default(),
+        specular_model: SpecularModelInput::Phong,
+        diffuse_roughness: 0.0,
+        fresnel: false,
+        bump: None,
+        uv_pattern: false,
+        pattern_blend: PatternBlendInput::Replace,
+    })
+}
+
+fn noise_scale_default() -> f64 {
+    1.0
+}
+
+fn octaves_default() -> u32 {
+    1
+}
+
+fn persistence_default() -> f64 {
+    0.5
+}
+
+fn uv_checkers_default() -> f64 {
+    2.0
+}
+
+fn stripe_width_default() -> f64 {
+    1.0
+}
+
+fn bump_scale_default() -> f64 {
+    1.0
+}
+
+fn ambient_default() -> f64 {
+    0.1
+}
+
+fn diffuse_default() -> f64 {
+    0.9
+}
+
+fn specular_default() -> f64 {
+    0.9
+}
+
+fn shininess_default() -> f64 {
+    200.0
+}
+
+fn refractive_default() -> f64 {
+    1.0
+}
+
+fn from_default() -> (f64, f64, f64) {
+    (0.0, 0.0, 0.0)
+}
+
+fn at_default() -> (f64, f64, f64) {
+    (0.0, 0.0, -1.0)
+}
+
+fn up_default() -> (f64, f64, f64) {
+    (0.0, 1.0, 0.0)
+}
+
+fn vfov_default() -> f64 {
+    90.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{fuzzy_eq_f64, fuzzy_eq_vec};
+    use crate::colour::fuzzy_eq_colour;
+    use crate::ray::Ray;
+    use crate::post::{Pipeline, Effect, Bloom, Vignette};
+
+    // Make sure the test scene file parses without error.
+    #[test]
+    fn test_simple_input() {
+        
+        let yaml = "
+            camera:
+                look_from:  [1.0, 2.0, 3.0]
+                look_at:    [4.0, 5.0, 6.0]
+                vup:        [7.0, 8.0, 9.0]
+                vfov:       90.0
+                aperture:   0.0
+                focus_dist: 1.0
+
+            objects:
+                - type: !Sphere
+                  material: !Custom
+                    colour: [1.0, 0.0, 0.0]
+                    ambient: 0.1
+                    diffuse: 0.9
+                    specular: 0.9
+                    shininess: 200.0
+                    reflective: 0.0
+                    transparency: 0.0
+                    refractive_index: 1.0
+
+                  transform:
+                    - !Translate [0.0, 0.0, -1.0]
+                    - !Scale [0.5, 0.5, 0.5]
+
+            lights:
+                - position: [0.0, 0.0, -10.0]
+                  colour:   [1.0, 1.0, 1.0]
+        ";
+
+        let a: Inputs = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(a.camera.look_from, (1.0, 2.0, 3.0));
+        assert_eq!(a.camera.look_at, (4.0, 5.0, 6.0));
+        assert_eq!(a.camera.vup, (7.0, 8.0, 9.0));
+        assert_eq!(a.camera.vfov, 90.0);
+        assert_eq!(a.camera.aperture, 0.0);
+        assert_eq!(a.camera.focus_dist, Some(1.0));
+
+        assert_eq!(a.objects.len(), 1);
+        assert_eq!(a.objects[0].r#type, ObjectType::Sphere);
+        assert_eq!(a.objects[0].material, 
+            Some(MaterialInputs::Custom(CustomInputs {
+                colour: (1.0, 0.0, 0.0),
+                pattern: None,
+                ambient: ambient_default(),
+                diffuse: diffuse_default(),
+                specular: specular_default(),
+                shininess: shininess_default(),
+                reflective: 0.0,
+                transparency: 0.0,
+                refractive_index: refractive_default(),
+                absorption: (0.0, 0.0, 0.0),
+                roughness: 0.0,
+                glossy_samples: glossy_samples_default(),
+                specular_model: SpecularModelInput::Phong,
+                diffuse_roughness: 0.0,
+                fresnel: false,
+                bump: None,
+                uv_pattern: false,
+                pattern_blend: PatternBlendInput::Replace,
+            })));
+        assert_eq!(a.objects[0].transform, Some(vec![
+            TransformationInput::Translate(0.0, 0.0, -1.0),
+            TransformationInput::Scale(0.5, 0.5, 0.5),
+        ]));
+
+        assert_eq!(a.lights.len(), 1);
+        let LightInputs::Point(light) = &a.lights[0] else { panic!("expected a point light") };
+        assert_eq!(light.position, (0.0, 0.0, -10.0));
+        assert_eq!(light.colour, (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_input_from_file() {
+        let a: Inputs = serde_yaml::from_slice(&read("scenes/tests/test_input.yaml").unwrap()).unwrap();
+        
+        assert_eq!(a.camera.look_from, (0.0, 0.0, 2.0));
+        assert_eq!(a.camera.vfov, 15.0);
+
+        let sphere = &a.objects[0];
+        assert_eq!(sphere.r#type, ObjectType::Sphere);
+        assert_eq!(sphere.material, Some(MaterialInputs::Plastic(PlasticInputs {
+            colour: (1.0, 0.0, 1.0),
+            shininess: None,
+            pattern: Some(
+                PatternInputs::Stripes(StripesInputs {
+                    colour_a: ColourOrPatternInput::Colour((1.0, 0.0, 1.0)),
+                    colour_b: ColourOrPatternInput::Colour((0.0, 0.0, 1.0)),
+                    transform: Some(vec![
+                        TransformationInput::Scale_uniform(0.1),
+                        TransformationInput::Rotate_z(90.0)
+                    ]),
+                    width: 1.0,
+                    axis: AxisInput::X,
+                })
+            )
+        })));
+        assert_eq!(sphere.transform, Some(vec![
+            TransformationInput::Translate(30.0, 30.0, 2.0),
+            TransformationInput::Scale_uniform(4.0),
+        ]));
+
+        let cone = &a.objects[1];
+        assert_eq!(cone.r#type, ObjectType::Cone {
+            angle: None, radius: None,
+            min: -f64::INFINITY,
+            max: f64::INFINITY,
+            closed: false,
+        });
+        assert_eq!(cone.material, Some(MaterialInputs::Glass(None)));
+        assert_eq!(cone.transform, Some(vec![TransformationInput::Rotate_x(45.0)]));
+
+        let boxx = &a.objects[2];
+        assert_eq!(boxx.r#type, ObjectType::Box(None));
+        assert_eq!(boxx.material, Some(MaterialInputs::Metal(MetalInputs {
+            colour: (1.0, 0.5, 1.0),
+            pattern: None,
+            reflective: None,
+            roughness: None,
+            glossy_samples: None,
+        })));
+
+        let lights = &a.lights;
+        assert_eq!(lights[0], LightInputs::Point(PointLightInputs {
+            position: (-10.0, 30.0, 20.0),
+            colour: (1.0, 1.0, 1.0),
+            temperature: None,
+            intensity: 1.0,
+            enabled: true,
+            attenuation: (1.0, 0.0, 0.0),
+        }));
+    }
+
+    const NAMED_MATERIALS: &str = "
+        materials:
+            chrome: !Metal
+                colour: [0.8, 0.8, 0.8]
+
+        objects:
+            - type: !Sphere
+              material: !Ref chrome
+            - type: !Sphere
+              material: !Ref chrome
+            - type: !Sphere
+              material: !Override
+                base: chrome
+                reflective: 0.6
+                colour: \"#222233\"
+            - type: !Sphere
+              material: !Override
+                base: chrome
+                reflective: 0.6
+    ";
+
+    #[test]
+    fn test_material_override() {
+        let a: Inputs = serde_yaml::from_str(NAMED_MATERIALS).unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let base = scene.objects[0].material();
+        let over = scene.objects[2].material();
+
+        // Plain references share one material.
+        assert!(Arc::ptr_eq(base, scene.objects[1].material()));
+        // Overrides get their own.
+        assert!(!Arc::ptr_eq(base, over));
+        assert!(!Arc::ptr_eq(over, scene.objects[3].material()));
+
+        assert_eq!(over.colour, Colour::new(0x22 as f64 / 255.0, 0x22 as f64 / 255.0, 0x33 as f64 / 255.0));
+        assert_eq!(over.reflect, 0.6);
+        assert_eq!(base.reflect, 1.0);
+
+        let over = scene.objects[3].material();
+        assert_eq!(over.colour, base.colour);
+        assert_eq!(over.ambient, base.ambient);
+        assert_eq!(over.diffuse, base.diffuse);
+        assert_eq!(over.specular, base.specular);
+        assert_eq!(over.shininess, base.shininess);
+        assert_eq!(over.transparency, base.transparency);
+        assert_eq!(over.refractive_index, base.refractive_index);
+        assert!(over.pattern.is_none());
+    }
+
+    #[test]
+    fn test_material_names() {
+        let a: Inputs = serde_yaml::from_str("
+            materials:
+                clay: !Custom { colour: [0.8, 0.5, 0.3], diffuse_roughness: 0.5, specular: 0 }
+            default_material: clay
+            objects:
+                - type: !Sphere
+                  material: clay
+                - type: !Plane
+                  material: clay
+                - type: !Sphere
+                - type: !Sphere
+                  material: Mirror
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let clay = scene.objects[0].material();
+        assert_eq!(clay.diffuse_roughness, 0.5);
+        assert!(Arc::ptr_eq(clay, scene.objects[1].material()));
+        assert!(Arc::ptr_eq(clay, scene.objects[2].material()));
+        assert_eq!(**scene.objects[3].material(), Material::mirror());
+
+        let a: Inputs = serde_yaml::from_str("
+            materials:
+                clay: !Matte { colour: red }
+            objects:
+                - type: !Sphere
+                  material: clay
+                - type: !Sphere
+                  name: teapot
+                  material: porcelain
+        ").unwrap();
+        let err = format!("{:#}", build_scene(a, (10, 10)).unwrap_err());
+        assert!(err.contains("teapot") && err.contains("porcelain"), "{}", err);
+    }
+
+    #[test]
+    fn test_material_override_errors() {
+        let unknown_field = NAMED_MATERIALS.replace("reflective: 0.6\n                colour", "sparkle: 0.6\n                colour");
+        assert!(serde_yaml::from_str::<Inputs>(&unknown_field).is_err());
+
+        let unknown_base = NAMED_MATERIALS.replace("base: chrome", "base: gold");
+        let a: Inputs = serde_yaml::from_str(&unknown_base).unwrap();
+        let err = build_scene(a, (10, 10)).unwrap_err();
+        assert!(format!("{:#}", err).contains("gold"));
+    }
+
+    #[test]
+    fn test_colour_strings() {
+        assert_eq!(parse_colour_str("#ff0080"), Ok((1.0, 0.0, 128.0 / 255.0)));
+        assert_eq!(parse_colour_str("Orange"), Ok((1.0, 0.5, 0.0)));
+        assert!(parse_colour_str("#ff00").is_err());
+        assert!(parse_colour_str("mauve").is_err());
+    }
+
+    fn material_from_str(yaml: &str) -> Arc<Material> {
+        let inputs: MaterialInputs = serde_yaml::from_str(yaml).unwrap();
+        parse_material(inputs, &HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_preset_overrides() {
+        let glass = material_from_str("!Glass");
+        assert_eq!(glass.refractive_index, 1.52);
+        assert_eq!(glass.transparency, 1.0);
+        let glass = material_from_str("!Glass { ior: 1.33 }");
+        assert_eq!(glass.refractive_index, 1.33);
+        assert_eq!(glass.transparency, 1.0);
+        assert!(serde_yaml::from_str::<MaterialInputs>("!Glass { colour: [1, 0, 0] }").is_err());
+
+        let metal = material_from_str("!Metal { colour: [0.1, 0.2, 0.3] }");
+        assert_eq!(metal.colour, Colour::new(0.1, 0.2, 0.3));
+        assert_eq!(metal.reflect, 1.0);
+        let metal = material_from_str("!Metal { colour: [0.1, 0.2, 0.3], reflect: 0.7 }");
+        assert_eq!(metal.reflect, 0.7);
+        assert_eq!(metal.specular, 1.0);
+
+        let plastic = material_from_str("!Plastic { colour: [0.1, 0.2, 0.3] }");
+        assert_eq!(plastic.colour, Colour::new(0.1, 0.2, 0.3));
+        assert_eq!(plastic.shininess, 100.0);
+        let plastic = material_from_str("!Plastic { colour: [0.1, 0.2, 0.3], shininess: 50 }");
+        assert_eq!(plastic.shininess, 50.0);
+        assert_eq!(plastic.diffuse, 0.5);
+        assert!(serde_yaml::from_str::<MaterialInputs>("!Metal { colour: red, shininess: 50 }").is_err());
+        assert!(serde_yaml::from_str::<MaterialInputs>("!Plastic { colour: red, roughness: 0.2 }").is_err());
+
+        assert_eq!(*material_from_str("!Water"), Material::water());
+        let rubber = material_from_str("!Rubber { colour: red }");
+        assert_eq!(*rubber, Material::rubber(Colour::new(1.0, 0.0, 0.0)));
+        assert!(material_from_str("!Rubber { colour: red, pattern: { type: !Checkers, colour_a: red, colour_b: black } }").pattern.is_some());
+    }
+
+    #[test]
+    fn test_named_ior() {
+        let custom = material_from_str("!Custom { transparency: 1.0, refractive_index: diamond }");
+        assert_eq!(custom.refractive_index, material::ior::DIAMOND);
+        let custom = material_from_str("!Custom { refractive_index: 1.1 }");
+        assert_eq!(custom.refractive_index, 1.1);
+        let glass = material_from_str("!Glass { ior: Water }");
+        assert_eq!(glass.refractive_index, material::ior::WATER);
+
+        let glass = material_from_str("!Glass { ior: Water, absorption: [0.2, 0.05, 0.05] }");
+        assert_eq!(glass.absorption, Colour::new(0.2, 0.05, 0.05));
+        assert_eq!(glass.refractive_index, material::ior::WATER);
+        let custom = material_from_str("!Custom { transparency: 1, absorption: [0, 0.5, 1] }");
+        assert_eq!(custom.absorption, Colour::new(0.0, 0.5, 1.0));
+        let chrome = material_from_str("!Metal { colour: [0.8, 0.8, 0.8], roughness: 0.3 }");
+        assert_eq!(chrome.roughness, 0.3);
+        assert_eq!(chrome.glossy_samples, material::GLOSSY_SAMPLES);
+        let custom = material_from_str("!Custom { reflective: 0.5, roughness: 0.1, glossy_samples: 4 }");
+        assert_eq!((custom.roughness, custom.glossy_samples), (0.1, 4));
+        let inputs: MaterialInputs = serde_yaml::from_str("!Metal { colour: red, glossy_samples: 0 }").unwrap();
+        assert!(parse_material(inputs, &HashMap::new()).unwrap_err().to_string().contains("glossy_samples"));
+        let inputs: MaterialInputs = serde_yaml::from_str("!Custom { absorption: [-1, 0, 0] }").unwrap();
+        assert!(parse_material(inputs, &HashMap::new()).unwrap_err().to_string().contains("absorption"));
+
+        let err = serde_yaml::from_str::<MaterialInputs>("!Glass { ior: jelly }").unwrap_err();
+        assert!(err.to_string().contains("jelly"));
+        assert!(err.to_string().contains("sapphire"));
+    }
+
+    #[test]
+    fn test_specular_model() {
+        assert_eq!(material_from_str("!Custom { shininess: 50 }").specular_model, SpecularModel::Phong);
+        assert_eq!(material_from_str("!Custom { specular_model: BlinnPhong }").specular_model, SpecularModel::BlinnPhong);
+        assert!(serde_yaml::from_str::<MaterialInputs>("!Custom { specular_model: Gouraud }").is_err());
+    }
+
+    #[test]
+    fn test_diffuse_roughness() {
+        assert_eq!(material_from_str("!Custom { diffuse_roughness: 0.5 }").diffuse_roughness, 0.5);
+        let inputs: MaterialInputs = serde_yaml::from_str("!Custom { diffuse_roughness: -0.1 }").unwrap();
+        assert!(parse_material(inputs, &HashMap::new()).unwrap_err().to_string().contains("diffuse_roughness"));
+    }
+
+    #[test]
+    fn test_background() {
+        let background = |yaml: &str| -> Result<Background> { parse_background(serde_yaml::from_str(yaml)?) };
+        assert_eq!(background("[0.1, 0.2, 0.3]").unwrap(), Background::Flat(Colour::new(0.1, 0.2, 0.3)));
+        assert_eq!(background("white").unwrap(), Background::Flat(Colour::new(1.0, 1.0, 1.0)));
+        assert_eq!(background("!Flat red").unwrap(), Background::Flat(Colour::new(1.0, 0.0, 0.0)));
+        assert!(background("!Sky { top: white, bottom: black }").is_err());
+        assert!(background("!VerticalGradient { top: white }").is_err());
+
+        // A mirror ball reflects the sky.
+        let a: Inputs = serde_yaml::from_str("
+            background: !VerticalGradient { top: [0.5, 0.7, 1], bottom: white }
+            objects:
+                - type: !Sphere
+                  material: !Custom { ambient: 0, diffuse: 0, specular: 0, reflective: 1 }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let up = scene.colour_at(&Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 1.0, 0.0)), 5);
+        assert!(fuzzy_eq_colour(up, Colour::new(0.5, 0.7, 1.0)));
+        // Hit 22.5 degrees up the ball, reflected 45 degrees up.
+        let y = std::f64::consts::FRAC_PI_8.sin();
+        let reflected = scene.colour_at(&Ray::new(Point3::new(0.0, y, -5.0), Vec3::z()), 5);
+        let t = 0.5 * (std::f64::consts::FRAC_1_SQRT_2 + 1.0);
+        assert!(fuzzy_eq_colour(reflected, Colour::new(1.0 - 0.5 * t, 1.0 - 0.3 * t, 1.0)));
+    }
+
+    #[test]
+    fn test_ambient() {
+        let scene = |ambient: &str| {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                {}
+                objects:
+                    - type: !Sphere
+                lights: []
+            ", ambient))?;
+            build_scene(a, (10, 10)).map(|(scene, _)| scene)
+        };
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::z());
+        let plain = scene("").unwrap();
+        assert_eq!(plain.ambient_light, Colour::new(1.0, 1.0, 1.0));
+        assert!(fuzzy_eq_colour(plain.colour_at(&ray, 0), Colour::new(0.1, 0.1, 0.1)));
+
+        let tinted = scene("ambient: [0.1, 0.1, 0.15]").unwrap();
+        assert!(fuzzy_eq_colour(tinted.colour_at(&ray, 0), Colour::new(0.01, 0.01, 0.015)));
+        assert_eq!(scene("ambient: black").unwrap().colour_at(&ray, 0), Colour::new(0.0, 0.0, 0.0));
+        assert!(scene("ambient: [0.1, -0.1, 0.1]").is_err());
+    }
+
+    #[test]
+    fn test_environment_background() {
+        // Red ahead, blue behind.
+        let path = std::env::temp_dir().join(format!("ray-tracer-{}-environment.png", std::process::id()));
+        image::RgbImage::from_fn(8, 4, |x, _| image::Rgb(if (2..6).contains(&x) { [255, 0, 0] } else { [0, 0, 255] })).save(&path).unwrap();
+        let scene = |rotation: f64| {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                background: !Environment {{ file: {:?}, rotation: {} }}
+                objects:
+                    - type: !Sphere
+                      material: !Custom {{ ambient: 0, diffuse: 0, specular: 0, reflective: 1 }}
+                      transform: [!Translate [0, 0, 5]]
+            ", path, rotation)).unwrap();
+            build_scene(a, (10, 10)).unwrap().0
+        };
+        let ahead = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::z());
+        let aside = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::x());
+        let (red, blue) = (Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 0.0, 1.0));
+        // The ball ahead reflects what's behind.
+        assert!(fuzzy_eq_colour(scene(0.0).colour_at(&ahead, 5), blue));
+        assert!(fuzzy_eq_colour(scene(180.0).colour_at(&ahead, 5), red));
+        assert!(fuzzy_eq_colour(scene(0.0).colour_at(&Ray::new(Point3::origin(), -Vec3::z()), 5), blue));
+        assert!(fuzzy_eq_colour(scene(90.0).colour_at(&aside, 5), red));
+        std::fs::remove_file(&path).unwrap();
+
+        let a: Inputs = serde_yaml::from_str("
+            background: !Environment { file: missing.hdr }
+            objects: []
+        ").unwrap();
+        let err = build_scene(a, (10, 10)).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing.hdr"));
+    }
+
+    #[test]
+    fn test_pattern_blend() {
+        let material = material_from_str("!Custom { colour: [1, 0, 0], pattern: { type: !Stripes, colour_a: white, colour_b: [0.5, 0.5, 0.5] } }");
+        assert_eq!(material.colour_at(&Point3::new(1.5, 0.0, 0.0), &Matrix4::identity()), Colour::new(0.5, 0.5, 0.5));
+        let material = material_from_str("!Custom { colour: [1, 0, 0], pattern_blend: Multiply, pattern: { type: !Stripes, colour_a: white, colour_b: [0.5, 0.5, 0.5] } }");
+        assert_eq!(material.colour_at(&Point3::new(1.5, 0.0, 0.0), &Matrix4::identity()), Colour::new(0.5, 0.0, 0.0));
+        assert!(serde_yaml::from_str::<MaterialInputs>("!Custom { pattern_blend: Dodge }").is_err());
+    }
+
+    #[test]
+    fn test_fresnel() {
+        assert!(!material_from_str("!Custom { reflective: 0.2 }").fresnel);
+        assert!(material_from_str("!Custom { reflective: 0.2, fresnel: true }").fresnel);
+    }
+
+    #[test]
+    fn test_bump() {
+        assert!(material_from_str("!Custom { colour: red }").normal_perturbation.is_none());
+        let inputs: MaterialInputs = serde_yaml::from_str("!Custom { bump: !Waves { amplitude: 0.1, frequency: 10 } }").unwrap();
+        let MaterialInputs::Custom(custom) = inputs else { panic!("not custom") };
+        assert_eq!(custom.bump, Some(BumpInputs::Waves { amplitude: 0.1, frequency: 10.0 }));
+        let inputs: MaterialInputs = serde_yaml::from_str("!Custom { bump: !Noise { amplitude: 0.2 } }").unwrap();
+        let MaterialInputs::Custom(custom) = inputs else { panic!("not custom") };
+        assert_eq!(custom.bump, Some(BumpInputs::Noise { amplitude: 0.2, scale: 1.0, seed: 0 }));
+        assert!(material_from_str("!Custom { bump: !Noise { amplitude: 0.2, scale: 5, seed: 3 } }").normal_perturbation.is_some());
+        assert!(serde_yaml::from_str::<MaterialInputs>("!Custom { bump: !Waves { amplitude: 0.1 } }").is_err());
+
+        for (bump, message) in [
+            ("!Waves { amplitude: .nan, frequency: 10 }", "amplitude NaN must be finite"),
+            ("!Waves { amplitude: 0.1, frequency: 0 }", "frequency 0 must be positive"),
+            ("!Noise { amplitude: .inf }", "amplitude inf must be finite"),
+            ("!Noise { amplitude: 0.2, scale: -1 }", "scale -1 must be positive"),
+            ("!Noise { amplitude: 0.2, scale: .nan }", "scale NaN must be positive"),
+        ] {
+            let inputs: MaterialInputs = serde_yaml::from_str(&format!("!Custom {{ bump: {} }}", bump)).unwrap();
+            let error = parse_material(inputs, &HashMap::new()).unwrap_err();
+            assert!(error.to_string().contains(message), "{}", error);
+        }
+    }
+
+    fn lights_from_str(yaml: &str) -> Result<Vec<Light>> {
+        parse_lights(serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_light_colour() {
+        let lights = lights_from_str("
+            - position: [0, 0, 0]
+            - position: [0, 0, 0]
+              colour: red
+              intensity: 2.5
+            - position: [0, 0, 0]
+              temperature: 2000
+            - position: [0, 0, 0]
+              colour: [0.5, 0.5, 0.5]
+              temperature: 2000
+              intensity: 2
+        ").unwrap();
+        assert_eq!(lights[0].radiance(), colour::WHITE);
+        assert_eq!(lights[1].colour, colour::RED);
+        assert_eq!(lights[1].radiance(), Colour::new(2.5, 0.0, 0.0));
+        assert_eq!(lights[2].radiance(), Colour::from_temperature(2000.0));
+        assert_eq!(lights[3].radiance(), Colour::from_temperature(2000.0));
+
+        let err = lights_from_str("
+            - position: [0, 0, 0]
+            - position: [0, 0, 0]
+              intensity: -1
+        ").unwrap_err();
+        assert!(err.to_string().contains("Light 1"));
+
+        let err = lights_from_str("
+            - position: [0, 0, 0]
+              temperature: 50
+        ").unwrap_err();
+        assert!(err.to_string().contains("Light 0"));
+    }
+
+    #[test]
+    fn test_light_intensity_shading() {
+        let lights = lights_from_str("
+            - position: [0, 0, -10]
+            - position: [0, 0, -10]
+              intensity: 2
+        ").unwrap();
+
+        // Diffuse only.
+        let sphere = Sphere::new(Material {
+            ambient: 0.0,
+            specular: 0.0,
+            ..Default::default()
+        });
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = &sphere.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+
+        let single = sphere.material().light(&lights[0], hit, colour::WHITE, colour::WHITE);
+        let double = sphere.material().light(&lights[1], hit, colour::WHITE, colour::WHITE);
+        assert_eq!(single, Colour::new(0.9, 0.9, 0.9));
+        assert_eq!(double, single * 2.0);
+        // Intensity stays separate from the colour.
+        assert_eq!(lights[1].colour, colour::WHITE);
+        assert_eq!(lights[1].intensity, 2.0);
+
+        let lights = lights_from_str("
+            - position: [0, 0, -10]
+              enabled: false
+            - !Directional { direction: [0, -1, 0], enabled: false }
+        ").unwrap();
+        assert!(lights.iter().all(|light| !light.enabled));
+    }
+
+    #[test]
+    fn test_light_attenuation() {
+        // 2 and 4 from the front of the sphere, and one without falloff.
+        let lights = lights_from_str("
+            - position: [0, 0, -3]
+              attenuation: [0, 0, 1]
+            - position: [0, 0, -5]
+              attenuation: [0, 0, 1]
+            - position: [0, 0, -5]
+        ").unwrap();
+        let sphere = Sphere::new(Material {
+            ambient: 0.0,
+            specular: 0.0,
+            ..Default::default()
+        });
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = &sphere.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+
+        let near = sphere.material().light(&lights[0], hit, colour::WHITE, colour::WHITE);
+        let far = sphere.material().light(&lights[1], hit, colour::WHITE, colour::WHITE);
+        assert!(fuzzy_eq_colour(far * 4.0, near));
+        assert!(fuzzy_eq_colour(near, Colour::new(0.9, 0.9, 0.9) * 0.25));
+        assert_eq!(sphere.material().light(&lights[2], hit, colour::WHITE, colour::WHITE), Colour::new(0.9, 0.9, 0.9));
+
+        let err = lights_from_str("
+            - position: [0, 0, 0]
+              attenuation: [0, 0, 0]
+        ").unwrap_err();
+        assert!(err.to_string().contains("attenuation"));
+    }
+
+    #[test]
+    fn test_directional_light() {
+        let lights = lights_from_str("
+            - !Directional
+              direction: [0, -1, 0]
+              colour: red
+            - !Point
+              position: [0, 1, 0]
+        ").unwrap();
+        assert_eq!(lights[0].kind, LightKind::Directional { direction: Vec3::new(0.0, -1.0, 0.0) });
+        assert_eq!(lights[0].colour, Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(lights[1].kind, LightKind::Point);
+
+        let err = lights_from_str("
+            - !Directional
+              direction: [0, 0, 0]
+        ").unwrap_err();
+        assert!(err.to_string().contains("direction"));
+        let err = serde_yaml::from_str::<Vec<LightInputs>>("- !Spot { position: [0, 0, 0] }").unwrap_err();
+        assert!(err.to_string().contains("Directional"));
+    }
+
+    #[test]
+    fn test_area_light() {
+        let lights = lights_from_str("
+            - !Area { corner: [-1, 5, -1], u: [2, 0, 0], v: [0, 0, 2], samples: 9, colour: [0.5, 0.5, 0.5] }
+            - !Area { corner: [0, 5, 0], u: [1, 0, 0], v: [0, 0, 1] }
+        ").unwrap();
+        assert_eq!(lights[0].kind, LightKind::Area {
+            corner: Point3::new(-1.0, 5.0, -1.0),
+            u: Vec3::new(2.0, 0.0, 0.0),
+            v: Vec3::new(0.0, 0.0, 2.0),
+            samples: 9,
+        });
+        assert_eq!(lights[0].position, Point3::new(0.0, 5.0, 0.0));
+        assert_eq!(lights[0].colour, Colour::new(0.5, 0.5, 0.5));
+        assert_eq!(lights[1].shadow_samples(), 16);
+
+        let err = lights_from_str("
+            - !Area { corner: [0, 5, 0], u: [1, 0, 0], v: [2, 0, 0] }
+        ").unwrap_err();
+        assert!(err.to_string().contains("parallel"));
+        let err = lights_from_str("
+            - !Area { corner: [0, 5, 0], u: [1, 0, 0], v: [0, 0, 1], samples: 0 }
+        ").unwrap_err();
+        assert!(err.to_string().contains("samples"));
+    }
+
+    #[test]
+    fn test_sphere_light() {
+        let lights = lights_from_str("
+            - !SphereLight { position: [0, 5, 0], radius: 0.5, samples: 8, colour: red }
+            - !SphereLight { position: [0, 5, 0], radius: 2 }
+        ").unwrap();
+        assert_eq!(lights[0].kind, LightKind::Sphere { radius: 0.5, samples: 8 });
+        assert_eq!(lights[0].position, Point3::new(0.0, 5.0, 0.0));
+        assert_eq!(lights[0].colour, Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(lights[1].shadow_samples(), 16);
+
+        let err = lights_from_str("
+            - !SphereLight { position: [0, 5, 0], radius: 0 }
+        ").unwrap_err();
+        assert!(err.to_string().contains("radius"));
+    }
+
+    #[test]
+    fn test_render_block() {
+        let a: Inputs = serde_yaml::from_str("
+            objects: []
+            render:
+                quality: medium
+                samples: 16
+        ").unwrap();
+        assert_eq!(a.render, RenderOverrides { quality: Some(Quality::Medium), samples: Some(16), ..Default::default() });
+
+        let a: Inputs = serde_yaml::from_str("objects: []").unwrap();
+        assert_eq!(a.render, RenderOverrides::default());
+
+        let a: Inputs = serde_yaml::from_str("
+            objects: []
+            render:
+                post:
+                    - !Bloom { intensity: 0.5, radius: 2 }
+                    - !Vignette { strength: 0.3 }
+        ").unwrap();
+        assert_eq!(a.render.post, Some(Pipeline(vec![
+            Effect::Bloom(Bloom { intensity: 0.5, threshold: 1.0, radius: 2.0 }),
+            Effect::Vignette(Vignette { strength: 0.3 }),
+        ])));
+
+        let a: Inputs = serde_yaml::from_str("
+            objects: []
+            render: { time_budget: 90s }
+        ").unwrap();
+        assert_eq!(a.render.time_budget, Some(std::time::Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn test_shadow_catcher_flag() {
+        let a: Inputs = serde_yaml::from_str("
+            materials:
+                floor: !Matte { colour: [1, 1, 1] }
+            objects:
+                - type: !Plane
+                  material: !Ref floor
+                  shadow_catcher: true
+                - type: !Plane
+                  material: !Ref floor
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        assert!(scene.objects[0].material().shadow_catcher);
+        assert!(!scene.objects[1].material().shadow_catcher);
+
+        // Instances without a material catch shadows in the prototype's.
+        let a: Inputs = serde_yaml::from_str("
+            prototypes:
+                floor:
+                    type: !Plane
+                    material: !Matte { colour: red }
+            objects:
+                - type: !Instance floor
+                  shadow_catcher: true
+                - type: !Instance floor
+                  transform: [!Translate [0, -1, 0]]
+                - type: !Lens { r1: 1, r2: 1, thickness: 0.5 }
+                  shadow_catcher: true
+                  transform: [!Translate [0, 5, 0]]
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let down = |y: f64| scene.trace(&Ray::new(Point3::new(0.0, y, 0.0), -Vec3::y())).unwrap();
+        let hit = down(1.0);
+        assert!(hit.material.shadow_catcher);
+        assert_eq!(hit.material.colour, Colour::new(1.0, 0.0, 0.0));
+        assert!(!down(-0.5).material.shadow_catcher);
+        let lens = down(10.0);
+        assert!(lens.material.shadow_catcher);
+        assert_eq!(lens.material.transparency, Material::glass().transparency);
+    }
+
+    #[test]
+    fn test_material_back() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Plane
+                  material: !Mirror
+                  material_back: !Matte { colour: [1, 0, 0] }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        assert_eq!(scene.objects[0].material_for(true).reflect, 1.0);
+        assert_eq!(scene.objects[0].material_for(false).colour, Colour::new(1.0, 0.0, 0.0));
+        assert!(!scene.objects[0].material_for(false).shadow_catcher);
+
+        // Shadow catchers catch on both sides.
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Plane
+                  material: !Mirror
+                  material_back: !Matte { colour: [1, 0, 0] }
+                  shadow_catcher: true
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        assert!(scene.objects[0].material_for(true).shadow_catcher);
+        assert!(scene.objects[0].material_for(false).shadow_catcher);
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+                  material_back: !Mirror
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+    }
+
+    #[test]
+    fn test_one_sided() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Plane { one_sided: true }
+                - type: !Disk { one_sided: true, theta_max: 180 }
+                  transform: [!Translate [0, 1, 0]]
+                - type: !Plane
+        ").unwrap();
+        assert_eq!(a.objects[0].r#type, ObjectType::Plane(Some(PlaneInputs { one_sided: true })));
+        assert_eq!(a.objects[2].r#type, ObjectType::Plane(None));
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+
+        let up = Ray::new(Point3::new(0.0, -1.0, 0.5), Vec3::new(0.0, 1.0, 0.0));
+        let down = Ray::new(Point3::new(0.0, 2.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(scene.objects[0].hit(&up, 0.0, f64::INFINITY).is_none());
+        assert!(scene.objects[1].hit(&up, 0.0, f64::INFINITY).is_none());
+        assert!(scene.objects[1].hit(&down, 0.0, f64::INFINITY).is_some());
+        assert!(scene.objects[2].hit(&up, 0.0, f64::INFINITY).is_some());
+    }
+
+    #[test]
+    fn test_disk_inner_radius() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Disk { inner_radius: 0.5 }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let hole = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let ring = Ray::new(Point3::new(0.7, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(scene.trace(&hole).is_none());
+        assert!(scene.trace(&ring).is_some());
+
+        for radius in ["-0.1", "1"] {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Disk {{ inner_radius: {} }}
+            ", radius)).unwrap();
+            assert!(build_scene(a, (10, 10)).is_err());
+        }
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Disk { radius: 3, inner_radius: 2 }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let down = |x: f64| Ray::new(Point3::new(x, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(scene.trace(&down(1.9)).is_none());
+        assert!(scene.trace(&down(2.9)).is_some());
+        assert!(scene.trace(&down(3.1)).is_none());
+
+        for inputs in ["radius: 0", "radius: 2, inner_radius: 2"] {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Disk {{ {} }}
+            ", inputs)).unwrap();
+            assert!(build_scene(a, (10, 10)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_box_corners() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Box { min: [0, 0, 0], max: [2, 1, \"2 * 2\"] }
+                  transform: [!Translate [10, 0, 0]]
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let ray = Ray::new(Point3::new(11.0, 5.0, 3.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&ray).unwrap().t, 4.0));
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Box { min: [0, 1, 0], max: [2, 1, 2] }
+        ").unwrap();
+        let err = build_scene(a, (10, 10)).unwrap_err();
+        assert!(err.to_string().contains("min must be below max on every axis"));
+    }
+
+    #[test]
+    fn test_sweep() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Disk
+                - type: !Disk { theta_max: 90 }
+                - type: !Cylinder { min: 0, max: 1, closed: true, theta_min: 45, theta_max: 135 }
+        ").unwrap();
+        assert_eq!(a.objects[0].r#type, ObjectType::Disk(None));
+        assert_eq!(a.objects[1].r#type, ObjectType::Disk(Some(DiskInputs { theta_min: 0.0, theta_max: 90.0, one_sided: false, radius: 1.0, inner_radius: 0.0 })));
+        assert_eq!(a.objects[2].r#type, ObjectType::Cylinder {
+            radius: 1.0, radius_bottom: None, radius_top: None, min: 0.0, max: 1.0, closed: true, theta_min: 45.0, theta_max: 135.0,
+        });
+        assert!(build_scene(a, (10, 10)).is_ok());
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Cylinder { theta_min: 90, theta_max: 90 }
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+    }
+
+    #[test]
+    fn test_cylinder_radii() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Cylinder { radius: 2, min: 0, max: 1, closed: true }
+                - type: !Cylinder { radius_bottom: 2, radius_top: 0, min: 0, max: 2, closed: true }
+                  transform: [!Translate [10, 0, 0]]
+        ", (10, 10), &[]).unwrap();
+        let down = |x: f64| Ray::new(Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&down(1.5)).unwrap().t, 4.0));
+        // Comes to a point, so it is half as wide halfway up.
+        assert!(fuzzy_eq_f64(scene.trace(&down(11.0)).unwrap().t, 4.0));
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Cylinder { radius_top: 2 }
+        ", (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("finite min and max"), "{}", err);
+        assert!(parse_scene_str("objects: [{ type: !Cylinder { radius: 0 } }]", (10, 10), &[]).is_err());
+    }
+
+    #[test]
+    fn test_cone_angle() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Cone { angle: 30, min: -1, max: 0 }
+                - type: !Cone { radius: 2, min: -1, max: 0 }
+                  transform: [!Translate [10, 0, 0]]
+        ", (10, 10), &[]).unwrap();
+        let across = |x: f64| Ray::new(Point3::new(x - 5.0, -1.0 + 1e-6, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let slope = 30.0_f64.to_radians().tan();
+        assert!((scene.trace(&across(0.0)).unwrap().t - (5.0 - slope)).abs() < 1e-4);
+        assert!((scene.trace(&across(10.0)).unwrap().t - 3.0).abs() < 1e-4);
+
+        assert!(parse_scene_str("objects: [{ type: !Cone { angle: 90 } }]", (10, 10), &[]).is_err());
+        assert!(parse_scene_str("objects: [{ type: !Cone { angle: 30, radius: 1, min: -1, max: 0 } }]", (10, 10), &[]).is_err());
+        assert!(parse_scene_str("objects: [{ type: !Cone { radius: 1 } }]", (10, 10), &[]).is_err());
+    }
+
+    #[test]
+    fn test_lathe() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Lathe { profile: [[0.0, 0.0], [0.5, 0.2], [0.3, 1.0]], closed: true }
+        ").unwrap();
+        assert_eq!(a.objects[0].r#type, ObjectType::Lathe {
+            profile: vec![(0.0, 0.0), (0.5, 0.2), (0.3, 1.0)],
+            closed: true,
+        });
+        assert!(build_scene(a, (10, 10)).is_ok());
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Lathe { profile: [[1.0, 0.0]] }
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+    }
+
+    #[test]
+    fn test_prism() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Prism { points: [[0, 0], [1, 0], [1, 1], [0, 1]], min: 0, max: 1, closed: true }
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_ok());
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Prism { points: [[0, 0], [2, 0], [2, 2], [1, 1], [0, 2]] }
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+    }
+
+    #[test]
+    fn test_heightfield() {
+        // Black on the left, white on the right.
+        let path = std::env::temp_dir().join(format!("ray-tracer-{}-heightmap.png", std::process::id()));
+        image::GrayImage::from_fn(4, 3, |x, _| image::Luma([if x < 2 { 0 } else { 255 }])).save(&path).unwrap();
+
+        let a: Inputs = serde_yaml::from_str(&format!("
+            objects:
+                - type: !Heightfield {{ file: {:?}, vertical_scale: 0.2 }}
+        ", path)).unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let ray = Ray::new(Point3::new(0.9, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&ray).unwrap().t, 4.8));
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Heightfield { file: missing.png }
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+    }
+
+    #[test]
+    fn test_files_relative_to_scene() {
+        let dir = std::env::temp_dir().join(format!("ray-tracer-{}-relative", std::process::id()));
+        std::fs::create_dir_all(dir.join("maps")).unwrap();
+        image::GrayImage::from_fn(2, 2, |_, _| image::Luma([255])).save(dir.join("maps/flat.png")).unwrap();
+        let scene = dir.join("scene.yaml");
+        std::fs::write(&scene, "objects: [{ type: !Heightfield { file: maps/flat.png } }]").unwrap();
+
+        // Found from the scene's directory, wherever it's rendered from.
+        let (scene, _) = parse_scene(&scene, (10, 10)).unwrap();
+        let ray = Ray::new(Point3::new(0.5, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&ray).unwrap().t, 4.0));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_texture() {
+        // Red facing the camera, blue behind.
+        let path = std::env::temp_dir().join(format!("ray-tracer-{}-texture.png", std::process::id()));
+        image::RgbImage::from_fn(8, 4, |x, _| image::Rgb(if (2..6).contains(&x) { [255, 0, 0] } else { [0, 0, 255] })).save(&path).unwrap();
+
+        let a: Inputs = serde_yaml::from_str(&format!("
+            objects:
+                - type: !Sphere
+                  material: !Custom {{ ambient: 1, diffuse: 0, specular: 0, pattern: {{ type: !Texture, file: {:?}, mapping: Spherical }} }}
+        ", path)).unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(fuzzy_eq_colour(scene.colour_at(&ray, 1), Colour::new(1.0, 0.0, 0.0)));
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+                  material: !Matte { colour: red, pattern: { type: !Texture, file: missing.png } }
+        ").unwrap();
+        let err = build_scene(a, (10, 10)).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing.png"));
+
+        // Following the sphere's own uvs, which put the image's middle in front too.
+        let a: Inputs = serde_yaml::from_str(&format!("
+            objects:
+                - type: !Sphere
+                  material: !Custom {{ ambient: 1, diffuse: 0, specular: 0, uv_pattern: true, pattern: {{ type: !Texture, file: {:?}, mapping: Cube }} }}
+                  transform: [!Scale [2, 2, 2]]
+        ", path)).unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        assert!(fuzzy_eq_colour(scene.colour_at(&ray, 1), Colour::new(1.0, 0.0, 0.0)));
+
+        let error = serde_yaml::from_str::<Inputs>("
+            objects:
+                - type: !Sphere
+                  material: !Matte { colour: red, pattern: { type: !Stripes, colour_a: red } }
+        ").unwrap_err();
+        assert!(error.to_string().contains("colour_b"), "{}", error);
+    }
+
+    #[test]
+    fn test_noise_pattern() {
+        let scene = |seed: u64| {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Plane
+                      material: !Custom {{ ambient: 1, diffuse: 0, specular: 0, pattern: {{ type: !Noise, seed: {}, octaves: 4, scale: 2, colour_a: white, colour_b: black }} }}
+            ", seed)).unwrap();
+            build_scene(a, (10, 10)).unwrap().0
+        };
+        let colours = |scene: &Scene| -> Vec<Colour> {
+            (0..20).map(|i| scene.colour_at(&Ray::new(Point3::new(i as f64 * 0.37, 1.0, 0.2), -Vec3::y()), 1)).collect()
+        };
+        assert_eq!(colours(&scene(42)), colours(&scene(42)));
+        assert_ne!(colours(&scene(42)), colours(&scene(7)));
+    }
+
+    #[test]
+    fn test_nested_pattern_colours() {
+        // Black and white stripes, with a red to blue gradient in the white
+        // stripes, twice as long as the stripes are wide.
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Plane
+                  material: !Custom
+                    ambient: 1
+                    diffuse: 0
+                    specular: 0
+                    pattern:
+                        type: !Stripes
+                        colour_a: black
+                        colour_b: { type: !Gradient, colour_a: red, colour_b: '#0000ff', transform: [!Scale [2, 1, 1]] }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let at = |x: f64| scene.colour_at(&Ray::new(Point3::new(x, 1.0, 0.3), -Vec3::y()), 1);
+        assert!(fuzzy_eq_colour(at(0.5), Colour::new(0.0, 0.0, 0.0)));
+        assert!(fuzzy_eq_colour(at(1.0), Colour::new(0.5, 0.0, 0.5)));
+        assert!(fuzzy_eq_colour(at(1.5), Colour::new(0.25, 0.0, 0.75)));
+        assert!(fuzzy_eq_colour(at(2.5), Colour::new(0.0, 0.0, 0.0)));
+
+        // Other patterns still need flat colours.
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Plane
+                  material: !Custom { pattern: { type: !Noise, colour_a: white, colour_b: { type: !Checkers, colour_a: white, colour_b: black } } }
+        ").unwrap();
+        let error = build_scene(a, (10, 10)).unwrap_err();
+        assert!(format!("{:#}", error).contains("can't be patterns"), "{:#}", error);
+    }
+
+    #[test]
+    fn test_gradient_mode() {
+        let scene = |mode: &str| {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Plane
+                      material: !Custom {{ ambient: 1, diffuse: 0, specular: 0, pattern: {{ type: !Gradient, colour_a: black, colour_b: white, {} }} }}
+            ", mode)).unwrap();
+            build_scene(a, (10, 10))
+        };
+        let grey = |scene: &Scene, x: f64| scene.colour_at(&Ray::new(Point3::new(x, 1.0, 0.3), -Vec3::y()), 1);
+        let (repeat, _) = scene("").unwrap();
+        let (mirror, _) = scene("mode: Mirror").unwrap();
+        let (clamp, _) = scene("mode: Clamp").unwrap();
+        assert!(fuzzy_eq_colour(grey(&repeat, 1.25), Colour::new(0.25, 0.25, 0.25)));
+        assert!(fuzzy_eq_colour(grey(&mirror, 1.25), Colour::new(0.75, 0.75, 0.75)));
+        assert!(fuzzy_eq_colour(grey(&clamp, 1.25), Colour::new(1.0, 1.0, 1.0)));
+        let error = serde_yaml::from_str::<PatternInputs>("{ type: !Gradient, colour_a: black, colour_b: white, mode: Multiply }").unwrap_err();
+        assert!(error.to_string().contains("unknown variant"), "{}", error);
+    }
+
+    #[test]
+    fn test_perturbed_pattern() {
+        let scene = |pattern: &str| {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Plane
+                      material: !Custom {{ ambient: 1, diffuse: 0, specular: 0, pattern: {} }}
+            ", pattern)).unwrap();
+            build_scene(a, (10, 10)).unwrap().0
+        };
+        let colours = |scene: &Scene| -> Vec<Colour> {
+            (0..40).map(|i| scene.colour_at(&Ray::new(Point3::new(i as f64 * 0.11, 1.0, 0.4), -Vec3::y()), 1)).collect()
+        };
+        let stripes = "{ type: !Stripes, colour_a: white, colour_b: black, transform: [!Scale_uniform 0.2] }";
+        let plain = colours(&scene(stripes));
+        let wobbly = colours(&scene(&format!("{{ type: !Perturbed, amount: 0.3, seed: 5, child: {} }}", stripes)));
+        assert_ne!(wobbly, plain);
+        assert_eq!(colours(&scene(&format!("{{ type: !Perturbed, amount: 0.3, seed: 5, child: {} }}", stripes))), wobbly);
+        assert_eq!(colours(&scene(&format!("{{ type: !Perturbed, amount: 0, child: {} }}", stripes))), plain);
+    }
+
+    #[test]
+    fn test_debug_pattern() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Plane
+                  material: !Custom { ambient: 1, diffuse: 0, specular: 0, pattern: { type: !Debug, transform: [!Scale_uniform 2] } }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let colour = scene.colour_at(&Ray::new(Point3::new(3.0, 1.0, -0.5), -Vec3::y()), 1);
+        assert!(fuzzy_eq_colour(colour, Colour::new(0.5, 0.0, 0.75)));
+    }
+
+    #[test]
+    fn test_stripes_axis() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+                  material: !Custom { ambient: 1, diffuse: 0, specular: 0, pattern: { type: !Stripes, colour_a: white, colour_b: black, axis: Y, width: 0.5 } }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let at = |y: f64| scene.colour_at(&Ray::new(Point3::new(0.0, y, -5.0), Vec3::z()), 1);
+        assert!(fuzzy_eq_colour(at(0.25), Colour::new(1.0, 1.0, 1.0)));
+        assert!(fuzzy_eq_colour(at(0.75), Colour::new(0.0, 0.0, 0.0)));
+        assert!(fuzzy_eq_colour(at(-0.25), Colour::new(0.0, 0.0, 0.0)));
+
+        for width in ["0", "-1", ".nan", ".inf"] {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Plane
+                      material: !Matte {{ colour: white, pattern: {{ type: !Stripes, colour_a: white, colour_b: black, width: {} }} }}
+            ", width)).unwrap();
+            assert!(build_scene(a, (10, 10)).is_err(), "width {}", width);
+        }
+    }
+
+    #[test]
+    fn test_blend_pattern() {
+        // Checkers 4 wide, red and white stripes in the black squares, and
+        // in the white ones half width stripes times unit checkers.
+        let a: Inputs = serde_yaml::from_str("
+            transforms:
+                thin: [!Scale_uniform 0.5]
+            objects:
+                - type: !Plane
+                  material: !Custom
+                    ambient: 1
+                    diffuse: 0
+                    specular: 0
+                    pattern:
+                        type: !Blend
+                        mode: !Mask { type: !Checkers, colour_a: black, colour_b: white, transform: [!Scale_uniform 4] }
+                        pattern_a: { type: !Stripes, colour_a: [1, 0, 0], colour_b: white }
+                        pattern_b:
+                            type: !Blend
+                            mode: Multiply
+                            pattern_a: { type: !Stripes, colour_a: white, colour_b: black, transform: [!Apply thin] }
+                            pattern_b: { type: !Checkers, colour_a: white, colour_b: [0.5, 0.5, 0.5] }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let at = |x: f64| scene.colour_at(&Ray::new(Point3::new(x, 1.0, 1.2), -Vec3::y()), 1);
+        assert!(fuzzy_eq_colour(at(0.5), Colour::new(1.0, 0.0, 0.0)));
+        assert!(fuzzy_eq_colour(at(1.5), Colour::new(1.0, 1.0, 1.0)));
+        assert!(fuzzy_eq_colour(at(5.2), Colour::new(1.0, 1.0, 1.0)));
+        assert!(fuzzy_eq_colour(at(5.7), Colour::new(0.0, 0.0, 0.0)));
+        assert!(fuzzy_eq_colour(at(6.2), Colour::new(0.5, 0.5, 0.5)));
+
+        assert!(serde_yaml::from_str::<Inputs>("
+            objects:
+                - type: !Plane
+                  material: !Custom { pattern: { type: !Blend, pattern_a: { type: !Checkers, colour_a: white, colour_b: black } } }
+        ").is_err());
+
+        // Gradient modes aren't blend modes.
+        let error = serde_yaml::from_str::<PatternInputs>("{ type: !Blend, mode: Clamp, pattern_a: { type: !Debug }, pattern_b: { type: !Debug } }").unwrap_err();
+        assert!(error.to_string().contains("unknown variant"), "{}", error);
+    }
+
+    #[test]
+    fn test_pattern_fields() {
+        let pattern = |yaml: &str| serde_yaml::from_str::<PatternInputs>(yaml);
+        assert!(pattern("{ type: !Noise, colour_a: white, colour_b: black, octaves: 3 }").is_ok());
+        assert!(pattern("{ type: Checkers, colour_a: white, colour_b: black }").is_ok());
+
+        // Fields of other kinds of pattern are rejected.
+        for yaml in [
+            "{ type: !Noise, colour_a: white, colour_b: black, width: 2 }",
+            "{ type: !Checkers, colour_a: white, colour_b: black, octaves: 3 }",
+            "{ type: !Texture, file: a.png, pattern_a: { type: !Debug } }",
+            "{ type: !Debug, colour_a: white }",
+        ] {
+            let error = pattern(yaml).unwrap_err();
+            assert!(error.to_string().contains("unknown field"), "{}", error);
+        }
+        assert!(pattern("{ type: !Spots, colour_a: white, colour_b: black }").is_err());
+        assert!(pattern("{ colour_a: white, colour_b: black }").is_err());
+    }
+
+    #[test]
+    fn test_uv_checkers() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+                  material: !Custom { ambient: 1, diffuse: 0, specular: 0, pattern: { type: !UvCheckers, width: 4, height: 2, colour_a: white, colour_b: black } }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        // u is 0 in front, 0.5 behind and 0.75 on the left, with a square every 0.25.
+        // Just below the equator, in the first row.
+        let at = |x: f64, z: f64| scene.colour_at(&Ray::new(Point3::new(x, -0.3, z), Vec3::new(-x, 0.0, -z).normalize()), 1);
+        assert!(fuzzy_eq_colour(at(0.0, -5.0), Colour::new(1.0, 1.0, 1.0)));
+        assert!(fuzzy_eq_colour(at(0.0, 5.0), Colour::new(1.0, 1.0, 1.0)));
+        assert!(fuzzy_eq_colour(at(-5.0, 0.0), Colour::new(0.0, 0.0, 0.0)));
+
+        for size in ["width: 0", "height: -2", "width: .nan"] {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Sphere
+                      material: !Matte {{ colour: white, pattern: {{ type: !UvCheckers, colour_a: white, colour_b: black, {} }} }}
+            ", size)).unwrap();
+            let error = build_scene(a, (10, 10)).unwrap_err();
+            assert!(format!("{:#}", error).contains("must be positive"), "{:#}", error);
+        }
+    }
+
+    #[test]
+    fn test_sdf() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sdf
+                    shape: !RoundedBox { half_size: [1, 1, 1], radius: 0.2 }
+                    max_steps: 64
+                - type: !Sdf
+                    shape: !SphereBlend { spheres: [[[0, 0, 0], 1], [[1, 0, 0], 0.5]], smoothness: 0.2 }
+                  transform: [!Translate [5, 0, 0]]
+                - type: !Sdf { shape: !Gyroid { scale: 10, thickness: 0.2 }, hit_epsilon: 0.00001 }
+                  transform: [!Translate [-5, 0, 0]]
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&ray).unwrap().t, 4.0));
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sdf { shape: !Gyroid { scale: 10, thickness: 0.2 }, hit_epsilon: 0 }
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+
+        for scale in ["0", "-2", ".nan"] {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                objects:
+                    - type: !Sdf {{ shape: !Gyroid {{ scale: {}, thickness: 0.2 }} }}
+            ", scale)).unwrap();
+            let error = build_scene(a, (10, 10)).unwrap_err();
+            assert!(format!("{:#}", error).contains("gyroid scale"), "{:#}", error);
+        }
+    }
+
+    #[test]
+    fn test_instances() {
+        let a: Inputs = serde_yaml::from_str("
+            transforms:
+                tall: [!Scale [1, 2, 1]]
+            prototypes:
+                pillar:
+                    type: !Cylinder { min: 0, max: 1, closed: true }
+                    material: !Matte { colour: red }
+                    transform: [!Apply tall]
+            objects:
+                - type: !Instance pillar
+                - type: !Instance pillar
+                  transform: [!Translate [5, 0, 0]]
+                  material: !Matte { colour: blue }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let down = |x: f64| Ray::new(Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let hit = scene.trace(&down(0.0)).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 3.0));
+        assert!(fuzzy_eq_colour(hit.colour, Colour::new(1.0, 0.0, 0.0)));
+        let hit = scene.trace(&down(5.0)).unwrap();
+        assert_eq!(hit.obj_id, scene.objects[1].id());
+        assert!(fuzzy_eq_f64(hit.t, 3.0));
+        assert!(fuzzy_eq_colour(hit.colour, Colour::new(0.0, 0.0, 1.0)));
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Instance missing
+        ").unwrap();
+        let err = build_scene(a, (10, 10)).unwrap_err();
+        assert_eq!(err.to_string(), "Object 0 is an instance of unknown prototype missing");
+    }
+
+    #[test]
+    fn test_csg() {
+        let a: Inputs = serde_yaml::from_str("
+            transforms:
+                hole: [!Scale [0.5, 2, 0.5]]
+            objects:
+                - type: !Csg
+                    op: Union
+                    left:
+                        type: !Csg
+                            op: Difference
+                            left: { type: !Box }
+                            right: { type: !Box, transform: [!Apply hole] }
+                    right: { type: !Sphere, material: !Mirror, transform: [!Scale_uniform 0.25] }
+                  material: !Matte { colour: red }
+                - type: !Sphere
+                  transform: [!Translate [10, 0, 0]]
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+
+        // Looking down the hole shows the sphere inside the box.
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = scene.trace(&ray).unwrap();
+        assert_eq!(hit.obj_id, scene.objects[0].id());
+        assert!(fuzzy_eq_f64(hit.t, 4.75));
+        assert_eq!(*hit.material, Material::mirror());
+
+        // Children without a material use the csg's.
+        let ray = Ray::new(Point3::new(0.9, 5.0, 0.9), Vec3::new(0.0, -1.0, 0.0));
+        let hit = scene.trace(&ray).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 4.0));
+        assert!(fuzzy_eq_colour(hit.colour, Colour::new(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_epsilon() {
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Plane
+                  transform:
+                    - !Scale [1000, 1000, 1000]
+                - type: !Sphere
+                  epsilon: 0.01
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        assert!(fuzzy_eq_f64(scene.objects[0].epsilon(), 0.1));
+        assert_eq!(scene.objects[1].epsilon(), 0.01);
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+                  epsilon: -1
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+    }
+
+    #[test]
+    fn test_camera_relative() {
+        let render = |yaml: &str| {
+            let (scene, camera) = build_scene(serde_yaml::from_str(yaml).unwrap(), (16, 16)).unwrap();
+            (0..16)
+                .flat_map(|y| (0..16).map(move |x| (x, y)))
+                .map(|(x, y)| scene.colour_at(&camera.ray_for_pixel(x, y), 5))
+                .collect::<Vec<_>>()
+        };
+
+        let near = render("
+            camera: { look_from: [0, 0, -5], look_at: [0, 0, 0], vfov: 30 }
+            objects:
+                - type: !Sphere
+            lights:
+                - position: [-10, 10, -10]
+        ");
+        let far = render("
+            camera: { look_from: [1000000, 0, 999995], look_at: [1000000, 0, 1000000], vfov: 30, relative: true }
+            objects:
+                - type: !Sphere
+                  transform:
+                    - !Translate [1000000, 0, 1000000]
+            lights:
+                - position: [999990, 10, 999990]
+        ");
+        for (a, b) in near.iter().zip(far.iter()) {
+            assert!(crate::colour::fuzzy_eq_colour(*a, *b), "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_camera_focus_dist() {
+        let camera = |camera: &str| {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                camera: {}
+                objects:
+                    - type: !Sphere
+            ", camera))?;
+            build_scene(a, (10, 10)).map(|(_, camera)| camera)
+        };
+        let focus = |yaml: &str| camera(yaml).unwrap().focus_dist();
+        assert_eq!(focus("{ look_from: [0, 0, -5], look_at: [0, 3, -1], aperture: 0.5, focus_dist: 2.5 }"), 2.5);
+        assert_eq!(focus("{ look_from: [0, 0, -5], look_at: [0, 3, -1], focus_dist: 10 / 4 }"), 2.5);
+        assert_eq!(focus("{ look_from: [0, 0, -5], look_at: [0, 3, -1] }"), 5.0);
+        assert_eq!(focus("{ look_from: [1, 0, 0], look_at: [1, 0, 2], relative: true }"), 2.0);
+        assert!(camera("{ look_from: [0, 0, -5], look_at: [0, 0, 0], focus_dist: 0 }").is_err());
+    }
+
+    #[test]
+    fn test_camera_transform() {
+        let build = |yaml: &str| build_scene(serde_yaml::from_str(yaml).unwrap(), (16, 16)).unwrap();
+        let rays = |camera: &Camera| (0..16).flat_map(|y| (0..16).map(move |x| (x, y))).map(|(x, y)| camera.ray_for_pixel(x, y)).collect::<Vec<_>>();
+
+        // Turning the view 45 degrees about y orbits the camera the other way.
+        let (_, turned) = build("
+            camera: { look_from: [0, 1, -5], look_at: [0, 1, 0], transform: [!Rotate_y 45] }
+            objects: []
+        ");
+        let (_, applied) = build("
+            camera: { look_from: [0, 1, -5], look_at: [0, 1, 0], transform: [!Apply orbit] }
+            transforms:
+                orbit: [!Rotate_y 45]
+            objects: []
+        ");
+        let s = 5.0 * std::f64::consts::FRAC_1_SQRT_2;
+        let (_, moved) = build(&format!("
+            camera: {{ look_from: [{}, 1, {}], look_at: [0, 1, 0] }}
+            objects: []
+        ", s, -s));
+        for camera in [turned, applied] {
+            for (a, b) in rays(&camera).iter().zip(rays(&moved)) {
+                assert!(fuzzy_eq_vec(&a.origin.coords, &b.origin.coords) && fuzzy_eq_vec(&a.direction, &b.direction));
+            }
+        }
+
+        // Pivots are where they were written, even when recentred on the camera.
+        let render = |yaml: &str| {
+            let (scene, camera) = build(yaml);
+            rays(&camera).iter().map(|ray| scene.colour_at(ray, 5)).collect::<Vec<_>>()
+        };
+        let near = render("
+            camera: { look_from: [0, 0, -5], look_at: [0, 0, 0], vfov: 30, transform: [!Rotate_y 60] }
+            objects:
+                - type: !Box
+            lights:
+                - position: [-10, 10, -10]
+        ");
+        let far = render("
+            camera: { look_from: [1000000, 0, 999995], look_at: [1000000, 0, 1000000], vfov: 30, relative: true,
+                      transform: [!Rotate_y_about { angle: 60, pivot: [1000000, 0, 1000000] }] }
+            objects:
+                - type: !Box
+                  transform:
+                    - !Translate [1000000, 0, 1000000]
+            lights:
+                - position: [999990, 10, 999990]
+        ");
+        for (a, b) in near.iter().zip(far.iter()) {
+            assert!(fuzzy_eq_colour(*a, *b), "{} != {}", a, b);
+        }
+        assert!(build_scene(serde_yaml::from_str("{ camera: { transform: [!Apply nowhere] }, objects: [] }").unwrap(), (4, 4)).is_err());
+    }
+
+    #[test]
+    fn test_camera_projection() {
+        let camera = |projection: &str| {
+            let a: Inputs = serde_yaml::from_str(&format!("
+                camera: {{ look_from: [0, 0, 0], look_at: [0, 0, 1], {} }}
+                objects: []
+            ", projection)).unwrap();
+            build_scene(a, (20, 10)).unwrap().1
+        };
+        // The left edge looks behind in a panorama.
+        assert!(camera("projection: Panorama").ray_for_pixel(0, 5).direction.z < -0.9);
+        assert!(camera("projection: Perspective").ray_for_pixel(0, 5).direction.z > 0.0);
+        assert!(camera("").ray_for_pixel(0, 5).direction.z > 0.0);
+        assert!(serde_yaml::from_str::<CameraInputs>("projection: Fisheye").is_err());
+    }
+
+    #[test]
+    fn test_default_material() {
+        let a: Inputs = serde_yaml::from_str("
+            materials:
+                clay: !Matte { colour: [0.8, 0.8, 0.8] }
+            default_material: !Ref clay
+            objects:
+                - type: !Sphere
+                - type: !Plane
+                - type: !Box
+                - type: !Sphere
+                  material: !Mirror
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        let clay = scene.default_material();
+        assert_eq!(clay.colour, Colour::new(0.8, 0.8, 0.8));
+        for obj in &scene.objects[..3] {
+            assert!(Arc::ptr_eq(obj.material(), clay));
+        }
+        assert_eq!(*scene.objects[3].material().as_ref(), Material::mirror());
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        assert_eq!(*scene.objects[0].material().as_ref(), Material::default());
+    }
+
+    #[test]
+    fn test_named_transforms() {
+        let a: Inputs = serde_yaml::from_str("
+            transforms:
+                shrink: [!Scale_uniform 0.5]
+                on_table: [!Translate [0, 1, 0], !Apply shrink]
+            objects:
+                - type: !Sphere
+                  transform: [!Rotate_y 30, !Apply on_table]
+                - type: !Sphere
+                  transform: [!Rotate_y 30, !Translate [0, 1, 0], !Scale_uniform 0.5]
+                - type: !Plane
+                  material: !Matte
+                    colour: red
+                    pattern: { type: !Stripes, colour_a: red, colour_b: blue, transform: [!Apply shrink] }
+        ").unwrap();
+        let (scene, _) = build_scene(a, (10, 10)).unwrap();
+        assert_eq!(scene.objects[0].transform(), scene.objects[1].transform());
+        let pattern = scene.objects[2].material().pattern.as_ref().unwrap();
+        assert_eq!(*pattern.transform(), Matrix4::new_scaling(0.5));
+
+        let a: Inputs = serde_yaml::from_str("
+            transforms:
+                a: [!Apply b]
+                b: [!Scale_uniform 2, !Apply a]
+            objects:
+                - type: !Sphere
+        ").unwrap();
+        let err = format!("{:#}", build_scene(a, (10, 10)).unwrap_err());
+        assert!(err.contains("a -> b -> a") || err.contains("b -> a -> b"), "{}", err);
+
+        let a: Inputs = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+                  transform: [!Apply nowhere]
+        ").unwrap();
+        assert!(build_scene(a, (10, 10)).is_err());
+    }
+
+    #[test]
+    fn test_params() {
+        let yaml = "
+            params:
+                size: 1.0
+            camera: { look_from: [0, 0, -5], look_at: [0, 0, 0] }
+            objects:
+                - type: !Sphere
+                  transform:
+                    - !Scale_uniform ${size}
+        ";
+        let centre = ray::Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit_distance = |params: &[(String, String)]| {
+            let (scene, _, _) = parse_scene_str(yaml, (10, 10), params).unwrap();
+            scene.trace(&centre).unwrap().t
+        };
+        assert!(fuzzy_eq_f64(hit_distance(&[]), 4.0));
+        assert!(fuzzy_eq_f64(hit_distance(&[("size".into(), "2".into())]), 3.0));
+        assert!(fuzzy_eq_f64(hit_distance(&[("size".into(), "0.5".into())]), 4.5));
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Sphere
+                  transform:
+                    - !Scale_uniform ${radius}
+        ", (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("radius"));
+    }
+
+    #[test]
+    fn test_expressions() {
+        let (scene, _, _) = parse_scene_str("
+            params:
+                radius: 3
+            camera:
+                look_from: [0, \"2 * ${radius}\", \"-${radius} * 2\"]
+                vfov: 90 / 2
+            objects:
+                - { type: !Box, transform: [!Translate [\"${radius} * cos(radians(360 / 7 * 0))\", 0, \"${radius} * sin(radians(360 / 7 * 0))\"]] }
+                - { type: !Box, transform: [!Translate [\"${radius} * cos(radians(360 / 7 * 1))\", 0, \"${radius} * sin(radians(360 / 7 * 1))\"]] }
+                - { type: !Box, transform: [!Translate [\"${radius} * cos(radians(360 / 7 * 2))\", 0, \"${radius} * sin(radians(360 / 7 * 2))\"]] }
+                - { type: !Box, transform: [!Translate [\"${radius} * cos(radians(360 / 7 * 3))\", 0, \"${radius} * sin(radians(360 / 7 * 3))\"]] }
+                - { type: !Box, transform: [!Translate [\"${radius} * cos(radians(360 / 7 * 4))\", 0, \"${radius} * sin(radians(360 / 7 * 4))\"]] }
+                - { type: !Box, transform: [!Translate [\"${radius} * cos(radians(360 / 7 * 5))\", 0, \"${radius} * sin(radians(360 / 7 * 5))\"]] }
+                - { type: !Box, transform: [!Translate [\"${radius} * cos(radians(360 / 7 * 6))\", 0, \"${radius} * sin(radians(360 / 7 * 6))\"]] }
+            lights:
+                - position: [0, \"10 + 1\", 0]
+        ", (10, 10), &[]).unwrap();
+
+        assert_eq!(scene.lights[0].position, Point3::new(0.0, 11.0, 0.0));
+        for (i, obj) in scene.objects.iter().enumerate() {
+            let centre = obj.transform().transform_point(&Point3::origin());
+            let angle = (i as f64 * 360.0 / 7.0).to_radians();
+            assert!(fuzzy_eq_f64(centre.x, 3.0 * angle.cos()));
+            assert!(fuzzy_eq_f64(centre.z, 3.0 * angle.sin()));
+            assert_eq!(centre.y, 0.0);
+        }
+
+        let a: Result<Inputs, _> = serde_yaml::from_str("
+            objects:
+                - type: !Sphere
+                  transform: [!Scale_uniform 1 / 0]
+        ");
+        assert!(a.unwrap_err().to_string().contains("Division by zero"));
+    }
+
+    #[test]
+    fn test_motion() {
+        let (scene, camera, _) = parse_scene_str("
+            camera: { look_from: [0, 0, -5], look_at: [0, 0, 0], shutter: [0, 1] }
+            objects:
+                - type: !Sphere
+                  transform: [!Scale_uniform 0.5]
+                  motion: { end: [4, 0, 0] }
+        ", (10, 10), &[]).unwrap();
+        let ray = |x: f64, time: f64| Ray::new(Point3::new(x, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0)).with_time(time);
+        assert!(scene.trace(&ray(0.0, 0.0)).is_some());
+        assert!(scene.trace(&ray(0.0, 1.0)).is_none());
+        assert!(fuzzy_eq_f64(scene.trace(&ray(3.0, 0.75)).unwrap().t, 4.5));
+        assert_eq!(camera.ray_for_pixel(5, 5).time, 0.5);
+
+        let err = parse_scene_str("
+            camera: { shutter: [1, 0] }
+            objects: []
+        ", (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("shutter"));
+    }
+
+    #[test]
+    fn test_ellipsoid() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Ellipsoid { radii: [2, 0.5, 1] }
+                  transform:
+                    - !Translate [0, 0, 10]
+                    - !Rotate_z 90
+        ", (10, 10), &[]).unwrap();
+        // The long axis is turned to point along y, and the translation isn't stretched.
+        let hit = scene.trace(&Ray::new(Point3::new(0.0, 5.0, 10.0), Vec3::new(0.0, -1.0, 0.0))).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 3.0));
+        let hit = scene.trace(&Ray::new(Point3::new(5.0, 0.0, 10.0), Vec3::new(-1.0, 0.0, 0.0))).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 4.5));
+
+        // The same as Sphere::ellipsoid with the list on top of its transform.
+        let mut placement = Sphere::new(Material::default());
+        placement.translate(0.0, 0.0, 10.0);
+        placement.rotate(Axis::Z, 90.0);
+        let mut expected = Sphere::ellipsoid(Material::default(), 2.0, 0.5, 1.0);
+        expected.set_transform(placement.transform() * expected.transform());
+        expected.set_inverse(expected.inverse() * placement.inverse());
+        for ray in [
+            Ray::new(Point3::new(0.3, 5.0, 10.2), Vec3::new(0.0, -1.0, 0.0)),
+            Ray::new(Point3::new(-4.0, 1.0, 6.0), Vec3::new(1.0, -0.2, 1.0).normalize()),
+        ] {
+            let hit = scene.trace(&ray).unwrap();
+            let want = &expected.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+            assert!(fuzzy_eq_f64(hit.t, want.t));
+            assert!(fuzzy_eq_vec(&hit.normal, &want.normal));
+        }
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Ellipsoid { radii: [1, 0, 1] }
+        ", (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("positive"));
+    }
+
+    #[test]
+    fn test_lens() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Lens { r1: 2.0, r2: 2.0, thickness: 0.3 }
+                - type: !Lens { r1: 1.0, r2: 3.0, thickness: 0.5 }
+                  material: !Matte { colour: [1, 0, 0] }
+                  transform: [!Translate [5, 0, 0]]
+        ", (10, 10), &[]).unwrap();
+        assert_eq!(scene.objects[0].material().transparency, 1.0);
+        assert_eq!(scene.objects[1].material().transparency, 0.0);
+        let hit = scene.trace(&Ray::new(Point3::new(5.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0))).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 4.75));
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Lens { r1: 1.0, r2: 1.0, thickness: 2.0 }
+        ", (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("thickness"));
+    }
+
+    #[test]
+    fn test_superellipsoid() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Superellipsoid
+                - type: !Superellipsoid { e1: 0.1, e2: 0.1 }
+                  transform: [!Translate [5, 0, 0]]
+        ", (10, 10), &[]).unwrap();
+        let down = |x: f64| Ray::new(Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&down(0.0)).unwrap().t, 4.0));
+        assert!(scene.trace(&down(0.9)).unwrap().t > 4.5);
+        assert!(scene.trace(&down(5.9)).unwrap().t < 4.01);
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Superellipsoid { e1: 0, e2: 1 }
+        ", (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("exponents"));
+    }
+
+    #[test]
+    fn test_rotate_axis() {
+        let (scene, _, _) = parse_scene_str("
+            transforms:
+                stand_up: [!Rotate_axis [0, 0, 1, 90]]
+            objects:
+                - type: !Cylinder { min: 0, max: 2, closed: true }
+                  transform: [!Apply stand_up]
+        ", (10, 10), &[]).unwrap();
+        // Lying along -x now.
+        let ray = Ray::new(Point3::new(-1.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&ray).unwrap().t, 4.0));
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Sphere
+                  transform: [!Rotate_axis [0, 0, 0, 90]]
+        ", (10, 10), &[]).unwrap_err();
+        assert!(format!("{:#}", err).contains("axis"));
+    }
+
+    #[test]
+    fn test_about_pivot() {
+        // Both at (5, 0, 0), the first turned in place, the second swung round the origin.
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Box { min: [0, 0, 0], max: [2, 1, 1] }
+                  transform: [!Rotate_y_about { angle: 90, pivot: [5, 0, 0] }, !Translate [5, 0, 0]]
+                - type: !Box { min: [0, 0, 0], max: [2, 1, 1] }
+                  transform: [!Rotate_y 90, !Translate [5, 0, 0]]
+                - type: !Sphere
+                  transform: [!Scale_about { scale: [2, 2, 2], pivot: [0, 0, 10] }]
+        ", (10, 10), &[]).unwrap();
+        let down = |x: f64, z: f64| Ray::new(Point3::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(scene.trace(&down(5.5, -1.5)).unwrap().obj_id, scene.objects[0].id());
+        assert!(scene.trace(&down(6.5, 0.5)).is_none());
+        assert_eq!(scene.trace(&down(0.5, -6.5)).unwrap().obj_id, scene.objects[1].id());
+        // Doubled away from z = 10, so centred on z = -10.
+        assert!(fuzzy_eq_f64(scene.trace(&down(0.0, -10.0)).unwrap().t, 3.0));
+    }
+
+    #[test]
+    fn test_look_at() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Cylinder { radius: 0.5, min: 0, max: 5, closed: true }
+                  transform: [!Look_at { from: [1, 2, 3], to: [4, -2, 3] }]
+        ", (10, 10), &[]).unwrap();
+        let ray = Ray::new(Point3::new(-2.0, 6.0, 3.0), Vec3::new(0.6, -0.8, 0.0));
+        assert!(fuzzy_eq_f64(scene.trace(&ray).unwrap().t, 5.0));
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Sphere
+                  transform: [!Look_at { from: [1, 2, 3], to: [1, 2, 3] }]
+        ", (10, 10), &[]).unwrap_err();
+        assert!(format!("{:#}", err).contains("different"));
+    }
+
+    #[test]
+    fn test_parametric() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Parametric { preset: WavyPlane, u_steps: 8, v_steps: 8 }
+                - type: !Parametric { preset: Mobius }
+                  transform: [!Translate [5, 0, 0]]
+        ", (10, 10), &[]).unwrap();
+        // A wave crest, an eighth of the way across.
+        let hit = scene.trace(&Ray::new(Point3::new(-0.75, 5.0, -0.75), Vec3::new(0.0, -1.0, 0.0))).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 4.9));
+        assert!(scene.trace(&Ray::new(Point3::new(6.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0))).is_some());
+
+        let err = parse_scene_str("
+            objects:
+                - type: !Parametric { preset: Klein, u_steps: 0 }
+        ", (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("step"));
+    }
+
+    #[test]
+    fn test_hexagon() {
+        let (scene, _, _) = parse_scene_str("
+            objects:
+                - type: !Hexagon
+                  material: !Matte { colour: [1, 0, 0] }
+                  transform: [!Translate [0, 1, 0]]
+        ", (10, 10), &[]).unwrap();
+        let hit = scene.trace(&Ray::new(Point3::new(0.0, 1.0, -5.0), Vec3::new(0.0, 0.0, 1.0))).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 3.75));
+        assert!(scene.trace(&Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn test_cast_shadow() {
+        let yaml = |cast_shadow: bool| format!("
+            objects:
+                - type: !Plane
+                - type: !Sphere
+                  transform: [!Translate [0, 2, 0]]
+                  cast_shadow: {}
+            lights:
+                - position: [0, 10, 0]
+        ", cast_shadow);
+        let under = Ray::new(Point3::new(0.0, 0.5, -5.0), Vec3::new(0.0, -0.5, 5.0).normalize());
+        let (scene, _, _) = parse_scene_str(&yaml(true), (10, 10), &[]).unwrap();
+        assert!(scene.is_shadowed(&Point3::new(0.0, 0.001, 0.0), &mut rand::thread_rng()));
+        let shadowed = scene.colour_at(&under, 1);
+
+        // Only shadow rays skip the sphere, camera rays still hit it.
+        let (scene, _, _) = parse_scene_str(&yaml(false), (10, 10), &[]).unwrap();
+        assert!(!scene.is_shadowed(&Point3::new(0.0, 0.001, 0.0), &mut rand::thread_rng()));
+        let hit = scene.trace(&Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0))).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 2.0));
+        let lit = scene.colour_at(&under, 1);
+        assert!(fuzzy_eq_colour(shadowed, Colour::new(0.1, 0.1, 0.1)), "{}", shadowed);
+        assert!(!fuzzy_eq_colour(lit, shadowed));
+    }
+
+    #[test]
+    fn test_visible_to_camera() {
+        let (scene, camera, _) = parse_scene_str("
+            camera: { look_from: [0, 0, -5], look_at: [0, 0, 0] }
+            objects:
+                - type: !Sphere
+                  visible_to_camera: false
+                - type: !Plane
+                  material: !Mirror
+                  transform: [!Translate [0, -2, 0]]
+            lights:
+                - position: [0, 10, -10]
+        ", (11, 11), &[]).unwrap();
+        // The camera looks straight through the sphere.
+        let ray = camera.ray_for_pixel(5, 5);
+        assert!(scene.trace(&ray).is_none());
+        assert!(scene.trace(&Ray::new(ray.origin, ray.direction)).is_some());
+
+        // But its reflection shows in the mirror, unlike the same view shifted to the side, and it still casts a shadow.
+        let to_mirror = |x: f64| Ray::new(Point3::new(x, 0.0, -5.0), Vec3::new(0.0, -2.0, 3.0).normalize()).as_primary();
+        assert!(!fuzzy_eq_colour(scene.colour_at(&to_mirror(0.0), 2), scene.colour_at(&to_mirror(3.0), 2)));
+        assert!(scene.is_shadowed(&Point3::new(0.0, -1.999, 2.0), &mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn test_object_names() {
+        let yaml = |second: &str| format!("
+            camera: {{ look_from: [0, 0, -5], look_at: [0, 0, 0] }}
+            objects:
+                - type: !Sphere
+                  name: ball
+                - type: !Plane
+                  name: {}
+                  transform: [!Translate [0, -2, 0]]
+        ", second);
+        let (scene, _, _) = parse_scene_str(&yaml("floor"), (10, 10), &[]).unwrap();
+        assert_eq!(scene.get_by_name("ball").unwrap().id(), scene.objects[0].id());
+        assert_eq!(scene.get_by_name("floor").unwrap().id(), scene.objects[1].id());
+        assert_eq!(scene.name_of(scene.objects[1].id()), Some("floor"));
+        assert!(scene.get_by_name("wall").is_none());
+
+        let err = parse_scene_str(&yaml("ball"), (10, 10), &[]).unwrap_err();
+        assert!(err.to_string().contains("\"ball\""), "{}", err);
+
+        // Errors name the object they are about.
+        let err = parse_scene_str("
+            objects:
+                - type: !Ellipsoid { radii: [1, 0, 1] }
+                  name: egg
+        ", (10, 10), &[]).unwrap_err();
+        assert!(format!("{:#}", err).contains("0 \"egg\""), "{:#}", err);
+    }
+}
@@ -1,9 +1,24 @@
+mod expr;
+#[cfg(feature = "gltf")]
+mod gltf;
 mod input;
 mod output;
+mod params;
+mod preview;
+mod tiles;
 
 pub use output::{
     OutputFormat,
     write_to_file,
+    RowWriter,
+    write_ray_paths,
 };
 
-pub use input::parse_scene;
+pub use tiles::{TileInfo, tile_rows, settings_hash, write_tile, read_tile, merge_tiles};
+
+pub use preview::{ColourMode, ansi_preview, terminal_width};
+
+pub use input::{parse_scene, parse_scene_file, scene_files};
+
+#[cfg(feature = "gltf")]
+pub use self::gltf::import_gltf;
@@ -1,7 +1,8 @@
-use anyhow::{Result, Context};
+use anyhow::{Result, Context, bail};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use crate::render::Image;
+use crate::debug::PixelPath;
 
 #[derive(clap::ValueEnum, Clone, Default)]
 pub enum OutputFormat {
@@ -17,35 +18,96 @@ pub fn write_to_file(
     dimensions: (u32, u32),
 ) -> Result<()> {
 
-    let extension: &str = match format {
+    let mut writer = RowWriter::create(file_name, format, dimensions)?;
+    writer.write_rows(&image)?;
+    writer.finish()
+}
+
+fn extension(format: &OutputFormat) -> &'static str {
+    match format {
         OutputFormat::PNG => "png",
         OutputFormat::PPM => "ppm",
-    };
-    let path = format!("{}.{}", file_name, extension);
-    let flat_img = image.into_iter().flatten().collect::<Vec<u8>>();
+    }
+}
 
-    match format {
-        OutputFormat::PNG => {
-            image::save_buffer_with_format(
-                &path,
-                flat_img.as_slice(),
-                dimensions.0,
-                dimensions.1,
-                image::ColorType::Rgb8,
-                image::ImageFormat::Png,
-            ).context("Could not save image buffer to PNG file format.")?;
-        },
-
-        OutputFormat::PPM => {
-            let mut file = File::create(&path)?;
-            file.write_all(format!("P3\n{} {}\n255\n", dimensions.0, dimensions.1).as_bytes())
-                .context("Could not write PPM header to file.")?;
-            for pixel in flat_img.chunks(3) {
-                file.write_all(format!("{} {} {}\n", pixel[0], pixel[1], pixel[2]).as_bytes())
-                .context("Could not write pixels to PPM file.")?;
-            }
+// Writes an image a few rows at a time, so the whole image never has to be in memory.
+pub struct RowWriter {
+    path:      String,
+    encoder:   RowEncoder,
+    remaining: u32,
+}
+
+enum RowEncoder {
+    Png(Box<png::StreamWriter<'static, BufWriter<File>>>),
+    Ppm(BufWriter<File>),
+}
+
+impl RowWriter {
+    pub fn create(file_name: &str, format: OutputFormat, dimensions: (u32, u32)) -> Result<Self> {
+        let path = format!("{}.{}", file_name, extension(&format));
+        let file = BufWriter::new(File::create(&path).with_context(|| format!("Could not create file {}.", path))?);
+
+        let encoder = match format {
+            OutputFormat::PNG => {
+                let mut encoder = png::Encoder::new(file, dimensions.0, dimensions.1);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                let writer = encoder.write_header().context("Could not write PNG header to file.")?;
+                RowEncoder::Png(Box::new(writer.into_stream_writer().context("Could not start PNG stream.")?))
+            },
+            OutputFormat::PPM => {
+                let mut file = file;
+                file.write_all(format!("P3\n{} {}\n255\n", dimensions.0, dimensions.1).as_bytes())
+                    .context("Could not write PPM header to file.")?;
+                RowEncoder::Ppm(file)
+            },
+        };
+        Ok(Self { path, encoder, remaining: dimensions.1 })
+    }
+
+    // Rows are 3 bytes (RGB) per pixel, top to bottom.
+    pub fn write_rows(&mut self, rows: &[Vec<u8>]) -> Result<()> {
+        if rows.len() as u32 > self.remaining {
+            bail!("More rows written than the image has.");
+        }
+        self.remaining -= rows.len() as u32;
+
+        match &mut self.encoder {
+            RowEncoder::Png(writer) => {
+                for row in rows {
+                    writer.write_all(row).context("Could not save image buffer to PNG file format.")?;
+                }
+            },
+            RowEncoder::Ppm(file) => {
+                for pixel in rows.iter().flat_map(|row| row.chunks(3)) {
+                    file.write_all(format!("{} {} {}\n", pixel[0], pixel[1], pixel[2]).as_bytes())
+                        .context("Could not write pixels to PPM file.")?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        if self.remaining > 0 {
+            bail!("Image is missing {} rows.", self.remaining);
+        }
+        match self.encoder {
+            RowEncoder::Png(writer) => {
+                writer.finish().context("Could not finish PNG file.")?;
+            },
+            RowEncoder::Ppm(mut file) => {
+                file.flush().context("Could not write pixels to PPM file.")?;
+            },
         }
+        println!("Image written to file \"{}\".", self.path);
+        Ok(())
     }
-    println!("Image written to file \"{}\".", path);
+}
+
+pub fn write_ray_paths(path: &str, paths: &[PixelPath]) -> Result<()> {
+    let file = File::create(path).context("Could not create ray path file.")?;
+    serde_json::to_writer_pretty(file, paths).context("Could not write ray paths to file.")?;
+    println!("Ray paths written to file \"{}\".", path);
     Ok(())
 }
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use anyhow::{Result, Context, anyhow, bail};
+use serde_yaml::{Mapping, Value};
+
+// Replaces ${name} references in the scene with values from its params block,
+// which command line values given as (name, value) override.
+pub fn substitute_params(scene: &mut Value, overrides: &[(String, String)]) -> Result<()> {
+    let declared = match scene.as_mapping_mut().and_then(|m| m.remove("params")) {
+        Some(Value::Mapping(params)) => params,
+        Some(Value::Null) | None => Mapping::new(),
+        Some(_) => bail!("params must be a map of name to default value"),
+    };
+
+    let mut params = HashMap::new();
+    for (name, default) in declared {
+        let name = name.as_str().context("Parameter names must be strings")?.to_string();
+        params.insert(name, default);
+    }
+
+    for (name, raw) in overrides {
+        let default = params.get(name)
+            .with_context(|| format!("Unknown parameter {}, it must be declared in params", name))?;
+        let value = parse_override(raw);
+        if !same_kind(default, &value) {
+            bail!("Parameter {} expects {}, got {}", name, kind(default), raw);
+        }
+        params.insert(name.clone(), value);
+    }
+
+    substitute(scene, &params)
+}
+
+// Command line values are read as YAML, so numbers and lists keep their type.
+fn parse_override(raw: &str) -> Value {
+    match serde_yaml::from_str(raw) {
+        Ok(Value::Null) | Err(_) => Value::String(raw.to_string()),
+        Ok(value) => value,
+    }
+}
+
+// Colours can be strings or lists, so those are interchangeable.
+fn same_kind(a: &Value, b: &Value) -> bool {
+    let colour = |v: &Value| v.is_string() || v.is_sequence();
+    std::mem::discriminant(a) == std::mem::discriminant(b) || (colour(a) && colour(b))
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null        => "nothing",
+        Value::Bool(_)     => "a boolean",
+        Value::Number(_)   => "a number",
+        Value::String(_)   => "a string",
+        Value::Sequence(_) => "a list",
+        Value::Mapping(_)  => "a map",
+        Value::Tagged(_)   => "a tagged value",
+    }
+}
+
+fn substitute(value: &mut Value, params: &HashMap<String, Value>) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            if let Some(replacement) = substitute_str(s, params)? {
+                *value = replacement;
+            }
+        },
+        Value::Sequence(seq) => {
+            for v in seq {
+                substitute(v, params)?;
+            }
+        },
+        Value::Mapping(map) => {
+            for (_, v) in map.iter_mut() {
+                substitute(v, params)?;
+            }
+        },
+        Value::Tagged(tagged) => substitute(&mut tagged.value, params)?,
+        _ => {},
+    }
+    Ok(())
+}
+
+// A string that is just "${name}" becomes the parameter's value, with its type.
+// References inside a longer string are replaced with text.
+fn substitute_str(s: &str, params: &HashMap<String, Value>) -> Result<Option<Value>> {
+    if !s.contains("${") {
+        return Ok(None);
+    }
+
+    let lookup = |name: &str| params.get(name)
+        .ok_or_else(|| anyhow!("Undefined parameter {}", name));
+
+    if let Some(name) = s.strip_prefix("${").and_then(|rest| rest.strip_suffix('}')) {
+        if !name.contains('}') {
+            return lookup(name).map(|v| Some(v.clone()));
+        }
+    }
+
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .with_context(|| format!("Unclosed parameter reference in {:?}", s))?;
+        let name = &rest[start + 2..start + end];
+        match lookup(name)? {
+            Value::String(v) => out.push_str(v),
+            Value::Number(v) => out.push_str(&v.to_string()),
+            Value::Bool(v)   => out.push_str(&v.to_string()),
+            other => bail!("Parameter {} is {} and can't be used inside text", name, kind(other)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Some(Value::String(out)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitute_params() {
+        let mut scene: Value = serde_yaml::from_str("
+            params:
+                size: 2
+                name: world
+            a: ${size}
+            b: [1, !Tag \"${size}\"]
+            c: hello ${name} ${size}
+        ").unwrap();
+        substitute_params(&mut scene, &[("name".into(), "there".into())]).unwrap();
+
+        let expected: Value = serde_yaml::from_str("
+            a: 2
+            b: [1, !Tag 2]
+            c: hello there 2
+        ").unwrap();
+        assert_eq!(scene, expected);
+    }
+
+    #[test]
+    fn test_param_errors() {
+        let scene: Value = serde_yaml::from_str("
+            params:
+                size: 2
+            a: ${size}
+            b: ${radius}
+        ").unwrap();
+
+        let err = substitute_params(&mut scene.clone(), &[]).unwrap_err();
+        assert!(err.to_string().contains("radius"));
+
+        let err = substitute_params(&mut scene.clone(), &[("size".into(), "big".into())]).unwrap_err();
+        assert!(err.to_string().contains("size"));
+
+        let err = substitute_params(&mut scene.clone(), &[("colour".into(), "red".into())]).unwrap_err();
+        assert!(err.to_string().contains("colour"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt::Write;
+use crate::render::Image;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourMode {
+    TrueColour,
+    Ansi256,
+}
+
+impl ColourMode {
+    // Terminals advertise 24-bit colour through COLORTERM.
+    pub fn detect() -> Self {
+        match std::env::var("COLORTERM") {
+            Ok(v) if v.contains("truecolor") || v.contains("24bit") => ColourMode::TrueColour,
+            _ => ColourMode::Ansi256,
+        }
+    }
+
+    fn code(self, layer: u8, [r, g, b]: [u8; 3]) -> String {
+        match self {
+            ColourMode::TrueColour => format!("\x1b[{}8;2;{};{};{}m", layer, r, g, b),
+            ColourMode::Ansi256 => format!("\x1b[{}8;5;{}m", layer, ansi_256([r, g, b])),
+        }
+    }
+}
+
+// Nearest colour in the 6x6x6 cube of the 256 colour palette.
+fn ansi_256(rgb: [u8; 3]) -> u8 {
+    let [r, g, b] = rgb.map(|c| ((c as f64 / 255.0) * 5.0).round() as u8);
+    16 + 36 * r + 6 * g + b
+}
+
+// Width of the terminal in characters, from COLUMNS or 80 if unknown.
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c| c > 0)
+        .unwrap_or(80)
+}
+
+// Averages the block of source pixels that falls into each output pixel.
+fn downscale(image: &Image, dimensions: (u32, u32), out: (usize, usize)) -> Vec<Vec<[u8; 3]>> {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let span = |i: usize, n: usize, len: usize| (i * len / n)..((i + 1) * len / n).max(i * len / n + 1);
+
+    (0..out.1).map(|y| {
+        (0..out.0).map(|x| {
+            let xs = span(x, out.0, width);
+            let mut sum = [0u32; 3];
+            let mut count = 0;
+            for row in &image[span(y, out.1, height)] {
+                for pixel in row[xs.start * 3..xs.end * 3].chunks(3) {
+                    for c in 0..3 {
+                        sum[c] += pixel[c] as u32;
+                    }
+                    count += 1;
+                }
+            }
+            sum.map(|s| (s / count) as u8)
+        }).collect()
+    }).collect()
+}
+
+// Renders the image as text at most max_width characters wide. Each character
+// is two pixels, the upper half block in the foreground colour over the background.
+pub fn ansi_preview(image: &Image, dimensions: (u32, u32), max_width: usize, mode: ColourMode) -> String {
+    let columns = (dimensions.0 as usize).min(max_width).max(1);
+    // Keep the aspect ratio, rows of pixels are half a character tall.
+    let rows = ((dimensions.1 as usize * columns) as f64 / dimensions.0 as f64).round().max(1.0) as usize;
+    let pixels = downscale(image, dimensions, (columns, rows));
+
+    let mut out = String::new();
+    for pair in pixels.chunks(2) {
+        for x in 0..columns {
+            out.push_str(&mode.code(3, pair[0][x]));
+            match pair.get(1) {
+                Some(bottom) => out.push_str(&mode.code(4, bottom[x])),
+                // Odd number of rows, leave the background alone.
+                None => out.push_str("\x1b[49m"),
+            }
+            out.push('▀');
+        }
+        writeln!(out, "\x1b[0m").unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    #[test]
+    fn test_preview_true_colour() {
+        let image = vec![
+            [RED, BLUE].concat(),
+            [BLUE, RED].concat(),
+        ];
+        let preview = ansi_preview(&image, (2, 2), 80, ColourMode::TrueColour);
+        assert_eq!(
+            preview,
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[38;2;0;0;255m\x1b[48;2;255;0;0m▀\x1b[0m\n",
+        );
+    }
+
+    #[test]
+    fn test_preview_downscale() {
+        // 4x2 halves down to 2x1, each character averages a 2x2 block.
+        let image = vec![
+            [RED, RED, BLUE, [0, 0, 0]].concat(),
+            [RED, RED, BLUE, [0, 0, 0]].concat(),
+        ];
+        let preview = ansi_preview(&image, (4, 2), 2, ColourMode::Ansi256);
+        assert_eq!(preview, "\x1b[38;5;196m\x1b[49m▀\x1b[38;5;18m\x1b[49m▀\x1b[0m\n");
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use anyhow::{Result, Context, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use crate::render::{Image, RenderSettings};
+
+// PNG text chunk holding a partial image's TileInfo as JSON.
+const TILE_KEYWORD: &str = "ray-tracer tile";
+
+// Where a partial image goes in the full one, and what it was rendered with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileInfo {
+    pub x:             u32,
+    pub y:             u32,
+    pub width:         u32,
+    pub height:        u32,
+    pub image_width:   u32,
+    pub image_height:  u32,
+    // Tiles can only be merged if these match, see settings_hash.
+    pub settings_hash: u64,
+}
+
+// Rows of the index-th of count horizontal bands, as even as possible.
+pub fn tile_rows(index: u32, count: u32, height: u32) -> Range<u32> {
+    let split = |i: u32| (i as u64 * height as u64 / count as u64) as u32;
+    split(index)..split(index + 1)
+}
+
+// FNV-1a over the scene file, parameters and render settings. Stable between
+// builds and machines, unlike the standard library's hasher.
+pub fn settings_hash(scene_source: &str, params: &[(String, String)], settings: &RenderSettings) -> u64 {
+    let text = format!("{}\n{:?}\n{:?}", scene_source, params, settings);
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Writes the rows to file_name.png, with the tile info in a text chunk.
+pub fn write_tile(file_name: &str, rows: &Image, info: &TileInfo) -> Result<()> {
+    let path = format!("{}.png", file_name);
+    let file = BufWriter::new(File::create(&path).with_context(|| format!("Could not create file {}.", path))?);
+
+    let mut encoder = png::Encoder::new(file, info.width, info.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk(TILE_KEYWORD.to_string(), serde_json::to_string(info)?)?;
+    let mut writer = encoder.write_header().context("Could not write PNG header to file.")?;
+    writer.write_image_data(&rows.concat()).context("Could not write tile to file.")?;
+    writer.finish().context("Could not write tile to file.")?;
+
+    println!("Tile written to file \"{}\".", path);
+    Ok(())
+}
+
+pub fn read_tile(path: &str) -> Result<(TileInfo, Image)> {
+    let file = File::open(path).with_context(|| format!("Could not open tile {}.", path))?;
+    let mut reader = png::Decoder::new(file).read_info().with_context(|| format!("Could not read tile {}.", path))?;
+
+    let info: TileInfo = reader.info().uncompressed_latin1_text.iter()
+        .find(|chunk| chunk.keyword == TILE_KEYWORD)
+        .ok_or_else(|| anyhow!("{} is not a tile, it has no tile info.", path))
+        .and_then(|chunk| serde_json::from_str(&chunk.text).with_context(|| format!("Invalid tile info in {}.", path)))?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).with_context(|| format!("Could not read tile {}.", path))?;
+    if frame.color_type != png::ColorType::Rgb || frame.bit_depth != png::BitDepth::Eight
+        || frame.width != info.width || frame.height != info.height {
+        bail!("{} doesn't match its tile info.", path);
+    }
+    let rows = buffer[..frame.buffer_size()]
+        .chunks(frame.line_size)
+        .map(|row| row.to_vec())
+        .collect();
+    Ok((info, rows))
+}
+
+// Stitches full width bands back into one image, checking they are from the
+// same render and cover every row exactly once.
+pub fn merge_tiles(mut tiles: Vec<(TileInfo, Image)>) -> Result<(Image, (u32, u32))> {
+    let first = tiles.first().ok_or_else(|| anyhow!("No tiles to merge."))?.0;
+    for (info, _) in &tiles {
+        if (info.image_width, info.image_height) != (first.image_width, first.image_height) {
+            bail!("Tiles are from images of different sizes.");
+        }
+        if info.settings_hash != first.settings_hash {
+            bail!("Tiles were rendered with different scenes or settings.");
+        }
+        if info.x != 0 || info.width != info.image_width {
+            bail!("Only full width tiles can be merged.");
+        }
+    }
+
+    tiles.sort_by_key(|(info, _)| info.y);
+    let mut image = Vec::with_capacity(first.image_height as usize);
+    let mut next = 0;
+    for (info, rows) in tiles {
+        if info.y > next {
+            bail!("Missing rows {}..{}.", next, info.y);
+        }
+        if info.y < next {
+            bail!("Tiles overlap at rows {}..{}.", info.y, next.min(info.y + info.height));
+        }
+        image.extend(rows);
+        next = info.y + info.height;
+    }
+    if next < first.image_height {
+        bail!("Missing rows {}..{}.", next, first.image_height);
+    }
+    Ok((image, (first.image_width, first.image_height)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::{Camera, Colour, Light, Material, Point3, Scene, Vec3};
+    use crate::object::Sphere;
+    use crate::render::{render_rows, render_with_stats};
+
+    fn temp_name(name: &str) -> String {
+        std::env::temp_dir().join(format!("ray-tracer-{}-{}", std::process::id(), name)).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_tile_rows() {
+        let rows: Vec<_> = (0..3).map(|i| tile_rows(i, 3, 10)).collect();
+        assert_eq!(rows, vec![0..3, 3..6, 6..10]);
+    }
+
+    #[test]
+    fn test_tiles_merge() {
+        let mut scene = Scene::default();
+        scene.push(Box::new(Sphere::new(Material::default())));
+        scene.lights.push(Light::new(Point3::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0)));
+        let scene = Arc::new(scene);
+        let settings = RenderSettings { dimensions: (12, 10), samples: 1, max_depth: 5, ..Default::default() };
+        let camera = Camera::new(Point3::new(0.0, 0.0, -5.0), Point3::origin(), Vec3::new(0.0, 1.0, 0.0), 45.0, settings.dimensions, 0.0, 5.0);
+        let hash = settings_hash("scene", &[], &settings);
+
+        let tiles = (0..3).map(|i| {
+            let rows = tile_rows(i, 3, 10);
+            let (image, _) = render_rows(Arc::clone(&scene), camera, &settings, rows.clone()).unwrap();
+            let info = TileInfo { x: 0, y: rows.start, width: 12, height: rows.len() as u32, image_width: 12, image_height: 10, settings_hash: hash };
+            let name = temp_name(&format!("tile{}", i));
+            write_tile(&name, &image, &info).unwrap();
+            read_tile(&format!("{}.png", name)).unwrap()
+        }).collect::<Vec<_>>();
+
+        let (merged, dimensions) = merge_tiles(tiles.clone()).unwrap();
+        let (expected, _) = render_with_stats(scene, camera, &settings);
+        assert_eq!(dimensions, (12, 10));
+        assert_eq!(merged, expected);
+
+        // Missing, overlapping and mismatched tiles.
+        let err = merge_tiles(vec![tiles[0].clone(), tiles[2].clone()]).unwrap_err();
+        assert_eq!(err.to_string(), "Missing rows 3..6.");
+        let err = merge_tiles(tiles[..2].to_vec()).unwrap_err();
+        assert_eq!(err.to_string(), "Missing rows 6..10.");
+        let err = merge_tiles(vec![tiles[0].clone(), tiles[0].clone(), tiles[1].clone(), tiles[2].clone()]).unwrap_err();
+        assert_eq!(err.to_string(), "Tiles overlap at rows 0..3.");
+        let mut other = tiles.clone();
+        other[1].0.settings_hash += 1;
+        assert!(merge_tiles(other).is_err());
+    }
+}
@@ -10,22 +10,35 @@ pub mod light;
 pub mod render;
 pub mod pattern;
 pub mod group;
+pub mod debug;
+pub mod watch;
+pub mod post;
+pub mod denoise;
+pub mod noise;
+pub mod bump;
 mod intersection;
 mod transform;
 mod math;
 mod io;
 
 pub use colour::Colour;
-pub use material::Material;
-pub use object::Object;
-pub use scene::Scene;
-pub use camera::Camera;
-pub use io::{OutputFormat, write_to_file, parse_scene};
-pub use render::{render, Image};
-pub use light::Light;
+pub use material::{Material, SpecularModel, PatternBlend};
+pub use object::{Object, ObjectId, ObjectFlags};
+pub use scene::{Scene, HitInfo, Background};
+pub use camera::{Camera, Projection};
+pub use io::{ColourMode, ansi_preview, terminal_width};
+pub use io::{TileInfo, tile_rows, settings_hash, write_tile, read_tile, merge_tiles};
+pub use io::{OutputFormat, RowWriter, write_to_file, write_ray_paths, parse_scene, parse_scene_file};
+#[cfg(feature = "gltf")]
+pub use io::import_gltf;
+pub use render::{render, render_with_stats, render_streamed, render_rows, Image, RenderSettings, RenderStats, RenderOverrides, Quality, HdrImage, parse_duration};
+pub use denoise::Denoise;
+pub use post::{PostProcess, Pipeline, Effect, Bloom, Vignette, ChromaticAberration};
+pub use light::{Light, LightKind};
 
 // Type aliases.
 pub type Point3       = nalgebra::Point3<f64>;
+pub type Vec2         = nalgebra::Vector2<f64>;
 pub type Vec3         = nalgebra::Vector3<f64>;
 pub type Matrix3      = nalgebra::Matrix3<f64>;
 pub type Matrix4      = nalgebra::Matrix4<f64>;
@@ -34,6 +47,7 @@ pub type Rotation     = nalgebra::geometry::Rotation3<f64>;
 pub type Transform    = nalgebra::geometry::Transform3<f64>;
 pub type Scale        = nalgebra::geometry::Scale3<f64>;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Axis { X, Y, Z }
 
 pub fn default_dims() -> (u32, u32) {
@@ -1,18 +1,231 @@
-use crate::Point3;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::{Point3, Vec3};
 use crate::colour::Colour;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    // Shines from position in every direction.
+    Point,
+    // Parallel rays along direction, like the sun's. Position isn't used.
+    Directional {
+        direction: Vec3,
+    },
+    // The rectangle from corner along u and v. Shadows are sampled at points
+    // across it, but shading treats it as a point light at its centre.
+    Area {
+        corner:  Point3,
+        u:       Vec3,
+        v:       Vec3,
+        samples: usize,
+    },
+    // A ball around position, also shaded as a point light at its centre.
+    Sphere {
+        radius:  f64,
+        samples: usize,
+    },
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Light {
     pub position: Point3,
-    pub intensity: Colour,
+    pub colour:   Colour,
+    // Brightness, multiplying colour.
+    pub intensity: f64,
+    // Disabled lights are skipped, as if they weren't in the scene.
+    pub enabled:  bool,
+    // Constant, linear and quadratic falloff with distance, see falloff.
+    pub attenuation: (f64, f64, f64),
+    pub kind: LightKind,
 }
 
 impl Light {
-    pub fn new(position: Point3, intensity: Colour) -> Self {
+    pub fn new(position: Point3, colour: Colour) -> Self {
         Self {
             position,
-            intensity,
+            colour,
+            intensity: 1.0,
+            enabled: true,
+            attenuation: (1.0, 0.0, 0.0),
+            kind: LightKind::Point,
+        }
+    }
+
+    // Light travelling along direction, from infinitely far away.
+    pub fn directional(direction: Vec3, colour: Colour) -> Self {
+        assert!(direction.norm() > 0.0, "directional lights need a direction");
+        Self {
+            kind: LightKind::Directional { direction: direction.normalize() },
+            ..Self::new(Point3::origin(), colour)
+        }
+    }
+
+    pub fn area(corner: Point3, u: Vec3, v: Vec3, samples: usize, colour: Colour) -> Self {
+        assert!(u.cross(&v).norm() > 0.0, "area lights need two edges that aren't parallel");
+        assert!(samples > 0, "area lights need at least one sample");
+        Self {
+            kind: LightKind::Area { corner, u, v, samples },
+            ..Self::new(corner + (u + v) / 2.0, colour)
+        }
+    }
+
+    pub fn sphere(position: Point3, radius: f64, samples: usize, colour: Colour) -> Self {
+        assert!(radius > 0.0, "sphere lights need a radius above 0");
+        assert!(samples > 0, "sphere lights need at least one sample");
+        Self {
+            kind: LightKind::Sphere { radius, samples },
+            ..Self::new(position, colour)
         }
     }
+
+    pub fn with_attenuation(self, constant: f64, linear: f64, quadratic: f64) -> Self {
+        Self { attenuation: (constant, linear, quadratic), ..self }
+    }
+
+    pub fn with_intensity(self, intensity: f64) -> Self {
+        Self { intensity, ..self }
+    }
+
+    pub fn with_enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    // Light given out, before any falloff.
+    pub fn radiance(&self) -> Colour {
+        self.colour * self.intensity
+    }
+
+    // Unit vector from the point towards the light.
+    pub fn direction_from(&self, point: &Point3) -> Vec3 {
+        match self.kind {
+            LightKind::Point | LightKind::Area { .. } | LightKind::Sphere { .. } => (self.position - point).normalize(),
+            LightKind::Directional { direction } => -direction,
+        }
+    }
+
+    pub fn distance_from(&self, point: &Point3) -> f64 {
+        match self.kind {
+            LightKind::Point | LightKind::Area { .. } | LightKind::Sphere { .. } => (self.position - point).norm(),
+            LightKind::Directional { .. } => f64::INFINITY,
+        }
+    }
+
+    // Where a shadow ray from the point ends, a unit step towards lights
+    // that are infinitely far away.
+    pub fn target_from(&self, point: &Point3) -> Point3 {
+        match self.kind {
+            LightKind::Point | LightKind::Area { .. } | LightKind::Sphere { .. } => self.position,
+            LightKind::Directional { .. } => point + self.direction_from(point),
+        }
+    }
+
+    // Shadow rays cast for each point shaded.
+    pub fn shadow_samples(&self) -> usize {
+        match self.kind {
+            LightKind::Area { samples, .. } | LightKind::Sphere { samples, .. } => samples,
+            _ => 1,
+        }
+    }
+
+    // Points on the light to cast shadow rays at from the point, one per
+    // sample. Rectangles are sampled all over, spheres only on the side facing
+    // the point. Other lights only have their position.
+    pub fn sample_points(&self, from: &Point3, rng: &mut impl Rng) -> Vec<Point3> {
+        match self.kind {
+            LightKind::Area { corner, u, v, samples } => {
+                jittered(samples, rng).into_iter()
+                    .map(|(s, t)| corner + u * s + v * t)
+                    .collect()
+            },
+            LightKind::Sphere { radius, samples } => {
+                let to_centre = self.position - from;
+                let distance = to_centre.norm();
+                if distance <= radius {
+                    return vec![self.position];
+                }
+                // Directions in the cone around the sphere, spread evenly over its solid angle.
+                let w = to_centre / distance;
+                let (a, b) = orthonormal_basis(&w);
+                let cos_max = (1.0 - (radius / distance).powi(2)).sqrt();
+                jittered(samples, rng).into_iter().map(|(s, t)| {
+                    let cos = 1.0 - s * (1.0 - cos_max);
+                    let sin = (1.0 - cos * cos).sqrt();
+                    let phi = std::f64::consts::TAU * t;
+                    let direction = a * (sin * phi.cos()) + b * (sin * phi.sin()) + w * cos;
+                    // Nearest point on the sphere that way.
+                    let along = distance * cos - (radius * radius - (distance * sin).powi(2)).max(0.0).sqrt();
+                    from + direction * along
+                }).collect()
+            },
+            _ => vec![self.position],
+        }
+    }
+
+    // How much of the light reaches a distance away, 1 with no attenuation.
+    // Lights infinitely far away don't fade.
+    pub fn falloff(&self, distance: f64) -> f64 {
+        if distance.is_infinite() {
+            return 1.0;
+        }
+        let (constant, linear, quadratic) = self.attenuation;
+        1.0 / (constant + linear * distance + quadratic * distance * distance)
+    }
+}
+
+// Jittered pairs in the unit square, one per sample. Each pair gets its own
+// column and row, matched up at random, so they are spread evenly along both sides.
+fn jittered(samples: usize, rng: &mut impl Rng) -> Vec<(f64, f64)> {
+    let mut rows: Vec<usize> = (0..samples).collect();
+    rows.shuffle(rng);
+    rows.into_iter().enumerate().map(|(column, row)| (
+        (column as f64 + rng.gen::<f64>()) / samples as f64,
+        (row as f64 + rng.gen::<f64>()) / samples as f64,
+    )).collect()
 }
 
+// Two unit vectors at right angles to each other and to w.
+fn orthonormal_basis(w: &Vec3) -> (Vec3, Vec3) {
+    let helper = if w.x.abs() > 0.9 { Vec3::y() } else { Vec3::x() };
+    let a = w.cross(&helper).normalize();
+    (a, w.cross(&a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn test_area_sample_points() {
+        let corner = Point3::new(-1.0, 5.0, -2.0);
+        let (u, v) = (Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 4.0));
+        let light = Light::area(corner, u, v, 8, Colour::new(1.0, 1.0, 1.0));
+        assert_eq!(light.position, Point3::new(0.0, 5.0, 0.0));
+
+        let points = light.sample_points(&Point3::origin(), &mut StdRng::seed_from_u64(3));
+        assert_eq!(points.len(), 8);
+        // One point in each eighth of either edge.
+        let mut columns: Vec<usize> = points.iter().map(|p| ((p.x + 1.0) / 2.0 * 8.0) as usize).collect();
+        let mut rows: Vec<usize> = points.iter().map(|p| ((p.z + 2.0) / 4.0 * 8.0) as usize).collect();
+        columns.sort();
+        rows.sort();
+        assert_eq!(columns, (0..8).collect::<Vec<_>>());
+        assert_eq!(rows, (0..8).collect::<Vec<_>>());
+        assert!(points.iter().all(|p| p.y == 5.0));
+    }
+
+    #[test]
+    fn test_sphere_sample_points() {
+        let light = Light::sphere(Point3::new(0.0, 10.0, 0.0), 2.0, 32, Colour::new(1.0, 1.0, 1.0));
+        let from = Point3::new(3.0, 0.0, 1.0);
+        let points = light.sample_points(&from, &mut StdRng::seed_from_u64(5));
+        assert_eq!(points.len(), 32);
+        for point in points {
+            // On the surface, on the side seen from the point.
+            assert!(((point - light.position).norm() - 2.0).abs() < 1e-9);
+            assert!((point - light.position).dot(&(from - light.position)) > 0.0);
+        }
+        // From inside there is nowhere to aim but the centre.
+        assert_eq!(light.sample_points(&Point3::new(0.0, 9.0, 0.0), &mut StdRng::seed_from_u64(5)), vec![light.position]);
+    }
+}
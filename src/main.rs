@@ -1,16 +1,28 @@
 use anyhow::Context;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use ray_tracer::OutputFormat;
-use ray_tracer::render;
-use ray_tracer::write_to_file;
-use ray_tracer::parse_scene;
+use ray_tracer::{render_with_stats, render_streamed, render_rows};
+use ray_tracer::{write_to_file, RowWriter};
+use ray_tracer::parse_scene_file;
+use ray_tracer::{RenderSettings, RenderOverrides, Quality, parse_duration};
+use ray_tracer::{Pipeline, Effect, Bloom, Vignette, ChromaticAberration, Denoise};
+use ray_tracer::write_ray_paths;
+use ray_tracer::{ColourMode, ansi_preview, terminal_width};
+use ray_tracer::{TileInfo, tile_rows, settings_hash, write_tile, read_tile, merge_tiles};
+use ray_tracer::debug::{debug_pixel, dump_rays};
+use ray_tracer::watch::{FileWatcher, scene_dependencies};
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
-#[command(author = "NathanW", about = "A simple ray tracer.")]
+#[command(author = "NathanW", about = "A simple ray tracer.", subcommand_negates_reqs = true)]
 pub struct Args {
-    #[clap(short, long)]
-    #[clap(help = "Path to scene YAML file.")]
-    pub scene: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // Always given when there's no subcommand.
+    #[clap(short, long, required = true)]
+    #[clap(help = "Path to scene YAML file, or a .glb/.gltf model with the gltf feature.")]
+    pub scene: Option<String>,
 
     #[clap(short, long)]
     #[clap(value_enum, default_value_t)]
@@ -25,21 +37,244 @@ pub struct Args {
     #[clap(long, default_value = "720")]
     pub height: u32,
 
-    #[clap(long, default_value = "300")]
-    #[clap(help = "")]
-    pub samples: u32,
+    #[clap(long, value_enum)]
+    #[clap(help = "Preset for samples and bounces, beating the scene's. Explicit --samples and --max-depth still take precedence.")]
+    pub quality: Option<Quality>,
+
+    #[clap(long)]
+    #[clap(help = "Samples per pixel. [default: 300]")]
+    pub samples: Option<u32>,
+
+    #[clap(long)]
+    #[clap(help = "Maximum number of bounces per ray. [default: 100]")]
+    pub max_depth: Option<u32>,
+
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    #[clap(help = "Render one sample per pixel at a time until this runs out, e.g. 60s or 2m. Samples is then a maximum.")]
+    pub time_budget: Option<Duration>,
+
+    #[clap(long, value_name = "INTENSITY,THRESHOLD,RADIUS", value_parser = parse_bloom)]
+    #[clap(help = "Glow around pixels brighter than the threshold, radius is in pixels.")]
+    pub bloom: Option<Bloom>,
+
+    #[clap(long, value_name = "STRENGTH")]
+    #[clap(help = "Darken the edges, by STRENGTH in the corners.")]
+    pub vignette: Option<f64>,
+
+    #[clap(long, value_name = "SHIFT_PX")]
+    #[clap(help = "Shift red outwards and blue inwards, by SHIFT_PX pixels in the corners.")]
+    pub chromatic_aberration: Option<f64>,
+
+    #[clap(long, value_name = "STRENGTH,ITERATIONS", num_args = 0..=1, default_missing_value = "1,3", value_parser = parse_denoise)]
+    #[clap(help = "Smooth noise while keeping edges, guided by normals and depth. [default: 1,3]")]
+    pub denoise: Option<Denoise>,
+
+    #[clap(long, value_name = "X,Y", value_parser = parse_pixel)]
+    #[clap(help = "Print the trace of the centre sample of one pixel instead of rendering.")]
+    pub trace_pixel: Option<(u32, u32)>,
+
+    #[clap(long, value_name = "X,Y;X,Y", value_delimiter = ';', value_parser = parse_pixel)]
+    #[clap(help = "Write the ray paths of the given pixels to a JSON file instead of rendering.")]
+    pub dump_rays: Vec<(u32, u32)>,
+
+    #[clap(long, default_value = "rays.json")]
+    pub rays_out: String,
 
-    #[clap(long, default_value = "100")]
-    #[clap(help = "Maximum number of bounces per ray.")]
-    pub max_depth: u32,
+    #[clap(short, long)]
+    #[clap(help = "Don't print render statistics.")]
+    pub quiet: bool,
+
+    #[clap(long = "param", value_name = "NAME=VALUE", value_parser = parse_param)]
+    #[clap(help = "Override a parameter from the scene's params block, can be repeated.")]
+    pub params: Vec<(String, String)>,
+
+    #[clap(short, long)]
+    #[clap(help = "Re-render whenever the scene or a file it loads changes, until interrupted.")]
+    pub watch: bool,
+
+    #[clap(long)]
+    #[clap(help = "Print a small preview of the render in the terminal.")]
+    pub term_preview: bool,
+
+    #[clap(long)]
+    #[clap(help = "Write the image a band of rows at a time as it renders, to bound memory for huge images.")]
+    pub stream_output: bool,
+
+    #[clap(long, default_value = "64")]
+    #[clap(help = "Rows per band for --stream-output.")]
+    pub band_height: u32,
+
+    #[clap(long, requires = "tile_index")]
+    #[clap(help = "Split the image into this many horizontal bands and render only one, see merge.")]
+    pub tile_count: Option<u32>,
+
+    #[clap(long, requires = "tile_count")]
+    #[clap(help = "Which band to render, from 0.")]
+    pub tile_index: Option<u32>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Stitch bands rendered with --tile-count/--tile-index into one image.
+    Merge {
+        #[clap(required = true)]
+        #[clap(help = "Tile PNG files, in any order.")]
+        tiles: Vec<String>,
+
+        #[clap(short = 'n', long, default_value = "image")]
+        image_name: String,
+
+        #[clap(short, long)]
+        #[clap(value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+impl Args {
+    fn scene(&self) -> &str {
+        self.scene.as_deref().expect("--scene is required without a subcommand")
+    }
+}
+
+fn parse_pixel(s: &str) -> Result<(u32, u32), String> {
+    let (x, y) = s.split_once(',').ok_or("expected X,Y")?;
+    let x = x.trim().parse().map_err(|e| format!("invalid x: {}", e))?;
+    let y = y.trim().parse().map_err(|e| format!("invalid y: {}", e))?;
+    Ok((x, y))
+}
+
+fn parse_bloom(s: &str) -> Result<Bloom, String> {
+    let values = s.split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|e| format!("invalid number {}: {}", v, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [intensity, threshold, radius] if intensity >= 0.0 && radius >= 0.0 => Ok(Bloom { intensity, threshold, radius }),
+        [_, _, _] => Err("intensity and radius must not be negative".into()),
+        _ => Err("expected INTENSITY,THRESHOLD,RADIUS".into()),
+    }
+}
+
+fn parse_denoise(s: &str) -> Result<Denoise, String> {
+    let (strength, iterations) = s.split_once(',').ok_or("expected STRENGTH,ITERATIONS")?;
+    let strength = strength.trim().parse().map_err(|e| format!("invalid strength: {}", e))?;
+    let iterations = iterations.trim().parse().map_err(|e| format!("invalid iterations: {}", e))?;
+    Ok(Denoise { strength, iterations })
+}
+
+// Effects given on the command line replace the scene file's, applied in lens order.
+fn post_pipeline(args: &Args) -> Option<Pipeline> {
+    let effects: Vec<Effect> = [
+        args.chromatic_aberration.map(|shift_px| Effect::ChromaticAberration(ChromaticAberration { shift_px })),
+        args.bloom.map(Effect::Bloom),
+        args.vignette.map(|strength| Effect::Vignette(Vignette { strength })),
+    ].into_iter().flatten().collect();
+
+    if effects.is_empty() { None } else { Some(Pipeline(effects)) }
+}
+
+fn parse_param(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once('=').ok_or("expected NAME=VALUE")?;
+    Ok((name.trim().to_string(), value.to_string()))
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    if let Some(Command::Merge { tiles, image_name, format }) = &args.command {
+        let tiles = tiles.iter().map(|path| read_tile(path)).collect::<anyhow::Result<Vec<_>>>()?;
+        let (image, dimensions) = merge_tiles(tiles).context("failed to merge tiles")?;
+        return write_to_file(image_name, image, format.clone(), dimensions).context("failed to write to file");
+    }
+    if !args.watch {
+        return run(&args);
+    }
+
+    for iteration in 1.. {
+        // Started before rendering so edits made during a render aren't missed.
+        let mut watcher = FileWatcher::new(scene_dependencies(args.scene(), &args.params), Duration::from_millis(200));
+        let start = Instant::now();
+        match run(&args) {
+            Ok(()) => println!("[{}] Rendered {} in {:.2?}", iteration, args.image_name, start.elapsed()),
+            // Keep watching, the next save may fix it.
+            Err(e) => eprintln!("[{}] Error: {:#}", iteration, e),
+        }
+        println!("Watching {} for changes...", args.scene());
+        while !watcher.poll(Instant::now()) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+    Ok(())
+}
+
+fn run(args: &Args) -> anyhow::Result<()> {
     let dimensions = (args.width, args.height);
-    let (scene, camera) = parse_scene(&args.scene, dimensions).context("failed to parse scene")?;
-    let image = render(scene, camera, dimensions, 100, 100);
-    write_to_file(&args.image_name, image, OutputFormat::PNG, dimensions).context("failed to write to file")?;
+    let (scene, camera, file_settings) = parse_scene_file(args.scene(), dimensions, &args.params).context("failed to parse scene")?;
+
+    let cli_settings = RenderOverrides {
+        quality:     args.quality,
+        samples:     args.samples,
+        max_depth:   args.max_depth,
+        post:        post_pipeline(args),
+        denoise:     args.denoise,
+        time_budget: args.time_budget,
+    };
+    let settings = RenderSettings::resolve(dimensions, &file_settings, &cli_settings);
+    if let Some((x, y)) = args.trace_pixel {
+        for event in debug_pixel(&scene, &camera, &settings, x, y) {
+            println!("{}", event);
+        }
+        return Ok(());
+    }
+
+    if !args.dump_rays.is_empty() {
+        let paths = dump_rays(&scene, &camera, &settings, &args.dump_rays);
+        write_ray_paths(&args.rays_out, &paths).context("failed to write ray paths")?;
+        return Ok(());
+    }
+
+    if let (Some(count), Some(index)) = (args.tile_count, args.tile_index) {
+        if index >= count || count > dimensions.1 {
+            anyhow::bail!("--tile-index must be less than --tile-count, which can't be more than the image height");
+        }
+        let rows = tile_rows(index, count, dimensions.1);
+        let (image, stats) = render_rows(scene, camera, &settings, rows.clone())?;
+        if !args.quiet {
+            println!("{}", stats);
+        }
+        let source = std::fs::read_to_string(args.scene()).context("failed to read scene")?;
+        let info = TileInfo {
+            x:             0,
+            y:             rows.start,
+            width:         dimensions.0,
+            height:        rows.len() as u32,
+            image_width:   dimensions.0,
+            image_height:  dimensions.1,
+            settings_hash: settings_hash(&source, &args.params, &settings),
+        };
+        write_tile(&format!("{}_tile{}", args.image_name, index), &image, &info).context("failed to write tile")?;
+        return Ok(());
+    }
+
+    if args.stream_output {
+        if args.term_preview {
+            anyhow::bail!("--term-preview can't be used with --stream-output, the image is never fully in memory");
+        }
+        let mut writer = RowWriter::create(&args.image_name, args.format.clone(), dimensions)?;
+        let stats = render_streamed(scene, camera, &settings, args.band_height, |rows| writer.write_rows(rows))?;
+        writer.finish().context("failed to write to file")?;
+        if !args.quiet {
+            println!("{}", stats);
+        }
+        return Ok(());
+    }
+
+    let (image, stats) = render_with_stats(scene, camera, &settings);
+    if !args.quiet {
+        println!("{}", stats);
+    }
+    if args.term_preview {
+        print!("{}", ansi_preview(&image, dimensions, terminal_width(), ColourMode::detect()));
+    }
+    write_to_file(&args.image_name, image, args.format.clone(), dimensions).context("failed to write to file")?;
     Ok(())
 }
 
@@ -1,15 +1,51 @@
 use std::sync::Arc;
 use crate::colour::{Colour, BLACK};
-use crate::{Matrix4, Point3};
+use crate::{Matrix4, Point3, Vec3};
 use crate::intersection::Intersection;
 use crate::light::Light;
 use crate::math::reflect;
 use crate::pattern::Pattern;
+use crate::bump::NormalMap;
 
-#[derive(Debug)]
+// Refractive indices of common media.
+pub mod ior {
+    pub const VACUUM:   f64 = 1.0;
+    pub const AIR:      f64 = 1.00029;
+    pub const ICE:      f64 = 1.31;
+    pub const WATER:    f64 = 1.333;
+    pub const GLASS:    f64 = 1.52;
+    pub const QUARTZ:   f64 = 1.544;
+    pub const SAPPHIRE: f64 = 1.77;
+    pub const DIAMOND:  f64 = 2.417;
+
+    pub const NAMED: [(&str, f64); 8] = [
+        ("vacuum",   VACUUM),
+        ("air",      AIR),
+        ("ice",      ICE),
+        ("water",    WATER),
+        ("glass",    GLASS),
+        ("quartz",   QUARTZ),
+        ("sapphire", SAPPHIRE),
+        ("diamond",  DIAMOND),
+    ];
+
+    // Case insensitive lookup of a named index.
+    pub fn from_name(name: &str) -> Option<f64> {
+        NAMED.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, ior)| *ior)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Material {
     pub colour:         Colour,
     pub pattern:        Option<Arc<dyn Pattern>>,
+    // Looks the pattern up by the surface's uv coordinates instead of its
+    // position, so it follows the surface.
+    pub uv_pattern:     bool,
+    // Bumps the surface's normals, see bump.
+    pub normal_perturbation: Option<Arc<dyn NormalMap>>,
     // Ambient reflection is background lighting, or light reflected from other
     // objects in the environment. The Phong model treats this as a constant,
     // coloring all points on the surface equally.
@@ -22,17 +58,85 @@ pub struct Material {
     pub shininess:      f64,
     // The amount of light reflected from a surface.
     pub reflect: f64,
+    // Weights reflections of opaque surfaces by the Schlick term like
+    // transparent ones, so they are strongest at grazing angles.
+    pub fresnel:        bool,
     // The amount of light refracted through a surface.
     pub transparency:   f64,
     // The index of refraction of a surface.
     pub refractive_index: f64,
+    // Invisible except for shadows it receives, used for compositing.
+    pub shadow_catcher: bool,
+    // How much of each channel is absorbed per unit travelled inside, so
+    // thicker transparent objects are more tinted. See Beer-Lambert.
+    pub absorption:     Colour,
+    // Blurs reflections, 0 for a perfect mirror.
+    pub roughness:      f64,
+    // Reflection rays averaged on rough surfaces.
+    pub glossy_samples: usize,
+    pub specular_model: SpecularModel,
+    // Oren-Nayar roughness in radians, brightens matte surfaces like clay
+    // towards grazing angles. 0 is plain Lambertian diffuse.
+    pub diffuse_roughness: f64,
+    // How the pattern's colour is combined with colour.
+    pub pattern_blend:  PatternBlend,
+}
+
+// How highlights fall off away from the mirror direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpecularModel {
+    // Reflected light against the eye, cut off sharply at grazing angles.
+    #[default]
+    Phong,
+    // Normal against the half way vector between the light and eye, softer at
+    // grazing angles. Its exponent is 4 times shininess, which makes highlights
+    // about as tight as Phong's.
+    BlinnPhong,
+}
+
+// Ways of combining a pattern's colour with the material's, like image
+// editors' layer modes with the pattern on top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PatternBlend {
+    // Just the pattern.
+    #[default]
+    Replace,
+    // Pattern tinted by the colour, e.g. a white and grey checkerboard made red.
+    Multiply,
+    // The inverse of multiplying the inverses, so always lighter.
+    Screen,
+    // Multiply in the colour's darker half and screen in the lighter, which
+    // adds contrast.
+    Overlay,
 }
 
+impl PatternBlend {
+    pub fn apply(self, pattern: Colour, colour: Colour) -> Colour {
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let screen = |a: Colour, b: Colour| white - (white - a) * (white - b);
+        match self {
+            PatternBlend::Replace => pattern,
+            PatternBlend::Multiply => pattern * colour,
+            PatternBlend::Screen => screen(pattern, colour),
+            PatternBlend::Overlay => {
+                let (p, c): (Vec3, Vec3) = (pattern.into(), colour.into());
+                let channel = |p: f64, c: f64| if c < 0.5 { 2.0 * p * c } else { 1.0 - 2.0 * (1.0 - p) * (1.0 - c) };
+                Colour::new(channel(p.x, c.x), channel(p.y, c.y), channel(p.z, c.z))
+            },
+        }
+    }
+}
+
+// Reflection rays for rough materials unless set otherwise.
+pub const GLOSSY_SAMPLES: usize = 8;
+
 impl Default for Material {
     fn default() -> Self {
         Self {
             colour:           Colour::new(1.0, 1.0, 1.0),
             pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
             ambient:          0.1,
             diffuse:          0.9,
             specular:         0.9,
@@ -40,11 +144,55 @@ impl Default for Material {
             reflect:          0.0,
             transparency:     0.0,
             refractive_index: 1.0,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
         }
     }
 }
 
+// Patterns are compared by identity.
+impl PartialEq for Material {
+    fn eq(&self, other: &Self) -> bool {
+        let same_pattern = match (&self.pattern, &other.pattern) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_bump = match (&self.normal_perturbation, &other.normal_perturbation) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_pattern
+            && same_bump
+            && self.colour == other.colour
+            && self.ambient == other.ambient
+            && self.diffuse == other.diffuse
+            && self.specular == other.specular
+            && self.shininess == other.shininess
+            && self.reflect == other.reflect
+            && self.transparency == other.transparency
+            && self.refractive_index == other.refractive_index
+            && self.shadow_catcher == other.shadow_catcher
+            && self.absorption == other.absorption
+            && self.roughness == other.roughness
+            && self.glossy_samples == other.glossy_samples
+            && self.specular_model == other.specular_model
+            && self.diffuse_roughness == other.diffuse_roughness
+            && self.fresnel == other.fresnel
+            && self.uv_pattern == other.uv_pattern
+            && self.pattern_blend == other.pattern_blend
+    }
+}
+
 impl Material {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         colour:           Colour, 
         pattern:          Option<Arc<dyn Pattern>>,
@@ -60,6 +208,8 @@ impl Material {
         Self {
             colour,
             pattern,
+            normal_perturbation: None,
+            uv_pattern: false,
             ambient,
             diffuse,
             specular,
@@ -67,6 +217,14 @@ impl Material {
             reflect,
             transparency,
             refractive_index,
+            shadow_catcher: false,
+            absorption: BLACK,
+            roughness: 0.0,
+            glossy_samples: GLOSSY_SAMPLES,
+            specular_model: SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel: false,
+            pattern_blend: PatternBlend::Replace,
         }
     }
 
@@ -74,13 +232,31 @@ impl Material {
         Material {
             colour:           Colour::new(1.0, 1.0, 1.0),
             pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
             ambient:          0.0,
             diffuse:          0.0,
             specular:         0.0,
             shininess:        00.0,
             reflect:          0.0,
             transparency:     1.0,
-            refractive_index: 1.52,
+            refractive_index: ior::GLASS,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
+        }
+    }
+
+    // Clear glass-like material with the given index of refraction.
+    pub fn dielectric(ior: f64) -> Material {
+        Material {
+            refractive_index: ior,
+            ..Material::glass()
         }
     }
 
@@ -88,6 +264,8 @@ impl Material {
         Material {
             colour,
             pattern,
+            normal_perturbation: None,
+            uv_pattern:       false,
             ambient:          0.0,
             diffuse:          0.0,
             specular:         1.0,
@@ -95,6 +273,14 @@ impl Material {
             reflect:          1.0,
             transparency:     0.0,
             refractive_index: 1.0,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
         }
     }
 
@@ -102,6 +288,8 @@ impl Material {
         Material {
             colour,
             pattern,
+            normal_perturbation: None,
+            uv_pattern:       false,
             ambient:          0.0,
             diffuse:          0.5,
             specular:         0.5,
@@ -109,47 +297,413 @@ impl Material {
             reflect:          0.0,
             transparency:     0.0,
             refractive_index: 1.0,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
+        }
+    }
+
+    // Perfect mirror, almost no colour of its own.
+    pub fn mirror() -> Material {
+        Material {
+            colour:           Colour::new(1.0, 1.0, 1.0),
+            pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
+            ambient:          0.0,
+            diffuse:          0.05,
+            specular:         1.0,
+            shininess:        300.0,
+            reflect:          1.0,
+            transparency:     0.0,
+            refractive_index: 1.0,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
+        }
+    }
+
+    // Absorbs red most, so deep water turns blue green.
+    pub fn water() -> Material {
+        Material {
+            colour:           Colour::new(1.0, 1.0, 1.0),
+            pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
+            ambient:          0.0,
+            diffuse:          0.0,
+            specular:         0.5,
+            shininess:        300.0,
+            reflect:          1.0,
+            transparency:     1.0,
+            refractive_index: ior::WATER,
+            shadow_catcher:   false,
+            absorption:       Colour::new(0.05, 0.01, 0.005),
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
+        }
+    }
+
+    pub fn diamond() -> Material {
+        Material {
+            colour:           Colour::new(1.0, 1.0, 1.0),
+            pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
+            ambient:          0.0,
+            diffuse:          0.0,
+            specular:         1.0,
+            shininess:        300.0,
+            reflect:          1.0,
+            transparency:     1.0,
+            refractive_index: ior::DIAMOND,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
+        }
+    }
+
+    // Purely diffuse, no highlights.
+    pub fn matte(colour: Colour) -> Material {
+        Material {
+            colour,
+            pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
+            ambient:          0.1,
+            diffuse:          0.9,
+            specular:         0.0,
+            shininess:        0.0,
+            reflect:          0.0,
+            transparency:     0.0,
+            refractive_index: 1.0,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
+        }
+    }
+
+    // Soft, broad highlights on a rough diffuse surface.
+    pub fn rubber(colour: Colour) -> Material {
+        Material {
+            colour,
+            pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
+            ambient:          0.1,
+            diffuse:          0.8,
+            specular:         0.1,
+            shininess:        10.0,
+            reflect:          0.0,
+            transparency:     0.0,
+            refractive_index: 1.0,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::BlinnPhong,
+            diffuse_roughness: 0.3,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
         }
     }
 
-    pub fn light(&self, light: &Light, hit: &Intersection, in_shadow: bool) -> Colour {
-        let effective_colour = hit.colour * light.intensity;
-        let ambient = effective_colour * self.ambient;
+    // Transmission is the light getting to the point past anything in the way,
+    // black in full shadow. Ambient light is the scene's, scaling the ambient term.
+    pub fn light(&self, light: &Light, hit: &Intersection, transmission: Colour, ambient_light: Colour) -> Colour {
+        let intensity = light.radiance() * light.falloff(light.distance_from(&hit.point));
+        let effective_colour = hit.colour * intensity;
+        let ambient = effective_colour * ambient_light * self.ambient;
 
-        if in_shadow {
+        if transmission == BLACK {
             return ambient;
         }
 
-        let light_direction = (light.position - hit.point).normalize();
+        let light_direction = light.direction_from(&hit.point);
         let light_dot_normal = light_direction.dot(&hit.normal);    // THIS IS ALWAYS NEGATIVE
         let (diffuse, specular) = if light_dot_normal < 0.0 {
             // Light is on the other side of the surface.
             (BLACK, BLACK)
         } else { 
               
-            let diffuse = effective_colour * self.diffuse * light_dot_normal;
+            let diffuse = effective_colour * self.diffuse * light_dot_normal * self.oren_nayar(&light_direction, hit);
 
-            let reflect_direction = reflect(&(-light_direction), &hit.normal);
-            let reflect_dot_eye = reflect_direction.dot(&hit.eye);
+            let (cos, exponent) = match self.specular_model {
+                SpecularModel::Phong => {
+                    let reflect_direction = reflect(&(-light_direction), &hit.normal);
+                    (reflect_direction.dot(&hit.eye), self.shininess)
+                },
+                SpecularModel::BlinnPhong => {
+                    let halfway = (light_direction + hit.eye).normalize();
+                    (halfway.dot(&hit.normal), self.shininess * 4.0)
+                },
+            };
 
-            let specular = if reflect_dot_eye <= 0.0 {
+            let specular = if cos <= 0.0 {
                 BLACK
             } else {
-                let factor = reflect_dot_eye.powf(self.shininess);
-                light.intensity * self.specular * factor
+                let factor = cos.powf(exponent);
+                intensity * self.specular * factor
             };
 
             (diffuse, specular)
         };
 
-        ambient + diffuse + specular
+        ambient + (diffuse + specular) * transmission
+    }
+
+    // Scales the Lambert term, using the qualitative Oren-Nayar model. Light
+    // is scattered back towards where it came from, so rough surfaces facing
+    // away still look lit when seen from near the light.
+    fn oren_nayar(&self, light_direction: &Vec3, hit: &Intersection) -> f64 {
+        if self.diffuse_roughness == 0.0 {
+            return 1.0;
+        }
+        let sigma2 = self.diffuse_roughness * self.diffuse_roughness;
+        let a = 1.0 - 0.5 * sigma2 / (sigma2 + 0.33);
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        let cos_i = light_direction.dot(&hit.normal).clamp(0.0, 1.0);
+        let cos_r = hit.eye.dot(&hit.normal).clamp(0.0, 1.0);
+        // Cosine of the angle between the light and eye around the normal.
+        let light_across = light_direction - hit.normal * cos_i;
+        let eye_across = hit.eye - hit.normal * cos_r;
+        let across = light_across.norm() * eye_across.norm();
+        let cos_phi = if across > 0.0 { light_across.dot(&eye_across) / across } else { 0.0 };
+
+        // sin(alpha) * tan(beta), alpha the larger angle from the normal.
+        let (theta_i, theta_r) = (cos_i.acos(), cos_r.acos());
+        let (alpha, beta) = (theta_i.max(theta_r), theta_i.min(theta_r));
+        a + b * cos_phi.max(0.0) * alpha.sin() * beta.tan()
+    }
+
+    // Share of each channel left after travelling distance inside.
+    pub fn transmittance(&self, distance: f64) -> Colour {
+        (self.absorption * -distance).map(f64::exp)
+    }
+
+    // The normal bumped at a point in object space, normalised and kept
+    // facing the eye, by mirroring it if the bump tipped it over.
+    pub fn perturb_normal(&self, obj_point: &Point3, normal: &Vec3, eye: &Vec3) -> Vec3 {
+        let Some(bump) = &self.normal_perturbation else {
+            return *normal;
+        };
+        let bumped = bump.perturb(obj_point, normal).normalize();
+        let facing = bumped.dot(eye);
+        if facing < 0.0 {
+            (bumped - eye * (2.0 * facing)).normalize()
+        } else {
+            bumped
+        }
     }
 
     pub fn colour_at(&self, point: &Point3, inverse: &Matrix4) -> Colour {
         if let Some(pattern) = &self.pattern {
-            pattern.colour_at(point, inverse)
+            self.pattern_blend.apply(pattern.colour_at(point, inverse), self.colour)
         } else {
             self.colour
         }
     }
+
+    pub fn colour_at_uv(&self, (u, v): (f64, f64)) -> Colour {
+        if let Some(pattern) = &self.pattern {
+            self.pattern_blend.apply(pattern.colour_at_uv(u, v), self.colour)
+        } else {
+            self.colour
+        }
+    }
+
+    // Whether the colour comes from the surface's texture coordinates.
+    pub fn uses_uv(&self) -> bool {
+        self.uv_pattern || self.pattern.as_ref().is_some_and(|pattern| pattern.uv_space())
+    }
+
+    // Colour of a hit at point, and its texture coordinates if they were
+    // needed. uv is only called for materials that use them.
+    pub fn surface_colour(&self, point: &Point3, inverse: &Matrix4, uv: impl FnOnce() -> (f64, f64)) -> (Colour, Option<(f64, f64)>) {
+        if self.uses_uv() {
+            let uv = uv();
+            (self.colour_at_uv(uv), Some(uv))
+        } else {
+            (self.colour_at(point, inverse), None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::{fuzzy_eq_colour, WHITE};
+
+    #[test]
+    fn test_pattern_blend() {
+        let pattern = Colour::new(0.2, 0.5, 0.8);
+        let colour = Colour::new(0.25, 0.5, 1.0);
+        let expected = [
+            (PatternBlend::Replace, Colour::new(0.2, 0.5, 0.8)),
+            (PatternBlend::Multiply, Colour::new(0.05, 0.25, 0.8)),
+            (PatternBlend::Screen, Colour::new(0.4, 0.75, 1.0)),
+            // Multiplied twice where the colour is under a half, screened twice above.
+            (PatternBlend::Overlay, Colour::new(0.1, 0.5, 1.0)),
+        ];
+        for (blend, result) in expected {
+            assert!(fuzzy_eq_colour(blend.apply(pattern, colour), result), "{:?}", blend);
+        }
+
+        // Patterned materials blend with their colour, plain ones are just the colour.
+        let material = Material {
+            colour,
+            pattern: Some(Arc::new(crate::pattern::Stripes::new(pattern, pattern))),
+            pattern_blend: PatternBlend::Multiply,
+            ..Default::default()
+        };
+        assert!(fuzzy_eq_colour(material.colour_at(&Point3::origin(), &Matrix4::identity()), Colour::new(0.05, 0.25, 0.8)));
+        assert!(fuzzy_eq_colour(material.colour_at_uv((0.3, 0.3)), Colour::new(0.05, 0.25, 0.8)));
+        let plain = Material { colour, pattern_blend: PatternBlend::Screen, ..Default::default() };
+        assert_eq!(plain.colour_at(&Point3::origin(), &Matrix4::identity()), colour);
+    }
+
+    fn assert_fields(m: &Material, fields: [f64; 7]) {
+        assert_eq!(
+            [m.ambient, m.diffuse, m.specular, m.shininess, m.reflect, m.transparency, m.refractive_index],
+            fields,
+        );
+    }
+
+    #[test]
+    fn test_presets() {
+        let white = Colour::new(1.0, 1.0, 1.0);
+
+        let mirror = Material::mirror();
+        assert_eq!(mirror.colour, white);
+        assert_fields(&mirror, [0.0, 0.05, 1.0, 300.0, 1.0, 0.0, 1.0]);
+
+        let water = Material::water();
+        assert_eq!(water.colour, white);
+        assert_fields(&water, [0.0, 0.0, 0.5, 300.0, 1.0, 1.0, 1.333]);
+
+        let diamond = Material::diamond();
+        assert_eq!(diamond.colour, white);
+        assert_fields(&diamond, [0.0, 0.0, 1.0, 300.0, 1.0, 1.0, 2.417]);
+
+        let matte = Material::matte(Colour::new(0.2, 0.4, 0.6));
+        assert_eq!(matte.colour, Colour::new(0.2, 0.4, 0.6));
+        assert!(matte.pattern.is_none());
+        assert_fields(&matte, [0.1, 0.9, 0.0, 0.0, 0.0, 0.0, 1.0]);
+
+        let rubber = Material::rubber(Colour::new(0.2, 0.4, 0.6));
+        assert_eq!(rubber.colour, Colour::new(0.2, 0.4, 0.6));
+        assert_fields(&rubber, [0.1, 0.8, 0.1, 10.0, 0.0, 0.0, 1.0]);
+        assert_eq!(rubber.diffuse_roughness, 0.3);
+    }
+
+    #[test]
+    fn test_dielectric() {
+        let water = Material::dielectric(ior::WATER);
+        let hand_built = Material {
+            colour:           Colour::new(1.0, 1.0, 1.0),
+            pattern:          None,
+            normal_perturbation: None,
+            uv_pattern:       false,
+            ambient:          0.0,
+            diffuse:          0.0,
+            specular:         0.0,
+            shininess:        0.0,
+            reflect:          0.0,
+            transparency:     1.0,
+            refractive_index: 1.333,
+            shadow_catcher:   false,
+            absorption:       BLACK,
+            roughness:        0.0,
+            glossy_samples:   GLOSSY_SAMPLES,
+            specular_model:   SpecularModel::Phong,
+            diffuse_roughness: 0.0,
+            fresnel:          false,
+            pattern_blend:    PatternBlend::Replace,
+        };
+        assert_eq!(water, hand_built);
+        assert_eq!(Material::dielectric(ior::GLASS), Material::glass());
+    }
+
+    #[test]
+    fn test_specular_models() {
+        use crate::{Object, Point3, Vec3, ray::Ray, object::Plane};
+
+        // Eye and light both 1 above the floor, either side of the origin.
+        let eye = Point3::new(0.0, 1.0, -4.0);
+        let light = Light::new(Point3::new(0.0, 1.0, 4.0), Colour::new(1.0, 1.0, 1.0));
+        let highlight = |model: SpecularModel, shininess: f64, x: f64, z: f64| {
+            let floor = Plane::new(Material { ambient: 0.0, diffuse: 0.0, shininess, specular_model: model, ..Default::default() });
+            let ray = Ray::new(eye, (Point3::new(x, 0.0, z) - eye).normalize());
+            let hit = &floor.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+            Vec3::from(floor.material().light(&light, hit, WHITE, WHITE)).x
+        };
+
+        // Both peak where the floor mirrors the light into the eye.
+        for model in [SpecularModel::Phong, SpecularModel::BlinnPhong] {
+            let peak = highlight(model, 50.0, 0.0, 0.0);
+            assert!((peak - 0.9).abs() < 1e-9);
+            for (x, z) in [(0.0, 0.3), (0.0, -0.3), (0.3, 0.0)] {
+                assert!(highlight(model, 50.0, x, z) < peak);
+            }
+        }
+        // Off to the side the reflection points away from the eye, so Phong's
+        // highlight has gone, but the halfway vector is still above the floor.
+        assert_eq!(highlight(SpecularModel::Phong, 1.0, 5.0, 0.0), 0.0);
+        assert!(highlight(SpecularModel::BlinnPhong, 1.0, 5.0, 0.0) > 0.0);
+    }
+
+    #[test]
+    fn test_oren_nayar() {
+        use crate::{Point3, Vec3};
+
+        // Light 60 degrees from the normal, eye 45 degrees, both on the same
+        // side or opposite sides of it.
+        let light = Light::new(Point3::new(60f64.to_radians().sin(), 0.5, 0.0), Colour::new(1.0, 1.0, 1.0));
+        let diffuse = |roughness: f64, eye_x: f64| {
+            let material = Material { ambient: 0.0, specular: 0.0, diffuse_roughness: roughness, ..Default::default() };
+            let hit = Intersection {
+                normal: Vec3::new(0.0, 1.0, 0.0),
+                eye: Vec3::new(eye_x, 1.0, 0.0).normalize(),
+                colour: Colour::new(1.0, 1.0, 1.0),
+                ..Default::default()
+            };
+            Vec3::from(material.light(&light, &hit, WHITE, WHITE)).x
+        };
+
+        assert!((diffuse(0.0, 1.0) - 0.45).abs() < 1e-9);
+        assert!((diffuse(0.0, -1.0) - 0.45).abs() < 1e-9);
+        assert!((diffuse(0.5, 1.0) - 0.4819658768692728).abs() < 1e-9);
+        assert!((diffuse(0.5, -1.0) - 0.3530172413793104).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ior_names() {
+        assert_eq!(ior::from_name("Diamond"), Some(ior::DIAMOND));
+        assert_eq!(ior::from_name("unobtainium"), None);
+    }
 }
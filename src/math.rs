@@ -47,12 +47,12 @@ pub fn near_zero(v: &Vec3) -> bool {
 }
 
 pub fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
-    incident - 2.0 * incident.dot(&normal) * normal
+    incident - 2.0 * incident.dot(normal) * normal
 }
 
 // Use Snell's law to calculate the refracted ray.
 pub fn refract(incident: &Vec3, normal: &Vec3, refraction_ratio: f64) -> Vec3 {
-    let cos_theta = (-incident).dot(&normal).min(1.0);
+    let cos_theta = (-incident).dot(normal).min(1.0);
     let r_out_perp = refraction_ratio * (incident + cos_theta * normal);
     let r_out_parallel = -(1.0 - r_out_perp.magnitude_squared()).abs().sqrt() * normal;
     r_out_perp + r_out_parallel
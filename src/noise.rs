@@ -0,0 +1,132 @@
+use crate::{Point3, Vec3};
+
+// Ken Perlin's improved gradient noise. The permutation is shuffled from the
+// seed with our own generator rather than rand's, so a seed gives the same
+// noise on every platform and version.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+        let mut state = seed;
+        for i in (1..256).rev() {
+            let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+        Self { perm: std::array::from_fn(|i| table[i % 256]) }
+    }
+
+    // Smooth noise in about -1 to 1, 0 on every integer lattice point.
+    pub fn noise(&self, point: &Point3) -> f64 {
+        let cell = point.map(f64::floor);
+        let (x, y, z) = (point.x - cell.x, point.y - cell.y, point.z - cell.z);
+        let wrap = |v: f64| (v as i64).rem_euclid(256) as usize;
+        let (xi, yi, zi) = (wrap(cell.x), wrap(cell.y), wrap(cell.z));
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.perm;
+        let hash = |dx: usize, dy: usize, dz: usize| p[p[p[xi + dx] as usize + yi + dy] as usize + zi + dz];
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            grad(hash(dx, dy, dz), x - dx as f64, y - dy as f64, z - dz as f64)
+        };
+
+        lerp(w,
+            lerp(v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0))),
+            lerp(v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1))))
+    }
+
+    // Octaves of noise, each twice the frequency of the last and persistence
+    // times as strong, scaled back to about -1 to 1.
+    pub fn fractal(&self, point: &Point3, octaves: u32, persistence: f64) -> f64 {
+        let (mut total, mut max) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+        for _ in 0..octaves {
+            total += self.noise(&(point * frequency)) * amplitude;
+            max += amplitude;
+            frequency *= 2.0;
+            amplitude *= persistence;
+        }
+        if max == 0.0 { 0.0 } else { total / max }
+    }
+
+    // Fractal noise for each axis, read from far apart so they're unrelated.
+    pub fn vector(&self, point: &Point3, octaves: u32, persistence: f64) -> Vec3 {
+        Vec3::new(
+            self.fractal(point, octaves, persistence),
+            self.fractal(&(point + Vec3::new(31.4, 0.0, 0.0)), octaves, persistence),
+            self.fractal(&(point + Vec3::new(0.0, 0.0, 27.1)), octaves, persistence),
+        )
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// Dot product with one of 12 edge directions of a cube, picked by the hash.
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perlin() {
+        let perlin = Perlin::new(42);
+        let points: Vec<Point3> = (0..200)
+            .map(|i| Point3::new(i as f64 * 0.137, i as f64 * 0.071 - 3.0, (i as f64 * 0.29).sin() * 5.0))
+            .collect();
+        let values: Vec<f64> = points.iter().map(|p| perlin.noise(p)).collect();
+        assert!(values.iter().all(|v| v.abs() <= 1.0));
+        assert!(values.iter().any(|&v| v > 0.1) && values.iter().any(|&v| v < -0.1));
+        assert_eq!(perlin.noise(&Point3::new(3.0, -2.0, 7.0)), 0.0);
+
+        // Same seed, same noise. Different seed, different noise.
+        let again = Perlin::new(42);
+        assert!(points.iter().all(|p| again.noise(p) == perlin.noise(p)));
+        let other = Perlin::new(7);
+        assert!(points.iter().any(|p| other.noise(p) != perlin.noise(p)));
+
+        // Continuous.
+        let p = Point3::new(1.3, 2.7, -0.4);
+        assert!((perlin.noise(&p) - perlin.noise(&(p + crate::Vec3::repeat(1e-6)))).abs() < 1e-4);
+
+        // One octave is plain noise, more add detail but stay in range.
+        assert!(points.iter().all(|p| perlin.fractal(p, 1, 0.5) == perlin.noise(p)));
+        assert!(points.iter().all(|p| perlin.fractal(p, 5, 0.7).abs() <= 1.0));
+        assert!(points.iter().any(|p| perlin.fractal(p, 4, 0.5) != perlin.noise(p)));
+    }
+
+    #[test]
+    fn test_perlin_reference() {
+        // Pinned, so changes to the generator, which would change every
+        // noisy scene, are noticed.
+        let perlin = Perlin::new(42);
+        let value = perlin.noise(&Point3::new(0.3, 1.7, -2.2));
+        assert!((value - -0.33113577306214415).abs() < 1e-12, "{}", value);
+    }
+}
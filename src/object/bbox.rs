@@ -1,29 +1,52 @@
 use std::sync::Arc;
 use crate::{Material, Matrix4, Object, ray::Ray, transform::Transformable, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::pattern::Mapping;
+
+// How close, as a fraction of the box's size, a point must be to more than one
+// face to count as on an edge.
+const EDGE_EPSILON: f64 = 1e-9;
+
+// Rays moving slower than this along an axis, per half the box's size along
+// it, are counted as parallel to the faces on that axis.
+const PARALLEL_EPSILON: f64 = 0.0001;
 
 #[derive(Debug)]
 pub struct AxisAlignedBoundingBox {
-    id:         usize,
+    id:         ObjectId,
     transform:  Matrix4,
     inverse:    Matrix4,
     material:   Arc<Material>,
+    flags:      ObjectFlags,
+    // Corners in object space, -1 to 1 unless made with from_corners.
+    min:        Vec3,
+    max:        Vec3,
 }
 
 impl AxisAlignedBoundingBox {
-    pub fn new(material: Material) -> Self {
+    pub fn new(material: impl Into<Arc<Material>>) -> Self {
         Self {
-            id: 0,
+            id: ObjectId::new(),
             transform: Matrix4::identity(),
             inverse: Matrix4::identity(),
-            material: Arc::new(material),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            min: Vec3::repeat(-1.0),
+            max: Vec3::repeat(1.0),
         }
     }
 
-    fn check_axis(&self, origin: f64, direction: f64) -> (f64, f64) {
-        let tmin_numerator = -1.0 - origin;
-        let tmax_numerator = 1.0 - origin;
+    // A box between two opposite corners, before any transforms.
+    pub fn from_corners(material: impl Into<Arc<Material>>, min: Point3, max: Point3) -> Self {
+        assert!(min.x < max.x && min.y < max.y && min.z < max.z, "box min corner must be below max on every axis");
+        Self { min: min.coords, max: max.coords, ..Self::new(material) }
+    }
 
-        let (mut close, mut far) = if direction.abs() >= 0.0001 {
+    fn check_axis(&self, origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
+        let tmin_numerator = min - origin;
+        let tmax_numerator = max - origin;
+
+        let (mut close, mut far) = if direction.abs() >= PARALLEL_EPSILON * (max - min) / 2.0 {
             (tmin_numerator / direction, tmax_numerator / direction)
         } else {
             (tmin_numerator * f64::INFINITY, tmax_numerator * f64::INFINITY)
@@ -41,9 +64,9 @@ impl Object for AxisAlignedBoundingBox {
     
     fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
         
-        let (tmin_x, tmax_x) = self.check_axis(obj_ray.origin.x, obj_ray.direction.x);
-        let (tmin_y, tmax_y) = self.check_axis(obj_ray.origin.y, obj_ray.direction.y);
-        let (tmin_z, tmax_z) = self.check_axis(obj_ray.origin.z, obj_ray.direction.z);
+        let (tmin_x, tmax_x) = self.check_axis(obj_ray.origin.x, obj_ray.direction.x, self.min.x, self.max.x);
+        let (tmin_y, tmax_y) = self.check_axis(obj_ray.origin.y, obj_ray.direction.y, self.min.y, self.max.y);
+        let (tmin_z, tmax_z) = self.check_axis(obj_ray.origin.z, obj_ray.direction.z, self.min.z, self.max.z);
 
         let close = tmin_x.max(tmin_y).max(tmin_z);
         let far = tmax_x.min(tmax_y).min(tmax_z);
@@ -62,30 +85,58 @@ impl Object for AxisAlignedBoundingBox {
         if hits.is_empty() { None } else { Some(hits) }
     }
 
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::new(Point3::from(self.min), Point3::from(self.max))
+    }
+
+    // Each face gets the whole square.
+    fn uv_at(&self, obj_point: &Point3) -> (f64, f64) {
+        let centre = (self.min + self.max) / 2.0;
+        let point = (obj_point.coords - centre).component_div(&((self.max - self.min) / 2.0));
+        Mapping::Cube.uv(&Point3::from(point))
+    }
+
     fn normal_obj(&self, point: &Point3) -> Vec3 {
-        let max_c = point.x.abs().max(point.y.abs()).max(point.z.abs());
+        self.normal_obj_hit(point, &Vec3::zeros())
+    }
+
+    // On an edge or corner more than one face could have been hit. Of those,
+    // the ray went most directly through the one it really hit.
+    fn normal_obj_hit(&self, point: &Point3, obj_direction: &Vec3) -> Vec3 {
+        // Relative to the centre, with the faces at -1 and 1.
+        let centre = (self.min + self.max) / 2.0;
+        let point = (point.coords - centre).component_div(&((self.max - self.min) / 2.0));
+        let max_c = point.abs().max();
 
         // Direction of normal is the direction of the largest component.
-        if max_c == point.x.abs() {
-            Vec3::new(point.x, 0.0, 0.0)
-        } else if max_c == point.y.abs() {
-            Vec3::new(0.0, point.y, 0.0)
-        } else {
-            Vec3::new(0.0, 0.0, point.z)
-        }
+        let axis = (0..3)
+            .filter(|&axis| point[axis].abs() >= max_c - EDGE_EPSILON)
+            .max_by(|&a, &b| obj_direction[a].abs().total_cmp(&obj_direction[b].abs()).then(b.cmp(&a)))
+            .unwrap_or(0);
+        let mut normal = Vec3::zeros();
+        normal[axis] = point[axis].signum();
+        normal
     }
 
     fn material(&self) -> &Arc<Material> {
         &self.material
     }
 
-    fn id(&self) -> usize {
+    fn id(&self) -> ObjectId {
         self.id
     }
 
-    fn set_id(&mut self, id: usize) {
+    fn set_id(&mut self, id: ObjectId) {
         self.id = id;
     }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
 }
 
 impl Transformable for AxisAlignedBoundingBox {
@@ -109,6 +160,7 @@ impl Transformable for AxisAlignedBoundingBox {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::math::{fuzzy_eq_f64, fuzzy_eq_vec};
 
     #[test]
     fn test_bbox_hit() {
@@ -181,4 +233,75 @@ mod tests {
         let obj_norm = bbox.normal_obj(&Point3::new(0.4, 0.4, -1.0));
         assert_eq!(obj_norm, Vec3::new(0.0, 0.0, -1.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_bbox_from_corners() {
+        let bbox = AxisAlignedBoundingBox::from_corners(Material::default(), Point3::new(1.0, -1.0, 2.0), Point3::new(3.0, 0.0, 6.0));
+
+        let ray = Ray::new(Point3::new(2.0, 5.0, 3.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(bbox.hit_obj(&ray, -f64::INFINITY, f64::INFINITY), Some(vec![5.0, 6.0]));
+        let ray = Ray::new(Point3::new(0.0, -0.5, 4.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(bbox.hit_obj(&ray, -f64::INFINITY, f64::INFINITY), Some(vec![1.0, 3.0]));
+        let ray = Ray::new(Point3::new(0.0, 0.5, 4.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(bbox.hit_obj(&ray, -f64::INFINITY, f64::INFINITY), None);
+
+        assert_eq!(bbox.normal_obj(&Point3::new(3.0, -0.5, 2.5)), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(bbox.normal_obj(&Point3::new(2.5, 0.0, 5.0)), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(bbox.normal_obj(&Point3::new(2.0, -0.5, 2.0)), Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_bbox_small_from_corners() {
+        // A millimetre box, and a ray that drifts out of it in y before reaching it in x.
+        let small = AxisAlignedBoundingBox::from_corners(Material::default(), Point3::origin(), Point3::new(1e-3, 1e-3, 1e-3));
+        let ray = Ray::new(Point3::new(-1.0, 0.96e-3, 0.5e-3), Vec3::new(1.0, 5e-5, 0.0));
+        assert_eq!(small.hit_obj(&ray, -f64::INFINITY, f64::INFINITY), None);
+
+        // The same as the unit box shrunk down.
+        let mut scaled = AxisAlignedBoundingBox::new(Material::default());
+        scaled.translate(0.5e-3, 0.5e-3, 0.5e-3);
+        scaled.scale_uniform(0.5e-3);
+        assert!(scaled.hit(&ray, -f64::INFINITY, f64::INFINITY).is_none());
+        let ray = Ray::new(Point3::new(-1.0, 0.9e-3, 0.5e-3), Vec3::new(1.0, 5e-5, 0.0));
+        let t = small.hit_obj(&ray, -f64::INFINITY, f64::INFINITY).unwrap();
+        let expected = scaled.hit(&ray, -f64::INFINITY, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(t[0], expected[0].t) && fuzzy_eq_f64(t[1], expected[1].t));
+    }
+
+    #[test]
+    fn test_bbox_corner_normals() {
+        let bbox = AxisAlignedBoundingBox::new(Material::default());
+        let corner = Point3::new(1.0, 1.0, 1.0);
+        // Coming in steepest through each face hits that face.
+        let cases = [
+            (Vec3::new(-1.0, -2.0, -3.0), Vec3::new(0.0, 0.0, 1.0)),
+            (Vec3::new(-3.0, -1.0, -2.0), Vec3::new(1.0, 0.0, 0.0)),
+            (Vec3::new(-1.0, -3.0, -2.0), Vec3::new(0.0, 1.0, 0.0)),
+        ];
+        for (direction, expected) in cases {
+            let direction = direction.normalize();
+            let ray = Ray::new(corner - direction * 5.0, direction);
+            let hits = bbox.hit(&ray, 0.0, f64::INFINITY).unwrap();
+            assert!(fuzzy_eq_vec(&hits[0].normal, &expected), "{:?} from {:?}", hits[0].normal, direction);
+            assert!(hits[0].front_face);
+        }
+    }
+
+    #[test]
+    fn test_bbox_edge_shading() {
+        // Rays at the x = 1, z = 1 edge, closer to head on to the z face.
+        let mut bbox = AxisAlignedBoundingBox::new(Material::default());
+        bbox.rotate(crate::Axis::Y, 30.0);
+        bbox.translate(0.5, 0.0, 0.0);
+        let direction = bbox.transform().transform_vector(&Vec3::new(-1.0, 0.0, -2.0)).normalize();
+        let hit_at = |x: f64| {
+            let target = bbox.transform().transform_point(&Point3::new(x, 0.0, 1.0));
+            bbox.hit(&Ray::new(target - direction * 5.0, direction), 0.0, f64::INFINITY).unwrap().remove(0)
+        };
+
+        // The edge is lit like the face beside it, rather than a seam lit like the other face.
+        let (edge, beside) = (hit_at(1.0), hit_at(0.999));
+        assert!(fuzzy_eq_vec(&edge.normal, &beside.normal), "{:?}", edge.normal);
+        assert!(fuzzy_eq_vec(&edge.reflect, &beside.reflect));
+    }
+}
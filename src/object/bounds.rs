@@ -0,0 +1,169 @@
+use crate::{Matrix4, Point3, Vec3};
+use crate::ray::Ray;
+
+// Axis aligned box from min to max. Unbounded objects have infinite sides.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Bounds {
+    pub fn new(min: Point3, max: Point3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn infinite() -> Self {
+        Self::new(Point3::from(Vec3::repeat(-f64::INFINITY)), Point3::from(Vec3::repeat(f64::INFINITY)))
+    }
+
+    // Contains nothing, so is the starting point for unions.
+    pub fn empty() -> Self {
+        Self::new(Point3::from(Vec3::repeat(f64::INFINITY)), Point3::from(Vec3::repeat(-f64::INFINITY)))
+    }
+
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Point3>) -> Self {
+        points.into_iter().fold(Self::empty(), |bounds, point| bounds.union(&Self::new(*point, *point)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min.iter().chain(self.max.iter()).all(|v| v.is_finite())
+    }
+
+    pub fn contains(&self, point: &Point3) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn union(&self, other: &Bounds) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    pub fn intersection(&self, other: &Bounds) -> Self {
+        Self::new(self.min.sup(&other.min), self.max.inf(&other.max))
+    }
+
+    // The t where the ray enters and leaves the box, if it passes through it.
+    pub fn ray_range(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (mut enter, mut exit) = (f64::NEG_INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            let a = (self.min[axis] - ray.origin[axis]) / ray.direction[axis];
+            let b = (self.max[axis] - ray.origin[axis]) / ray.direction[axis];
+            // NaN when the ray lies in a face, and max and min skip NaN.
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+        }
+        if enter <= exit { Some((enter, exit)) } else { None }
+    }
+
+    pub fn translate(&self, offset: &Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z), Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z), Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z), Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z), Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // Box around the transformed corners. Infinite sides could end up pointing
+    // any way, so infinite bounds stay infinite on every axis.
+    pub fn transform(&self, transform: &Matrix4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        if !self.is_finite() {
+            return Self::infinite();
+        }
+        let corners = self.corners().map(|corner| transform.transform_point(&corner));
+        Self::from_points(&corners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use crate::{Material, Object, Axis};
+    use crate::ray::Ray;
+    use crate::object::*;
+    use crate::transform::Transformable;
+
+    // Hits from rays at the object's centre from all around must all be inside
+    // its bounds, and agree with hit_any.
+    fn assert_contains_hits(object: &dyn Object) {
+        let bounds = object.bounds();
+        let centre = object.transform().transform_point(&Point3::origin());
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = centre + Vec3::new(rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0));
+            let target = centre + Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            let ray = Ray::new(origin, (target - origin).normalize());
+            let found = object.hit(&ray, 0.0, f64::INFINITY).unwrap_or_default();
+            assert_eq!(object.hit_any(&ray, 0.0, f64::INFINITY), !found.is_empty());
+            for hit in found {
+                let slack = Bounds::new(bounds.min - Vec3::repeat(1e-9), bounds.max + Vec3::repeat(1e-9));
+                assert!(slack.contains(&hit.point), "{:?} outside {:?}", hit.point, bounds);
+                hits += 1;
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    fn place(object: &mut dyn Object) {
+        object.translate(1.0, -2.0, 3.0);
+        object.rotate(Axis::X, 30.0);
+        object.rotate(Axis::Z, 50.0);
+        object.scale(0.5, 1.5, 2.0);
+    }
+
+    #[test]
+    fn test_transformed_bounds_contain_surface() {
+        let material = Arc::new(Material::default());
+        let mut objects: Vec<Box<dyn Object>> = vec![
+            Box::new(Sphere::new(Arc::clone(&material))),
+            Box::new(Disk::new(Arc::clone(&material))),
+            Box::new(AxisAlignedBoundingBox::from_corners(Arc::clone(&material), Point3::new(-1.0, 0.0, -0.5), Point3::new(0.5, 1.0, 1.0))),
+            Box::new(Cylinder::new(Arc::clone(&material), 1.0, -0.5, 1.0, true)),
+            Box::new(Cone::new(Arc::clone(&material), 45.0, -1.0, 0.5, true)),
+            Box::new(Superellipsoid::new(Arc::clone(&material), 0.3, 1.5)),
+        ];
+        let mut frustum = Cylinder::new(Arc::clone(&material), 1.0, -0.5, 1.0, true);
+        frustum.set_radii(0.5, 1.5);
+        objects.push(Box::new(frustum));
+        for mut object in objects {
+            place(&mut *object);
+            assert!(object.bounds().is_finite());
+            assert_contains_hits(&*object);
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        // Truncation shrinks the cone to the wider end.
+        let cone = Cone::new(Material::default(), 45.0, -1.0, 0.5, true);
+        assert_eq!(cone.bounds(), Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 0.5, 1.0)));
+        assert!(!Cone::default().bounds().is_finite());
+        assert!(!Plane::new(Material::default()).bounds().is_finite());
+
+        let mut sphere = Sphere::new(Material::default());
+        sphere.translate(2.0, 0.0, 0.0);
+        sphere.scale(1.0, 3.0, 1.0);
+        assert_eq!(sphere.bounds(), Bounds::new(Point3::new(1.0, -3.0, -1.0), Point3::new(3.0, 3.0, 1.0)));
+
+        let a = Bounds::new(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 2.0, 2.0));
+        let b = Bounds::new(Point3::new(1.0, -1.0, 1.0), Point3::new(3.0, 1.0, 3.0));
+        assert_eq!(a.union(&b), Bounds::new(Point3::new(0.0, -1.0, 0.0), Point3::new(3.0, 2.0, 3.0)));
+        assert_eq!(a.intersection(&b), Bounds::new(Point3::new(1.0, 0.0, 1.0), Point3::new(2.0, 1.0, 2.0)));
+        assert!(Bounds::empty().transform(&Matrix4::identity()).is_empty());
+    }
+}
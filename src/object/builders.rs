@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use crate::{Material, Object, Axis};
+use crate::object::{Group, Sphere, Cylinder};
+use crate::transform::Transformable;
+
+// The hexagon from the Ray Tracer Challenge, flat in the xz plane with its
+// corners 1 from the origin. Six spheres at the corners joined by six
+// cylinders, all sharing the material.
+pub fn hexagon(material: impl Into<Arc<Material>>) -> Box<dyn Object> {
+    let material = material.into();
+    let mut children: Vec<Box<dyn Object>> = vec![];
+    for n in 0..6 {
+        let angle = n as f64 * 60.0;
+
+        let mut corner = Sphere::new(Arc::clone(&material));
+        corner.rotate(Axis::Y, angle);
+        corner.translate(0.0, 0.0, -1.0);
+        corner.scale_uniform(0.25);
+        children.push(Box::new(corner));
+
+        // From this corner to the next, lying along x before it's turned.
+        let mut edge = Cylinder::new(Arc::clone(&material), 1.0, 0.0, 1.0, false);
+        edge.rotate(Axis::Y, angle);
+        edge.translate(0.0, 0.0, -1.0);
+        edge.rotate(Axis::Y, -30.0);
+        edge.rotate(Axis::Z, -90.0);
+        edge.scale(0.25, 1.0, 0.25);
+        children.push(Box::new(edge));
+    }
+    Box::new(Group::new(material, children))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Point3, Vec3};
+    use crate::ray::Ray;
+    use crate::math::*;
+
+    #[test]
+    fn test_hexagon() {
+        // Sides are cos(30) from the middle, with a radius of 0.25.
+        let side = 30f64.to_radians().cos();
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hexagon = hexagon(Material::default());
+        let hits = hexagon.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let ts: Vec<f64> = hits.iter().map(|hit| hit.t).collect();
+        let expected = [5.0 - side - 0.25, 5.0 - side + 0.25, 5.0 + side - 0.25, 5.0 + side + 0.25];
+        assert_eq!(ts.len(), 4);
+        assert!(ts.iter().zip(expected).all(|(&t, expected)| fuzzy_eq_f64(t, expected)), "{:?}", ts);
+        // Two opposite edges, each its own solid.
+        assert_eq!(hits[0].obj_id, hits[1].obj_id);
+        assert_eq!(hits[2].obj_id, hits[3].obj_id);
+        assert_ne!(hits[0].obj_id, hits[2].obj_id);
+        assert!(fuzzy_eq_vec(&hits[0].normal, &Vec3::new(-1.0, 0.0, 0.0)));
+
+        // Through two corners.
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(fuzzy_eq_f64(hexagon.hit(&ray, 0.0, f64::INFINITY).unwrap()[0].t, 3.75));
+        let ray = Ray::new(Point3::new(0.0, 0.3, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(hexagon.hit(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_hexagon_moves_as_one() {
+        let mut hexagon = hexagon(Material::default());
+        let before = hexagon.bounds();
+        hexagon.translate(3.0, 2.0, 0.0);
+        hexagon.rotate(Axis::X, 90.0);
+        let after = hexagon.bounds();
+        assert!(fuzzy_eq_f64(after.min.x, before.min.x + 3.0) && fuzzy_eq_f64(after.max.y, before.max.z + 2.0));
+
+        // Standing up now, facing z.
+        let side = 30f64.to_radians().cos();
+        let ray = Ray::new(Point3::new(-5.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let ts: Vec<f64> = hexagon.hit(&ray, 0.0, f64::INFINITY).unwrap().iter().map(|hit| hit.t).collect();
+        assert_eq!(ts.len(), 4);
+        assert!(fuzzy_eq_f64(ts[0], 8.0 - side - 0.25));
+        assert!(fuzzy_eq_f64(ts[3], 8.0 + side + 0.25));
+    }
+}
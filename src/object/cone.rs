@@ -1,43 +1,54 @@
 use std::sync::Arc;
 use crate::{Matrix4, Material, Object, ray::Ray, Point3, Vec3};
+use crate::object::{Bounds, ObjectId, ObjectFlags, GRAZE_EPSILON};
+use crate::object::cylinder::cap_uv;
 use crate::transform::Transformable;
+use crate::pattern::Mapping;
 
 #[derive(Debug)]
 pub struct Cone {
-    pub id:         usize,
+    pub id:         ObjectId,
     pub min:        f64,
     pub max:        f64,
     pub capped:     bool,
+    // Radius per unit along the axis, the tangent of the half-angle.
+    pub slope:      f64,
     pub transform:  Matrix4,
     pub inverse:    Matrix4,
     pub material:   Arc<Material>,
+    pub flags:      ObjectFlags,
 }
 
 impl Default for Cone {
     fn default() -> Self {
-        Self::new(Material::default(), -f64::INFINITY, f64::INFINITY, false)
+        Self::new(Material::default(), 45.0, -f64::INFINITY, f64::INFINITY, false)
     }
 }
 
 impl Cone {
-    pub fn new(material: Material, min: f64, max: f64, capped: bool) -> Self {
+    // angle is the half-angle at the apex in degrees, 45 makes the radius |y|.
+    pub fn new(material: impl Into<Arc<Material>>, angle: f64, min: f64, max: f64, capped: bool) -> Self {
+        assert!(angle > 0.0 && angle < 90.0, "cone angle must be between 0 and 90 degrees");
         Self {
-            id: 0,
+            id: ObjectId::new(),
             min,
             max,
             capped,
+            // tan is an ulp under 1 at 45 degrees, which rays along the sides notice.
+            slope: if angle == 45.0 { 1.0 } else { angle.to_radians().tan() },
             transform: Matrix4::identity(),
             inverse: Matrix4::identity(),
-            material: Arc::new(material),
+            material: material.into(),
+            flags: ObjectFlags::default(),
         }
     }
 
-    fn check_caps(ray: &Ray, t: f64) -> bool {
+    fn check_caps(&self, ray: &Ray, t: f64) -> bool {
         let x = ray.origin.x + t * ray.direction.x;
         let z = ray.origin.z + t * ray.direction.z;
         let y = ray.origin.y + t * ray.direction.y;
 
-        x.powi(2) + z.powi(2) <= y.abs()
+        x.powi(2) + z.powi(2) <= (self.slope * y).powi(2)
     }
 
     fn hit_caps(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<f64> {
@@ -48,12 +59,12 @@ impl Cone {
         
         let mut hits = vec![];
         let close = (self.min - ray.origin.y) / ray.direction.y;
-        if close >= t_min && close <= t_max && Self::check_caps(ray, close) {
+        if close >= t_min && close <= t_max && self.check_caps(ray, close) {
                 hits.push(close);
         }
 
         let far = (self.max - ray.origin.y) / ray.direction.y;
-        if far >= t_min && far <= t_max && Self::check_caps(ray, far) {
+        if far >= t_min && far <= t_max && self.check_caps(ray, far) {
                 hits.push(far);
         }
         hits
@@ -64,11 +75,13 @@ impl Object for Cone {
 
     fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
 
-        let a = obj_ray.direction.x.powi(2) - obj_ray.direction.y.powi(2) + obj_ray.direction.z.powi(2);
+        // The sides are x^2 + z^2 = (slope * y)^2.
+        let k = self.slope.powi(2);
+        let a = obj_ray.direction.x.powi(2) - k * obj_ray.direction.y.powi(2) + obj_ray.direction.z.powi(2);
         let b = 2.0 * obj_ray.origin.x * obj_ray.direction.x 
-        - 2.0 * obj_ray.origin.y * obj_ray.direction.y 
+        - 2.0 * k * obj_ray.origin.y * obj_ray.direction.y 
         + 2.0 * obj_ray.origin.z * obj_ray.direction.z;
-        let c = obj_ray.origin.x.powi(2) - obj_ray.origin.y.powi(2) + obj_ray.origin.z.powi(2);
+        let c = obj_ray.origin.x.powi(2) - k * obj_ray.origin.y.powi(2) + obj_ray.origin.z.powi(2);
         
         if a.abs() < 1e-8 {
             if b.abs() < 1e-8 {
@@ -84,6 +97,11 @@ impl Object for Cone {
         if disc < 0.0 {
             return None;
         }
+        // Roots sqrt(disc) / |a| apart in t, so this far apart along the ray.
+        if disc.sqrt() / a.abs() * obj_ray.direction.norm() < GRAZE_EPSILON {
+            let hits = self.hit_caps(obj_ray, t_min, t_max);
+            return if hits.is_empty() { None } else { Some(hits) };
+        }
         
         let mut hits = vec![];
         let mut close = (-b - disc.sqrt()) / (2.0 * a);
@@ -109,25 +127,52 @@ impl Object for Cone {
         if hits.is_empty() { None } else { Some(hits) }
     }
 
+    // The radius at each end is the slope times the distance from the apex.
+    fn bounds_obj(&self) -> Bounds {
+        let radius = self.slope * self.min.abs().max(self.max.abs());
+        Bounds::new(Point3::new(-radius, self.min, -radius), Point3::new(radius, self.max, radius))
+    }
+
+    fn uv_at(&self, obj_point: &Point3) -> (f64, f64) {
+        let on_cap = self.capped && (obj_point.y >= self.max - 1e-8 || obj_point.y <= self.min + 1e-8);
+        if on_cap && self.slope * obj_point.y.abs() > 0.0 {
+            cap_uv(obj_point, self.slope * obj_point.y.abs())
+        } else {
+            Mapping::Cylindrical.uv(obj_point)
+        }
+    }
+
     fn normal_obj(&self, point: &Point3) -> Vec3 {
         let dist = point.x.powi(2) + point.z.powi(2);
-        if dist < 1.0 && point.y >= self.max - 1e-8 {
+        // Caps have the radius of the cone where they cut it, slope * |y|.
+        if self.capped && dist <= (self.slope * self.max).powi(2) + 1e-8 && point.y >= self.max - 1e-8 {
             Vec3::new(0.0, 1.0, 0.0)
-        } else if dist < 1.0 && point.y <= self.min + 1e-8 {
+        } else if self.capped && dist <= (self.slope * self.min).powi(2) + 1e-8 && point.y <= self.min + 1e-8 {
             Vec3::new(0.0, -1.0, 0.0)
+        } else if dist < 1e-16 {
+            // The sides have no normal at the apex, point along the axis away from them.
+            if self.max > 0.0 { Vec3::new(0.0, -1.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) }
         } else {
-            Vec3::new(point.x, -point.y, point.z)
+            Vec3::new(point.x, -self.slope * dist.sqrt().copysign(point.y), point.z)
         }
     }
 
-    fn id(&self) -> usize {
+    fn id(&self) -> ObjectId {
         self.id
     }
 
-    fn set_id(&mut self, id: usize) {
+    fn set_id(&mut self, id: ObjectId) {
         self.id = id;
     }
 
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+
     fn material(&self) -> &Arc<Material> {
         &self.material
     }
@@ -155,22 +200,88 @@ impl Transformable for Cone {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::math::*;
 
     #[test]
     fn test_cone_hit() {
         let cone = Cone::default();
 
+        // Through the apex, and along the side touching it at (5, 5, 0), only graze it.
         let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
-        let t = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
-        assert_eq!(t[0].t, 5.0);
-        assert_eq!(t[1].t, 5.0);
+        assert!(cone.hit(&ray, 0.0, f64::INFINITY).is_none());
 
         let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(1.0, 1.0, 1.0));
+        assert!(cone.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+        // Just above the apex the ray goes in and out of the top half.
+        let ray = Ray::new(Point3::new(0.0, 0.001, -5.0), Vec3::new(0.0, 0.0, 1.0));
         let t = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
-        println!("{:?}", t[0].t);
-        // assert!(fuzzy_eq_f64(t[0].t, 8.66025));
-        // assert!(fuzzy_eq_f64(t[1].t, 8.66025));
+        assert_eq!(t.len(), 2);
+        assert!(fuzzy_eq_f64(t[0].t, 4.999) && fuzzy_eq_f64(t[1].t, 5.001));
+
+        // A capped cone still hits its cap.
+        let capped = Cone::new(Material::default(), 45.0, -1.0, 0.0, true);
+        let ray = Ray::new(Point3::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(capped.hit(&ray, 0.0, f64::INFINITY).is_some());
     }
 
+    #[test]
+    fn test_cone_normal() {
+        let cone = Cone::default();
+        let cases = [
+            (Point3::new(1.0, 1.0, 1.0), Vec3::new(1.0, -f64::sqrt(2.0), 1.0)),
+            (Point3::new(-1.0, -1.0, 0.0), Vec3::new(-1.0, 1.0, 0.0)),
+            (Point3::new(0.0, -2.0, 2.0), Vec3::new(0.0, 2.0, 2.0)),
+        ];
+        for (point, expected) in cases {
+            assert!(fuzzy_eq_vec(&cone.normal_obj(&point), &expected));
+        }
+
+        // Rays through the apex get a usable normal.
+        let normal = cone.normal_at(&Point3::origin());
+        assert!(normal.iter().all(|n| n.is_finite()));
+        assert_eq!(normal, Vec3::new(0.0, -1.0, 0.0));
+        let lower = Cone::new(Material::default(), 45.0, -1.0, 0.0, false);
+        assert_eq!(lower.normal_at(&Point3::origin()), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_cone_cap_normal() {
+        let cone = Cone::new(Material::default(), 45.0, -1.0, 2.0, true);
+        assert_eq!(cone.normal_obj(&Point3::new(0.0, 2.0, 1.5)), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(cone.normal_obj(&Point3::new(0.5, -1.0, 0.0)), Vec3::new(0.0, -1.0, 0.0));
+        // The rim of the wider top cap belongs to the cap.
+        assert_eq!(cone.normal_obj(&Point3::new(2.0, 2.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+        // A cap cutting through the apex.
+        let cone = Cone::new(Material::default(), 45.0, -1.0, 0.0, true);
+        assert_eq!(cone.normal_obj(&Point3::origin()), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_cone_angle() {
+        let cone = Cone::new(Material::default(), 30.0, -2.0, 0.0, true);
+        let slope = 30.0_f64.to_radians().tan();
+
+        // Down beside the axis, in through the side and out through the base.
+        let ray = Ray::new(Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut hits = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        assert!(fuzzy_eq_f64(hits[0].t, 5.0 + 0.5 / slope));
+        assert!(fuzzy_eq_f64(hits[1].t, 7.0));
+        // The side leans 30 degrees in from the axis, so its normal is 30 degrees up.
+        assert!(fuzzy_eq_vec(&hits[0].normal, &Vec3::new(30.0_f64.to_radians().cos(), 30.0_f64.to_radians().sin(), 0.0)));
+        assert!(fuzzy_eq_vec(&cone.normal_at(&hits[1].point), &Vec3::new(0.0, -1.0, 0.0)));
 
-}
\ No newline at end of file
+        // Across the flank at y = -1, where the radius is the slope.
+        let ray = Ray::new(Point3::new(-5.0, -1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut hits = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        assert!(fuzzy_eq_f64(hits[0].t, 5.0 - slope));
+        assert!(fuzzy_eq_f64(hits[1].t, 5.0 + slope));
+        assert!(fuzzy_eq_vec(&hits[0].normal, &Vec3::new(-(3.0_f64.sqrt()) / 2.0, 0.5, 0.0)));
+
+        // Just past the side misses.
+        let ray = Ray::new(Point3::new(-5.0, -1.0, slope + 0.01), Vec3::new(1.0, 0.0, 0.0));
+        assert!(cone.hit(&ray, 0.0, f64::INFINITY).is_none());
+    }
+}
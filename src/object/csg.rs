@@ -0,0 +1,303 @@
+use std::sync::Arc;
+use serde::Deserialize;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::intersection::Intersection;
+use crate::object::{Bounds, ObjectId, ObjectFlags, nearest_normal};
+use crate::transform::Transformable;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOp {
+    // Whether a hit on one child is on the surface of the combined solid,
+    // given whether the ray is currently inside the left and right children.
+    pub fn allows(&self, left_hit: bool, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOp::Union        => (left_hit && !in_right) || (!left_hit && !in_left),
+            CsgOp::Intersection => (left_hit && in_right) || (!left_hit && in_left),
+            CsgOp::Difference   => (left_hit && !in_right) || (!left_hit && in_left),
+        }
+    }
+}
+
+// Two objects combined into one solid. The children keep their own materials
+// and transforms, the csg's transform is applied on top of them.
+#[derive(Debug)]
+pub struct Csg {
+    pub id:        ObjectId,
+    pub op:        CsgOp,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    left:          Box<dyn Object>,
+    right:         Box<dyn Object>,
+    // Children's transforms relative to the csg, as (transform, inverse).
+    left_local:    (Matrix4, Matrix4),
+    right_local:   (Matrix4, Matrix4),
+}
+
+impl Csg {
+    pub fn new(material: impl Into<Arc<Material>>, op: CsgOp, left: Box<dyn Object>, right: Box<dyn Object>) -> Self {
+        let left_local = (*left.transform(), *left.inverse());
+        let right_local = (*right.transform(), *right.inverse());
+        Self {
+            id: ObjectId::new(),
+            op,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            left,
+            right,
+            left_local,
+            right_local,
+        }
+    }
+
+    pub fn left(&self) -> &dyn Object {
+        &*self.left
+    }
+
+    pub fn right(&self) -> &dyn Object {
+        &*self.right
+    }
+
+    // Keeps the hits, sorted by t, that lie on the surface of the combined solid.
+    // The ray is assumed to start outside both children.
+    fn filter(&self, hits: Vec<(bool, Intersection)>) -> Vec<Intersection> {
+        let (mut in_left, mut in_right) = (false, false);
+        let mut kept = vec![];
+        for (left_hit, hit) in hits {
+            if self.op.allows(left_hit, in_left, in_right) {
+                kept.push(hit);
+            }
+            if left_hit {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+        }
+        kept
+    }
+}
+
+impl Object for Csg {
+
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<Intersection>> {
+        // Children are hit along the whole line so inside/outside is known at t_min.
+        let tagged = |child: &dyn Object, left: bool| {
+            child.hit(ray, -f64::INFINITY, f64::INFINITY)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |hit| (left, hit))
+        };
+        let mut hits: Vec<_> = tagged(&*self.left, true).chain(tagged(&*self.right, false)).collect();
+        hits.sort_by(|a, b| a.1.t.total_cmp(&b.1.t));
+
+        let hits: Vec<_> = self.filter(hits).into_iter()
+            .filter(|hit| hit.t > t_min && hit.t < t_max)
+            // Hits bound one solid, which is what refraction needs to track.
+            .map(|hit| Intersection { obj_id: self.id, ..hit })
+            .collect();
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    // Which hits count depends on both children, so this needs them all.
+    fn hit_any(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.hit(ray, t_min, t_max).is_some_and(|hits| !hits.is_empty())
+    }
+
+    // Only used outside of hit, which works with the children directly.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let world_ray = obj_ray.transform(&self.transform);
+        self.hit(&world_ray, t_min, t_max).map(|hits| hits.into_iter().map(|hit| hit.t).collect())
+    }
+
+    // Children are already in world space.
+    fn bounds(&self) -> Bounds {
+        let (left, right) = (self.left.bounds(), self.right.bounds());
+        match self.op {
+            CsgOp::Union        => left.union(&right),
+            CsgOp::Intersection => left.intersection(&right),
+            CsgOp::Difference   => left,
+        }
+    }
+
+    // Hits take their normals from whichever child was hit, see hit. Anywhere
+    // else it's the child whose surface is nearest the point.
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let world_point = self.transform.transform_point(point);
+        // Points nowhere near a child get an arbitrary normal.
+        let normal = nearest_normal([&*self.left, &*self.right], &world_point).unwrap_or_else(Vec3::y);
+        (self.transform.transpose() * normal.to_homogeneous()).xyz().normalize()
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+
+    fn set_epsilon(&mut self, epsilon: f64) {
+        self.flags.epsilon = Some(epsilon);
+        self.left.set_epsilon(epsilon);
+        self.right.set_epsilon(epsilon);
+    }
+}
+
+impl Transformable for Csg {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+        self.left.set_transform(transform * self.left_local.0);
+        self.right.set_transform(transform * self.right_local.0);
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+        self.left.set_inverse(self.left_local.1 * inverse);
+        self.right.set_inverse(self.right_local.1 * inverse);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::{Light, Colour, Scene};
+    use crate::object::{Sphere, AxisAlignedBoundingBox};
+
+    fn spheres(op: CsgOp) -> Csg {
+        // Unit spheres at x = -0.5 and 0.5.
+        let mut left = Sphere::new(Material::default());
+        left.translate(-0.5, 0.0, 0.0);
+        let mut right = Sphere::new(Material::default());
+        right.translate(0.5, 0.0, 0.0);
+        Csg::new(Material::default(), op, Box::new(left), Box::new(right))
+    }
+
+    fn hit_ts(csg: &Csg, ray: &Ray, t_min: f64) -> Vec<f64> {
+        csg.hit(ray, t_min, f64::INFINITY).unwrap_or_default().iter().map(|hit| hit.t).collect()
+    }
+
+    #[test]
+    fn test_csg_rules() {
+        use CsgOp::*;
+        // (op, left_hit, in_left, in_right, allowed) from the Ray Tracer Challenge.
+        let cases = [
+            (Union, true, true, true, false), (Union, true, true, false, true),
+            (Union, true, false, true, false), (Union, true, false, false, true),
+            (Union, false, true, true, false), (Union, false, true, false, false),
+            (Union, false, false, true, true), (Union, false, false, false, true),
+            (Intersection, true, true, true, true), (Intersection, true, true, false, false),
+            (Intersection, true, false, true, true), (Intersection, true, false, false, false),
+            (Intersection, false, true, true, true), (Intersection, false, true, false, true),
+            (Intersection, false, false, true, false), (Intersection, false, false, false, false),
+            (Difference, true, true, true, false), (Difference, true, true, false, true),
+            (Difference, true, false, true, false), (Difference, true, false, false, true),
+            (Difference, false, true, true, true), (Difference, false, true, false, true),
+            (Difference, false, false, true, false), (Difference, false, false, false, false),
+        ];
+        for (op, left_hit, in_left, in_right, allowed) in cases {
+            assert_eq!(op.allows(left_hit, in_left, in_right), allowed, "{:?} {} {} {}", op, left_hit, in_left, in_right);
+        }
+    }
+
+    #[test]
+    fn test_csg_hits() {
+        // Along x the left sphere spans -1.5..0.5 and the right -0.5..1.5.
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let cases = [
+            (CsgOp::Union, vec![3.5, 6.5]),
+            (CsgOp::Intersection, vec![4.5, 5.5]),
+            (CsgOp::Difference, vec![3.5, 4.5]),
+        ];
+        for (op, expected) in cases {
+            let ts = hit_ts(&spheres(op), &ray, 0.0);
+            assert_eq!(ts.len(), expected.len());
+            assert!(ts.iter().zip(&expected).all(|(a, b)| fuzzy_eq_f64(*a, *b)));
+        }
+
+        // Starting inside, hits behind the origin still decide what is inside.
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let ts = hit_ts(&spheres(CsgOp::Union), &ray, 0.0);
+        assert_eq!(ts.len(), 1);
+        assert!(fuzzy_eq_f64(ts[0], 1.5));
+
+        // Missing the overlap misses the intersection.
+        let ray = Ray::new(Point3::new(-1.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(spheres(CsgOp::Intersection).hit(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_csg_ids_and_transform() {
+        let mut inner = spheres(CsgOp::Difference);
+        inner.translate(0.0, 0.0, 10.0);
+        let csg = Csg::new(Material::default(), CsgOp::Union, Box::new(inner), Box::new(Sphere::new(Material::default())));
+        let ids = [csg.id(), csg.left().id(), csg.right().id()];
+        assert!(ids[0] != ids[1] && ids[0] != ids[2] && ids[1] != ids[2]);
+
+        // Pushing doesn't renumber.
+        let mut scene = Scene::default();
+        scene.push(Box::new(Sphere::new(Material::default())));
+        scene.push(Box::new(csg));
+        assert_eq!(scene.objects[1].id(), ids[0]);
+
+        // The inner difference keeps its own offset under the outer transform.
+        let mut csg = spheres(CsgOp::Difference);
+        csg.translate(0.0, 0.0, 10.0);
+        csg.scale_uniform(2.0);
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 10.0), Vec3::new(1.0, 0.0, 0.0));
+        let hits = csg.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(hits[0].t, 2.0));
+        assert!(fuzzy_eq_f64(hits[1].t, 4.0));
+        // The second hit is on the right sphere, entered from outside so facing the ray.
+        assert!(fuzzy_eq_vec(&hits[1].normal, &Vec3::new(-1.0, 0.0, 0.0)));
+        assert!(hits.iter().all(|hit| hit.obj_id == csg.id()));
+        // Same normal from the point alone, without the ray.
+        assert!(fuzzy_eq_vec(&csg.normal_at(&hits[1].point), &Vec3::new(-1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_csg_shadow() {
+        // A box with the middle cut out, light shines through the hole onto a point below.
+        let mut hole = AxisAlignedBoundingBox::new(Material::default());
+        hole.scale(0.5, 2.0, 0.5);
+        let mut slab = AxisAlignedBoundingBox::new(Material::default());
+        slab.scale(2.0, 0.1, 2.0);
+        let csg = Csg::new(Material::default(), CsgOp::Difference, Box::new(slab), Box::new(hole));
+
+        let mut scene = Scene::default();
+        scene.push(Box::new(csg));
+        scene.lights.push(Light::new(Point3::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+        assert!(!scene.is_shadowed(&Point3::new(0.0, -5.0, 0.0), &mut rand::thread_rng()));
+        assert!(scene.is_shadowed(&Point3::new(1.5, -0.5, 0.0), &mut rand::thread_rng()));
+    }
+}
@@ -1,41 +1,132 @@
 use std::sync::Arc;
 use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags, in_sweep};
 use crate::transform::Transformable;
+use crate::pattern::Mapping;
 
 #[derive(Debug)]
 pub struct Cylinder{
-    pub id: usize,
+    pub id: ObjectId,
     pub min: f64,
     pub max: f64,
     pub capped: bool,
+    // Radii at min and max, the wall tapers between them.
+    pub radius_bottom: f64,
+    pub radius_top: f64,
+    // Swept angle around the y axis in degrees.
+    pub theta_min: f64,
+    pub theta_max: f64,
     pub transform: Matrix4,
     pub inverse: Matrix4,
     pub material: Arc<Material>,
+    pub flags:   ObjectFlags,
 }
 
 impl Default for Cylinder {
     fn default() -> Self {
-        Self::new(Material::default(), -f64::INFINITY, f64::INFINITY, false)
+        Self::new(Material::default(), 1.0, -f64::INFINITY, f64::INFINITY, false)
     }
 }
 
 impl Cylinder {
-    pub fn new(material: Material, min: f64, max: f64, capped: bool) -> Self {
+    pub fn new(material: impl Into<Arc<Material>>, radius: f64, min: f64, max: f64, capped: bool) -> Self {
         Self {
             min,
             max,
             capped,
-            id: 0,
+            radius_bottom: radius,
+            radius_top: radius,
+            theta_min: 0.0,
+            theta_max: 360.0,
+            id: ObjectId::new(),
             transform: Matrix4::identity(),
             inverse: Matrix4::identity(),
-            material: Arc::new(material),
+            material: material.into(),
+            flags: ObjectFlags::default(),
         }
     }
 
-    fn check_caps(ray: &Ray, t: f64) -> bool {
-        let x = ray.origin.x + t * ray.direction.x;
-        let z = ray.origin.z + t * ray.direction.z;
-        x.powi(2) + z.powi(2) <= 1.0
+    // Only keep the wall between the two angles. Capped cylinders are closed off with flat cut faces.
+    pub fn set_sweep(&mut self, theta_min: f64, theta_max: f64) {
+        self.theta_min = theta_min;
+        self.theta_max = theta_max;
+    }
+
+    // Different radii at each end make a frustum, which needs finite ends.
+    pub fn set_radii(&mut self, bottom: f64, top: f64) {
+        assert!(bottom == top || (self.min.is_finite() && self.max.is_finite()), "tapered cylinders need finite min and max");
+        self.radius_bottom = bottom;
+        self.radius_top = top;
+    }
+
+    // Change in radius per unit of height.
+    fn slope(&self) -> f64 {
+        if self.radius_bottom == self.radius_top {
+            0.0
+        } else {
+            (self.radius_top - self.radius_bottom) / (self.max - self.min)
+        }
+    }
+
+    fn radius_at(&self, y: f64) -> f64 {
+        if self.radius_bottom == self.radius_top {
+            self.radius_bottom
+        } else {
+            self.radius_bottom + self.slope() * (y - self.min)
+        }
+    }
+
+    fn is_partial(&self) -> bool {
+        self.theta_max - self.theta_min < 360.0
+    }
+
+    fn check_caps(&self, ray: &Ray, t: f64, radius: f64) -> bool {
+        let point = ray.at(t);
+        point.x.powi(2) + point.z.powi(2) <= radius.powi(2) && in_sweep(&point, self.theta_min, self.theta_max)
+    }
+
+    // Cut faces of a partial sweep, as (direction away from the axis, outward normal).
+    fn cut_faces(&self) -> [(Vec3, Vec3); 2] {
+        let (sin_min, cos_min) = self.theta_min.to_radians().sin_cos();
+        let (sin_max, cos_max) = self.theta_max.to_radians().sin_cos();
+        [
+            (Vec3::new(cos_min, 0.0, sin_min), Vec3::new(sin_min, 0.0, -cos_min)),
+            (Vec3::new(cos_max, 0.0, sin_max), Vec3::new(-sin_max, 0.0, cos_max)),
+        ]
+    }
+
+    // Normal of the cut face the point lies on, if any.
+    fn cut_normal(&self, point: &Point3) -> Option<Vec3> {
+        if !self.capped || !self.is_partial() || point.x.powi(2) + point.z.powi(2) >= self.radius_at(point.y).powi(2) {
+            return None;
+        }
+        self.cut_faces().into_iter()
+            .find(|(radial, normal)| point.coords.dot(normal).abs() < 1e-8 && point.coords.dot(radial) >= 0.0)
+            .map(|(_, normal)| normal)
+    }
+
+    fn hit_cuts(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<f64> {
+
+        if !self.capped || !self.is_partial() {
+            return vec![];
+        }
+
+        let mut hits = vec![];
+        for (radial, normal) in self.cut_faces() {
+            let denom = ray.direction.dot(&normal);
+            if denom.abs() < 1e-8 {
+                continue;
+            }
+            let t = -ray.origin.coords.dot(&normal) / denom;
+            if t < t_min || t > t_max {
+                continue;
+            }
+            let point = ray.at(t);
+            if (0.0..=self.radius_at(point.y)).contains(&point.coords.dot(&radial)) && point.y > self.min && point.y < self.max {
+                hits.push(t);
+            }
+        }
+        hits
     }
 
     fn hit_caps(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<f64> {
@@ -46,12 +137,12 @@ impl Cylinder {
         
         let mut hits = vec![];
         let t0 = (self.min - ray.origin.y) / ray.direction.y;
-        if t0 >= t_min && t0 <= t_max && Self::check_caps(ray, t0) {
+        if t0 >= t_min && t0 <= t_max && self.check_caps(ray, t0, self.radius_bottom) {
                 hits.push(t0);
         }
 
         let t1 = (self.max - ray.origin.y) / ray.direction.y;
-        if t1 >= t_min && t1 <= t_max && Self::check_caps(ray, t1) {
+        if t1 >= t_min && t1 <= t_max && self.check_caps(ray, t1, self.radius_top) {
                 hits.push(t1);
         }
         hits
@@ -61,18 +152,31 @@ impl Cylinder {
 
 impl Object for Cylinder {
 
+    // The wall is x^2 + z^2 = r(y)^2, with r changing linearly along y.
     fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
-        
-        let a = obj_ray.direction.x.powi(2) + obj_ray.direction.z.powi(2);
-        // No wall intersections.
+        let (o, d) = (obj_ray.origin, obj_ray.direction);
+        let k = self.slope();
+        let r0 = self.radius_at(o.y);
+
+        let a = d.x.powi(2) + d.z.powi(2) - (k * d.y).powi(2);
+        let b = 2.0 * o.x * d.x + 2.0 * o.z * d.z - 2.0 * k * d.y * r0;
+        let c = o.x.powi(2) + o.z.powi(2) - r0.powi(2);
+
+        // No wall intersections, or one if the ray is parallel to a tapered wall.
         if a.abs() < 1e-8 {
-            let t = self.hit_caps(obj_ray, t_min, t_max);
+            let mut t = vec![];
+            if k != 0.0 && b.abs() > 1e-8 {
+                let wall = -c / b;
+                let p = obj_ray.at(wall);
+                if wall > t_min && wall < t_max && p.y < self.max && p.y > self.min && in_sweep(&p, self.theta_min, self.theta_max) {
+                    t.push(wall);
+                }
+            }
+            t.extend(self.hit_caps(obj_ray, t_min, t_max));
+            t.extend(self.hit_cuts(obj_ray, t_min, t_max));
             return if t.is_empty() { None } else { Some(t) }
         }
 
-        let b = 2.0 * obj_ray.origin.x * obj_ray.direction.x + 2.0 * obj_ray.origin.z * obj_ray.direction.z;
-        let c = obj_ray.origin.x.powi(2) + obj_ray.origin.z.powi(2) - 1.0;
-
         let disc = b.powi(2) - 4.0 * a * c;
         if disc < 0.0 {
             return None;
@@ -86,31 +190,51 @@ impl Object for Cylinder {
 
         let mut hits = vec![];
         if close > t_min && close < t_max {
-            let y0 = obj_ray.origin.y + close * obj_ray.direction.y;
-            if y0 < self.max && y0 > self.min {
+            let p0 = obj_ray.at(close);
+            if p0.y < self.max && p0.y > self.min && in_sweep(&p0, self.theta_min, self.theta_max) {
                 hits.push(close);
             }
         }
         if far > t_min && far < t_max {
-            let y1 = obj_ray.origin.y + far * obj_ray.direction.y;
-            if y1 < self.max && y1 > self.min {
+            let p1 = obj_ray.at(far);
+            if p1.y < self.max && p1.y > self.min && in_sweep(&p1, self.theta_min, self.theta_max) {
                 hits.push(far);
             }
         }
 
         hits.extend(self.hit_caps(obj_ray, t_min, t_max));
+        hits.extend(self.hit_cuts(obj_ray, t_min, t_max));
         if hits.is_empty() { None } else { Some(hits) }
     }
 
+    fn bounds_obj(&self) -> Bounds {
+        let r = self.radius_bottom.max(self.radius_top);
+        Bounds::new(Point3::new(-r, self.min, -r), Point3::new(r, self.max, r))
+    }
+
+    // Caps are mapped flat, with the square just around them.
+    fn uv_at(&self, obj_point: &Point3) -> (f64, f64) {
+        let normal = self.normal_obj(obj_point);
+        if normal.y.abs() == 1.0 {
+            let radius = if normal.y > 0.0 { self.radius_top } else { self.radius_bottom };
+            cap_uv(obj_point, radius)
+        } else {
+            Mapping::Cylindrical.uv(obj_point)
+        }
+    }
+
     fn normal_obj(&self, point: &Point3) -> Vec3 {
         let dist = point.x.powi(2) + point.z.powi(2);
 
-        if dist < 1.0 && point.y >= self.max - 1e-8 {
+        if dist < self.radius_top.powi(2) && point.y >= self.max - 1e-8 {
             Vec3::new(0.0, 1.0, 0.0)
-        } else if dist < 1.0 && point.y <= self.min + 1e-8 {
+        } else if dist < self.radius_bottom.powi(2) && point.y <= self.min + 1e-8 {
             Vec3::new(0.0, -1.0, 0.0)
+        } else if let Some(normal) = self.cut_normal(point) {
+            normal
         } else {
-            Vec3::new(point.x, 0.0, point.z)
+            // Tilted up where the wall narrows going up.
+            Vec3::new(point.x, -self.radius_at(point.y) * self.slope(), point.z)
         }
     }
 
@@ -118,13 +242,26 @@ impl Object for Cylinder {
         &self.material
     }
 
-    fn id(&self) -> usize {
+    fn id(&self) -> ObjectId {
         self.id
     }
 
-    fn set_id(&mut self, id: usize) {
+    fn set_id(&mut self, id: ObjectId) {
         self.id = id;
     }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+// x and z from -radius to radius onto the unit square.
+pub(crate) fn cap_uv(point: &Point3, radius: f64) -> (f64, f64) {
+    ((point.x / radius + 1.0) / 2.0, (point.z / radius + 1.0) / 2.0)
 }
 
 impl Transformable for Cylinder{
@@ -149,6 +286,7 @@ impl Transformable for Cylinder{
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::math::*;
 
     #[test]
     fn test_cylinder_miss() {
@@ -185,7 +323,7 @@ mod tests {
 
     #[test]
     fn test_cylinder_truncation() {
-        let cyl = Cylinder::new(Material::default(), 1.0, 2.0, false);
+        let cyl = Cylinder::new(Material::default(), 1.0, 1.0, 2.0, false);
 
         let ray = Ray::new(Point3::new(0.0, 1.5, 0.0), Vec3::new(0.1, 1.0, 0.0));
         println!("{:?}", cyl.hit(&ray, -f64::INFINITY, f64::INFINITY));
@@ -200,7 +338,7 @@ mod tests {
 
     #[test]
     fn test_cylinder_hit_cap() {
-        let cyl = Cylinder::new(Material::default(), 1.0, 2.0, true);
+        let cyl = Cylinder::new(Material::default(), 1.0, 1.0, 2.0, true);
 
         let ray = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
         assert_eq!(cyl.hit(&ray, -f64::INFINITY, f64::INFINITY).as_ref().unwrap().len(), 2);
@@ -217,7 +355,7 @@ mod tests {
 
     #[test]
     fn test_cylinder_obj_normal() {
-        let cyl = Cylinder::new(Material::default(), 1.0, 2.0, true);
+        let cyl = Cylinder::new(Material::default(), 1.0, 1.0, 2.0, true);
 
         let point = Point3::new(0.0, 1.0, 0.0);
         assert_eq!(cyl.normal_at(&point), Vec3::new(0.0, -1.0, 0.0));
@@ -228,4 +366,76 @@ mod tests {
         let point = Point3::new(0.0, 2.0, 0.0);
         assert_eq!(cyl.normal_at(&point), Vec3::new(0.0, 1.0, 0.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cylinder_sweep() {
+        let mut cyl = Cylinder::new(Material::default(), 1.0, -1.0, 1.0, true);
+        cyl.set_sweep(0.0, 90.0);
+
+        // Enters through the open side, so crosses the 90 degree cut face then the far wall.
+        let ray = Ray::new(Point3::new(-2.0, 0.0, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        let mut hits = cyl.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+        assert_eq!(hits.len(), 2);
+        assert!(fuzzy_eq_f64(hits[0].t, 2.0));
+        assert!(fuzzy_eq_f64(hits[1].t, 2.0 + 0.75_f64.sqrt()));
+
+        let cut_normal = hits[0].normal;
+        assert!(fuzzy_eq_vec(&cut_normal, &Vec3::new(-1.0, 0.0, 0.0)));
+        assert_eq!(cut_normal.y, 0.0);
+        assert!(fuzzy_eq_f64(cut_normal.dot(&Vec3::new(0.0, 0.0, 1.0)), 0.0));
+        assert!(fuzzy_eq_vec(&cyl.normal_at(&hits[1].point), &Vec3::new(0.75_f64.sqrt(), 0.0, 0.5)));
+
+        // Open cylinders have no cut faces.
+        cyl.capped = false;
+        assert_eq!(cyl.hit(&ray, 0.0, f64::INFINITY).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_cylinder_radius() {
+        let cyl = Cylinder::new(Material::default(), 2.0, -1.0, 1.0, true);
+
+        // Parallel to the axis, through the caps inside the radius and past it outside.
+        let ray = Ray::new(Point3::new(1.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut hits = cyl.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        assert_eq!(hits.len(), 2);
+        assert!(fuzzy_eq_f64(hits[0].t, 4.0));
+        assert!(fuzzy_eq_f64(hits[1].t, 6.0));
+        let ray = Ray::new(Point3::new(2.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cyl.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut hits = cyl.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        assert!(fuzzy_eq_f64(hits[0].t, 3.0));
+        assert!(fuzzy_eq_vec(&hits[0].normal, &Vec3::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_cylinder_frustum() {
+        let mut cyl = Cylinder::new(Material::default(), 1.0, 0.0, 2.0, true);
+        cyl.set_radii(2.0, 1.0);
+
+        // The caps have their own radii.
+        let down = |x: f64| Ray::new(Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut hits = cyl.hit(&down(0.5), 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        assert!(fuzzy_eq_f64(hits[0].t, 3.0) && fuzzy_eq_f64(hits[1].t, 5.0));
+        // Past the top cap, so in through the sloped wall and out through the bottom.
+        let mut hits = cyl.hit(&down(1.5), 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        assert!(fuzzy_eq_f64(hits[0].t, 4.0));
+        assert!(fuzzy_eq_f64(hits[1].t, 5.0));
+        assert!(fuzzy_eq_vec(&hits[0].normal, &Vec3::new(2.0, 1.0, 0.0).normalize()));
+        assert!(cyl.hit(&down(2.5), 0.0, f64::INFINITY).is_none());
+
+        // Halfway up the radius is 1.5, and the wall leans in by 1 over a height of 2.
+        let ray = Ray::new(Point3::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut hits = cyl.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        assert!(fuzzy_eq_f64(hits[0].t, 3.5));
+        assert!(fuzzy_eq_vec(&cyl.normal_at(&Point3::new(1.5, 1.0, 0.0)), &Vec3::new(2.0, 1.0, 0.0).normalize()));
+        assert_eq!(cyl.bounds(), Bounds::new(Point3::new(-2.0, 0.0, -2.0), Point3::new(2.0, 2.0, 2.0)));
+    }
+}
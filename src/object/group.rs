@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::intersection::Intersection;
+use crate::object::{Bounds, ObjectId, ObjectFlags, nearest_normal};
+use crate::transform::Transformable;
+
+// Objects moved together as one. Like a csg the children keep their own
+// materials and transforms, with the group's transform applied on top, but
+// each stays its own solid, so hits keep the child's id.
+#[derive(Debug)]
+pub struct Group {
+    pub id:        ObjectId,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    children:      Vec<Box<dyn Object>>,
+    // Children's transforms relative to the group, as (transform, inverse).
+    locals:        Vec<(Matrix4, Matrix4)>,
+}
+
+impl Group {
+    pub fn new(material: impl Into<Arc<Material>>, children: Vec<Box<dyn Object>>) -> Self {
+        let locals = children.iter().map(|child| (*child.transform(), *child.inverse())).collect();
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            children,
+            locals,
+        }
+    }
+
+    pub fn children(&self) -> &[Box<dyn Object>] {
+        &self.children
+    }
+}
+
+impl Object for Group {
+
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<Intersection>> {
+        let mut hits: Vec<_> = self.children.iter()
+            .filter_map(|child| child.hit(ray, t_min, t_max))
+            .flatten()
+            .collect();
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    fn hit_any(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.children.iter().any(|child| child.hit_any(ray, t_min, t_max))
+    }
+
+    // Only used outside of hit, which works with the children directly.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let world_ray = obj_ray.transform(&self.transform);
+        self.hit(&world_ray, t_min, t_max).map(|hits| hits.into_iter().map(|hit| hit.t).collect())
+    }
+
+    // Children are already in world space.
+    fn bounds(&self) -> Bounds {
+        self.children.iter().fold(Bounds::empty(), |bounds, child| bounds.union(&child.bounds()))
+    }
+
+    // Hits take their normals from whichever child was hit, see hit. Anywhere
+    // else it's the child whose surface is nearest the point.
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let world_point = self.transform.transform_point(point);
+        // Points nowhere near a child get an arbitrary normal.
+        let normal = nearest_normal(self.children.iter().map(|child| &**child), &world_point).unwrap_or_else(Vec3::y);
+        (self.transform.transpose() * normal.to_homogeneous()).xyz().normalize()
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    // Hits keep the children's IDs, so those are the group's too.
+    fn owns(&self, id: ObjectId) -> bool {
+        id == self.id || self.children.iter().any(|child| child.owns(id))
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+
+    fn set_epsilon(&mut self, epsilon: f64) {
+        self.flags.epsilon = Some(epsilon);
+        for child in &mut self.children {
+            child.set_epsilon(epsilon);
+        }
+    }
+}
+
+impl Transformable for Group {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+        for (child, local) in self.children.iter_mut().zip(&self.locals) {
+            child.set_transform(transform * local.0);
+        }
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+        for (child, local) in self.children.iter_mut().zip(&self.locals) {
+            child.set_inverse(local.1 * inverse);
+        }
+    }
+}
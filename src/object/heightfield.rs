@@ -0,0 +1,292 @@
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Result, Context, bail};
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::object::mesh::hit_triangle;
+use crate::transform::Transformable;
+
+// Terrain over the unit square in x and z, from a grid of heights. Each cell
+// is split into two triangles along its (0, 0) to (1, 1) diagonal.
+#[derive(Debug)]
+pub struct Heightfield {
+    pub id:        ObjectId,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    // heights[row][col], rows along z and columns along x.
+    heights:       Vec<Vec<f64>>,
+    // Per vertex normals, from the slopes either side.
+    normals:       Vec<Vec<Vec3>>,
+    min_height:    f64,
+    max_height:    f64,
+}
+
+impl Heightfield {
+    pub fn new(material: impl Into<Arc<Material>>, heights: Vec<Vec<f64>>) -> Self {
+        let (rows, cols) = (heights.len(), heights.first().map_or(0, |r| r.len()));
+        assert!(rows >= 2 && cols >= 2, "heightfield needs at least 2x2 heights");
+        assert!(heights.iter().all(|r| r.len() == cols), "heightfield rows must be the same length");
+
+        let (dx, dz) = (1.0 / (cols - 1) as f64, 1.0 / (rows - 1) as f64);
+        let normals = (0..rows)
+            .map(|r| (0..cols).map(|c| {
+                let (c0, c1) = (c.saturating_sub(1), (c + 1).min(cols - 1));
+                let (r0, r1) = (r.saturating_sub(1), (r + 1).min(rows - 1));
+                let slope_x = (heights[r][c1] - heights[r][c0]) / ((c1 - c0) as f64 * dx);
+                let slope_z = (heights[r1][c] - heights[r0][c]) / ((r1 - r0) as f64 * dz);
+                Vec3::new(-slope_x, 1.0, -slope_z).normalize()
+            }).collect())
+            .collect();
+        let min_height = heights.iter().flatten().cloned().fold(f64::INFINITY, f64::min);
+        let max_height = heights.iter().flatten().cloned().fold(-f64::INFINITY, f64::max);
+
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            heights,
+            normals,
+            min_height,
+            max_height,
+        }
+    }
+
+    // Heights from an image's brightness, 0 to vertical_scale. The top row of
+    // the image is at z = 0.
+    pub fn load<P: AsRef<Path>>(material: impl Into<Arc<Material>>, path: P, vertical_scale: f64) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path).with_context(|| format!("Could not read heightmap {}", path.display()))?.to_luma16();
+        if image.width() < 2 || image.height() < 2 {
+            bail!("Heightmap {} must be at least 2x2 pixels", path.display());
+        }
+        let heights = image.rows()
+            .map(|row| row.map(|p| p.0[0] as f64 / u16::MAX as f64 * vertical_scale).collect())
+            .collect();
+        Ok(Self::new(material, heights))
+    }
+
+    fn cells(&self) -> (usize, usize) {
+        (self.heights[0].len() - 1, self.heights.len() - 1)
+    }
+
+    fn vertex(&self, col: usize, row: usize) -> Point3 {
+        let (cols, rows) = self.cells();
+        Point3::new(col as f64 / cols as f64, self.heights[row][col], row as f64 / rows as f64)
+    }
+
+    // Hits on the two triangles of a cell.
+    fn hit_cell(&self, ray: &Ray, col: usize, row: usize, hits: &mut Vec<f64>) {
+        let p00 = self.vertex(col, row);
+        let p10 = self.vertex(col + 1, row);
+        let p11 = self.vertex(col + 1, row + 1);
+        let p01 = self.vertex(col, row + 1);
+        for (e1, e2) in [(p10 - p00, p11 - p00), (p11 - p00, p01 - p00)] {
+            if let Some((t, _, _)) = hit_triangle(ray, &p00, &e1, &e2) {
+                hits.push(t);
+            }
+        }
+    }
+
+    // Where the ray is inside the heightfield's bounding box.
+    fn clip(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let bounds = [(0.0, 1.0), (self.min_height, self.max_height), (0.0, 1.0)];
+        let (mut close, mut far) = (-f64::INFINITY, f64::INFINITY);
+        for (axis, (min, max)) in bounds.into_iter().enumerate() {
+            let (o, d) = (ray.origin[axis], ray.direction[axis]);
+            if d.abs() < 1e-12 {
+                if o < min || o > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - o) / d, (max - o) / d);
+            close = close.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        if close > far { None } else { Some((close, far)) }
+    }
+}
+
+impl Object for Heightfield {
+
+    // Walks the cells the ray passes over in order, only testing their triangles.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let (close, far) = self.clip(obj_ray)?;
+        let (cols, rows) = self.cells();
+        let (size_x, size_z) = (1.0 / cols as f64, 1.0 / rows as f64);
+
+        let start = obj_ray.at(close);
+        let mut col = ((start.x / size_x).floor().max(0.0) as usize).min(cols - 1);
+        let mut row = ((start.z / size_z).floor().max(0.0) as usize).min(rows - 1);
+
+        // Distance along the ray to the next cell boundary, and between boundaries, per axis.
+        let axis = |o: f64, d: f64, cell: usize, size: f64| -> (f64, f64) {
+            if d > 0.0 {
+                (((cell + 1) as f64 * size - o) / d, size / d)
+            } else if d < 0.0 {
+                ((cell as f64 * size - o) / d, -size / d)
+            } else {
+                (f64::INFINITY, f64::INFINITY)
+            }
+        };
+        let (mut next_x, step_x) = axis(obj_ray.origin.x, obj_ray.direction.x, col, size_x);
+        let (mut next_z, step_z) = axis(obj_ray.origin.z, obj_ray.direction.z, row, size_z);
+
+        let mut hits = vec![];
+        loop {
+            self.hit_cell(obj_ray, col, row, &mut hits);
+            if next_x.min(next_z) > far {
+                break;
+            }
+            if next_x < next_z {
+                if obj_ray.direction.x > 0.0 { col += 1 } else { col = col.wrapping_sub(1) }
+                next_x += step_x;
+            } else {
+                if obj_ray.direction.z > 0.0 { row += 1 } else { row = row.wrapping_sub(1) }
+                next_z += step_z;
+            }
+            if col >= cols || row >= rows {
+                break;
+            }
+        }
+
+        // Rays through shared edges hit both triangles beside them.
+        hits.retain(|&t| t > t_min && t < t_max);
+        hits.sort_by(|a, b| a.total_cmp(b));
+        hits.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::new(Point3::new(0.0, self.min_height, 0.0), Point3::new(1.0, self.max_height, 1.0))
+    }
+
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let (cols, rows) = self.cells();
+        let x = (point.x * cols as f64).clamp(0.0, cols as f64);
+        let z = (point.z * rows as f64).clamp(0.0, rows as f64);
+        let (col, row) = ((x as usize).min(cols - 1), (z as usize).min(rows - 1));
+        let (fx, fz) = (x - col as f64, z - row as f64);
+
+        let n = |c: usize, r: usize| self.normals[row + r][col + c];
+        let normal = if fx >= fz {
+            n(0, 0) * (1.0 - fx) + n(1, 0) * (fx - fz) + n(1, 1) * fz
+        } else {
+            n(0, 0) * (1.0 - fz) + n(0, 1) * (fz - fx) + n(1, 1) * fx
+        };
+        normal.normalize()
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for Heightfield {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn test_heightfield_slope() {
+        // Rises from 0 at x = 0 to 1 at x = 1.
+        let field = Heightfield::new(Material::default(), vec![vec![0.0, 0.5, 1.0]; 4]);
+        let ray = Ray::new(Point3::new(0.3, 5.0, 0.6), Vec3::new(0.0, -1.0, 0.0));
+        let hits = field.hit_obj(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(fuzzy_eq_f64(hits[0], 4.7));
+
+        let expected = Vec3::new(-1.0, 1.0, 0.0).normalize();
+        assert!(fuzzy_eq_vec(&field.normal_obj(&ray.at(hits[0])), &expected));
+
+        // Beside the square.
+        let ray = Ray::new(Point3::new(1.5, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!(field.hit_obj(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_heightfield_normal_interpolation() {
+        // A single raised vertex in the middle.
+        let mut heights = vec![vec![0.0; 3]; 3];
+        heights[1][1] = 0.5;
+        let field = Heightfield::new(Material::default(), heights);
+
+        // At a vertex the normal is the vertex's, and varies smoothly between.
+        assert!(fuzzy_eq_vec(&field.normal_obj(&Point3::new(0.5, 0.5, 0.5)), &Vec3::new(0.0, 1.0, 0.0)));
+        let a = field.normal_obj(&Point3::new(0.3, 0.0, 0.2));
+        let b = field.normal_obj(&Point3::new(0.31, 0.0, 0.2));
+        assert!(a.dot(&b) > 0.999);
+        // Tilted away from the peak, towards -z.
+        assert!(a.z < 0.0);
+    }
+
+    #[test]
+    fn test_heightfield_walk_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let heights: Vec<Vec<f64>> = (0..9).map(|_| (0..7).map(|_| rng.gen_range(0.0..0.4)).collect()).collect();
+        let field = Heightfield::new(Material::default(), heights);
+        let (cols, rows) = field.cells();
+
+        let mut total = 0;
+        for _ in 0..500 {
+            let origin = Point3::new(rng.gen_range(-1.0..2.0), rng.gen_range(-0.5..1.0), rng.gen_range(-1.0..2.0));
+            let target = Point3::new(rng.gen_range(0.0..1.0), rng.gen_range(0.0..0.4), rng.gen_range(0.0..1.0));
+            let ray = Ray::new(origin, (target - origin).normalize());
+
+            let mut brute = vec![];
+            for row in 0..rows {
+                for col in 0..cols {
+                    field.hit_cell(&ray, col, row, &mut brute);
+                }
+            }
+            brute.retain(|&t| t > 0.0);
+            brute.sort_by(|a, b| a.total_cmp(b));
+            brute.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+
+            let walked = field.hit_obj(&ray, 0.0, f64::INFINITY).unwrap_or_default();
+            assert_eq!(walked, brute);
+            total += walked.len();
+        }
+        assert!(total > 100);
+    }
+}
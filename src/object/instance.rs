@@ -0,0 +1,302 @@
+use std::sync::Arc;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::intersection::Intersection;
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::math::reflect;
+use crate::transform::Transformable;
+
+// A placement of a shared prototype object, so heavy objects like meshes are
+// only stored once however many times they appear. The instance's transform
+// is applied on top of the prototype's own.
+#[derive(Debug)]
+pub struct Instance {
+    pub id:        ObjectId,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub flags:     ObjectFlags,
+    prototype:     Arc<dyn Object>,
+    // Replaces the prototype's material on both sides, if set.
+    material:      Option<Arc<Material>>,
+    // The prototype's materials as shadow catchers, paired with the originals,
+    // for instances that catch shadows in the prototype's own materials.
+    catchers:      Option<Vec<(Arc<Material>, Arc<Material>)>>,
+}
+
+impl Instance {
+    pub fn new(prototype: Arc<dyn Object>) -> Self {
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            flags: ObjectFlags::default(),
+            prototype,
+            material: None,
+            catchers: None,
+        }
+    }
+
+    pub fn set_material(&mut self, material: impl Into<Arc<Material>>) {
+        self.material = Some(material.into());
+    }
+
+    // Makes the instance a shadow catcher without replacing its material. Use
+    // set_material with a catcher material to do both.
+    pub fn set_shadow_catcher(&mut self) {
+        let mut catchers: Vec<(Arc<Material>, Arc<Material>)> = vec![];
+        for material in [self.prototype.material_for(true), self.prototype.material_for(false)] {
+            if !catchers.iter().any(|(original, _)| Arc::ptr_eq(original, material)) {
+                catchers.push((Arc::clone(material), as_catcher(material)));
+            }
+        }
+        self.catchers = Some(catchers);
+    }
+
+    pub fn prototype(&self) -> &Arc<dyn Object> {
+        &self.prototype
+    }
+}
+
+// Children of prototypes made of several objects can have materials of
+// their own, which aren't paired up ahead of time.
+fn as_catcher(material: &Arc<Material>) -> Arc<Material> {
+    if material.shadow_catcher {
+        return Arc::clone(material);
+    }
+    Arc::new(Material { shadow_catcher: true, ..(**material).clone() })
+}
+
+impl Object for Instance {
+
+    // Hits the prototype in its own space, then moves the results into the instance's.
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<Intersection>> {
+        let local_ray = ray.transform(&self.inverse);
+        let hits = self.prototype.hit(&local_ray, t_min, t_max)?;
+
+        let normal_matrix = self.inverse.transpose();
+        let to_world = |normal: &Vec3| (normal_matrix * normal.to_homogeneous()).xyz().normalize();
+        let proto_inverse = self.prototype.inverse();
+        let hits = hits.into_iter()
+            .map(|hit| {
+                let surface_normal = to_world(&hit.surface_normal);
+                let (material, colour, normal, uv) = match &self.material {
+                    Some(material) => {
+                        // Bumped by the override instead of the prototype's material.
+                        let obj_point = proto_inverse.transform_point(&hit.point);
+                        let normal = material.perturb_normal(&obj_point, &surface_normal, &-ray.direction);
+                        let uv = || hit.uv.unwrap_or_else(|| self.prototype.uv_at(&obj_point));
+                        let (colour, uv) = material.surface_colour(&hit.over_point, proto_inverse, uv);
+                        (Arc::clone(material), colour, normal, uv)
+                    },
+                    None => {
+                        let material = match &self.catchers {
+                            Some(catchers) => catchers.iter()
+                                .find(|(original, _)| Arc::ptr_eq(original, &hit.material))
+                                .map_or_else(|| as_catcher(&hit.material), |(_, catcher)| Arc::clone(catcher)),
+                            None => hit.material,
+                        };
+                        (material, hit.colour, to_world(&hit.normal), hit.uv)
+                    },
+                };
+                let point = self.transform.transform_point(&hit.point);
+                let (over_point, under_point) = match self.flags.epsilon {
+                    Some(epsilon) => (point + normal * epsilon, point - normal * epsilon),
+                    None => (self.transform.transform_point(&hit.over_point), self.transform.transform_point(&hit.under_point)),
+                };
+                Intersection {
+                    obj_id: self.id,
+                    point,
+                    normal,
+                    surface_normal,
+                    material,
+                    eye: -ray.direction,
+                    reflect: reflect(&ray.direction, &normal),
+                    colour,
+                    over_point,
+                    under_point,
+                    uv,
+                    ..hit
+                }
+            })
+            .collect();
+        Some(hits)
+    }
+
+    fn hit_any(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.prototype.hit_any(&ray.transform(&self.inverse), t_min, t_max)
+    }
+
+    // Takes rays in the instance's object space, which is the prototype's world space.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        self.prototype.hit_obj(&obj_ray.transform(self.prototype.inverse()), t_min, t_max)
+    }
+
+    // The prototype's world space is the instance's object space.
+    fn bounds(&self) -> Bounds {
+        self.prototype.bounds().transform(&self.transform)
+    }
+
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        self.prototype.normal_at(point)
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        self.material.as_ref().unwrap_or_else(|| self.prototype.material())
+    }
+
+    fn material_for(&self, front_face: bool) -> &Arc<Material> {
+        self.material.as_ref().unwrap_or_else(|| self.prototype.material_for(front_face))
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    // Without an epsilon set, hit points are offset by the prototype's epsilon, scaled by the instance's transform.
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for Instance {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::{Colour, Scene};
+    use crate::object::{Sphere, Mesh, Plane};
+    use crate::bump::Waves;
+
+    #[test]
+    fn test_instance_matches_transformed_object() {
+        let mut prototype = Sphere::new(Material::default());
+        prototype.scale(1.0, 2.0, 1.0);
+        let prototype: Arc<dyn Object> = Arc::new(prototype);
+
+        let mut instance = Instance::new(Arc::clone(&prototype));
+        instance.translate(3.0, 0.0, 0.0);
+        instance.rotate(crate::Axis::Z, 30.0);
+        let mut expected = Sphere::new(Material::default());
+        expected.translate(3.0, 0.0, 0.0);
+        expected.rotate(crate::Axis::Z, 30.0);
+        expected.scale(1.0, 2.0, 1.0);
+
+        let ray = Ray::new(Point3::new(-2.0, 1.0, -5.0), Vec3::new(5.0, -0.5, 5.0).normalize());
+        let hits = instance.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let expected_hits = expected.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hits.len(), expected_hits.len());
+        for (hit, expected) in hits.iter().zip(&expected_hits) {
+            assert!(fuzzy_eq_f64(hit.t, expected.t));
+            assert!(fuzzy_eq_vec(&hit.normal, &expected.normal));
+            assert!(fuzzy_eq_vec(&hit.point.coords, &expected.point.coords));
+            assert!(fuzzy_eq_vec(&hit.reflect, &expected.reflect));
+            assert_eq!(hit.front_face, expected.front_face);
+        }
+
+        // Same answer through hit_obj and normal_obj.
+        let obj_ray = ray.transform(instance.inverse());
+        let ts = instance.hit_obj(&obj_ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(ts[0], expected_hits[0].t));
+        assert!(fuzzy_eq_vec(&instance.normal_at(&expected_hits[0].point), &expected_hits[0].normal));
+    }
+
+    #[test]
+    fn test_instance_shares_prototype() {
+        let triangle = Mesh::from_obj(Material::default(), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let prototype: Arc<dyn Object> = Arc::new(triangle);
+
+        let mut scene = Scene::default();
+        for i in 0..50 {
+            let mut instance = Instance::new(Arc::clone(&prototype));
+            instance.translate(i as f64 * 2.0, 0.0, 0.0);
+            if i == 1 {
+                instance.set_material(Material::matte(Colour::new(1.0, 0.0, 0.0)));
+            }
+            scene.push(Box::new(instance));
+        }
+        assert_eq!(Arc::strong_count(&prototype), 51);
+
+        // Each instance has its own id, and the override only applies to its instance.
+        let ray = |x: f64| Ray::new(Point3::new(x, 0.25, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = scene.trace(&ray(2.25)).unwrap();
+        assert_eq!(hit.obj_id, scene.objects[1].id());
+        assert_eq!(hit.colour, Colour::new(1.0, 0.0, 0.0));
+        let hit = scene.trace(&ray(40.25)).unwrap();
+        assert_eq!(hit.obj_id, scene.objects[20].id());
+        assert_eq!(hit.colour, Colour::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_instance_material_uv() {
+        // The prototype's material doesn't use uvs, the override's does.
+        let mut instance = Instance::new(Arc::new(Sphere::new(Material::default())));
+        instance.set_material(Material { uv_pattern: true, ..Default::default() });
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = &instance.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        assert_eq!(hit.uv, Some(Sphere::new(Material::default()).uv_at(&Point3::new(0.0, 0.0, -1.0))));
+    }
+
+    #[test]
+    fn test_instance_material_bump() {
+        let bumped = Material { normal_perturbation: Some(Arc::new(Waves::new(0.3, 2.0))), ..Default::default() };
+        let mut instance = Instance::new(Arc::new(Plane::new(Material::default())));
+        instance.set_material(bumped.clone());
+        instance.rotate(crate::Axis::Z, 20.0);
+        instance.translate(0.0, 1.0, 0.0);
+        let mut expected = Plane::new(bumped);
+        expected.rotate(crate::Axis::Z, 20.0);
+        expected.translate(0.0, 1.0, 0.0);
+
+        // The override's bump applies to a plain prototype.
+        for x in [0.0, 0.4, 1.3] {
+            let ray = Ray::new(Point3::new(x, 5.0, 0.5), Vec3::new(0.1, -1.0, 0.0).normalize());
+            let hit = &instance.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+            let want = &expected.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+            assert!(fuzzy_eq_vec(&hit.normal, &want.normal));
+            assert!(fuzzy_eq_vec(&hit.reflect, &want.reflect));
+        }
+
+        // And a plain override drops the prototype's bump.
+        let mut instance = Instance::new(Arc::new(expected));
+        instance.set_material(Material::default());
+        let ray = Ray::new(Point3::new(0.4, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        let hit = &instance.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        let flat = (Matrix4::new_rotation(Vec3::z() * 20f64.to_radians()) * Vec3::y().to_homogeneous()).xyz();
+        assert!(fuzzy_eq_vec(&hit.normal, &flat));
+
+        // The prototype's own bump isn't bumped again by the override.
+        let waves = Material { normal_perturbation: Some(Arc::new(Waves::new(0.3, 2.0))), ..Default::default() };
+        let prototype = Plane::new(waves.clone());
+        let mut instance = Instance::new(Arc::new(prototype));
+        instance.set_material(waves);
+        let ray = Ray::new(Point3::new(0.4, 5.0, 0.5), Vec3::new(0.1, -1.0, 0.0).normalize());
+        let hit = &instance.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        assert_eq!(hit.surface_normal, Vec3::y());
+        assert!(fuzzy_eq_vec(&hit.normal, &instance.material().perturb_normal(&hit.point, &Vec3::y(), &-ray.direction)));
+    }
+}
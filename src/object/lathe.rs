@@ -0,0 +1,295 @@
+use std::sync::Arc;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec2, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::transform::Transformable;
+
+// One piece of the profile, a truncated cone (or a flat ring if horizontal).
+// Points and normals are in the (radius, y) plane.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start:        Vec2,
+    end:          Vec2,
+    normal_start: Vec2,
+    normal_end:   Vec2,
+}
+
+impl Segment {
+    fn flat(start: Vec2, end: Vec2) -> Self {
+        let normal = profile_normal(start, end);
+        Self { start, end, normal_start: normal, normal_end: normal }
+    }
+
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<f64>) {
+        let (r0, y0) = (self.start.x, self.start.y);
+        let (r1, y1) = (self.end.x, self.end.y);
+        let (low, high) = if y0 < y1 { (y0, y1) } else { (y1, y0) };
+
+        // Flat ring.
+        if (y1 - y0).abs() < 1e-12 {
+            if ray.direction.y.abs() < 1e-8 {
+                return;
+            }
+            let t = (y0 - ray.origin.y) / ray.direction.y;
+            let point = ray.at(t);
+            let radius = (point.x.powi(2) + point.z.powi(2)).sqrt();
+            if t > t_min && t < t_max && radius >= r0.min(r1) && radius <= r0.max(r1) {
+                hits.push(t);
+            }
+            return;
+        }
+
+        // Radius along the ray is linear in t, r = r_a + r_b * t.
+        let slope = (r1 - r0) / (y1 - y0);
+        let r_a = r0 + slope * (ray.origin.y - y0);
+        let r_b = slope * ray.direction.y;
+
+        let a = ray.direction.x.powi(2) + ray.direction.z.powi(2) - r_b.powi(2);
+        let b = 2.0 * (ray.origin.x * ray.direction.x + ray.origin.z * ray.direction.z - r_a * r_b);
+        let c = ray.origin.x.powi(2) + ray.origin.z.powi(2) - r_a.powi(2);
+
+        let roots = if a.abs() < 1e-8 {
+            if b.abs() < 1e-8 {
+                return;
+            }
+            vec![-c / b]
+        } else {
+            let disc = b.powi(2) - 4.0 * a * c;
+            if disc < 0.0 {
+                return;
+            }
+            vec![(-b - disc.sqrt()) / (2.0 * a), (-b + disc.sqrt()) / (2.0 * a)]
+        };
+
+        for t in roots {
+            let y = ray.origin.y + t * ray.direction.y;
+            // Half open so a ray through the joint of two segments only hits once.
+            if t > t_min && t < t_max && y >= low && y < high {
+                hits.push(t);
+            }
+        }
+    }
+
+    // Distance from a point in the profile plane, and how far along the segment it is.
+    fn project(&self, p: Vec2) -> (f64, f64) {
+        let edge = self.end - self.start;
+        let s = ((p - self.start).dot(&edge) / edge.norm_squared()).clamp(0.0, 1.0);
+        ((self.start + edge * s - p).norm(), s)
+    }
+}
+
+// Perpendicular to the profile edge, pointing away from the axis for profiles going up.
+// Lathe::new reorders profiles so this holds whichever way they're listed.
+fn profile_normal(start: Vec2, end: Vec2) -> Vec2 {
+    let edge = end - start;
+    Vec2::new(edge.y, -edge.x).normalize()
+}
+
+// Twice the signed area of the profile closed along the axis, positive if anticlockwise.
+fn signed_area(points: &[Vec2]) -> f64 {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let outline = points.iter().copied()
+        .chain([Vec2::new(0.0, last.y), Vec2::new(0.0, first.y)]);
+    outline.clone().zip(outline.cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum()
+}
+
+// A profile of (radius, y) points revolved around the y axis.
+#[derive(Debug)]
+pub struct Lathe {
+    pub id:        ObjectId,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    segments:      Vec<Segment>,
+}
+
+impl Lathe {
+    // Profile needs at least 2 points with non-negative radii.
+    // Closed lathes get flat caps at both ends of the profile.
+    pub fn new(material: impl Into<Arc<Material>>, profile: &[(f64, f64)], closed: bool) -> Self {
+        assert!(profile.len() >= 2, "lathe profile needs at least 2 points");
+        assert!(profile.iter().all(|&(r, _)| r >= 0.0), "lathe profile radii must be non-negative");
+
+        let mut points: Vec<Vec2> = profile.iter().map(|&(r, y)| Vec2::new(r, y)).collect();
+        // Closing the profile through the axis, a clockwise outline means it runs downwards.
+        if signed_area(&points) < 0.0 {
+            points.reverse();
+        }
+        let mut segments: Vec<Segment> = points.windows(2)
+            .filter(|w| w[0] != w[1])
+            .map(|w| Segment::flat(w[0], w[1]))
+            .collect();
+
+        // Smooth normals by averaging at the joints.
+        for i in 1..segments.len() {
+            let normal = (segments[i - 1].normal_end + segments[i].normal_start).normalize();
+            segments[i - 1].normal_end = normal;
+            segments[i].normal_start = normal;
+        }
+
+        if closed {
+            let (first, last) = (points[0], points[points.len() - 1]);
+            if first.x > 0.0 {
+                segments.push(Segment::flat(Vec2::new(0.0, first.y), first));
+            }
+            if last.x > 0.0 {
+                segments.push(Segment::flat(last, Vec2::new(0.0, last.y)));
+            }
+        }
+
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            segments,
+        }
+    }
+}
+
+impl Object for Lathe {
+
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let mut hits = vec![];
+        for segment in &self.segments {
+            segment.hit(obj_ray, t_min, t_max, &mut hits);
+        }
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    fn bounds_obj(&self) -> Bounds {
+        let points = self.segments.iter().flat_map(|s| [s.start, s.end]);
+        let (radius, min, max) = points.fold((0.0, f64::INFINITY, -f64::INFINITY), |(r, min, max): (f64, f64, f64), p| {
+            (r.max(p.x), min.min(p.y), max.max(p.y))
+        });
+        Bounds::new(Point3::new(-radius, min, -radius), Point3::new(radius, max, radius))
+    }
+
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let radius = (point.x.powi(2) + point.z.powi(2)).sqrt();
+        let p = Vec2::new(radius, point.y);
+
+        let (segment, s) = self.segments.iter()
+            .map(|segment| (segment, segment.project(p)))
+            .min_by(|a, b| a.1.0.partial_cmp(&b.1.0).unwrap())
+            .map(|(segment, (_, s))| (segment, s))
+            .unwrap();
+        let normal = segment.normal_start.lerp(&segment.normal_end, s);
+
+        let (x, z) = if radius > 1e-12 { (point.x / radius, point.z / radius) } else { (0.0, 0.0) };
+        Vec3::new(normal.x * x, normal.y, normal.x * z)
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for Lathe {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::object::{Cylinder, Cone};
+
+    fn rays() -> Vec<Ray> {
+        vec![
+            Ray::new(Point3::new(0.3, 0.6, -5.0), Vec3::new(0.0, 0.0, 1.0)),
+            Ray::new(Point3::new(-3.0, 2.0, 0.1), Vec3::new(1.0, -0.5, 0.2)),
+            Ray::new(Point3::new(0.2, 3.0, 0.1), Vec3::new(0.05, -1.0, 0.1)),
+            Ray::new(Point3::new(0.0, 0.7, 0.0), Vec3::new(0.3, 0.2, 1.0)),
+            Ray::new(Point3::new(2.0, 0.9, 2.0), Vec3::new(-1.0, -0.1, -0.9)),
+            Ray::new(Point3::new(5.0, 5.0, 5.0), Vec3::new(1.0, 0.0, 0.0)),
+        ]
+    }
+
+    fn assert_same(a: &dyn Object, b: &dyn Object) {
+        for ray in rays() {
+            let mut hits_a = a.hit(&ray, 0.0, f64::INFINITY).unwrap_or_default();
+            let mut hits_b = b.hit(&ray, 0.0, f64::INFINITY).unwrap_or_default();
+            hits_a.sort_by(|x, y| x.t.partial_cmp(&y.t).unwrap());
+            hits_b.sort_by(|x, y| x.t.partial_cmp(&y.t).unwrap());
+
+            assert_eq!(hits_a.len(), hits_b.len(), "{:?}", ray);
+            for (x, y) in hits_a.iter().zip(hits_b.iter()) {
+                assert!(fuzzy_eq_f64(x.t, y.t));
+                assert!(fuzzy_eq_vec(&x.normal, &y.normal));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lathe_cylinder() {
+        let lathe = Lathe::new(Material::default(), &[(1.0, 0.0), (1.0, 1.0)], true);
+        let cylinder = Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true);
+        assert_same(&lathe, &cylinder);
+    }
+
+    #[test]
+    fn test_lathe_cone() {
+        let lathe = Lathe::new(Material::default(), &[(0.5, 0.5), (1.0, 1.0)], false);
+        let cone = Cone::new(Material::default(), 45.0, 0.5, 1.0, false);
+        assert_same(&lathe, &cone);
+    }
+
+    #[test]
+    fn test_lathe_downward_profile() {
+        // Listed top to bottom, the normals still point away from the axis.
+        let lathe = Lathe::new(Material::default(), &[(1.0, 1.0), (1.0, 0.0)], true);
+        let side = lathe.normal_at(&Point3::new(1.0, 0.5, 0.0));
+        let top = lathe.normal_at(&Point3::new(0.5, 1.0, 0.0));
+        let bottom = lathe.normal_at(&Point3::new(0.5, 0.0, 0.0));
+        assert!(fuzzy_eq_vec(&side, &Vec3::new(1.0, 0.0, 0.0)));
+        assert!(fuzzy_eq_vec(&top, &Vec3::new(0.0, 1.0, 0.0)));
+        assert!(fuzzy_eq_vec(&bottom, &Vec3::new(0.0, -1.0, 0.0)));
+
+        let cylinder = Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true);
+        assert_same(&lathe, &cylinder);
+    }
+
+    #[test]
+    fn test_lathe_smooth_normal() {
+        // Bends outwards at y = 1, the joint normal is between the two segments.
+        let lathe = Lathe::new(Material::default(), &[(1.0, 0.0), (1.0, 1.0), (2.0, 2.0)], false);
+        let normal = lathe.normal_at(&Point3::new(1.0, 1.0, 0.0));
+        let expected = Vec3::new(1.0, 0.0, 0.0) + Vec3::new(1.0, -1.0, 0.0).normalize();
+        assert!(fuzzy_eq_vec(&normal, &expected.normalize()));
+    }
+}
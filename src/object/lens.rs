@@ -0,0 +1,202 @@
+use std::sync::Arc;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::transform::Transformable;
+
+// A biconvex lens along the y axis, the solid inside two spheres. The top
+// surface has radius of curvature r1 and the bottom r2, and the lens is
+// thickness thick on the axis, centred on the origin.
+#[derive(Debug)]
+pub struct Lens {
+    pub id:        ObjectId,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    r1:            f64,
+    r2:            f64,
+    thickness:     f64,
+}
+
+impl Lens {
+    pub fn new(material: impl Into<Arc<Material>>, r1: f64, r2: f64, thickness: f64) -> Self {
+        assert!(r1 > 0.0 && r2 > 0.0, "lens radii must be positive");
+        assert!(thickness > 0.0 && thickness < r1 + r2, "lens thickness must be positive and less than r1 + r2");
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            r1,
+            r2,
+            thickness,
+        }
+    }
+
+    // Centre and radius of each surface's sphere, top then bottom.
+    fn spheres(&self) -> [(Point3, f64); 2] {
+        let half = self.thickness / 2.0;
+        [
+            (Point3::new(0.0, half - self.r1, 0.0), self.r1),
+            (Point3::new(0.0, self.r2 - half, 0.0), self.r2),
+        ]
+    }
+}
+
+// Where the ray is inside a sphere, if it passes through it.
+fn sphere_span(ray: &Ray, centre: &Point3, radius: f64) -> Option<(f64, f64)> {
+    let oc = ray.origin - centre;
+    let a = ray.direction.magnitude_squared();
+    let half_b = oc.dot(&ray.direction);
+    let c = oc.dot(&oc) - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    Some(((-half_b - root) / a, (-half_b + root) / a))
+}
+
+impl Object for Lens {
+
+    // The lens is where the ray is inside both spheres, so it enters at the
+    // later of the two entries and leaves at the earlier of the two exits.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let [(c1, r1), (c2, r2)] = self.spheres();
+        let (enter1, exit1) = sphere_span(obj_ray, &c1, r1)?;
+        let (enter2, exit2) = sphere_span(obj_ray, &c2, r2)?;
+        let (enter, exit) = (enter1.max(enter2), exit1.min(exit2));
+        if enter >= exit {
+            return None;
+        }
+
+        let hits: Vec<f64> = [enter, exit].into_iter().filter(|&t| t > t_min && t < t_max).collect();
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    // Both spheres contain the lens, so it is no wider than the smaller one.
+    fn bounds_obj(&self) -> Bounds {
+        let (half, radius) = (self.thickness / 2.0, self.r1.min(self.r2));
+        Bounds::new(Point3::new(-radius, -half, -radius), Point3::new(radius, half, radius))
+    }
+
+    // From the centre of whichever sphere's surface the point is on.
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let [(c1, r1), (c2, r2)] = self.spheres();
+        let (d1, d2) = (point - c1, point - c2);
+        if (d1.norm() - r1).abs() <= (d2.norm() - r2).abs() {
+            d1.normalize()
+        } else {
+            d2.normalize()
+        }
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for Lens {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::intersection::compute_intersections;
+
+    // Bends the ray through a hit, as in Scene::refracted_colour_at.
+    fn refract(ray: &Ray, hit: &crate::intersection::Intersection) -> Ray {
+        let idx_ratio = hit.exit_idx / hit.enter_idx;
+        let cos_i = hit.eye.dot(&hit.normal);
+        let cos_t = (1.0 - idx_ratio.powi(2) * (1.0 - cos_i.powi(2))).sqrt();
+        let direction = hit.normal * (idx_ratio * cos_i - cos_t) - hit.eye * idx_ratio;
+        assert!(ray.direction.dot(&direction) > 0.0);
+        Ray::new(hit.under_point, direction.normalize())
+    }
+
+    #[test]
+    fn test_lens_focus() {
+        let (r, thickness) = (2.0, 0.3);
+        let lens = Lens::new(Material::glass(), r, r, thickness);
+        let n = lens.material.refractive_index;
+
+        // Straight down the axis, through the top then the bottom.
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut hits = lens.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        compute_intersections(&mut hits);
+        assert!(fuzzy_eq_f64(hits[0].t, 5.0 - thickness / 2.0));
+        assert!(fuzzy_eq_f64(hits[1].t, 5.0 + thickness / 2.0));
+        assert_eq!((hits[0].exit_idx, hits[0].enter_idx), (1.0, n));
+        assert_eq!((hits[1].exit_idx, hits[1].enter_idx), (n, 1.0));
+        assert!(fuzzy_eq_vec(&refract(&ray, &hits[0]).direction, &ray.direction));
+
+        // A ray beside the axis is bent back across it at the back focal length.
+        let ray = Ray::new(Point3::new(0.05, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut hits = lens.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        compute_intersections(&mut hits);
+        let inside = refract(&ray, &hits[0]);
+        let mut exit = lens.hit(&inside, 0.0, f64::INFINITY).unwrap();
+        compute_intersections(&mut exit);
+        // Alone, the exit hit is counted as entering, so swap the indices back.
+        exit[0].exit_idx = n;
+        exit[0].enter_idx = 1.0;
+        let out = refract(&inside, &exit[0]);
+        let crossing = out.at(-out.origin.x / out.direction.x);
+
+        // Lensmaker's equation for a thick lens, and the focus measured from the back surface.
+        let power = (n - 1.0) * (2.0 / r - (n - 1.0) * thickness / (n * r * r));
+        let back_focus = (1.0 - (n - 1.0) * thickness / (n * r)) / power;
+        assert!((crossing.y - (-thickness / 2.0 - back_focus)).abs() < 0.01, "{}", crossing.y);
+    }
+
+    #[test]
+    fn test_lens_rim() {
+        let lens = Lens::new(Material::glass(), 2.0, 2.0, 0.3);
+        // The rim is where the two spheres meet, at y = 0.
+        let rim = (2.0f64.powi(2) - (2.0f64 - 0.15).powi(2)).sqrt();
+        let ray = |x: f64| Ray::new(Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(lens.hit_obj(&ray(rim - 0.01), 0.0, f64::INFINITY).is_some());
+        assert!(lens.hit_obj(&ray(rim + 0.01), 0.0, f64::INFINITY).is_none());
+
+        // Through the edge sideways, in one sphere but beside the other.
+        let ray = Ray::new(Point3::new(-5.0, 0.1, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let ts = lens.hit_obj(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(ts.len(), 2);
+        assert!(fuzzy_eq_vec(&lens.normal_obj(&ray.at(ts[0])), &(ray.at(ts[0]) - Point3::new(0.0, -1.85, 0.0)).normalize()));
+        assert!(lens.bounds().contains(&ray.at(ts[0])));
+    }
+}
@@ -0,0 +1,646 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::f64::consts::TAU;
+use anyhow::{Result, Context, bail};
+use serde::Deserialize;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3, Colour};
+use crate::object::{Bounds, ObjectId, ObjectFlags, ply, intersection};
+use crate::intersection::Intersection;
+use crate::transform::Transformable;
+
+#[derive(Debug, Clone)]
+struct Triangle {
+    vertices: [usize; 3],
+    // Indices into the mesh's normals, if the face gave them.
+    normals:  Option<[usize; 3]>,
+    e1:       Vec3,
+    e2:       Vec3,
+    normal:   Vec3,
+}
+
+// A list of triangles, shaded flat or with normals interpolated from the
+// vertex normals when every vertex of a face has one. Meshes with vertex
+// colours are coloured by them instead of the material's colour.
+#[derive(Debug)]
+pub struct Mesh {
+    pub id:        ObjectId,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    vertices:      Vec<Point3>,
+    normals:       Vec<Vec3>,
+    // One per vertex, or empty.
+    colours:       Vec<Colour>,
+    triangles:     Vec<Triangle>,
+}
+
+impl Mesh {
+    // Faces index into vertices, and optionally normals, with one entry per corner.
+    pub fn new(
+        material: impl Into<Arc<Material>>,
+        vertices: Vec<Point3>,
+        normals:  Vec<Vec3>,
+        faces:    &[([usize; 3], Option<[usize; 3]>)],
+    ) -> Self {
+        let triangles = faces.iter()
+            .map(|&(v, n)| {
+                let e1 = vertices[v[1]] - vertices[v[0]];
+                let e2 = vertices[v[2]] - vertices[v[0]];
+                Triangle { vertices: v, normals: n, e1, e2, normal: e1.cross(&e2).normalize() }
+            })
+            .collect();
+        let normals = normals.into_iter().map(|n| n.normalize()).collect();
+
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            vertices,
+            normals,
+            colours: vec![],
+            triangles,
+        }
+    }
+
+    pub fn set_colours(&mut self, colours: Vec<Colour>) {
+        assert_eq!(colours.len(), self.vertices.len(), "mesh needs one colour per vertex");
+        self.colours = colours;
+    }
+
+    // OBJ, or PLY for files ending in .ply.
+    pub fn load<P: AsRef<Path>>(material: impl Into<Arc<Material>>, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ply")) {
+            return Self::from_ply_file(material, path);
+        }
+        let source = fs::read_to_string(path).with_context(|| format!("Could not read mesh {}", path.display()))?;
+        Self::from_obj(material, &source).with_context(|| format!("Invalid mesh {}", path.display()))
+    }
+
+    // Reads v, vn and f records from a Wavefront OBJ file, ignoring the rest.
+    // Faces with more than 3 corners are split into a fan of triangles.
+    pub fn from_obj(material: impl Into<Arc<Material>>, source: &str) -> Result<Self> {
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut faces = vec![];
+
+        for (line_no, line) in source.lines().enumerate() {
+            let line_no = line_no + 1;
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("v") => vertices.push(Point3::from(parse_xyz(fields, line_no)?)),
+                Some("vn") => normals.push(parse_xyz(fields, line_no)?),
+                Some("f") => {
+                    let corners = fields
+                        .map(|corner| parse_corner(corner, vertices.len(), normals.len(), line_no))
+                        .collect::<Result<Vec<_>>>()?;
+                    if corners.len() < 3 {
+                        bail!("Face on line {} has fewer than 3 corners", line_no);
+                    }
+                    for i in 1..corners.len() - 1 {
+                        let (a, b, c) = (corners[0], corners[i], corners[i + 1]);
+                        let normals = match (a.1, b.1, c.1) {
+                            (Some(na), Some(nb), Some(nc)) => Some([na, nb, nc]),
+                            _ => None,
+                        };
+                        faces.push(([a.0, b.0, c.0], normals));
+                    }
+                },
+                _ => {},
+            }
+        }
+        if faces.is_empty() {
+            bail!("Mesh has no faces");
+        }
+        Ok(Self::new(material, vertices, normals, &faces))
+    }
+
+    pub fn from_ply_file<P: AsRef<Path>>(material: impl Into<Arc<Material>>, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("Could not read mesh {}", path.display()))?;
+        Self::from_ply(material, &bytes).with_context(|| format!("Invalid mesh {}", path.display()))
+    }
+
+    // ASCII or little endian binary PLY, with optional vertex normals and colours.
+    pub fn from_ply(material: impl Into<Arc<Material>>, bytes: &[u8]) -> Result<Self> {
+        let data = ply::parse(bytes)?;
+        let smooth = !data.normals.is_empty();
+        let mut faces = vec![];
+        for (i, face) in data.faces.iter().enumerate() {
+            if face.len() < 3 {
+                bail!("Face {} has fewer than 3 corners", i);
+            }
+            if let Some(&v) = face.iter().find(|&&v| v >= data.vertices.len()) {
+                bail!("Face {} has vertex index {} out of range", i, v);
+            }
+            for j in 1..face.len() - 1 {
+                let corners = [face[0], face[j], face[j + 1]];
+                faces.push((corners, if smooth { Some(corners) } else { None }));
+            }
+        }
+        if faces.is_empty() {
+            bail!("Mesh has no faces");
+        }
+
+        let mut mesh = Self::new(material, data.vertices, data.normals, &faces);
+        if !data.colours.is_empty() {
+            mesh.set_colours(data.colours);
+        }
+        Ok(mesh)
+    }
+
+    // A grid of u_steps by v_steps quads over the surface at f(u, v), for u and
+    // v from 0 to 1, split into smoothly shaded triangles. Points on the edge
+    // of the grid that land on each other, where the surface wraps around, are
+    // joined so there's no crack along the seam. Normals aren't, so a seam that
+    // joins with a twist, like a Möbius strip's, still shades smoothly.
+    pub fn from_parametric(
+        f:        impl Fn(f64, f64) -> Point3,
+        u_steps:  usize,
+        v_steps:  usize,
+        material: impl Into<Arc<Material>>,
+    ) -> Self {
+        assert!(u_steps > 0 && v_steps > 0, "parametric surfaces need at least 1 step each way");
+        let grid = |i: usize, j: usize| i * (v_steps + 1) + j;
+        let points: Vec<Point3> = (0..=u_steps)
+            .flat_map(|i| (0..=v_steps).map(move |j| (i as f64 / u_steps as f64, j as f64 / v_steps as f64)))
+            .map(|(u, v)| f(u, v))
+            .collect();
+
+        // Normals from central differences, one sided at the edges.
+        let h = 1e-5;
+        let normals: Vec<Vec3> = (0..=u_steps)
+            .flat_map(|i| (0..=v_steps).map(move |j| (i as f64 / u_steps as f64, j as f64 / v_steps as f64)))
+            .map(|(u, v)| {
+                let du = f((u + h).min(1.0), v) - f((u - h).max(0.0), v);
+                let dv = f(u, (v + h).min(1.0)) - f(u, (v - h).max(0.0));
+                du.cross(&dv)
+            })
+            .collect();
+
+        let bounds = Bounds::from_points(&points);
+        let tolerance = 1e-9 * (bounds.max - bounds.min).norm().max(1.0);
+        let mut vertices: Vec<Point3> = vec![];
+        let mut edge: Vec<usize> = vec![];
+        let mut index = vec![0; points.len()];
+        for i in 0..=u_steps {
+            for j in 0..=v_steps {
+                let point = points[grid(i, j)];
+                let on_edge = i == 0 || i == u_steps || j == 0 || j == v_steps;
+                let joined = if on_edge {
+                    edge.iter().copied().find(|&k| (vertices[k] - point).norm() <= tolerance)
+                } else {
+                    None
+                };
+                index[grid(i, j)] = joined.unwrap_or_else(|| {
+                    vertices.push(point);
+                    if on_edge {
+                        edge.push(vertices.len() - 1);
+                    }
+                    vertices.len() - 1
+                });
+            }
+        }
+
+        let mut faces = vec![];
+        for i in 0..u_steps {
+            for j in 0..v_steps {
+                let quad = [grid(i, j), grid(i + 1, j), grid(i + 1, j + 1), grid(i, j + 1)];
+                for corners in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                    let [a, b, c] = corners.map(|k| index[k]);
+                    // Squashed to a line or point, as at a pole.
+                    if (vertices[b] - vertices[a]).cross(&(vertices[c] - vertices[a])).norm() == 0.0 {
+                        continue;
+                    }
+                    let smooth = corners.iter().all(|&k| normals[k].norm() > 0.0);
+                    faces.push(([a, b, c], if smooth { Some(corners) } else { None }));
+                }
+            }
+        }
+        let normals = normals.into_iter().map(|n| if n.norm() > 0.0 { n } else { Vec3::y() }).collect();
+        Self::new(material, vertices, normals, &faces)
+    }
+
+    // The triangle a point lies in, or failing that the one whose plane it is
+    // closest to, and the point's barycentric u, v in it. Only for points that
+    // didn't come from a hit, which carries its triangle.
+    fn locate(&self, point: &Point3) -> (usize, f64, f64) {
+        let mut best = (f64::INFINITY, 0, (0.0, 0.0));
+        for (i, tri) in self.triangles.iter().enumerate() {
+            let (u, v) = self.barycentric(tri, point);
+            let outside = (-u).max(-v).max(u + v - 1.0).max(0.0);
+            let dist = tri.normal.dot(&(point - self.vertices[tri.vertices[0]])).abs() + outside;
+            if dist < best.0 {
+                best = (dist, i, (u, v));
+            }
+        }
+        let (_, i, (u, v)) = best;
+        (i, u, v)
+    }
+
+    // Every hit between t_min and t_max in order, as t, triangle index and
+    // barycentric u, v.
+    fn hit_faces(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Vec<(f64, usize, f64, f64)> {
+        let mut hits: Vec<_> = self.triangles.iter().enumerate()
+            .filter_map(|(i, tri)| {
+                let (t, u, v) = hit_triangle(obj_ray, &self.vertices[tri.vertices[0]], &tri.e1, &tri.e2)?;
+                Some((t, i, u, v))
+            })
+            .filter(|&(t, ..)| t > t_min && t < t_max)
+            .collect();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits
+    }
+
+    fn face_normal(&self, i: usize, u: f64, v: f64) -> Vec3 {
+        let tri = &self.triangles[i];
+        match tri.normals {
+            Some([a, b, c]) => {
+                let normal = self.normals[a] * (1.0 - u - v) + self.normals[b] * u + self.normals[c] * v;
+                normal.normalize()
+            },
+            None => tri.normal,
+        }
+    }
+
+    // Interpolated from the triangle's vertices.
+    fn face_colour(&self, i: usize, u: f64, v: f64) -> Option<Colour> {
+        if self.colours.is_empty() {
+            return None;
+        }
+        let [a, b, c] = self.triangles[i].vertices;
+        Some(self.colours[a] * (1.0 - u - v) + self.colours[b] * u + self.colours[c] * v)
+    }
+
+    // Barycentric u, v of a point in the triangle's plane.
+    fn barycentric(&self, tri: &Triangle, point: &Point3) -> (f64, f64) {
+        let p = point - self.vertices[tri.vertices[0]];
+        let (d00, d01, d11) = (tri.e1.dot(&tri.e1), tri.e1.dot(&tri.e2), tri.e2.dot(&tri.e2));
+        let (d20, d21) = (p.dot(&tri.e1), p.dot(&tri.e2));
+        let denom = d00 * d11 - d01 * d01;
+        ((d11 * d20 - d01 * d21) / denom, (d00 * d21 - d01 * d20) / denom)
+    }
+}
+
+// Surfaces for Mesh::from_parametric, selectable from scene files.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ParametricPreset {
+    // Radius 1 around the y axis and 1 wide.
+    Mobius,
+    // The figure eight immersion, around the y axis, about 3.5 across.
+    Klein,
+    // Ripples over the square from -1 to 1 in x and z.
+    WavyPlane,
+}
+
+impl ParametricPreset {
+    pub fn point(&self, u: f64, v: f64) -> Point3 {
+        match self {
+            ParametricPreset::Mobius => {
+                let (angle, across) = (u * TAU, v - 0.5);
+                let r = 1.0 + across * (angle / 2.0).cos();
+                Point3::new(r * angle.cos(), across * (angle / 2.0).sin(), r * angle.sin())
+            },
+            ParametricPreset::Klein => {
+                let (angle, around) = (u * TAU, v * TAU);
+                let (half_cos, half_sin) = ((angle / 2.0).cos(), (angle / 2.0).sin());
+                let r = 1.0 + 0.5 * (half_cos * around.sin() - half_sin * (2.0 * around).sin());
+                let y = 0.5 * (half_sin * around.sin() + half_cos * (2.0 * around).sin());
+                Point3::new(r * angle.cos(), y, r * angle.sin())
+            },
+            ParametricPreset::WavyPlane => {
+                let (x, z) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+                Point3::new(x, 0.1 * (2.0 * TAU * u).sin() * (2.0 * TAU * v).sin(), z)
+            },
+        }
+    }
+}
+
+// Möller–Trumbore, returning t and the barycentric u, v of the hit on the
+// triangle with corner a and edges e1, e2 from it.
+pub(crate) fn hit_triangle(ray: &Ray, a: &Point3, e1: &Vec3, e2: &Vec3) -> Option<(f64, f64, f64)> {
+    let p = ray.direction.cross(e2);
+    let det = e1.dot(&p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let f = 1.0 / det;
+    let s = ray.origin - a;
+    let u = f * s.dot(&p);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(e1);
+    let v = f * ray.direction.dot(&q);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some((f * e2.dot(&q), u, v))
+}
+
+fn parse_xyz<'a>(mut fields: impl Iterator<Item = &'a str>, line_no: usize) -> Result<Vec3> {
+    let mut xyz = [0.0; 3];
+    for value in xyz.iter_mut() {
+        *value = fields.next()
+            .and_then(|f| f.parse().ok())
+            .with_context(|| format!("Expected 3 numbers on line {}", line_no))?;
+    }
+    Ok(Vec3::new(xyz[0], xyz[1], xyz[2]))
+}
+
+// A face corner, v, v/vt, v//vn or v/vt/vn. Indices start at 1, negative ones
+// count back from the latest vertex or normal.
+fn parse_corner(corner: &str, vertex_count: usize, normal_count: usize, line_no: usize) -> Result<(usize, Option<usize>)> {
+    let index = |field: &str, count: usize| -> Result<usize> {
+        let i: i64 = field.parse().with_context(|| format!("Invalid face index \"{}\" on line {}", field, line_no))?;
+        let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+        if i == 0 || resolved < 0 || resolved >= count as i64 {
+            bail!("Face index {} out of range on line {}", i, line_no);
+        }
+        Ok(resolved as usize)
+    };
+
+    let mut parts = corner.split('/');
+    let vertex = index(parts.next().unwrap_or_default(), vertex_count)?;
+    let normal = match parts.nth(1) {
+        Some(n) if !n.is_empty() => Some(index(n, normal_count)?),
+        _ => None,
+    };
+    Ok((vertex, normal))
+}
+
+impl Object for Mesh {
+
+    // Shaded from the triangle each hit landed on.
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<Intersection>> {
+        let hits = self.hit_faces(&ray.transform(&self.inverse), t_min, t_max);
+        if hits.is_empty() {
+            return None;
+        }
+        let hits = hits.into_iter()
+            .map(|(t, i, u, v)| {
+                let hit = intersection(self, ray, t, &self.face_normal(i, u, v), self.face_colour(i, u, v));
+                Intersection { face: Some((i, u, v)), ..hit }
+            })
+            .collect();
+        Some(hits)
+    }
+
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let hits: Vec<f64> = self.hit_faces(obj_ray, t_min, t_max).into_iter().map(|(t, ..)| t).collect();
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::from_points(&self.vertices)
+    }
+
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let (i, u, v) = self.locate(point);
+        self.face_normal(i, u, v)
+    }
+
+    fn colour_obj(&self, point: &Point3) -> Option<Colour> {
+        if self.colours.is_empty() {
+            return None;
+        }
+        let (i, u, v) = self.locate(point);
+        self.face_colour(i, u, v)
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for Mesh {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::colour::fuzzy_eq_colour;
+
+    // One triangle in the z = 0 plane, with normals tilted outwards at each corner.
+    const SMOOTH: &str = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn -1 -1 -1
+vn 1 0 -1
+vn 0 1 -1
+f 1//1 2//2 3//3
+";
+
+    #[test]
+    fn test_mesh_obj() {
+        let mesh = Mesh::from_obj(Material::default(), "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        assert_eq!(mesh.triangles.len(), 2);
+        assert!(mesh.triangles.iter().all(|t| t.normals.is_none()));
+
+        let mesh = Mesh::from_obj(Material::default(), "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 -1\nf -3/1/1 -2/2/1 -1/3/1\n").unwrap();
+        assert_eq!(mesh.triangles[0].vertices, [0, 1, 2]);
+        assert_eq!(mesh.triangles[0].normals, Some([0, 0, 0]));
+
+        assert!(Mesh::from_obj(Material::default(), "v 0 0 0\nf 1 2 3\n").is_err());
+        assert!(Mesh::from_obj(Material::default(), "v 0 0 0\n").is_err());
+    }
+
+    #[test]
+    fn test_mesh_smooth_normal() {
+        let mesh = Mesh::from_obj(Material::default(), SMOOTH).unwrap();
+        let ray = Ray::new(Point3::new(0.25, 0.25, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hits = mesh.hit_obj(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(fuzzy_eq_f64(hits[0], 5.0));
+
+        // u = v = 0.25, so half the first normal and a quarter of each other one.
+        let n = [Vec3::new(-1.0, -1.0, -1.0).normalize(), Vec3::new(1.0, 0.0, -1.0).normalize(), Vec3::new(0.0, 1.0, -1.0).normalize()];
+        let expected = (n[0] * 0.5 + n[1] * 0.25 + n[2] * 0.25).normalize();
+        assert!(fuzzy_eq_vec(&mesh.normal_obj(&ray.at(hits[0])), &expected));
+
+        // At a corner the normal is the vertex normal.
+        assert!(fuzzy_eq_vec(&mesh.normal_obj(&Point3::new(1.0, 0.0, 0.0)), &n[1]));
+    }
+
+    #[test]
+    fn test_mesh_flat_normal() {
+        let mesh = Mesh::from_obj(Material::default(), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        for point in [Point3::new(0.1, 0.1, 0.0), Point3::new(0.6, 0.3, 0.0)] {
+            assert_eq!(mesh.normal_obj(&point), Vec3::new(0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_mesh_transformed_normal() {
+        let mut mesh = Mesh::from_obj(Material::default(), SMOOTH).unwrap();
+        mesh.scale(2.0, 1.0, 1.0);
+        mesh.translate(0.0, 0.0, 3.0);
+
+        let ray = Ray::new(Point3::new(0.5, 0.25, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hits = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(hits[0].t, 8.0));
+
+        // Object space normal at (0.25, 0.25, 0), through the inverse transpose of the scale.
+        let obj = mesh.normal_obj(&Point3::new(0.25, 0.25, 0.0));
+        let expected = Vec3::new(obj.x / 2.0, obj.y, obj.z).normalize();
+        assert!(fuzzy_eq_vec(&hits[0].normal, &expected));
+    }
+
+    // A quad split into two triangles, red, green, blue and white at the corners.
+    const PLY: &str = "ply
+format ascii 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    #[test]
+    fn test_mesh_ply_vertex_colours() {
+        let mesh = Mesh::from_ply(Material::default(), PLY.as_bytes()).unwrap();
+        assert_eq!(mesh.triangles.len(), 2);
+
+        let ray = Ray::new(Point3::new(0.5, 0.25, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hits = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        // In the first triangle with u = v = 0.25, so half red, a quarter green and a quarter blue.
+        assert!(fuzzy_eq_colour(hits[0].colour, Colour::new(0.5, 0.25, 0.25)));
+
+        // Vertices must have positions.
+        assert!(Mesh::from_ply(Material::default(), PLY.replace("property float z\n", "").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_mesh_hit_face() {
+        // The same triangle twice, red then blue, so both are hit at the same point.
+        let vertices = [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)].repeat(2);
+        let mut mesh = Mesh::new(Material::default(), vertices, vec![], &[([0, 1, 2], None), ([3, 4, 5], None)]);
+        let (red, blue) = (Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 0.0, 1.0));
+        mesh.set_colours(vec![red, red, red, blue, blue, blue]);
+
+        let ray = Ray::new(Point3::new(0.25, 0.5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut hits = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        hits.sort_by_key(|hit| hit.face.unwrap().0);
+        let faces: Vec<_> = hits.iter().map(|hit| hit.face.unwrap()).collect();
+        assert_eq!(faces, [(0, 0.25, 0.5), (1, 0.25, 0.5)]);
+        // Each coloured by its own triangle.
+        assert_eq!((hits[0].colour, hits[1].colour), (red, blue));
+    }
+
+    #[test]
+    fn test_mesh_ply_binary() {
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+            property float nx\nproperty float ny\nproperty float nz\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n".to_vec();
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        for v in vertices {
+            for value in v.into_iter().chain([0.0, 0.0, -1.0]) {
+                bytes.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+        bytes.push(3);
+        for i in [0i32, 1, 2] {
+            bytes.extend_from_slice(&i.to_le_bytes());
+        }
+
+        let mesh = Mesh::from_ply(Material::default(), &bytes).unwrap();
+        assert_eq!(mesh.vertices[1], Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.triangles[0].normals, Some([0, 1, 2]));
+        assert_eq!(mesh.normal_obj(&Point3::new(0.2, 0.2, 0.0)), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.colour_obj(&Point3::new(0.2, 0.2, 0.0)).is_none());
+
+        // Cut off part way through the faces.
+        assert!(Mesh::from_ply(Material::default(), &bytes[..bytes.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_mesh_parametric() {
+        // A tube around y, which wraps in u.
+        let tube = |u: f64, v: f64| Point3::new((u * TAU).cos(), v, (u * TAU).sin());
+        let mesh = Mesh::from_parametric(tube, 8, 2, Material::default());
+        assert_eq!(mesh.vertices.len(), 8 * 3);
+        assert_eq!(mesh.triangles.len(), 8 * 2 * 2);
+        // Normals are smooth, along the line from the axis.
+        let ray = Ray::new(Point3::new(5.0, 0.5, 0.3), Vec3::new(-1.0, 0.0, 0.0));
+        let t = mesh.hit_obj(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        let normal = mesh.normal_obj(&ray.at(t));
+        assert!(normal.x.abs() > 0.9 && normal.y.abs() < 1e-6 && normal.x * normal.z > 0.0);
+
+        // Poles are squashed triangles, which are dropped.
+        let sphere = |u: f64, v: f64| {
+            let (theta, phi) = (u * TAU, v * std::f64::consts::PI);
+            Point3::new(theta.cos() * phi.sin(), phi.cos(), theta.sin() * phi.sin())
+        };
+        let mesh = Mesh::from_parametric(sphere, 8, 4, Material::default());
+        assert_eq!(mesh.vertices.len(), 8 * 3 + 2);
+        assert_eq!(mesh.triangles.len(), 8 * 2 * 2 + 8 * 2);
+        assert!(mesh.triangles.iter().all(|tri| tri.normal.iter().all(|n| n.is_finite())));
+    }
+
+    #[test]
+    fn test_mesh_parametric_presets() {
+        // Both seams join with a twist, the Möbius strip's edge being one loop.
+        let mobius = Mesh::from_parametric(|u, v| ParametricPreset::Mobius.point(u, v), 8, 2, Material::default());
+        assert_eq!(mobius.vertices.len(), 8 * 3);
+        // The figure eight also crosses itself halfway round the seam.
+        let klein = Mesh::from_parametric(|u, v| ParametricPreset::Klein.point(u, v), 8, 4, Material::default());
+        assert_eq!(klein.vertices.len(), 8 * 4 - 1);
+        let wavy = Mesh::from_parametric(|u, v| ParametricPreset::WavyPlane.point(u, v), 8, 8, Material::default());
+        assert_eq!(wavy.vertices.len(), 9 * 9);
+
+        // Across the Möbius seam, where the strip crosses x = 1.
+        let ray = Ray::new(Point3::new(1.2, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mobius = Mesh::from_parametric(|u, v| ParametricPreset::Mobius.point(u, v), 64, 4, Material::default());
+        let t = mobius.hit_obj(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        assert!(fuzzy_eq_f64(t, 5.0));
+        assert!(mobius.normal_obj(&ray.at(t)).y.abs() > 0.99);
+    }
+}
@@ -1,24 +1,109 @@
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::sync::Arc;
-use crate::{Vec3, Point3, Material};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::{Vec3, Point3, Matrix4, Material, Colour};
 use crate::intersection::Intersection;
 use crate::ray::Ray;
 use crate::math::reflect;
 use crate::transform::Transformable;
+use crate::pattern::Mapping;
 
 mod sphere;
 mod plane;
 mod cylinder;
 mod cone;
 mod bbox;
+mod lathe;
+mod prism;
+mod mesh;
+mod csg;
+mod group;
+mod heightfield;
+mod sdf;
+mod instance;
+mod lens;
+mod superellipsoid;
+mod moving;
+mod ply;
+mod bounds;
+pub mod builders;
 
 pub use sphere::Sphere;
 pub use plane::{Plane, Disk};
 pub use bbox::AxisAlignedBoundingBox;
 pub use cylinder::Cylinder;
 pub use cone::Cone;
+pub use lathe::Lathe;
+pub use prism::Prism;
+pub use mesh::{Mesh, ParametricPreset};
+pub use csg::{Csg, CsgOp};
+pub use group::Group;
+pub use heightfield::Heightfield;
+pub use instance::Instance;
+pub use lens::Lens;
+pub use superellipsoid::Superellipsoid;
+pub use moving::Moving;
+pub use sdf::{SdfObject, SdfShape, Sdf, rounded_box, gyroid, sphere_blend};
+pub use bounds::Bounds;
+
+// Surface offset for unit sized objects, see Object::epsilon. Also how close
+// to their origin camera and reflected rays can hit something.
+pub const EPSILON: f64 = 0.0001;
+
+// Rays whose two hits on a curved surface are closer than this, in object
+// space, only graze it. A pair that close can't be reliably ordered as
+// entering then leaving, so the ray is counted as missing.
+pub(crate) const GRAZE_EPSILON: f64 = 1e-6;
+
+// Object space normal to world space, through the inverse transpose.
+pub(crate) fn world_normal(inverse: &Matrix4, obj_normal: &Vec3) -> Vec3 {
+    let world_normal = inverse.transpose() * obj_normal.to_homogeneous();
+    Vec3::new(world_normal.x, world_normal.y, world_normal.z).normalize()
+}
+
+// Identifies an object. Each one is handed out once, when the object is made,
+// so objects from different scenes can be mixed without their IDs colliding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(u64);
+
+impl ObjectId {
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for ObjectId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Settings that aren't about an object's shape, the same for every kind of object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectFlags {
+    // Fixed epsilon, see Object::set_epsilon.
+    pub epsilon:           Option<f64>,
+    pub cast_shadow:       bool,
+    pub visible_to_camera: bool,
+}
+
+impl Default for ObjectFlags {
+    fn default() -> Self {
+        Self {
+            epsilon:           None,
+            cast_shadow:       true,
+            visible_to_camera: true,
+        }
+    }
+}
 
-// TODO: Change id to uuid.
 // An object is something that can be hit by a ray.
 pub trait Object: Transformable + Send + Sync + Debug {
 
@@ -29,63 +114,183 @@ pub trait Object: Transformable + Send + Sync + Debug {
     
     fn material(&self) -> &Arc<Material>;
 
+    // Material seen from the given side of the surface.
+    fn material_for(&self, _front_face: bool) -> &Arc<Material> {
+        self.material()
+    }
+
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<Intersection>> {
         
         let obj_ray = ray.transform(self.inverse()); // Convert ray to object space.
-        let hits = self.hit_obj(&obj_ray, t_min, t_max);
-        
-        if let Some(hits) = hits {
-            let mut intersections = Vec::new();
-
-            for t in hits {
-
-                let obj_id = self.id();
-                let point = ray.at(t);
-                let outward_normal = self.normal_at(&point);
-                let eye = -ray.direction;
-                let front_face = ray.direction.dot(&outward_normal) < 0.0;
-                let normal = if front_face { outward_normal } else { -outward_normal };
-                let reflect = reflect(&ray.direction, &normal);
-                // TODO: See what happens if we change epsilon.
-                let over_point = point + normal * 0.0001;
-                let under_point = point - normal * 0.0001;
-                let colour = self.material().colour_at(&over_point, self.inverse());
-
-                intersections.push(Intersection {
-                    id: 0,
-                    obj_id,
-                    point,
-                    normal,
-                    material: self.material().clone(),
-                    t,
-                    front_face,
-                    eye,
-                    reflect,
-                    colour,
-                    over_point,
-                    under_point,
-                    exit_idx: 1.0,
-                    enter_idx: 1.0,
-                });
-            }
-            Some(intersections)
-        } else {
-            None
-        }
+        let hits = self.hit_obj(&obj_ray, t_min, t_max)?;
+        let intersections = hits.into_iter()
+            .map(|t| {
+                let obj_point = self.inverse().transform_point(&ray.at(t));
+                let obj_normal = self.normal_obj_hit(&obj_point, &obj_ray.direction);
+                intersection(self, ray, t, &obj_normal, self.colour_obj(&obj_point))
+            })
+            .collect();
+        Some(intersections)
+    }
+
+    // Whether the ray hits at all, without working out where, for shadow rays.
+    fn hit_any(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.hit_obj(&ray.transform(self.inverse()), t_min, t_max).is_some_and(|ts| !ts.is_empty())
+    }
+
+    // Surface colour at a point in object space, for objects that carry their
+    // own colours. Replaces the material's colour when given.
+    fn colour_obj(&self, _point: &Point3) -> Option<Colour> {
+        None
+    }
+
+    // Texture coordinates of a point on the surface in object space, each
+    // from 0 to 1. Wrapped spherically unless the object says otherwise.
+    fn uv_at(&self, obj_point: &Point3) -> (f64, f64) {
+        Mapping::Spherical.uv(obj_point)
+    }
+
+    // Extent in object space, infinite unless the object says otherwise.
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::infinite()
+    }
+
+    // Extent in world space, the object space box around the object transformed.
+    fn bounds(&self) -> Bounds {
+        self.bounds_obj().transform(self.transform())
     }
 
     fn normal_at(&self, point: &Point3) -> Vec3 {
         let obj_point = self.inverse().transform_point(point);
-        let obj_normal = self.normal_obj(&obj_point);
-        let world_normal = self.inverse().transpose() * obj_normal.to_homogeneous();
-        let world_normal = Vec3::new(world_normal.x, world_normal.y, world_normal.z);
-        world_normal.normalize()
+        world_normal(self.inverse(), &self.normal_obj(&obj_point))
+    }
+
+    // Normal where a ray going in the given object space direction hit. Only
+    // differs from normal_obj for objects with edges, where the point alone
+    // can't say which face was hit.
+    fn normal_obj_hit(&self, point: &Point3, _obj_direction: &Vec3) -> Vec3 {
+        self.normal_obj(point)
     }
 
     // Unique identifier for the object.
-    fn id(&self) -> usize;
+    fn id(&self) -> ObjectId;
 
-    fn set_id(&mut self, id: usize);
+    // Whether hits with the given ID belong to this object, true for its own
+    // and for those of any children whose hits keep their own IDs.
+    fn owns(&self, id: ObjectId) -> bool {
+        id == self.id()
+    }
+
+    fn set_id(&mut self, id: ObjectId);
+
+    // Settings every object carries, read and set through the methods below.
+    fn flags(&self) -> &ObjectFlags;
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags;
+
+    // Distance hit points are moved off the surface so rays leaving it don't hit it again.
+    // Scales with the object, so very large or small objects don't get acne or light leaks.
+    fn epsilon(&self) -> f64 {
+        self.epsilon_override().unwrap_or_else(|| EPSILON * self.max_scale())
+    }
+
+    fn epsilon_override(&self) -> Option<f64> {
+        self.flags().epsilon
+    }
+
+    // Fixed epsilon for objects where the scaled one doesn't work.
+    fn set_epsilon(&mut self, epsilon: f64) {
+        self.flags_mut().epsilon = Some(epsilon);
+    }
+
+    // Whether the object blocks light. Camera and reflected rays still see it either way.
+    fn cast_shadow(&self) -> bool {
+        self.flags().cast_shadow
+    }
+
+    fn set_cast_shadow(&mut self, cast_shadow: bool) {
+        self.flags_mut().cast_shadow = cast_shadow;
+    }
+
+    // Whether camera rays hit the object. Shadow and reflected rays hit it either way.
+    fn visible_to_camera(&self) -> bool {
+        self.flags().visible_to_camera
+    }
+
+    fn set_visible_to_camera(&mut self, visible: bool) {
+        self.flags_mut().visible_to_camera = visible;
+    }
+}
+
+// Intersection at t along the world space ray, given the object space normal
+// there and the object's own colour if it has one. For Object::hit, and for
+// objects that find the normal while finding the hit.
+pub(crate) fn intersection<O: Object + ?Sized>(obj: &O, ray: &Ray, t: f64, obj_normal: &Vec3, colour: Option<Colour>) -> Intersection {
+    let point = ray.at(t);
+    let obj_point = obj.inverse().transform_point(&point);
+    let outward_normal = world_normal(obj.inverse(), obj_normal);
+    let eye = -ray.direction;
+    let front_face = ray.direction.dot(&outward_normal) < 0.0;
+    let material = obj.material_for(front_face);
+    let surface_normal = if front_face { outward_normal } else { -outward_normal };
+    let normal = material.perturb_normal(&obj_point, &surface_normal, &eye);
+    let reflect = reflect(&ray.direction, &normal);
+    let epsilon = obj.epsilon();
+    let over_point = point + normal * epsilon;
+    let under_point = point - normal * epsilon;
+    let (colour, uv) = match colour {
+        Some(colour) => (colour, None),
+        None => material.surface_colour(&over_point, obj.inverse(), || obj.uv_at(&obj_point)),
+    };
+
+    Intersection {
+        id: 0,
+        obj_id: obj.id(),
+        point,
+        normal,
+        surface_normal,
+        material: material.clone(),
+        t,
+        front_face,
+        eye,
+        reflect,
+        colour,
+        over_point,
+        under_point,
+        exit_idx: 1.0,
+        enter_idx: 1.0,
+        time: ray.time,
+        uv,
+        face: None,
+    }
+}
+
+// World space normal of whichever object has its surface nearest the world
+// space point, found by casting lines through the point along each axis. For
+// objects made of others, whose normals come from their children.
+pub(crate) fn nearest_normal<'a>(objects: impl IntoIterator<Item = &'a dyn Object>, point: &Point3) -> Option<Vec3> {
+    let axes = [Vec3::x(), Vec3::y(), Vec3::z()];
+    objects.into_iter()
+        .filter_map(|obj| {
+            let dist = axes.iter()
+                .filter_map(|axis| obj.hit(&Ray::new(*point, *axis), f64::NEG_INFINITY, f64::INFINITY))
+                .flatten()
+                .map(|hit| hit.t.abs())
+                .min_by(f64::total_cmp)?;
+            Some((dist, obj))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, obj)| obj.normal_at(point))
+}
+
+// True if the angle of the point around the y axis, atan2(z, x) in degrees,
+// lies within [theta_min, theta_max].
+pub(crate) fn in_sweep(point: &Point3, theta_min: f64, theta_max: f64) -> bool {
+    if theta_max - theta_min >= 360.0 {
+        return true;
+    }
+    let theta = point.z.atan2(point.x).to_degrees();
+    (theta - theta_min).rem_euclid(360.0) <= theta_max - theta_min
 }
 
 #[cfg(test)]
@@ -106,4 +311,51 @@ mod tests {
         assert!(fuzzy_eq_vec(&int.reflect, &Vec3::new(0.0, f64::sqrt(2.0) / 2.0, f64::sqrt(2.0) / 2.0)));
     }
 
+    #[test]
+    fn test_hit_uvs() {
+        let down = |x: f64, z: f64| Ray::new(Point3::new(x, 10.0, z), -Vec3::y());
+        let uv = |object: &dyn Object, ray: &Ray| {
+            let hits = object.hit(ray, 0.0, f64::INFINITY).unwrap();
+            hits.into_iter().min_by(|a, b| a.t.total_cmp(&b.t)).unwrap().uv.unwrap()
+        };
+        let near = |(u, v): (f64, f64), expected: (f64, f64)| fuzzy_eq_f64(u, expected.0) && fuzzy_eq_f64(v, expected.1);
+        // Only worked out for materials that use them.
+        let material = || Material { uv_pattern: true, ..Default::default() };
+        let plain = Sphere::new(Material::default()).hit(&Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::z()), 0.0, f64::INFINITY).unwrap();
+        assert_eq!(plain[0].uv, None);
+
+        let sphere = Sphere::new(material());
+        assert!(near(uv(&sphere, &Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::z())), (0.0, 0.5)));
+        let plane = Plane::new(material());
+        assert!(near(uv(&plane, &down(1.25, -0.25)), (0.25, 0.75)));
+        // Objects are mapped in object space.
+        let mut cube = AxisAlignedBoundingBox::from_corners(material(), Point3::new(-2.0, 0.0, -2.0), Point3::new(2.0, 4.0, 2.0));
+        cube.translate(10.0, 0.0, 0.0);
+        assert!(near(uv(&cube, &down(11.0, 1.0)), (0.75, 0.25)));
+        // Cylinder walls wrap around, caps are flat.
+        let cylinder = Cylinder::new(material(), 1.0, 0.0, 2.0, true);
+        assert!(near(uv(&cylinder, &down(0.5, 0.0)), (0.75, 0.5)));
+        assert!(near(uv(&cylinder, &Ray::new(Point3::new(5.0, 0.5, 0.0), -Vec3::x())), (0.25, 0.5)));
+        let cone = Cone::new(material(), 45.0, -2.0, 0.0, true);
+        assert!(near(uv(&cone, &Ray::new(Point3::new(0.0, -10.0, 1.0), Vec3::y())), (0.5, 0.75)));
+    }
+
+    #[test]
+    fn test_uv_pattern() {
+        use crate::pattern::Stripes;
+        use crate::Colour;
+
+        // Half a stripe per u, so the front and back of a sphere differ.
+        let mut stripes = Stripes::new(Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 0.0, 1.0));
+        stripes.scale_uniform(0.5);
+        let stripes: Arc<dyn crate::pattern::Pattern> = Arc::new(stripes);
+        let colours = |uv_pattern: bool| {
+            let sphere = Sphere::new(Material { pattern: Some(Arc::clone(&stripes)), uv_pattern, ..Default::default() });
+            let colour = |z: f64| sphere.hit(&Ray::new(Point3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, -z.signum())), 0.0, f64::INFINITY).unwrap()[0].colour;
+            (colour(-5.0), colour(5.0))
+        };
+        assert_eq!(colours(false), (Colour::new(1.0, 0.0, 0.0), Colour::new(1.0, 0.0, 0.0)));
+        assert_eq!(colours(true), (Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 0.0, 1.0)));
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::intersection::Intersection;
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::transform::Transformable;
+
+// An object moving in a straight line while the shutter is open, for motion
+// blur. It is translated by start at time 0 and end at time 1, on top of its
+// own transform, and in between by how far the ray's time is between them.
+#[derive(Debug)]
+pub struct Moving {
+    pub start: Vec3,
+    pub end:   Vec3,
+    object:    Box<dyn Object>,
+}
+
+impl Moving {
+    pub fn new(object: Box<dyn Object>, start: Vec3, end: Vec3) -> Self {
+        Self { start, end, object }
+    }
+
+    pub fn object(&self) -> &dyn Object {
+        &*self.object
+    }
+
+    pub fn offset_at(&self, time: f64) -> Vec3 {
+        self.start + (self.end - self.start) * time
+    }
+}
+
+impl Object for Moving {
+
+    // Moving the ray back by the offset is the same as moving the object forward by it.
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<Intersection>> {
+        let offset = self.offset_at(ray.time);
+        let local_ray = Ray { origin: ray.origin - offset, ..*ray };
+        let hits = self.object.hit(&local_ray, t_min, t_max)?;
+        let hits = hits.into_iter()
+            .map(|hit| Intersection {
+                point: hit.point + offset,
+                over_point: hit.over_point + offset,
+                under_point: hit.under_point + offset,
+                ..hit
+            })
+            .collect();
+        Some(hits)
+    }
+
+    fn hit_any(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        let local_ray = Ray { origin: ray.origin - self.offset_at(ray.time), ..*ray };
+        self.object.hit_any(&local_ray, t_min, t_max)
+    }
+
+    // Object space is the same at every time.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        self.object.hit_obj(obj_ray, t_min, t_max)
+    }
+
+    // Everywhere it is between time 0 and 1.
+    fn bounds(&self) -> Bounds {
+        let bounds = self.object.bounds();
+        bounds.translate(&self.start).union(&bounds.translate(&self.end))
+    }
+
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        self.object.normal_obj(point)
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        self.object.material()
+    }
+
+    fn material_for(&self, front_face: bool) -> &Arc<Material> {
+        self.object.material_for(front_face)
+    }
+
+    fn id(&self) -> ObjectId {
+        self.object.id()
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.object.set_id(id);
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        self.object.flags()
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        self.object.flags_mut()
+    }
+}
+
+impl Transformable for Moving {
+
+    fn transform(&self) -> &Matrix4 {
+        self.object.transform()
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        self.object.inverse()
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.object.set_transform(transform);
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.object.set_inverse(inverse);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::object::Sphere;
+
+    #[test]
+    fn test_moving_hits_at_ray_time() {
+        let mut sphere = Sphere::new(Material::default());
+        sphere.scale_uniform(0.5);
+        let moving = Moving::new(Box::new(sphere), Vec3::zeros(), Vec3::new(4.0, 0.0, 0.0));
+
+        let ray = |x: f64, time: f64| Ray::new(Point3::new(x, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0)).with_time(time);
+        assert!(moving.hit(&ray(0.0, 0.0), 0.0, f64::INFINITY).is_some());
+        assert!(moving.hit(&ray(0.0, 1.0), 0.0, f64::INFINITY).is_none());
+
+        // Halfway through, the sphere is at x = 2.
+        let hits = moving.hit(&ray(2.0, 0.5), 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(hits[0].t, 4.5));
+        assert!(fuzzy_eq_vec(&hits[0].point.coords, &Vec3::new(2.0, 0.0, -0.5)));
+        assert!(fuzzy_eq_vec(&hits[0].normal, &Vec3::new(0.0, 0.0, -1.0)));
+        assert!(hits[0].over_point.z < -0.5);
+        assert_eq!(hits[0].time, 0.5);
+    }
+
+    #[test]
+    fn test_static_objects_ignore_time() {
+        let sphere = Sphere::new(Material::default());
+        let ray = Ray::new(Point3::new(0.3, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let still = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        let later = sphere.hit(&ray.with_time(0.8), 0.0, f64::INFINITY).unwrap();
+        assert_eq!(still[0].t, later[0].t);
+        assert_eq!(still[0].point, later[0].point);
+    }
+}
@@ -2,29 +2,47 @@ use std::sync::Arc;
 use crate::{Vec3, Point3, Matrix4};
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::object::Object;
+use crate::object::{Object, Bounds, ObjectId, ObjectFlags, in_sweep};
 use crate::transform::Transformable;
+use crate::pattern::Mapping;
 
 // A plane can be defined as a point representing how far the plane is from the world's origin and a normal (defining the orientation of the plane).
 // We start by defining the point as the origin and the normal as the z-axis, then we can transform this to our liking.
 #[derive(Debug)]
 pub struct Plane {
-    id:        usize,
+    id:        ObjectId,
     transform: Matrix4,
     inverse:   Matrix4,
     material:  Arc<Material>,
+    flags:     ObjectFlags,
+    // Used when hit from below, if set.
+    material_back: Option<Arc<Material>>,
+    // Only hit from above.
+    one_sided: bool,
 }
 
 // Non-transformed plane has its origin at the world's origin and its normal is the y-axis.
 impl Plane {
-    pub fn new(material: Material) -> Self {
+    pub fn new(material: impl Into<Arc<Material>>) -> Self {
         Self {
-            id:        0,
+            id:        ObjectId::new(),
             transform: Matrix4::identity(),
             inverse:   Matrix4::identity(),
-            material:  Arc::new(material),
+            material:  material.into(),
+            flags:     ObjectFlags::default(),
+            material_back: None,
+            one_sided: false,
         }
     }
+
+    pub fn set_material_back(&mut self, material: impl Into<Arc<Material>>) {
+        self.material_back = Some(material.into());
+    }
+
+    // One sided planes are invisible from below, and cast no shadows there.
+    pub fn set_one_sided(&mut self, one_sided: bool) {
+        self.one_sided = one_sided;
+    }
 }
 
 impl Object for Plane {
@@ -34,8 +52,11 @@ impl Object for Plane {
         t_min: f64, 
         t_max: f64
     ) -> Option<Vec<f64>> {
-        // Infinite solutions (div by 0).
-        if ray.direction.y.abs() < 1e-6 {
+        // Infinite solutions (div by 0). Relative to the length of the ray, which scaling changes.
+        if ray.direction.y.abs() < 1e-6 * ray.direction.norm() {
+            return None;
+        }
+        if self.one_sided && ray.direction.y > 0.0 {
             return None;
         }
         
@@ -48,6 +69,15 @@ impl Object for Plane {
     }
     
     // Normal without transformation points upwards.
+    // Flat along y, but unbounded in x and z.
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::new(Point3::new(-f64::INFINITY, 0.0, -f64::INFINITY), Point3::new(f64::INFINITY, 0.0, f64::INFINITY))
+    }
+
+    fn uv_at(&self, obj_point: &Point3) -> (f64, f64) {
+        Mapping::Planar.uv(obj_point)
+    }
+
     fn normal_obj(&self, _point: &Point3) -> Vec3 {
         Vec3::new(0.0, 1.0, 0.0)
     }
@@ -56,13 +86,28 @@ impl Object for Plane {
         &self.material
     }
 
-    fn id(&self) -> usize {
+    fn material_for(&self, front_face: bool) -> &Arc<Material> {
+        match &self.material_back {
+            Some(back) if !front_face => back,
+            _ => &self.material,
+        }
+    }
+
+    fn id(&self) -> ObjectId {
         self.id
     }
 
-    fn set_id(&mut self, id: usize) {
+    fn set_id(&mut self, id: ObjectId) {
         self.id = id;
     }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
 }
 
 impl Transformable for Plane {
@@ -87,22 +132,70 @@ impl Transformable for Plane {
 // A disk is a plane with a radius.
 #[derive(Debug)]
 pub struct Disk{
-    id:        usize,
+    id:        ObjectId,
     transform: Matrix4,
     inverse:   Matrix4,
     material:  Arc<Material>,
+    flags:     ObjectFlags,
+    // Used when hit from below, if set.
+    material_back: Option<Arc<Material>>,
+    // Swept angle around the y axis in degrees.
+    theta_min: f64,
+    theta_max: f64,
+    // Only hit from above.
+    one_sided: bool,
+    radius:       f64,
+    // Radius of the hole in the middle, 0 for a solid disk.
+    inner_radius: f64,
 }
 
 // A disk is a plane with a radius.
 impl Disk {
-    pub fn new(material: Material) -> Self {
+    // Unit radius.
+    pub fn new(material: impl Into<Arc<Material>>) -> Self {
+        Self::with_radius(material, 1.0)
+    }
+
+    // Sized without a scale, so patterns on it aren't stretched.
+    pub fn with_radius(material: impl Into<Arc<Material>>, radius: f64) -> Self {
+        assert!(radius > 0.0, "disk radius must be positive");
         Self { 
-            id:        0,
+            id:        ObjectId::new(),
             transform: Matrix4::identity(),
             inverse:   Matrix4::identity(),
-            material:  Arc::new(material),
+            material:  material.into(),
+            flags:     ObjectFlags::default(),
+            material_back: None,
+            theta_min: 0.0,
+            theta_max: 360.0,
+            one_sided: false,
+            radius,
+            inner_radius: 0.0,
         }
     }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn set_material_back(&mut self, material: impl Into<Arc<Material>>) {
+        self.material_back = Some(material.into());
+    }
+
+    // Makes the disk a ring, with hits closer to the centre than this rejected.
+    pub fn set_inner_radius(&mut self, inner_radius: f64) {
+        self.inner_radius = inner_radius;
+    }
+
+    pub fn set_one_sided(&mut self, one_sided: bool) {
+        self.one_sided = one_sided;
+    }
+
+    // Only keep the slice between the two angles, like a pie chart.
+    pub fn set_sweep(&mut self, theta_min: f64, theta_max: f64) {
+        self.theta_min = theta_min;
+        self.theta_max = theta_max;
+    }
 }
 
 impl Object for Disk {
@@ -113,8 +206,11 @@ impl Object for Disk {
         t_max: f64
     ) -> Option<Vec<f64>> {
 
-        // Infinite solutions (div by 0).
-        if obj_ray.direction.y.abs() < 1e-6 {
+        // Infinite solutions (div by 0). Relative to the length of the ray, which scaling changes.
+        if obj_ray.direction.y.abs() < 1e-6 * obj_ray.direction.norm() {
+            return None;
+        }
+        if self.one_sided && obj_ray.direction.y > 0.0 {
             return None;
         }
         
@@ -125,13 +221,21 @@ impl Object for Disk {
 
         let point = obj_ray.at(t);
         let distance = (point - Point3::origin()).magnitude();
-        if distance > 1.0 {
+        if distance > self.radius || distance < self.inner_radius || !in_sweep(&point, self.theta_min, self.theta_max) {
             None
         } else {
             Some(vec![t])
         }
     }
 
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::new(Point3::new(-self.radius, 0.0, -self.radius), Point3::new(self.radius, 0.0, self.radius))
+    }
+
+    fn uv_at(&self, obj_point: &Point3) -> (f64, f64) {
+        Mapping::Planar.uv(obj_point)
+    }
+
     fn normal_obj(&self, _point: &Point3) -> Vec3 {
         Vec3::new(0.0, 1.0, 0.0)
     }
@@ -140,13 +244,28 @@ impl Object for Disk {
         &self.material
     }
 
-    fn id(&self) -> usize {
+    fn material_for(&self, front_face: bool) -> &Arc<Material> {
+        match &self.material_back {
+            Some(back) if !front_face => back,
+            _ => &self.material,
+        }
+    }
+
+    fn id(&self) -> ObjectId {
         self.id
     }
 
-    fn set_id(&mut self, id: usize) {
+    fn set_id(&mut self, id: ObjectId) {
         self.id = id;
     }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
 }
 
 impl Transformable for Disk {
@@ -167,3 +286,114 @@ impl Transformable for Disk {
         &self.inverse
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Colour;
+
+    #[test]
+    fn test_material_back() {
+        let mut plane = Plane::new(Material::matte(Colour::new(1.0, 0.0, 0.0)));
+        plane.set_material_back(Material::matte(Colour::new(0.0, 0.0, 1.0)));
+
+        let from_above = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = &plane.hit(&from_above, 0.0, f64::INFINITY).unwrap()[0];
+        assert_eq!(hit.colour, Colour::new(1.0, 0.0, 0.0));
+
+        let from_below = Ray::new(Point3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = &plane.hit(&from_below, 0.0, f64::INFINITY).unwrap()[0];
+        assert_eq!(hit.colour, Colour::new(0.0, 0.0, 1.0));
+        assert!(Arc::ptr_eq(&hit.material, plane.material_back.as_ref().unwrap()));
+
+        // Without a back material both sides match.
+        let disk = Disk::new(Material::matte(Colour::new(1.0, 0.0, 0.0)));
+        let hit = &disk.hit(&from_below, 0.0, f64::INFINITY).unwrap()[0];
+        assert_eq!(hit.colour, Colour::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_disk_sweep() {
+        let mut disk = Disk::new(Material::default());
+        disk.set_sweep(-45.0, 45.0);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+
+        assert!(disk.hit(&Ray::new(Point3::new(0.5, 1.0, 0.2), down), 0.0, f64::INFINITY).is_some());
+        assert!(disk.hit(&Ray::new(Point3::new(0.5, 1.0, -0.2), down), 0.0, f64::INFINITY).is_some());
+        assert!(disk.hit(&Ray::new(Point3::new(0.2, 1.0, 0.5), down), 0.0, f64::INFINITY).is_none());
+        assert!(disk.hit(&Ray::new(Point3::new(-0.5, 1.0, 0.0), down), 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_disk_inner_radius() {
+        let mut disk = Disk::new(Material::default());
+        disk.set_inner_radius(0.5);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+
+        // Straight through the hole, including its centre.
+        assert!(disk.hit(&Ray::new(Point3::new(0.0, 1.0, 0.0), down), 0.0, f64::INFINITY).is_none());
+        assert!(disk.hit(&Ray::new(Point3::new(0.3, 1.0, -0.3), down), 0.0, f64::INFINITY).is_none());
+        // On the ring, and exactly on the inner edge.
+        assert!(disk.hit(&Ray::new(Point3::new(0.0, 1.0, 0.75), down), 0.0, f64::INFINITY).is_some());
+        assert!(disk.hit(&Ray::new(Point3::new(0.5, 1.0, 0.0), down), 0.0, f64::INFINITY).is_some());
+        assert!(disk.hit(&Ray::new(Point3::new(0.5 - 1e-9, 1.0, 0.0), down), 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_one_sided() {
+        let mut plane = Plane::new(Material::default());
+        plane.rotate(crate::Axis::X, 90.0);
+        plane.set_one_sided(true);
+        let mut disk = Disk::new(Material::default());
+        disk.set_one_sided(true);
+
+        // The plane now faces +z.
+        let front = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let back = Ray::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(plane.hit(&front, 0.0, f64::INFINITY).is_some());
+        assert!(plane.hit(&back, 0.0, f64::INFINITY).is_none());
+
+        let from_above = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let from_below = Ray::new(Point3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(disk.hit(&from_above, 0.0, f64::INFINITY).is_some());
+        assert!(disk.hit(&from_below, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_one_sided_shadow() {
+        use crate::{Colour, Light, Scene};
+        // A ceiling facing up, with the light above and a point below.
+        let mut ceiling = Plane::new(Material::default());
+        ceiling.translate(0.0, 5.0, 0.0);
+        let mut scene = Scene::default();
+        scene.lights.push(Light::new(Point3::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+        scene.push(Box::new(ceiling));
+        assert!(scene.is_shadowed(&Point3::origin(), &mut rand::thread_rng()));
+
+        scene.objects.clear();
+        let mut ceiling = Plane::new(Material::default());
+        ceiling.translate(0.0, 5.0, 0.0);
+        ceiling.set_one_sided(true);
+        scene.push(Box::new(ceiling));
+        assert!(!scene.is_shadowed(&Point3::origin(), &mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn test_disk_radius() {
+        use crate::pattern::Stripes;
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let stripes = || Material { pattern: Some(Arc::new(Stripes::new(white, black))), ..Default::default() };
+        let disk = Disk::with_radius(stripes(), 3.0);
+
+        let down = |x: f64| Ray::new(Point3::new(x, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(disk.hit(&down(2.999), 0.0, f64::INFINITY).is_some());
+        assert!(disk.hit(&down(3.001), 0.0, f64::INFINITY).is_none());
+
+        // Stripes stay a unit wide, where scaling a unit disk stretches them.
+        let mut scaled = Disk::new(stripes());
+        scaled.scale(3.0, 1.0, 3.0);
+        assert_eq!(disk.hit(&down(1.5), 0.0, f64::INFINITY).unwrap()[0].colour, black);
+        assert_eq!(scaled.hit(&down(1.5), 0.0, f64::INFINITY).unwrap()[0].colour, white);
+        assert_eq!(disk.bounds(), scaled.bounds());
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Result, Context, anyhow, bail};
+use crate::{Colour, Point3, Vec3};
+
+// What a PLY file gives a mesh. Normals and colours are per vertex, and
+// empty if the file doesn't have them.
+#[derive(Debug, Default)]
+pub(crate) struct PlyData {
+    pub vertices: Vec<Point3>,
+    pub normals:  Vec<Vec3>,
+    pub colours:  Vec<Colour>,
+    pub faces:    Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8, U8, I16, U16, I32, U32, F32, F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => bail!("Unknown PLY property type \"{}\"", name),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Largest value, so integer colours can be scaled to 0 - 1.
+    fn colour_scale(&self) -> f64 {
+        match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Property {
+    name:   String,
+    scalar: Scalar,
+    // Type of the length before a list's items, for list properties.
+    count:  Option<Scalar>,
+}
+
+#[derive(Debug)]
+struct Element {
+    name:       String,
+    count:      usize,
+    properties: Vec<Property>,
+}
+
+// Where values are read from after the header.
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary(&'a [u8]),
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(|| anyhow!("PLY file ends early"))?;
+                token.parse().with_context(|| format!("Invalid PLY value \"{}\"", token))
+            },
+            Body::Binary(bytes) => {
+                let size = scalar.size();
+                if bytes.len() < size {
+                    bail!("PLY file ends early");
+                }
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+                Ok(match scalar {
+                    Scalar::I8 => value[0] as i8 as f64,
+                    Scalar::U8 => value[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([value[0], value[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([value[0], value[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes(value.try_into()?) as f64,
+                    Scalar::U32 => u32::from_le_bytes(value.try_into()?) as f64,
+                    Scalar::F32 => f32::from_le_bytes(value.try_into()?) as f64,
+                    Scalar::F64 => f64::from_le_bytes(value.try_into()?),
+                })
+            },
+        }
+    }
+}
+
+// Reads vertex and face elements from an ASCII or little endian binary PLY
+// file. Other elements and properties are skipped.
+pub(crate) fn parse(bytes: &[u8]) -> Result<PlyData> {
+    let header_end = bytes.windows(10).position(|w| w == b"end_header")
+        .ok_or_else(|| anyhow!("PLY file has no end_header"))?;
+    let body_start = bytes[header_end..].iter().position(|&b| b == b'\n')
+        .map_or(bytes.len(), |i| header_end + i + 1);
+    let header = std::str::from_utf8(&bytes[..header_end]).context("PLY header is not text")?;
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        bail!("Not a PLY file");
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", other, _] => bail!("Unsupported PLY format {}", other),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().with_context(|| format!("Invalid PLY element count \"{}\"", count))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, scalar, name] => elements.last_mut()
+                .ok_or_else(|| anyhow!("PLY property {} before any element", name))?
+                .properties.push(Property { name: name.to_string(), scalar: Scalar::parse(scalar)?, count: Some(Scalar::parse(count)?) }),
+            ["property", scalar, name] => elements.last_mut()
+                .ok_or_else(|| anyhow!("PLY property {} before any element", name))?
+                .properties.push(Property { name: name.to_string(), scalar: Scalar::parse(scalar)?, count: None }),
+            ["comment", ..] | ["obj_info", ..] | [] => {},
+            _ => bail!("Invalid PLY header line \"{}\"", line),
+        }
+    }
+
+    let mut body = match format {
+        Some(Format::Ascii) => Body::Ascii(std::str::from_utf8(&bytes[body_start..]).context("PLY body is not text")?.split_ascii_whitespace()),
+        Some(Format::BinaryLittleEndian) => Body::Binary(&bytes[body_start..]),
+        None => bail!("PLY file has no format line"),
+    };
+
+    let mut data = PlyData::default();
+    for element in &elements {
+        let find = |name: &str| element.properties.iter().position(|p| p.name == name);
+        let position = [find("x"), find("y"), find("z")];
+        let normal = [find("nx"), find("ny"), find("nz")];
+        let colour = [find("red"), find("green"), find("blue")];
+        let indices = find("vertex_indices").or_else(|| find("vertex_index"));
+
+        for _ in 0..element.count {
+            let mut values = Vec::with_capacity(element.properties.len());
+            let mut list = vec![];
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.count {
+                    Some(count) => {
+                        let len = body.read(count)? as usize;
+                        let items = (0..len).map(|_| body.read(property.scalar)).collect::<Result<Vec<_>>>()?;
+                        if Some(i) == indices {
+                            list = items;
+                        }
+                        values.push(0.0);
+                    },
+                    None => values.push(body.read(property.scalar)?),
+                }
+            }
+
+            let get = |index: [Option<usize>; 3]| index.map(|i| i.map(|i| values[i]));
+            match element.name.as_str() {
+                "vertex" => {
+                    let [Some(x), Some(y), Some(z)] = get(position) else {
+                        bail!("PLY vertices need x, y and z");
+                    };
+                    data.vertices.push(Point3::new(x, y, z));
+                    if let [Some(x), Some(y), Some(z)] = get(normal) {
+                        data.normals.push(Vec3::new(x, y, z));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = get(colour) {
+                        let scale = element.properties[colour[0].unwrap()].scalar.colour_scale();
+                        data.colours.push(Colour::new(r / scale, g / scale, b / scale));
+                    }
+                },
+                "face" => {
+                    if indices.is_none() {
+                        bail!("PLY faces need vertex_indices");
+                    }
+                    data.faces.push(list.into_iter().map(|i| i as usize).collect());
+                },
+                _ => {},
+            }
+        }
+    }
+    Ok(data)
+}
@@ -0,0 +1,270 @@
+use std::f64::consts::TAU;
+use std::sync::Arc;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec2, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::transform::Transformable;
+
+// A convex polygon in the xz plane extruded along y between min and max.
+#[derive(Debug)]
+pub struct Prism {
+    pub id:        ObjectId,
+    pub min:       f64,
+    pub max:       f64,
+    pub capped:    bool,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    // A point on each side and its outward normal, as (x, z).
+    sides:         Vec<(Vec2, Vec2)>,
+}
+
+impl Prism {
+    // True if the (x, z) points go once around a convex polygon, in either direction.
+    pub fn is_convex(points: &[(f64, f64)]) -> bool {
+        if points.len() < 3 {
+            return false;
+        }
+
+        let mut sign = 0.0;
+        let mut turning = 0.0;
+        for (i, &a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            let c = points[(i + 2) % points.len()];
+            let (ab, bc) = (Vec2::new(b.0 - a.0, b.1 - a.1), Vec2::new(c.0 - b.0, c.1 - b.1));
+            if ab.norm() < 1e-12 {
+                return false;
+            }
+
+            let cross = ab.perp(&bc);
+            if cross.abs() > 1e-12 {
+                if sign == 0.0 {
+                    sign = cross.signum();
+                } else if cross.signum() != sign {
+                    return false;
+                }
+            }
+            turning += cross.atan2(ab.dot(&bc));
+        }
+        // Star shaped polygons turn the same way at every corner but go round more than once.
+        sign != 0.0 && (turning.abs() - TAU).abs() < 1e-6
+    }
+
+    pub fn new(material: impl Into<Arc<Material>>, points: &[(f64, f64)], min: f64, max: f64, capped: bool) -> Self {
+        assert!(Self::is_convex(points), "prism polygon must be convex");
+
+        let vertices: Vec<Vec2> = points.iter().map(|&(x, z)| Vec2::new(x, z)).collect();
+        let centre = vertices.iter().sum::<Vec2>() / vertices.len() as f64;
+        let sides = vertices.iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .map(|(&a, &b)| {
+                let edge = b - a;
+                let normal = Vec2::new(edge.y, -edge.x).normalize();
+                // Works for either winding.
+                if normal.dot(&(centre - a)) > 0.0 { (a, -normal) } else { (a, normal) }
+            })
+            .collect();
+
+        Self {
+            id: ObjectId::new(),
+            min,
+            max,
+            capped,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            sides,
+        }
+    }
+}
+
+impl Object for Prism {
+
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let origin = Vec2::new(obj_ray.origin.x, obj_ray.origin.z);
+        let direction = Vec2::new(obj_ray.direction.x, obj_ray.direction.z);
+
+        // Clip the ray against each side's half plane.
+        let mut close = -f64::INFINITY;
+        let mut far = f64::INFINITY;
+        for (point, normal) in &self.sides {
+            let dist = normal.dot(&(origin - point));
+            let denom = normal.dot(&direction);
+            if denom.abs() < 1e-12 {
+                if dist > 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let t = -dist / denom;
+            if denom < 0.0 {
+                close = close.max(t);
+            } else {
+                far = far.min(t);
+            }
+        }
+        if close > far {
+            return None;
+        }
+
+        let mut hits = vec![];
+        if self.capped {
+            // Closed prisms are convex solids, clip against the caps as well.
+            if obj_ray.direction.y.abs() < 1e-12 {
+                if obj_ray.origin.y < self.min || obj_ray.origin.y > self.max {
+                    return None;
+                }
+            } else {
+                let mut t0 = (self.min - obj_ray.origin.y) / obj_ray.direction.y;
+                let mut t1 = (self.max - obj_ray.origin.y) / obj_ray.direction.y;
+                if t0 > t1 {
+                    std::mem::swap(&mut t0, &mut t1);
+                }
+                close = close.max(t0);
+                far = far.min(t1);
+            }
+            if close > far {
+                return None;
+            }
+            hits.extend([close, far].into_iter().filter(|&t| t > t_min && t < t_max));
+        } else {
+            hits.extend([close, far].into_iter().filter(|&t| {
+                let y = obj_ray.origin.y + t * obj_ray.direction.y;
+                t > t_min && t < t_max && y > self.min && y < self.max
+            }));
+        }
+
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    // Sides start at each corner of the polygon.
+    fn bounds_obj(&self) -> Bounds {
+        let corners: Vec<Point3> = self.sides.iter().map(|(a, _)| Point3::new(a.x, 0.0, a.y)).collect();
+        let polygon = Bounds::from_points(&corners);
+        Bounds::new(Point3::new(polygon.min.x, self.min, polygon.min.z), Point3::new(polygon.max.x, self.max, polygon.max.z))
+    }
+
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let p = Vec2::new(point.x, point.z);
+
+        // Normal of whichever face the point is closest to (least inside).
+        let mut best = (-f64::INFINITY, Vec3::zeros());
+        for (q, normal) in &self.sides {
+            let dist = normal.dot(&(p - q));
+            if dist > best.0 {
+                best = (dist, Vec3::new(normal.x, 0.0, normal.y));
+            }
+        }
+        if self.capped {
+            if point.y - self.max > best.0 {
+                best = (point.y - self.max, Vec3::new(0.0, 1.0, 0.0));
+            }
+            if self.min - point.y > best.0 {
+                best = (self.min - point.y, Vec3::new(0.0, -1.0, 0.0));
+            }
+        }
+        best.1
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for Prism {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::object::AxisAlignedBoundingBox;
+
+    #[test]
+    fn test_prism_box() {
+        // Clockwise winding, to check sides still face outwards.
+        let prism = Prism::new(Material::default(), &[(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)], -1.0, 1.0, true);
+        let bbox = AxisAlignedBoundingBox::new(Material::default());
+
+        let rays = [
+            Ray::new(Point3::new(5.0, 0.5, 0.0), Vec3::new(-1.0, 0.0, 0.0)),
+            Ray::new(Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+            Ray::new(Point3::new(0.5, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0)),
+            Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+            Ray::new(Point3::new(-3.0, 2.0, 0.3), Vec3::new(1.0, -0.7, 0.1)),
+            Ray::new(Point3::new(-2.0, 0.0, 0.0), Vec3::new(0.2673, 0.5345, 0.8018)),
+            Ray::new(Point3::new(2.0, 2.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)),
+        ];
+        for ray in rays {
+            let hits = prism.hit_obj(&ray, -f64::INFINITY, f64::INFINITY);
+            assert_eq!(hits, bbox.hit_obj(&ray, -f64::INFINITY, f64::INFINITY));
+            for t in hits.unwrap_or_default() {
+                let point = ray.at(t);
+                assert!(fuzzy_eq_vec(&prism.normal_obj(&point), &bbox.normal_obj(&point)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_prism_hexagon_cap() {
+        let hexagon: Vec<(f64, f64)> = (0..6)
+            .map(|i| (i as f64 * TAU / 6.0).sin_cos())
+            .collect();
+        let prism = Prism::new(Material::default(), &hexagon, 0.0, 1.0, true);
+
+        let ray = Ray::new(Point3::new(0.2, 5.0, 0.1), Vec3::new(0.0, -1.0, 0.0));
+        let hits = prism.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(hits[0].t, 4.0));
+        assert_eq!(hits[0].normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // Open prisms only have sides.
+        let prism = Prism::new(Material::default(), &hexagon, 0.0, 1.0, false);
+        assert!(prism.hit(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_convex() {
+        assert!(Prism::is_convex(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]));
+        assert!(!Prism::is_convex(&[(0.0, 0.0), (1.0, 0.0)]));
+        // Dent in one side.
+        assert!(!Prism::is_convex(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (1.0, 1.0), (0.0, 2.0)]));
+        // Pentagram.
+        let star: Vec<(f64, f64)> = (0..5)
+            .map(|i| (i as f64 * 2.0 * TAU / 5.0).sin_cos())
+            .collect();
+        assert!(!Prism::is_convex(&star));
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt;
+use std::sync::Arc;
+use serde::Deserialize;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::transform::Transformable;
+
+pub type Sdf = Box<dyn Fn(&Point3) -> f64 + Send + Sync>;
+
+// A surface given by a signed distance function, negative inside, found by
+// sphere tracing. The function must never overestimate the distance to the
+// surface, or rays can step through it.
+pub struct SdfObject {
+    pub id:           ObjectId,
+    pub transform:    Matrix4,
+    pub inverse:      Matrix4,
+    pub material:     Arc<Material>,
+    pub flags:        ObjectFlags,
+    // Steps before a ray is counted as a miss.
+    pub max_steps:    u32,
+    // Distance from the surface that counts as a hit.
+    pub hit_epsilon:  f64,
+    // Object space box around the surface. Rays are only marched through it,
+    // so they stop at the far side instead of running on until out of steps.
+    pub bounds:       Bounds,
+    sdf:              Sdf,
+}
+
+impl fmt::Debug for SdfObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SdfObject")
+            .field("id", &self.id)
+            .field("transform", &self.transform)
+            .field("material", &self.material)
+            .field("max_steps", &self.max_steps)
+            .field("hit_epsilon", &self.hit_epsilon)
+            .field("bounds", &self.bounds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SdfObject {
+    pub fn new(material: impl Into<Arc<Material>>, sdf: impl Fn(&Point3) -> f64 + Send + Sync + 'static) -> Self {
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            max_steps: 256,
+            hit_epsilon: 1e-6,
+            bounds: Bounds::infinite(),
+            sdf: Box::new(sdf),
+        }
+    }
+
+    pub fn distance(&self, point: &Point3) -> f64 {
+        (self.sdf)(point)
+    }
+}
+
+// Built in distance functions, selectable from scene files.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum SdfShape {
+    RoundedBox {
+        // Half the box's extent along each axis, including the rounding.
+        half_size: [f64; 3],
+        radius:    f64,
+    },
+    // Gyroid sheet filling the cube from -1 to 1.
+    Gyroid {
+        // Repeats per unit length, in radians.
+        scale:     f64,
+        thickness: f64,
+    },
+    // Spheres merged together with a smooth minimum.
+    SphereBlend {
+        // (centre, radius) of each sphere.
+        spheres:    Vec<([f64; 3], f64)>,
+        smoothness: f64,
+    },
+}
+
+impl SdfShape {
+    // Object space box the surface lies in.
+    pub fn bounds(&self) -> Bounds {
+        match self {
+            SdfShape::RoundedBox { half_size, .. } => Bounds::new(Point3::from(-Vec3::from(*half_size)), Point3::from(*half_size)),
+            SdfShape::Gyroid { .. } => Bounds::new(Point3::from(Vec3::repeat(-1.0)), Point3::from(Vec3::repeat(1.0))),
+            // The smooth minimum is at most a quarter of the smoothness below the plain one.
+            SdfShape::SphereBlend { spheres, smoothness } => spheres.iter()
+                .map(|&(centre, radius)| {
+                    let reach = Vec3::repeat(radius + smoothness.max(0.0) / 4.0);
+                    Bounds::new(Point3::from(centre) - reach, Point3::from(centre) + reach)
+                })
+                .fold(Bounds::empty(), |bounds, sphere| bounds.union(&sphere)),
+        }
+    }
+
+    pub fn build(self) -> Sdf {
+        match self {
+            SdfShape::RoundedBox { half_size, radius } => Box::new(rounded_box(Vec3::from(half_size), radius)),
+            SdfShape::Gyroid { scale, thickness } => Box::new(gyroid(scale, thickness)),
+            SdfShape::SphereBlend { spheres, smoothness } => {
+                let spheres = spheres.into_iter().map(|(c, r)| (Point3::from(c), r)).collect();
+                Box::new(sphere_blend(spheres, smoothness))
+            },
+        }
+    }
+}
+
+pub fn rounded_box(half_size: Vec3, radius: f64) -> impl Fn(&Point3) -> f64 + Send + Sync {
+    move |p| {
+        let q = p.coords.abs() - half_size + Vec3::repeat(radius);
+        q.sup(&Vec3::zeros()).norm() + q.max().min(0.0) - radius
+    }
+}
+
+pub fn gyroid(scale: f64, thickness: f64) -> impl Fn(&Point3) -> f64 + Send + Sync {
+    let bounds = rounded_box(Vec3::repeat(1.0), 0.0);
+    move |p| {
+        let s = p.coords * scale;
+        let value = s.x.sin() * s.y.cos() + s.y.sin() * s.z.cos() + s.z.sin() * s.x.cos();
+        // Not a true distance, the gradient can reach sqrt(3) * scale, so scale it down to stay under.
+        let sheet = (value.abs() - thickness) / (scale * 3f64.sqrt());
+        sheet.max(bounds(p))
+    }
+}
+
+pub fn sphere_blend(spheres: Vec<(Point3, f64)>, smoothness: f64) -> impl Fn(&Point3) -> f64 + Send + Sync {
+    move |p| {
+        spheres.iter()
+            .map(|(centre, radius)| (p - centre).norm() - radius)
+            .reduce(|a, b| smooth_min(a, b, smoothness))
+            .unwrap_or(f64::INFINITY)
+    }
+}
+
+// Polynomial smooth minimum, blending within k of where a and b are equal.
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b * (1.0 - h) + a * h - k * h * (1.0 - h)
+}
+
+impl Object for SdfObject {
+
+    // The nearest hit only. Rays starting inside the surface miss.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let (enter, exit) = self.bounds.ray_range(obj_ray)?;
+        let t_max = t_max.min(exit);
+        // Object space rays aren't unit length once scaled.
+        let speed = obj_ray.direction.norm();
+        let mut t = t_min.max(0.0).max(enter);
+        if self.distance(&obj_ray.at(t)) < 0.0 {
+            return None;
+        }
+
+        for _ in 0..self.max_steps {
+            if t >= t_max {
+                return None;
+            }
+            let dist = self.distance(&obj_ray.at(t));
+            if dist < self.hit_epsilon {
+                return if t > t_min { Some(vec![t]) } else { None };
+            }
+            t += dist / speed;
+        }
+        None
+    }
+
+    fn bounds_obj(&self) -> Bounds {
+        self.bounds
+    }
+
+    // Central differences of the distance.
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let h = 1e-5;
+        let diff = |axis: Vec3| self.distance(&(point + axis * h)) - self.distance(&(point - axis * h));
+        Vec3::new(diff(Vec3::x()), diff(Vec3::y()), diff(Vec3::z())).normalize()
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for SdfObject {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::object::Sphere;
+
+    fn unit_sphere() -> SdfObject {
+        SdfObject::new(Material::default(), |p: &Point3| p.coords.norm() - 1.0)
+    }
+
+    #[test]
+    fn test_sdf_matches_sphere() {
+        let mut sdf = unit_sphere();
+        let mut sphere = Sphere::new(Material::default());
+        sdf.scale(2.0, 1.0, 1.0);
+        sphere.scale(2.0, 1.0, 1.0);
+
+        let ray = Ray::new(Point3::new(-5.0, 0.3, -4.0), Vec3::new(1.0, 0.0, 1.0).normalize());
+        let expected = &sphere.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        let hit = &sdf.hit(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        assert!(fuzzy_eq_f64(hit.t, expected.t));
+        assert!(fuzzy_eq_vec(&hit.normal, &expected.normal));
+    }
+
+    #[test]
+    fn test_sdf_misses() {
+        let sdf = unit_sphere();
+        // Starting inside.
+        let ray = Ray::new(Point3::origin(), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sdf.hit_obj(&ray, 0.0, f64::INFINITY).is_none());
+        // Past the edge, and beyond t_max.
+        let ray = Ray::new(Point3::new(0.0, 1.5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sdf.hit_obj(&ray, 0.0, f64::INFINITY).is_none());
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sdf.hit_obj(&ray, 0.0, 3.0).is_none());
+
+        // Grazing rays creep along the surface, and give up when out of steps.
+        let mut sdf = unit_sphere();
+        sdf.max_steps = 10;
+        let ray = Ray::new(Point3::new(0.0, 1.0 + 1e-5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sdf.hit_obj(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_sdf_bounds() {
+        // Marched to the box, however far away it is.
+        let mut sdf = unit_sphere();
+        sdf.bounds = Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Point3::new(0.0, 0.0, -500.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(fuzzy_eq_f64(sdf.hit_obj(&ray, 0.0, f64::INFINITY).unwrap()[0], 499.0));
+
+        // Missing the box misses, and passing through it stops at its far side.
+        sdf.max_steps = 20;
+        let ray = Ray::new(Point3::new(0.0, 1.5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(sdf.bounds.ray_range(&ray), None);
+        let ray = Ray::new(Point3::new(0.0, 1.0 + 1e-4, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sdf.hit_obj(&ray, 0.0, f64::INFINITY).is_none());
+
+        // Each shape's surface is inside its bounds.
+        let shapes = [
+            SdfShape::RoundedBox { half_size: [1.0, 0.5, 0.5], radius: 0.1 },
+            SdfShape::Gyroid { scale: 6.0, thickness: 0.3 },
+            SdfShape::SphereBlend { spheres: vec![([-1.0, 0.0, 0.0], 1.0), ([1.0, 0.0, 0.0], 0.5)], smoothness: 2.0 },
+        ];
+        for shape in shapes {
+            let bounds = shape.bounds();
+            let sdf = shape.build();
+            // Just past the middle of each face.
+            let centre = Point3::from((bounds.min.coords + bounds.max.coords) / 2.0);
+            for axis in 0..3 {
+                for (side, out) in [(bounds.min[axis], -1e-6), (bounds.max[axis], 1e-6)] {
+                    let mut point = centre;
+                    point[axis] = side + out;
+                    assert!(sdf(&point) > 0.0, "{:?} at {}", bounds, point);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sdf_shapes() {
+        let rounded = SdfShape::RoundedBox { half_size: [1.0, 0.5, 0.5], radius: 0.1 }.build();
+        assert!(fuzzy_eq_f64(rounded(&Point3::new(2.0, 0.0, 0.0)), 1.0));
+        assert!(fuzzy_eq_f64(rounded(&Point3::new(0.0, 0.0, 0.0)), -0.5));
+        // Corners are rounded off.
+        assert!(fuzzy_eq_f64(rounded(&Point3::new(1.0, 0.5, 0.5)), 0.1 * (3f64.sqrt() - 1.0)));
+
+        let blend = SdfShape::SphereBlend { spheres: vec![([-1.0, 0.0, 0.0], 1.0), ([1.0, 0.0, 0.0], 1.0)], smoothness: 0.5 }.build();
+        assert!(fuzzy_eq_f64(blend(&Point3::new(-3.0, 0.0, 0.0)), 1.0));
+        // Where they meet is filled in, closer than either sphere.
+        assert!(blend(&Point3::new(0.0, 1.0, 0.0)) < 2f64.sqrt() - 1.0 - 0.1);
+
+        let gyroid = SdfShape::Gyroid { scale: 6.0, thickness: 0.3 }.build();
+        assert!(fuzzy_eq_f64(gyroid(&Point3::new(3.0, 0.0, 0.0)), 2.0));
+        let sdf = SdfObject::new(Material::default(), gyroid);
+        let ray = Ray::new(Point3::new(0.1, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let t = sdf.hit_obj(&ray, 0.0, f64::INFINITY).unwrap()[0];
+        assert!(sdf.distance(&ray.at(t)).abs() < 1e-5);
+    }
+}
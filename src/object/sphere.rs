@@ -1,27 +1,40 @@
 use std::sync::Arc;
 use crate::transform::Transformable;
+use crate::pattern::Mapping;
 use crate::{Point3, Matrix4, Vec3};
-use crate::object::Object;
+use crate::object::{Object, Bounds, ObjectId, ObjectFlags, GRAZE_EPSILON};
 use crate::material::Material;
 use crate::ray::Ray;
 
 #[derive(Debug)]
 pub struct Sphere {
-    id:         usize,
+    id:         ObjectId,
     transform:  Matrix4,
     inverse:    Matrix4,
     material:   Arc<Material>,
+    flags:      ObjectFlags,
 }
 
 impl Sphere {
-    pub fn new(material: Material) -> Self {
+    pub fn new(material: impl Into<Arc<Material>>) -> Self {
         Self { 
-            id:        0,
+            id:        ObjectId::new(),
             transform: Matrix4::identity(), 
             inverse:   Matrix4::identity(),            
-            material:  Arc::new(material),
+            material:  material.into(),
+            flags:     ObjectFlags::default(),
         }
     }
+
+    // A unit sphere stretched to the given radius along each axis. The scale
+    // is part of the transform, so transforms added later are applied inside
+    // it. To move the ellipsoid, put the placement on top of the transform, as
+    // scene files do with an object's transform list.
+    pub fn ellipsoid(material: impl Into<Arc<Material>>, rx: f64, ry: f64, rz: f64) -> Self {
+        let mut sphere = Self::new(material);
+        sphere.scale(rx, ry, rz);
+        sphere
+    }
 }
 
 impl Object for Sphere {
@@ -44,6 +57,8 @@ impl Object for Sphere {
         // Discriminant tells us how many roots there are.
         let discriminant = half_b * half_b - a * c;
         if discriminant < 0.0 { return None; }
+        // Roots 2 * sqrt(discriminant) / a apart in t, so this far apart along the ray.
+        if 2.0 * (discriminant / a).sqrt() < GRAZE_EPSILON { return None; }
 
         let mut close_root = (-half_b - discriminant.sqrt()) / a;
         let mut far_root = (-half_b + discriminant.sqrt()) / a;
@@ -62,6 +77,14 @@ impl Object for Sphere {
         if t.is_empty() { None } else { Some(t) }
     }
 
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    fn uv_at(&self, obj_point: &Point3) -> (f64, f64) {
+        Mapping::Spherical.uv(obj_point)
+    }
+
     fn normal_obj(&self, point: &Point3) -> Vec3 {
         (point - Point3::origin()).normalize()
     }
@@ -70,13 +93,21 @@ impl Object for Sphere {
         &self.material
     }
 
-    fn id(&self) -> usize {
+    fn id(&self) -> ObjectId {
         self.id
     }
 
-    fn set_id(&mut self, id: usize) {
+    fn set_id(&mut self, id: ObjectId) {
         self.id = id;
     }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
 }
 
 impl Transformable for Sphere {
@@ -97,3 +128,46 @@ impl Transformable for Sphere {
         &self.inverse
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+
+    #[test]
+    fn test_ellipsoid_normals() {
+        let ellipsoid = Sphere::ellipsoid(Material::default(), 2.0, 0.5, 1.0);
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hits = ellipsoid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(hits[0].t, 4.5));
+        assert!(fuzzy_eq_vec(&hits[0].normal, &Vec3::new(0.0, 1.0, 0.0)));
+
+        // Off axis the normal is the gradient of x²/4 + 4y² + z², tilted towards the squashed axis.
+        let point = Point3::new(2.0 * 0.6, 0.5 * 0.8, 0.0);
+        let expected = Vec3::new(point.x / 4.0, point.y * 4.0, 0.0).normalize();
+        assert!(fuzzy_eq_vec(&ellipsoid.normal_at(&point), &expected));
+    }
+
+    #[test]
+    fn test_tangent_ray_misses() {
+        let sphere = Sphere::new(Material::glass());
+        let ray = |y: f64| Ray::new(Point3::new(0.0, y, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(sphere.hit(&ray(1.0), 0.0, f64::INFINITY).is_none());
+
+        // Just inside the edge the ray enters and leaves, and the indices pair up.
+        let mut hits = sphere.hit(&ray(1.0 - 1e-9), 0.0, f64::INFINITY).unwrap();
+        crate::intersection::compute_intersections(&mut hits);
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].front_face && !hits[1].front_face);
+        assert_eq!((hits[0].exit_idx, hits[0].enter_idx), (1.0, 1.52));
+        assert_eq!((hits[1].exit_idx, hits[1].enter_idx), (1.52, 1.0));
+
+        // Grazing the edge shows what's behind, not black.
+        let mut scene = crate::Scene::default();
+        let background = crate::Colour::new(0.5, 0.5, 0.5);
+        scene.background = background.into();
+        scene.lights.push(crate::Light::new(Point3::new(-10.0, 10.0, -10.0), crate::Colour::new(1.0, 1.0, 1.0)));
+        scene.push(Box::new(sphere));
+        assert!(crate::colour::fuzzy_eq_colour(scene.colour_at(&ray(1.0), 5), background));
+    }
+}
@@ -0,0 +1,253 @@
+use std::sync::Arc;
+use crate::{Matrix4, Material, Object, ray::Ray, Vec3, Point3};
+use crate::object::{Bounds, ObjectId, ObjectFlags};
+use crate::transform::Transformable;
+
+// Steps along the ray before it is counted as a miss.
+const MAX_STEPS: u32 = 512;
+// Object space distance from the surface that counts as a hit.
+const HIT_EPSILON: f64 = 1e-9;
+
+// A superquadric filling the cube from -1 to 1, with y as its polar axis.
+// e1 rounds it from the top down and e2 around y. 1 is a sphere, towards 0 a
+// box, and 2 an octahedron. Above 2 it isn't convex, which the marcher needs.
+#[derive(Debug)]
+pub struct Superellipsoid {
+    pub id:        ObjectId,
+    pub transform: Matrix4,
+    pub inverse:   Matrix4,
+    pub material:  Arc<Material>,
+    pub flags:     ObjectFlags,
+    e1:            f64,
+    e2:            f64,
+    // Radius of a ball inside the surface, which bounds how fast size grows.
+    inner_radius:  f64,
+}
+
+impl Superellipsoid {
+    pub fn new(material: impl Into<Arc<Material>>, e1: f64, e2: f64) -> Self {
+        assert!(e1 > 0.0 && e1 <= 2.0 && e2 > 0.0 && e2 <= 2.0, "superellipsoid exponents must be above 0 and at most 2");
+        // Above 1 the surface is closest to the centre on the diagonals.
+        let diagonal = |e: f64| 2f64.powf((e - 1.0) / 2.0).max(1.0);
+        Self {
+            id: ObjectId::new(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            material: material.into(),
+            flags: ObjectFlags::default(),
+            e1,
+            e2,
+            inner_radius: 1.0 / (diagonal(e1) * diagonal(e2)),
+        }
+    }
+
+    pub fn exponents(&self) -> (f64, f64) {
+        (self.e1, self.e2)
+    }
+
+    // How many times bigger the surface would need to be to pass through the
+    // point, 1 on it. The usual inside-outside function to the power e1 / 2.
+    pub fn size_at(&self, point: &Point3) -> f64 {
+        let h = power_norm(point.x, point.z, 2.0 / self.e2);
+        power_norm(h, point.y, 2.0 / self.e1)
+    }
+
+    // Size grows by at most 1 / inner_radius per unit moved, so this never
+    // overestimates the distance to the surface from outside.
+    fn distance(&self, point: &Point3) -> f64 {
+        (self.size_at(point) - 1.0) * self.inner_radius
+    }
+
+    // Sphere traces from start towards end, both inside the cube.
+    fn march(&self, ray: &Ray, start: f64, end: f64) -> Option<f64> {
+        let step = (end - start).signum() / ray.direction.norm();
+        let mut t = start;
+        for _ in 0..MAX_STEPS {
+            let dist = self.distance(&ray.at(t));
+            if dist < HIT_EPSILON {
+                return Some(t);
+            }
+            t += dist * step;
+            if (t - end) * step > 0.0 {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+// (|a|^p + |b|^p)^(1 / p), scaled by the larger so large powers don't overflow.
+fn power_norm(a: f64, b: f64, p: f64) -> f64 {
+    let (a, b) = (a.abs(), b.abs());
+    let m = a.max(b);
+    if m == 0.0 {
+        return 0.0;
+    }
+    m * ((a / m).powf(p) + (b / m).powf(p)).powf(1.0 / p)
+}
+
+// sign(v) * (|v| / of)^p, 0 at 0.
+fn signed_ratio(v: f64, of: f64, p: f64) -> f64 {
+    if v == 0.0 { 0.0 } else { (v.abs() / of).powf(p).copysign(v) }
+}
+
+impl Object for Superellipsoid {
+
+    // Convex, so the ray goes in once, found marching forwards through the
+    // cube, and out once, found marching backwards.
+    fn hit_obj(&self, obj_ray: &Ray, t_min: f64, t_max: f64) -> Option<Vec<f64>> {
+        let (mut near, mut far) = (-f64::INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            let (origin, direction) = (obj_ray.origin[axis], obj_ray.direction[axis]);
+            if direction == 0.0 {
+                if origin.abs() > 1.0 {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((-1.0 - origin) / direction, (1.0 - origin) / direction);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        if near >= far {
+            return None;
+        }
+
+        let enter = self.march(obj_ray, near, far)?;
+        let exit = self.march(obj_ray, far, near)?;
+        if enter >= exit {
+            return None;
+        }
+        let hits: Vec<f64> = [enter, exit].into_iter().filter(|&t| t > t_min && t < t_max).collect();
+        if hits.is_empty() { None } else { Some(hits) }
+    }
+
+    fn bounds_obj(&self) -> Bounds {
+        Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    // Gradient of size, written in ratios no bigger than 1 so it doesn't
+    // overflow for boxy exponents.
+    fn normal_obj(&self, point: &Point3) -> Vec3 {
+        let (p1, p2) = (2.0 / self.e1, 2.0 / self.e2);
+        let h = power_norm(point.x, point.z, p2);
+        let size = power_norm(h, point.y, p1);
+        if size == 0.0 {
+            return Vec3::y();
+        }
+        let around = if h == 0.0 { 0.0 } else { (h / size).powf(p1 - 1.0) };
+        Vec3::new(
+            around * signed_ratio(point.x, h, p2 - 1.0),
+            signed_ratio(point.y, size, p1 - 1.0),
+            around * signed_ratio(point.z, h, p2 - 1.0),
+        ).normalize()
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ObjectId) {
+        self.id = id;
+    }
+
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+
+    fn flags_mut(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+}
+
+impl Transformable for Superellipsoid {
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::object::Sphere;
+
+    #[test]
+    fn test_superellipsoid_sphere() {
+        let mut round = Superellipsoid::new(Material::default(), 1.0, 1.0);
+        let mut sphere = Sphere::new(Material::default());
+        round.scale(2.0, 1.0, 1.0);
+        sphere.scale(2.0, 1.0, 1.0);
+
+        for ray in [
+            Ray::new(Point3::new(-5.0, 0.3, -4.0), Vec3::new(1.0, 0.0, 1.0).normalize()),
+            Ray::new(Point3::new(0.2, 5.0, 0.1), Vec3::new(0.0, -1.0, 0.0)),
+            Ray::new(Point3::new(0.5, 0.1, 0.0), Vec3::new(0.3, 0.2, 1.0).normalize()),
+        ] {
+            let expected = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+            let hits = round.hit(&ray, 0.0, f64::INFINITY).unwrap();
+            assert_eq!(hits.len(), expected.len());
+            for (hit, expected) in hits.iter().zip(&expected) {
+                assert!(fuzzy_eq_f64(hit.t, expected.t));
+                assert!(fuzzy_eq_vec(&hit.normal, &expected.normal));
+            }
+        }
+        let ray = Ray::new(Point3::new(0.0, 1.01, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(round.hit(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_superellipsoid_boxy() {
+        let boxy = Superellipsoid::new(Material::default(), 0.01, 0.01);
+        // Faces are flat, and close to filling the cube.
+        let ray = Ray::new(Point3::new(0.3, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let ts = boxy.hit_obj(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((ts[0] - 4.0).abs() < 1e-3 && (ts[1] - 6.0).abs() < 1e-3);
+        assert!(fuzzy_eq_vec(&boxy.normal_obj(&ray.at(ts[0])), &Vec3::new(0.0, 0.0, -1.0)));
+        let ray = Ray::new(Point3::new(0.95, 0.95, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(boxy.hit_obj(&ray, 0.0, f64::INFINITY).is_some());
+
+        // Skimming along a face gives up rather than looping, or hits.
+        for offset in [1e-3, 1e-6, 1e-9, 0.0] {
+            let ray = Ray::new(Point3::new(-5.0, 1.0 - offset, 0.0), Vec3::new(1.0, 0.0, 0.0));
+            if let Some(ts) = boxy.hit_obj(&ray, 0.0, f64::INFINITY) {
+                assert!(ts.iter().all(|&t| t > 3.9 && t < 6.1));
+            }
+        }
+        let ray = Ray::new(Point3::new(-5.0, 1.0, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(boxy.hit_obj(&ray, 0.0, f64::INFINITY).is_none());
+
+        // Rays starting inside only leave.
+        let ray = Ray::new(Point3::origin(), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(boxy.hit_obj(&ray, 0.0, f64::INFINITY).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_superellipsoid_pointy() {
+        // An octahedron, |x| + |y| + |z| = 1.
+        let pointy = Superellipsoid::new(Material::default(), 2.0, 2.0);
+        let ray = Ray::new(Point3::new(0.2, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let ts = pointy.hit_obj(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(ts[0], 4.4) && fuzzy_eq_f64(ts[1], 5.6));
+        assert!(fuzzy_eq_vec(&pointy.normal_obj(&ray.at(ts[0])), &Vec3::new(1.0, 1.0, -1.0).normalize()));
+        let ray = Ray::new(Point3::new(0.5, 0.6, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(pointy.hit_obj(&ray, 0.0, f64::INFINITY).is_none());
+    }
+}
@@ -1,6 +1,12 @@
 use std::fmt::Debug;
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use image::DynamicImage;
 
-use crate::{Point3, Colour, Matrix4};
+use crate::{Point3, Colour, Matrix4, Vec3, Axis};
+use crate::noise::Perlin;
 use crate::transform::Transformable;
 
 pub trait Pattern: Transformable + Send + Sync + Debug {
@@ -12,33 +18,107 @@ pub trait Pattern: Transformable + Send + Sync + Debug {
         let pattern_point = self.inverse().transform_point(&obj_point);
         self.colour_at_pattern(&pattern_point)
     }
+
+    // Colour at a surface's texture coordinates, for materials with
+    // uv_pattern. u and v are laid out along x and z, then transformed.
+    fn colour_at_uv(&self, u: f64, v: f64) -> Colour {
+        let pattern_point = self.inverse().transform_point(&Point3::new(u, 0.0, v));
+        self.colour_at_pattern(&pattern_point)
+    }
+
+    // Colour at a point in a parent pattern's space, for patterns nested in
+    // others, which keep their own transforms.
+    fn colour_at_nested(&self, point: &Point3) -> Colour {
+        let pattern_point = self.inverse().transform_point(point);
+        self.colour_at_pattern(&pattern_point)
+    }
+
+    // Patterns that only make sense on a surface, which always get its uv
+    // coordinates whether or not the material asks for them.
+    fn uv_space(&self) -> bool {
+        false
+    }
+}
+
+// Nudges points just under a cell boundary over it, so surfaces lying on one,
+// like the default floor, don't sparkle with floating point noise.
+const CELL_EPSILON: f64 = 1e-6;
+
+// What fills each half of a two colour pattern, a flat colour or a pattern
+// nested inside it, e.g. stripes with a gradient in every other stripe.
+#[derive(Debug, Clone)]
+pub enum PatternOrColour {
+    Colour(Colour),
+    Pattern(Arc<dyn Pattern>),
+}
+
+impl PatternOrColour {
+    // Point is in the outer pattern's space.
+    pub fn colour_at(&self, point: &Point3) -> Colour {
+        match self {
+            PatternOrColour::Colour(colour) => *colour,
+            PatternOrColour::Pattern(pattern) => pattern.colour_at_nested(point),
+        }
+    }
+}
+
+impl From<Colour> for PatternOrColour {
+    fn from(colour: Colour) -> Self {
+        PatternOrColour::Colour(colour)
+    }
+}
+
+impl From<Arc<dyn Pattern>> for PatternOrColour {
+    fn from(pattern: Arc<dyn Pattern>) -> Self {
+        PatternOrColour::Pattern(pattern)
+    }
 }
 
 #[derive(Debug)]
 pub struct Stripes {
-    a:          Colour,
-    b:          Colour,
+    a:          PatternOrColour,
+    b:          PatternOrColour,
+    // Stripes change along the axis, so x gives vertical stripes.
+    axis:       Axis,
+    width:      f64,
     transform:  Matrix4,
     inverse:    Matrix4,
 }
 
 impl Stripes {
-    pub fn new(a: Colour, b: Colour) -> Self {
+    pub fn new(a: impl Into<PatternOrColour>, b: impl Into<PatternOrColour>) -> Self {
         Self {
-            a,
-            b,
+            a: a.into(),
+            b: b.into(),
+            axis: Axis::X,
+            width: 1.0,
             transform: Matrix4::identity(),
             inverse: Matrix4::identity(),
         }
     }
+
+    pub fn with_axis(mut self, axis: Axis) -> Self {
+        self.axis = axis;
+        self
+    }
+
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = width;
+        self
+    }
 }
 
 impl Pattern for Stripes {
     fn colour_at_pattern(&self, point: &Point3) -> Colour {
-        if point.x.floor() as i32 % 2 == 0 {
-            self.a
+        let along = match self.axis {
+            Axis::X => point.x,
+            Axis::Y => point.y,
+            Axis::Z => point.z,
+        };
+        if (along / self.width).floor() as i32 % 2 == 0 {
+            self.a.colour_at(point)
         } else {
-            self.b
+            self.b.colour_at(point)
         }
     }
 }
@@ -62,30 +142,53 @@ impl Transformable for Stripes {
     }
 }
 
+// What gradients do past x = 1 and before x = 0.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GradientMode {
+    // a before 0 and b after 1.
+    Clamp,
+    // Starts again from a every unit, with a hard edge where b meets a.
+    #[default]
+    Repeat,
+    // a to b then back to a every two units, without the edge.
+    Mirror,
+}
+
 #[derive(Debug)]
 pub struct Gradient {
-    a:          Colour,
-    b:          Colour,
+    a:          PatternOrColour,
+    b:          PatternOrColour,
+    mode:       GradientMode,
     transform:  Matrix4,
     inverse:    Matrix4,
 }
 
 impl Gradient {
-    pub fn new(a: Colour, b: Colour) -> Self {
+    pub fn new(a: impl Into<PatternOrColour>, b: impl Into<PatternOrColour>) -> Self {
         Self {
-            a,
-            b,
+            a: a.into(),
+            b: b.into(),
+            mode: GradientMode::default(),
             transform: Matrix4::identity(),
             inverse: Matrix4::identity(),
         }
     }
+
+    pub fn with_mode(mut self, mode: GradientMode) -> Self {
+        self.mode = mode;
+        self
+    }
 }
 
 impl Pattern for Gradient {
     fn colour_at_pattern(&self, point: &Point3) -> Colour {
-        let distance = self.b - self.a;
-        let fraction = point.x - point.x.floor();
-        self.a + distance * fraction
+        let (a, b) = (self.a.colour_at(point), self.b.colour_at(point));
+        let fraction = match self.mode {
+            GradientMode::Clamp => point.x.clamp(0.0, 1.0),
+            GradientMode::Repeat => point.x - point.x.floor(),
+            GradientMode::Mirror => 1.0 - (point.x.rem_euclid(2.0) - 1.0).abs(),
+        };
+        a + (b - a) * fraction
     }
 }
 
@@ -110,17 +213,17 @@ impl Transformable for Gradient {
 
 #[derive(Debug)]
 pub struct Rings {
-    a:          Colour,
-    b:          Colour,
+    a:          PatternOrColour,
+    b:          PatternOrColour,
     transform:  Matrix4,
     inverse:    Matrix4,
 }
 
 impl Rings {
-    pub fn new(a: Colour, b: Colour) -> Self {
+    pub fn new(a: impl Into<PatternOrColour>, b: impl Into<PatternOrColour>) -> Self {
         Self {
-            a,
-            b,
+            a: a.into(),
+            b: b.into(),
             transform: Matrix4::identity(),
             inverse: Matrix4::identity(),
         }
@@ -130,9 +233,9 @@ impl Rings {
 impl Pattern for Rings {
     fn colour_at_pattern(&self, point: &Point3) -> Colour {
         if (point.x.powi(2) + point.z.powi(2)).sqrt().floor() as i32 % 2 == 0 {
-            self.a
+            self.a.colour_at(point)
         } else {
-            self.b
+            self.b.colour_at(point)
         }
     }
 }
@@ -158,15 +261,276 @@ impl Transformable for Rings {
 
 #[derive(Debug)]
 pub struct Checkers {
+    a:          PatternOrColour,
+    b:          PatternOrColour,
+    transform:  Matrix4,
+    inverse:    Matrix4,
+}
+
+impl Checkers {
+    pub fn new(a: impl Into<PatternOrColour>, b: impl Into<PatternOrColour>) -> Self {
+        Self {
+            a: a.into(),
+            b: b.into(),
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for Checkers {
+    fn colour_at_pattern(&self, point: &Point3) -> Colour {
+        let cell = |v: f64| (v + CELL_EPSILON).floor() as i32;
+        if (cell(point.x) + cell(point.y) + cell(point.z)) % 2 == 0 {
+            self.a.colour_at(point)
+        } else {
+            self.b.colour_at(point)
+        }
+    }
+}
+
+impl Transformable for Checkers {
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+}
+
+// Fractal Perlin noise blending from a to b, for marble, clouds and the like.
+// Seeded, so the same seed gives the same pattern every render.
+#[derive(Debug)]
+pub struct Noise {
+    a:           Colour,
+    b:           Colour,
+    perlin:      Perlin,
+    // Features per unit, higher for finer noise.
+    scale:       f64,
+    octaves:     u32,
+    // How much each octave counts compared to the last.
+    persistence: f64,
+    transform:   Matrix4,
+    inverse:     Matrix4,
+}
+
+impl Noise {
+    pub fn new(a: Colour, b: Colour, seed: u64) -> Self {
+        Self {
+            a,
+            b,
+            perlin: Perlin::new(seed),
+            scale: 1.0,
+            octaves: 1,
+            persistence: 0.5,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: u32, persistence: f64) -> Self {
+        self.octaves = octaves;
+        self.persistence = persistence;
+        self
+    }
+}
+
+impl Pattern for Noise {
+    fn colour_at_pattern(&self, point: &Point3) -> Colour {
+        let noise = self.perlin.fractal(&(point * self.scale), self.octaves, self.persistence);
+        let fraction = ((noise + 1.0) / 2.0).clamp(0.0, 1.0);
+        self.a + (self.b - self.a) * fraction
+    }
+}
+
+impl Transformable for Noise {
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+}
+
+#[derive(Debug)]
+pub enum BlendMode {
+    Average,
+    Multiply,
+    // From a where the mask is black to b where it's white, by its average
+    // channel, so a gradient mask fades between them.
+    Mask(Arc<dyn Pattern>),
+}
+
+// Two patterns combined, e.g. checkers masking two stripes to get squares
+// striped different ways.
+#[derive(Debug)]
+pub struct Blend {
+    a:          Arc<dyn Pattern>,
+    b:          Arc<dyn Pattern>,
+    mode:       BlendMode,
+    transform:  Matrix4,
+    inverse:    Matrix4,
+}
+
+impl Blend {
+    pub fn new(a: Arc<dyn Pattern>, b: Arc<dyn Pattern>, mode: BlendMode) -> Self {
+        Self {
+            a,
+            b,
+            mode,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+}
+
+impl Pattern for Blend {
+    fn colour_at_pattern(&self, point: &Point3) -> Colour {
+        let (a, b) = (self.a.colour_at_nested(point), self.b.colour_at_nested(point));
+        match &self.mode {
+            BlendMode::Average => (a + b) * 0.5,
+            BlendMode::Multiply => a * b,
+            BlendMode::Mask(mask) => {
+                let mask: Vec3 = mask.colour_at_nested(point).into();
+                let fraction = mask.mean().clamp(0.0, 1.0);
+                a + (b - a) * fraction
+            },
+        }
+    }
+}
+
+impl Transformable for Blend {
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+}
+
+// Another pattern looked up at points moved about by noise, for wobbly
+// stripes, marbling and the like. Seeded like Noise.
+#[derive(Debug)]
+pub struct Perturbed {
+    child:       Arc<dyn Pattern>,
+    // About the furthest a point moves, in this pattern's space.
+    amount:      f64,
+    perlin:      Perlin,
+    scale:       f64,
+    octaves:     u32,
+    persistence: f64,
+    transform:   Matrix4,
+    inverse:     Matrix4,
+}
+
+impl Perturbed {
+    pub fn new(child: Arc<dyn Pattern>, amount: f64, seed: u64) -> Self {
+        Self {
+            child,
+            amount,
+            perlin: Perlin::new(seed),
+            scale: 1.0,
+            octaves: 1,
+            persistence: 0.5,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: u32, persistence: f64) -> Self {
+        self.octaves = octaves;
+        self.persistence = persistence;
+        self
+    }
+}
+
+impl Pattern for Perturbed {
+    fn colour_at_pattern(&self, point: &Point3) -> Colour {
+        // Left exactly alone, not moved by 0 * noise.
+        if self.amount == 0.0 {
+            return self.child.colour_at_nested(point);
+        }
+        let offset = self.perlin.vector(&(point * self.scale), self.octaves, self.persistence);
+        self.child.colour_at_nested(&(point + offset * self.amount))
+    }
+}
+
+impl Transformable for Perturbed {
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+}
+
+// Checkers over a surface's texture coordinates, width across u and height
+// up v, so they follow curved surfaces and tile faces evenly.
+#[derive(Debug)]
+pub struct UvCheckers {
+    width:      f64,
+    height:     f64,
     a:          Colour,
     b:          Colour,
     transform:  Matrix4,
     inverse:    Matrix4,
 }
 
-impl Checkers {
-    pub fn new(a: Colour, b: Colour) -> Self {
+impl UvCheckers {
+    pub fn new(width: f64, height: f64, a: Colour, b: Colour) -> Self {
         Self {
+            width,
+            height,
             a,
             b,
             transform: Matrix4::identity(),
@@ -175,17 +539,24 @@ impl Checkers {
     }
 }
 
-impl Pattern for Checkers {
+impl Pattern for UvCheckers {
+    // Given u and v as x and z, see Pattern::colour_at_uv.
     fn colour_at_pattern(&self, point: &Point3) -> Colour {
-        if (point.x.floor() as i32 + point.y.floor() as i32 + point.z.floor() as i32) % 2 == 0 {
+        let u = (point.x * self.width + CELL_EPSILON).floor() as i64;
+        let v = (point.z * self.height + CELL_EPSILON).floor() as i64;
+        if (u + v).rem_euclid(2) == 0 {
             self.a
         } else {
             self.b
         }
     }
+
+    fn uv_space(&self) -> bool {
+        true
+    }
 }
 
-impl Transformable for Checkers {
+impl Transformable for UvCheckers {
 
     fn set_transform(&mut self, transform: Matrix4) {
         self.transform = transform;
@@ -204,32 +575,199 @@ impl Transformable for Checkers {
     }
 }
 
-#[cfg(test)]
+// How points are wrapped onto the unit square of texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mapping {
+    // Longitude and latitude around the origin, with the poles on y. The
+    // image's left and right edges meet behind, at -z.
+    #[default]
+    Spherical,
+    // x and z, repeating every unit.
+    Planar,
+    // Around the y axis like spherical, and up it repeating every unit.
+    Cylindrical,
+    // Each face of the cube from -1 to 1 gets the whole square, picked by
+    // the point's largest coordinate.
+    Cube,
+}
+
+impl Mapping {
+    pub fn uv(&self, point: &Point3) -> (f64, f64) {
+        let around = |point: &Point3| 1.0 - (point.x.atan2(point.z) / (2.0 * PI) + 0.5);
+        match self {
+            Mapping::Spherical => {
+                let radius = point.coords.norm();
+                let phi = if radius == 0.0 { 0.0 } else { (point.y / radius).clamp(-1.0, 1.0).acos() };
+                (around(point), 1.0 - phi / PI)
+            }
+            Mapping::Planar => (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0)),
+            Mapping::Cylindrical => (around(point), point.y.rem_euclid(1.0)),
+            Mapping::Cube => {
+                let face = |a: f64, b: f64| ((a + 1.0).rem_euclid(2.0) / 2.0, (b + 1.0).rem_euclid(2.0) / 2.0);
+                let (x, y, z) = (point.x, point.y, point.z);
+                let largest = x.abs().max(y.abs()).max(z.abs());
+                if largest == x {
+                    face(-z, y)
+                } else if largest == -x {
+                    face(z, y)
+                } else if largest == y {
+                    face(x, -z)
+                } else if largest == -y {
+                    face(x, z)
+                } else if largest == z {
+                    face(x, y)
+                } else {
+                    face(-x, y)
+                }
+            }
+        }
+    }
+}
+
+// An image wrapped around the object. u wraps around, so the image's left
+// and right edges blend into each other, and v is clamped at the top and bottom.
 #[derive(Debug)]
-pub struct MockPattern {
-    pub transform:          Matrix4,
-    pub inverse:            Matrix4,
+pub struct Texture {
+    width:      usize,
+    height:     usize,
+    // Rows from the top of the image, in linear colour.
+    pixels:     Vec<Colour>,
+    mapping:    Mapping,
+    transform:  Matrix4,
+    inverse:    Matrix4,
 }
 
-#[cfg(test)]
-impl MockPattern {
+impl Texture {
+    pub fn new(image: &image::RgbImage, mapping: Mapping) -> Self {
+        // Squared to undo the gamma 2 applied to renders, so images come out
+        // as they went in.
+        let pixels = image.pixels()
+            .map(|p| Colour::new(p.0[0] as f64, p.0[1] as f64, p.0[2] as f64) * (1.0 / 255.0))
+            .map(|c| c.map(|v| v * v))
+            .collect();
+        Self::from_pixels(image.width(), image.height(), pixels, mapping)
+    }
+
+    // Float images, from HDR and EXR files, are already linear and can go
+    // above 1, which environment maps need for bright lights like the sun.
+    pub fn from_linear(image: &image::Rgb32FImage, mapping: Mapping) -> Self {
+        let pixels = image.pixels()
+            .map(|p| Colour::new(p.0[0] as f64, p.0[1] as f64, p.0[2] as f64))
+            .collect();
+        Self::from_pixels(image.width(), image.height(), pixels, mapping)
+    }
+
+    fn from_pixels(width: u32, height: u32, pixels: Vec<Colour>, mapping: Mapping) -> Self {
+        Self {
+            width: width as usize,
+            height: height as usize,
+            pixels,
+            mapping,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, mapping: Mapping) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path).with_context(|| format!("Could not read texture {}", path.display()))?;
+        Ok(match image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => Self::from_linear(&image.to_rgb32f(), mapping),
+            _ => Self::new(&image.to_rgb8(), mapping),
+        })
+    }
+
+    fn pixel(&self, x: i64, y: i64) -> Colour {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    // Bilinear between the four nearest pixel centres, v = 1 at the top.
+    pub fn sample(&self, u: f64, v: f64) -> Colour {
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = self.pixel(x0, y0) * (1.0 - fx) + self.pixel(x0 + 1, y0) * fx;
+        let bottom = self.pixel(x0, y0 + 1) * (1.0 - fx) + self.pixel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+impl Pattern for Texture {
+    fn colour_at_pattern(&self, point: &Point3) -> Colour {
+        let (u, v) = self.mapping.uv(point);
+        self.sample(u, v)
+    }
+
+    // Already in texture coordinates, so skips the mapping.
+    fn colour_at_uv(&self, u: f64, v: f64) -> Colour {
+        let point = self.inverse.transform_point(&Point3::new(u, 0.0, v));
+        self.sample(point.x, point.z)
+    }
+}
+
+impl Transformable for Texture {
+
+    fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    fn set_inverse(&mut self, inverse: Matrix4) {
+        self.inverse = inverse;
+    }
+
+    fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+}
+
+// Colours points by where they are, red for x, green for y and blue for z,
+// to check mappings and transforms by eye. On uv patterns red is u and blue v.
+#[derive(Debug)]
+pub struct TestPattern {
+    // Only the part after the point, so colours repeat every unit.
+    wrap:       bool,
+    transform:  Matrix4,
+    inverse:    Matrix4,
+}
+
+impl TestPattern {
     pub fn new() -> Self {
         Self {
-            transform:          Matrix4::identity(),
-            inverse:            Matrix4::identity(),
+            wrap: true,
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
         }
     }
+
+    // The point as it is, unwrapped, like the book's test pattern.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn raw() -> Self {
+        Self { wrap: false, ..Self::new() }
+    }
 }
 
-#[cfg(test)]
-impl Pattern for MockPattern {
+impl Default for TestPattern {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pattern for TestPattern {
     fn colour_at_pattern(&self, point: &Point3) -> Colour {
-        Colour::new(point.x, point.y, point.z)
+        let channel = |v: f64| if self.wrap { v - v.floor() } else { v };
+        Colour::new(channel(point.x), channel(point.y), channel(point.z))
     }
 }
 
-#[cfg(test)]
-impl Transformable for MockPattern {
+impl Transformable for TestPattern {
 
     fn set_transform(&mut self, transform: Matrix4) {
         self.transform = transform;
@@ -247,3 +785,246 @@ impl Transformable for MockPattern {
         &self.inverse
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_1_SQRT_2;
+    use crate::colour::fuzzy_eq_colour;
+    use crate::math::fuzzy_eq_f64;
+
+    // Columns black to white then back, 4 wide and 2 tall.
+    fn texture() -> Texture {
+        let image = image::RgbImage::from_fn(4, 2, |x, _| {
+            let v = [0, 255, 255, 0][x as usize];
+            image::Rgb([v, v, v])
+        });
+        Texture::new(&image, Mapping::Spherical)
+    }
+
+    fn assert_uvs(mapping: Mapping, cases: &[([f64; 3], [f64; 2])]) {
+        for &([x, y, z], [u, v]) in cases {
+            let (got_u, got_v) = mapping.uv(&Point3::new(x, y, z));
+            assert!((got_u - u).abs() < 1e-5 && (got_v - v).abs() < 1e-5, "{:?} {:?} at {:?}", mapping, (got_u, got_v), (x, y, z));
+        }
+    }
+
+    #[test]
+    fn test_spherical_mapping() {
+        assert_uvs(Mapping::Spherical, &[
+            ([0.0, 0.0, -1.0], [0.0, 0.5]),
+            ([1.0, 0.0, 0.0], [0.25, 0.5]),
+            ([0.0, 0.0, 1.0], [0.5, 0.5]),
+            ([-1.0, 0.0, 0.0], [0.75, 0.5]),
+            ([0.0, 1.0, 0.0], [0.5, 1.0]),
+            ([0.0, -1.0, 0.0], [0.5, 0.0]),
+            ([FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0], [0.25, 0.75]),
+        ]);
+    }
+
+    #[test]
+    fn test_planar_mapping() {
+        assert_uvs(Mapping::Planar, &[
+            ([0.25, 0.0, 0.5], [0.25, 0.5]),
+            ([0.25, 0.0, -0.25], [0.25, 0.75]),
+            ([0.25, 0.5, -0.25], [0.25, 0.75]),
+            ([1.25, 0.0, 0.5], [0.25, 0.5]),
+            ([0.25, 0.0, -1.75], [0.25, 0.25]),
+            ([1.0, 0.0, -1.0], [0.0, 0.0]),
+            ([0.0, 0.0, 0.0], [0.0, 0.0]),
+        ]);
+    }
+
+    #[test]
+    fn test_cylindrical_mapping() {
+        const H: f64 = FRAC_1_SQRT_2;
+        assert_uvs(Mapping::Cylindrical, &[
+            ([0.0, 0.0, -1.0], [0.0, 0.0]),
+            ([0.0, 0.5, -1.0], [0.0, 0.5]),
+            ([0.0, 1.0, -1.0], [0.0, 0.0]),
+            ([H, 0.5, -H], [0.125, 0.5]),
+            ([1.0, 0.5, 0.0], [0.25, 0.5]),
+            ([H, 0.5, H], [0.375, 0.5]),
+            ([0.0, -0.25, 1.0], [0.5, 0.75]),
+            ([-H, 0.5, H], [0.625, 0.5]),
+            ([-1.0, 1.25, 0.0], [0.75, 0.25]),
+            ([-H, 0.5, -H], [0.875, 0.5]),
+        ]);
+    }
+
+    #[test]
+    fn test_cube_mapping() {
+        assert_uvs(Mapping::Cube, &[
+            // Front, back, left, right, up, down.
+            ([-0.5, 0.5, 1.0], [0.25, 0.75]), ([0.5, -0.5, 1.0], [0.75, 0.25]),
+            ([0.5, 0.5, -1.0], [0.25, 0.75]), ([-0.5, -0.5, -1.0], [0.75, 0.25]),
+            ([-1.0, 0.5, -0.5], [0.25, 0.75]), ([-1.0, -0.5, 0.5], [0.75, 0.25]),
+            ([1.0, 0.5, 0.5], [0.25, 0.75]), ([1.0, -0.5, -0.5], [0.75, 0.25]),
+            ([-0.5, 1.0, -0.5], [0.25, 0.75]), ([0.5, 1.0, 0.5], [0.75, 0.25]),
+            ([-0.5, -1.0, 0.5], [0.25, 0.75]), ([0.5, -1.0, -0.5], [0.75, 0.25]),
+        ]);
+    }
+
+    #[test]
+    fn test_checkers_on_boundaries() {
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let checkers = Checkers::new(white, black);
+        for noise in [0.0, 1e-12, -1e-12] {
+            assert_eq!(checkers.colour_at_pattern(&Point3::new(0.5, noise, 0.5)), white);
+            assert_eq!(checkers.colour_at_pattern(&Point3::new(1.5, 1.0 + noise, 0.5)), white);
+            assert_eq!(checkers.colour_at_pattern(&Point3::new(1.5, noise, 0.5)), black);
+        }
+    }
+
+    #[test]
+    fn test_uv_checkers() {
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let checkers = UvCheckers::new(2.0, 2.0, white, black);
+        assert!(checkers.uv_space());
+        for ((u, v), colour) in [
+            ((0.0, 0.0), white), ((0.5, 0.0), black), ((0.0, 0.5), black),
+            ((0.5, 0.5), white), ((1.0, 1.0), white), ((0.49, 0.99), black),
+        ] {
+            assert_eq!(checkers.colour_at_uv(u, v), colour);
+        }
+    }
+
+    #[test]
+    fn test_noise_pattern() {
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let noise = Noise::new(white, black, 42).with_scale(3.0).with_octaves(4, 0.5);
+        let points: Vec<Point3> = (0..100).map(|i| Point3::new(i as f64 * 0.13, 0.5, i as f64 * -0.07)).collect();
+        let colours: Vec<Colour> = points.iter().map(|p| noise.colour_at_pattern(p)).collect();
+        // Greys between the two colours, not all the same.
+        let greys: Vec<Vec3> = colours.iter().map(|&c| c.into()).collect();
+        assert!(greys.iter().all(|c| c.x == c.y && c.y == c.z && (0.0..=1.0).contains(&c.x)));
+        assert!(greys.iter().any(|c| c.x > 0.6) && greys.iter().any(|c| c.x < 0.4));
+
+        let again = Noise::new(white, black, 42).with_scale(3.0).with_octaves(4, 0.5);
+        assert!(points.iter().zip(&colours).all(|(p, &c)| again.colour_at_pattern(p) == c));
+        let other = Noise::new(white, black, 43).with_scale(3.0).with_octaves(4, 0.5);
+        assert!(points.iter().zip(&colours).any(|(p, &c)| other.colour_at_pattern(p) != c));
+    }
+
+    #[test]
+    fn test_gradient_modes() {
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let grey = |mode: GradientMode, x: f64| -> f64 {
+            let colour: Vec3 = Gradient::new(black, white).with_mode(mode).colour_at_pattern(&Point3::new(x, 0.0, 0.0)).into();
+            colour.x
+        };
+        // Same inside 0 to 1.
+        for mode in [GradientMode::Clamp, GradientMode::Repeat, GradientMode::Mirror] {
+            assert!(fuzzy_eq_f64(grey(mode, 0.25), 0.25));
+        }
+        assert!(fuzzy_eq_f64(grey(GradientMode::Clamp, 1.5), 1.0));
+        assert!(fuzzy_eq_f64(grey(GradientMode::Clamp, -0.5), 0.0));
+        assert!(fuzzy_eq_f64(grey(GradientMode::Repeat, 1.25), 0.25));
+        assert!(fuzzy_eq_f64(grey(GradientMode::Mirror, 1.25), 0.75));
+        assert!(fuzzy_eq_f64(grey(GradientMode::Mirror, -0.25), 0.25));
+
+        // Mirror is continuous across the ends, where repeat jumps.
+        for x in [1.0, 2.0, -1.0, 0.0] {
+            assert!((grey(GradientMode::Mirror, x - 1e-9) - grey(GradientMode::Mirror, x + 1e-9)).abs() < 1e-6);
+        }
+        assert!((grey(GradientMode::Repeat, 1.0 - 1e-9) - grey(GradientMode::Repeat, 1.0 + 1e-9)).abs() > 0.9);
+    }
+
+    #[test]
+    fn test_stripes() {
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let stripes = Stripes::new(white, black);
+        for (x, colour) in [(0.0, white), (0.9, white), (1.0, black), (-0.1, black), (-1.1, white)] {
+            assert_eq!(stripes.colour_at_pattern(&Point3::new(x, 5.5, -3.5)), colour);
+        }
+
+        // Horizontal bands half a unit wide.
+        let bands = Stripes::new(white, black).with_axis(Axis::Y).with_width(0.5);
+        for (y, colour) in [(0.25, white), (0.75, black), (1.25, white), (-0.25, black)] {
+            assert_eq!(bands.colour_at_pattern(&Point3::new(3.5, y, 1.5)), colour);
+        }
+    }
+
+    #[test]
+    fn test_test_pattern() {
+        let mut pattern = TestPattern::new();
+        pattern.translate(0.5, 0.0, 0.0);
+        assert!(fuzzy_eq_colour(pattern.colour_at(&Point3::new(2.25, -0.25, 0.5), &Matrix4::identity()), Colour::new(0.75, 0.75, 0.5)));
+        assert!(fuzzy_eq_colour(pattern.colour_at_uv(0.75, 0.25), Colour::new(0.25, 0.0, 0.25)));
+        let raw = TestPattern::raw();
+        assert!(fuzzy_eq_colour(raw.colour_at_pattern(&Point3::new(2.25, -0.25, 0.5)), Colour::new(2.25, -0.25, 0.5)));
+    }
+
+    #[test]
+    fn test_perturbed() {
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let stripes = || {
+            let mut stripes = Stripes::new(white, black);
+            stripes.scale_uniform(0.25);
+            Arc::new(stripes) as Arc<dyn Pattern>
+        };
+        let points: Vec<Point3> = (0..200).map(|i| Point3::new(i as f64 * 0.031, 0.3, i as f64 * 0.017)).collect();
+        let colours = |pattern: &dyn Pattern| -> Vec<Colour> {
+            points.iter().map(|p| pattern.colour_at(p, &Matrix4::identity())).collect()
+        };
+        let plain = colours(&*stripes());
+        assert_eq!(colours(&Perturbed::new(stripes(), 0.0, 1)), plain);
+
+        let wobbly = colours(&Perturbed::new(stripes(), 0.3, 1).with_octaves(3, 0.5));
+        assert_ne!(wobbly, plain);
+        assert_eq!(colours(&Perturbed::new(stripes(), 0.3, 1).with_octaves(3, 0.5)), wobbly);
+        assert_ne!(colours(&Perturbed::new(stripes(), 0.3, 2).with_octaves(3, 0.5)), wobbly);
+
+        // Points move by up to about amount on each axis.
+        let mock = Perturbed::new(Arc::new(TestPattern::raw()), 0.3, 1).with_scale(4.0);
+        for point in &points {
+            let moved: Vec3 = mock.colour_at_pattern(point).into();
+            assert!((moved - point.coords).amax() <= 0.3);
+        }
+    }
+
+    #[test]
+    fn test_blend() {
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+        let mock = || Arc::new(TestPattern::raw()) as Arc<dyn Pattern>;
+        let point = Point3::new(0.2, 0.4, 0.6);
+        let average = Blend::new(mock(), Arc::new(Checkers::new(white, white)), BlendMode::Average);
+        assert!(fuzzy_eq_colour(average.colour_at_pattern(&point), Colour::new(0.6, 0.7, 0.8)));
+        let multiply = Blend::new(mock(), mock(), BlendMode::Multiply);
+        assert!(fuzzy_eq_colour(multiply.colour_at_pattern(&point), Colour::new(0.04, 0.16, 0.36)));
+
+        // Children keep their own transforms, inside the blend's.
+        let mut shifted = TestPattern::raw();
+        shifted.translate(1.0, 0.0, 0.0);
+        let mut mask = Blend::new(Arc::new(shifted), mock(), BlendMode::Mask(Arc::new(Stripes::new(black, white))));
+        mask.scale_uniform(2.0);
+        assert!(fuzzy_eq_colour(mask.colour_at(&Point3::new(1.0, 2.0, 3.0), &Matrix4::identity()), Colour::new(-0.5, 1.0, 1.5)));
+        assert!(fuzzy_eq_colour(mask.colour_at(&Point3::new(3.0, 2.0, 3.0), &Matrix4::identity()), Colour::new(1.5, 1.0, 1.5)));
+    }
+
+    #[test]
+    fn test_texture_bilinear() {
+        let texture = texture();
+        let grey = |v: f64| Colour::new(v, v, v);
+        // Pixel centres are exact, and between them blend.
+        assert!(fuzzy_eq_colour(texture.colour_at_uv(0.375, 0.75), grey(1.0)));
+        assert!(fuzzy_eq_colour(texture.colour_at_uv(0.125, 0.25), grey(0.0)));
+        assert!(fuzzy_eq_colour(texture.colour_at_uv(0.25, 0.5), grey(0.5)));
+        // v is clamped at the top and bottom.
+        assert!(fuzzy_eq_colour(texture.colour_at_uv(0.375, 1.0), grey(1.0)));
+
+        // Either side of the seam blends the first and last columns, so is the same.
+        for v in [0.1, 0.5, 0.9] {
+            assert!(fuzzy_eq_colour(texture.colour_at_uv(0.0, v), texture.colour_at_uv(1.0, v)));
+            assert!(fuzzy_eq_colour(texture.colour_at_uv(1e-9, v), texture.colour_at_uv(1.0 - 1e-9, v)));
+        }
+        let behind = |x: f64| texture.colour_at_pattern(&Point3::new(x, 0.0, -1.0));
+        assert!(fuzzy_eq_colour(behind(1e-9), behind(-1e-9)));
+    }
+
+    #[test]
+    fn test_texture_missing_file() {
+        let err = Texture::load("no-such-texture.png", Mapping::Spherical).unwrap_err();
+        assert!(err.to_string().contains("no-such-texture.png"));
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt::Debug;
+use serde::Deserialize;
+use crate::colour::Colour;
+use crate::render::HdrImage;
+
+// An effect applied to the linear framebuffer before gamma correction.
+pub trait PostProcess: Debug + Send + Sync {
+    fn apply(&self, image: &mut HdrImage);
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Bloom(Bloom),
+    Vignette(Vignette),
+    ChromaticAberration(ChromaticAberration),
+}
+
+impl PostProcess for Effect {
+    fn apply(&self, image: &mut HdrImage) {
+        match self {
+            Effect::Bloom(bloom)               => bloom.apply(image),
+            Effect::Vignette(vignette)         => vignette.apply(image),
+            Effect::ChromaticAberration(shift) => shift.apply(image),
+        }
+    }
+}
+
+// Effects run one after another, in the order given.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Pipeline(pub Vec<Effect>);
+
+impl PostProcess for Pipeline {
+    fn apply(&self, image: &mut HdrImage) {
+        for effect in &self.0 {
+            effect.apply(image);
+        }
+    }
+}
+
+// Glow around bright pixels. Light above the threshold is blurred at a few
+// scales of radius (in pixels) and added back, scaled by intensity.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Bloom {
+    pub intensity: f64,
+    #[serde(default = "bloom_threshold_default")]
+    pub threshold: f64,
+    #[serde(default = "bloom_radius_default")]
+    pub radius:    f64,
+}
+
+fn bloom_threshold_default() -> f64 { 1.0 }
+fn bloom_radius_default() -> f64 { 4.0 }
+
+// Multiples of the radius that are blurred and averaged.
+const BLOOM_SCALES: [f64; 3] = [1.0, 2.0, 4.0];
+
+impl PostProcess for Bloom {
+    fn apply(&self, image: &mut HdrImage) {
+        if self.intensity == 0.0 || image.is_empty() {
+            return;
+        }
+
+        let bright: HdrImage = image.iter()
+            .map(|row| row.iter().map(|c| c.map(|v| (v - self.threshold).max(0.0))).collect())
+            .collect();
+
+        let weight = self.intensity / BLOOM_SCALES.len() as f64;
+        for scale in BLOOM_SCALES {
+            let kernel = gaussian_kernel(self.radius * scale);
+            let blurred = blur_vertical(&blur_horizontal(&bright, &kernel), &kernel);
+            for (row, blurred_row) in image.iter_mut().zip(blurred) {
+                for (pixel, glow) in row.iter_mut().zip(blurred_row) {
+                    *pixel += glow * weight;
+                }
+            }
+        }
+    }
+}
+
+// Normalised weights for offsets -3 sigma..=3 sigma, the middle is offset 0.
+pub(crate) fn gaussian_kernel(sigma: f64) -> Vec<f64> {
+    let half = (3.0 * sigma).ceil().max(0.0) as i64;
+    let weights: Vec<f64> = (-half..=half)
+        .map(|x| if sigma > 0.0 { (-(x * x) as f64 / (2.0 * sigma * sigma)).exp() } else { 1.0 })
+        .collect();
+    let total: f64 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+// Pixels outside the image are black, so light spread past the edges is lost.
+fn blur_horizontal(image: &HdrImage, kernel: &[f64]) -> HdrImage {
+    let half = (kernel.len() / 2) as i64;
+    image.iter().map(|row| {
+        (0..row.len() as i64).map(|x| {
+            kernel.iter().enumerate().fold(Colour::default(), |acc, (k, &w)| {
+                match row.get((x + k as i64 - half) as usize) {
+                    Some(&c) if x + k as i64 >= half => acc + c * w,
+                    _ => acc,
+                }
+            })
+        }).collect()
+    }).collect()
+}
+
+fn blur_vertical(image: &HdrImage, kernel: &[f64]) -> HdrImage {
+    let half = (kernel.len() / 2) as i64;
+    (0..image.len() as i64).map(|y| {
+        (0..image[0].len()).map(|x| {
+            kernel.iter().enumerate().fold(Colour::default(), |acc, (k, &w)| {
+                match image.get((y + k as i64 - half) as usize) {
+                    Some(row) if y + k as i64 >= half => acc + row[x] * w,
+                    _ => acc,
+                }
+            })
+        }).collect()
+    }).collect()
+}
+
+// Offset of a pixel centre from the image centre, scaled so the corners are at length 1.
+fn from_centre(x: f64, y: f64, width: usize, height: usize) -> (f64, f64) {
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let corner = (cx * cx + cy * cy).sqrt();
+    ((x + 0.5 - cx) / corner, (y + 0.5 - cy) / corner)
+}
+
+// Darkens towards the edges, by 1 - strength * r^2 where r is 1 in the corners.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Vignette {
+    pub strength: f64,
+}
+
+impl PostProcess for Vignette {
+    fn apply(&self, image: &mut HdrImage) {
+        let height = image.len();
+        for (y, row) in image.iter_mut().enumerate() {
+            let width = row.len();
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let (dx, dy) = from_centre(x as f64, y as f64, width, height);
+                *pixel = *pixel * (1.0 - self.strength * (dx * dx + dy * dy)).max(0.0);
+            }
+        }
+    }
+}
+
+// Lens colour fringing. Red is pushed outwards and blue inwards, by shift_px
+// pixels at the corners and less towards the centre.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChromaticAberration {
+    pub shift_px: f64,
+}
+
+impl PostProcess for ChromaticAberration {
+    fn apply(&self, image: &mut HdrImage) {
+        if self.shift_px == 0.0 || image.is_empty() {
+            return;
+        }
+        let (width, height) = (image[0].len(), image.len());
+        let source = image.clone();
+        for (y, row) in image.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let (dx, dy) = from_centre(x as f64, y as f64, width, height);
+                let (sx, sy) = (dx * self.shift_px, dy * self.shift_px);
+                let [r, _, _] = sample(&source, x as f64 - sx, y as f64 - sy);
+                let [_, g, _] = sample(&source, x as f64, y as f64);
+                let [_, _, b] = sample(&source, x as f64 + sx, y as f64 + sy);
+                *pixel = Colour::new(r, g, b);
+            }
+        }
+    }
+}
+
+// Bilinear interpolation between pixel centres, clamped to the edges.
+fn sample(image: &HdrImage, x: f64, y: f64) -> [f64; 3] {
+    let (width, height) = (image[0].len(), image.len());
+    let x = x.clamp(0.0, (width - 1) as f64);
+    let y = y.clamp(0.0, (height - 1) as f64);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+    let top = image[y0][x0] * (1.0 - fx) + image[y0][x1] * fx;
+    let bottom = image[y1][x0] * (1.0 - fx) + image[y1][x1] * fx;
+    let c = crate::Vec3::from(top * (1.0 - fy) + bottom * fy);
+    [c.x, c.y, c.z]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vec3;
+    use crate::colour::fuzzy_eq_colour;
+    use crate::math::fuzzy_eq_f64;
+
+    const SIZE: usize = 41;
+
+    fn single_pixel(value: f64) -> HdrImage {
+        let mut image = vec![vec![Colour::default(); SIZE]; SIZE];
+        image[SIZE / 2][SIZE / 2] = Colour::new(value, value, value);
+        image
+    }
+
+    fn energy(image: &HdrImage) -> f64 {
+        image.iter().flatten().map(|&c| Vec3::from(c).sum()).sum()
+    }
+
+    #[test]
+    fn test_bloom_falloff() {
+        let bloom = Bloom { intensity: 0.5, threshold: 1.0, radius: 1.0 };
+        let mut image = single_pixel(101.0);
+        bloom.apply(&mut image);
+
+        // Each scale adds a separable gaussian of the 100 units above the threshold.
+        let expected = |dx: usize, dy: usize| -> f64 {
+            BLOOM_SCALES.iter().map(|&scale| {
+                let kernel = gaussian_kernel(scale);
+                let half = kernel.len() / 2;
+                let weight = |d: usize| kernel.get(half + d).copied().unwrap_or(0.0);
+                weight(dx) * weight(dy)
+            }).sum::<f64>() * 100.0 * bloom.intensity / BLOOM_SCALES.len() as f64
+        };
+        let grey = |v: f64| Colour::new(v, v, v);
+
+        let c = SIZE / 2;
+        assert!(fuzzy_eq_colour(image[c][c], grey(101.0 + expected(0, 0))));
+        for (dx, dy) in [(1, 0), (0, 1), (2, 0), (1, 1), (3, 2), (5, 0)] {
+            assert!(fuzzy_eq_colour(image[c + dy][c + dx], grey(expected(dx, dy))));
+            assert!(fuzzy_eq_colour(image[c - dy][c - dx], grey(expected(dx, dy))));
+        }
+        assert!(expected(1, 0) > expected(2, 0));
+        assert!(expected(2, 0) > expected(2, 2));
+    }
+
+    #[test]
+    fn test_bloom_energy() {
+        let bloom = Bloom { intensity: 0.3, threshold: 2.0, radius: 1.5 };
+        let mut image = single_pixel(12.0);
+        let before = energy(&image);
+        bloom.apply(&mut image);
+        assert!(fuzzy_eq_f64(energy(&image) - before, 0.3 * 10.0 * 3.0));
+
+        // Nothing above the threshold, or no intensity, leaves the image alone.
+        let mut image = single_pixel(1.0);
+        bloom.apply(&mut image);
+        assert_eq!(image, single_pixel(1.0));
+
+        let mut image = single_pixel(12.0);
+        Bloom { intensity: 0.0, ..bloom }.apply(&mut image);
+        assert_eq!(image, single_pixel(12.0));
+    }
+
+    #[test]
+    fn test_vignette() {
+        let mut image = vec![vec![Colour::new(1.0, 1.0, 1.0); 4]; 4];
+        Vignette { strength: 0.5 }.apply(&mut image);
+
+        // Pixel centres at 1.5 and 0.5 pixels out on each axis, of a corner distance of sqrt(8).
+        let corner = 1.0 - 0.5 * 4.5 / 8.0;
+        let centre = 1.0 - 0.5 * 0.5 / 8.0;
+        assert!(fuzzy_eq_colour(image[0][0], Colour::new(corner, corner, corner)));
+        assert!(fuzzy_eq_colour(image[3][0], image[0][0]));
+        assert!(fuzzy_eq_colour(image[1][2], Colour::new(centre, centre, centre)));
+    }
+
+    #[test]
+    fn test_chromatic_aberration() {
+        // A flat image has nothing to fringe.
+        let mut image = vec![vec![Colour::new(0.5, 0.5, 0.5); 8]; 8];
+        ChromaticAberration { shift_px: 2.0 }.apply(&mut image);
+        assert!(image.iter().flatten().all(|&c| fuzzy_eq_colour(c, Colour::new(0.5, 0.5, 0.5))));
+
+        // A white pixel near the corner, red spreads outwards and blue inwards.
+        let mut image = vec![vec![Colour::default(); 8]; 8];
+        image[6][6] = Colour::new(1.0, 1.0, 1.0);
+        ChromaticAberration { shift_px: 2.0 }.apply(&mut image);
+        let red = |x: usize, y: usize| Vec3::from(image[y][x]).x;
+        let blue = |x: usize, y: usize| Vec3::from(image[y][x]).z;
+        assert!(red(7, 7) > 0.0 && blue(7, 7) == 0.0);
+        assert!(blue(5, 5) > 0.0 && red(5, 5) == 0.0);
+        assert_eq!(Vec3::from(image[6][6]).y, 1.0);
+    }
+
+    #[test]
+    fn test_pipeline_order() {
+        let bloom = Effect::Bloom(Bloom { intensity: 1.0, threshold: 1.0, radius: 1.0 });
+        let vignette = Effect::Vignette(Vignette { strength: 0.9 });
+        let mut image = vec![vec![Colour::default(); 16]; 16];
+        image[2][2] = Colour::new(5.0, 5.0, 5.0);
+
+        let mut expected = image.clone();
+        vignette.apply(&mut expected);
+        bloom.apply(&mut expected);
+        let mut piped = image.clone();
+        Pipeline(vec![vignette, bloom]).apply(&mut piped);
+        assert_eq!(piped, expected);
+
+        let mut reversed = image.clone();
+        Pipeline(vec![bloom, vignette]).apply(&mut reversed);
+        assert_ne!(reversed, expected);
+    }
+}
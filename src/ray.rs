@@ -4,11 +4,23 @@ use crate::{Point3, Vec3, Matrix4};
 pub struct Ray{
     pub origin: Point3,
     pub direction: Vec3,
+    // When the ray was cast, for moving objects.
+    pub time: f64,
+    // Cast from the camera, rather than bounced or towards a light.
+    pub primary: bool,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self { origin, direction, time: 0.0, primary: false }
+    }
+
+    pub fn with_time(self, time: f64) -> Self {
+        Self { time, ..self }
+    }
+
+    pub fn as_primary(self) -> Self {
+        Self { primary: true, ..self }
     }
 
     pub fn at(&self, t: f64) -> Point3 {
@@ -23,6 +35,8 @@ impl Ray {
         Self {
             origin: transform.transform_point(&self.origin),
             direction: transform.transform_vector(&self.direction),
+            time: self.time,
+            primary: self.primary,
         }
     }
 }
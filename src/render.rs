@@ -1,11 +1,210 @@
+use std::fmt;
+use std::ops::{AddAssign, Range};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
 use rayon::prelude::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use rand::rngs::ThreadRng;
 use crate::Camera;
 use crate::Scene;
+use crate::Vec3;
 use crate::colour::Colour;
+use crate::debug::Recorder;
+use crate::post::{Pipeline, PostProcess};
+use crate::denoise::{Aovs, Denoise};
 
 pub type Image = Vec<Vec<u8>>;
+// Rows of linear colour, before gamma correction.
+pub type HdrImage = Vec<Vec<Colour>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub dimensions: (u32, u32),
+    pub samples:    u32,
+    // Maximum number of bounces per ray.
+    pub max_depth:  u32,
+    // Effects applied to the image after rendering.
+    pub post:       Pipeline,
+    pub denoise:    Option<Denoise>,
+    // Stop after the pass (one sample per pixel) in which this runs out, samples is then a maximum.
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            dimensions: crate::default_dims(),
+            samples:    300,
+            max_depth:  100,
+            post:       Pipeline::default(),
+            denoise:    None,
+            time_budget: None,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    /// 4 samples, 4 bounces.
+    Draft,
+    /// 64 samples, 10 bounces.
+    Medium,
+    /// 500 samples, 50 bounces.
+    Final,
+}
+
+impl RenderSettings {
+    pub fn preset(quality: Quality, dimensions: (u32, u32)) -> Self {
+        let (samples, max_depth) = match quality {
+            Quality::Draft  => (4, 4),
+            Quality::Medium => (64, 10),
+            Quality::Final  => (500, 50),
+        };
+        Self { dimensions, samples, max_depth, ..Default::default() }
+    }
+
+    // Precedence is scene file preset < scene file values < command line
+    // preset < command line values.
+    pub fn resolve(dimensions: (u32, u32), file: &RenderOverrides, cli: &RenderOverrides) -> Self {
+        let mut settings = match file.quality {
+            Some(quality) => Self::preset(quality, dimensions),
+            None => Self { dimensions, ..Default::default() },
+        };
+        file.apply(&mut settings);
+        if let Some(quality) = cli.quality {
+            let preset = Self::preset(quality, dimensions);
+            settings.samples = preset.samples;
+            settings.max_depth = preset.max_depth;
+        }
+        cli.apply(&mut settings);
+        settings
+    }
+}
+
+// Settings given explicitly, either in a scene file or on the command line.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RenderOverrides {
+    pub quality:   Option<Quality>,
+    pub samples:   Option<u32>,
+    pub max_depth: Option<u32>,
+    pub post:      Option<Pipeline>,
+    pub denoise:   Option<Denoise>,
+    #[serde(default, deserialize_with = "de_time_budget")]
+    pub time_budget: Option<Duration>,
+}
+
+// Durations like "60s", "2m", "500ms" or a number of seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, scale) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60.0)
+    } else if let Some(n) = s.strip_suffix('h') {
+        (n, 3600.0)
+    } else {
+        (s, 1.0)
+    };
+    let value: f64 = number.trim().parse().map_err(|_| format!("invalid duration {}", s))?;
+    // Negative, NaN and too long for a Duration are all errors.
+    Duration::try_from_secs_f64(value * scale).map_err(|_| format!("invalid duration {}", s))
+}
+
+fn de_time_budget<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Budget { Seconds(f64), Text(String) }
+
+    match Budget::deserialize(deserializer)? {
+        Budget::Seconds(secs) => parse_duration(&secs.to_string()),
+        Budget::Text(text) => parse_duration(&text),
+    }.map(Some).map_err(serde::de::Error::custom)
+}
+
+impl RenderOverrides {
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        settings.samples = self.samples.unwrap_or(settings.samples);
+        settings.max_depth = self.max_depth.unwrap_or(settings.max_depth);
+        settings.denoise = self.denoise.or(settings.denoise);
+        settings.time_budget = self.time_budget.or(settings.time_budget);
+        if let Some(post) = &self.post {
+            settings.post = post.clone();
+        }
+    }
+}
+
+// Counters gathered while rendering. Each thread keeps its own copy which are summed at the end.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RenderStats {
+    pub primary_rays:    u64,
+    pub shadow_rays:     u64,
+    pub reflection_rays: u64,
+    pub refraction_rays: u64,
+    // Rays (of any kind except shadow) that hit/missed the scene.
+    pub hits:            u64,
+    pub misses:          u64,
+    pub shading_time:    Duration,
+    // Samples per pixel actually taken, fewer than asked for if a time budget ran out.
+    pub samples:         u32,
+}
+
+impl RenderStats {
+    pub fn total_rays(&self) -> u64 {
+        self.primary_rays + self.shadow_rays + self.reflection_rays + self.refraction_rays
+    }
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.primary_rays    += rhs.primary_rays;
+        self.shadow_rays     += rhs.shadow_rays;
+        self.reflection_rays += rhs.reflection_rays;
+        self.refraction_rays += rhs.refraction_rays;
+        self.hits            += rhs.hits;
+        self.misses          += rhs.misses;
+        self.shading_time    += rhs.shading_time;
+        self.samples          = self.samples.max(rhs.samples);
+    }
+}
+
+fn millions(n: u64) -> f64 {
+    n as f64 / 1e6
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.shading_time.as_secs_f64();
+        let rate = if secs > 0.0 { millions(self.total_rays()) / secs } else { 0.0 };
+        write!(
+            f,
+            "{} samples per pixel, {:.1} M primary rays, {:.1} M shadow rays, {:.1} M reflection rays, {:.1} M refraction rays, {:.1} s shading, {:.1} Mrays/s",
+            self.samples,
+            millions(self.primary_rays),
+            millions(self.shadow_rays),
+            millions(self.reflection_rays),
+            millions(self.refraction_rays),
+            secs,
+            rate,
+        )
+    }
+}
+
+// Per-thread state threaded through Scene::colour_at_traced.
+#[derive(Debug, Default)]
+pub(crate) struct TraceContext<'a> {
+    pub(crate) stats:    RenderStats,
+    pub(crate) recorder: Option<&'a mut Recorder>,
+    // For glossy reflections and soft shadows.
+    pub(crate) rng:      ThreadRng,
+    // Normal and distance of the first thing a camera ray hit, for the
+    // denoiser's guide buffers. Cleared by the caller before each camera ray.
+    pub(crate) primary_hit: Option<(Vec3, f64)>,
+}
 
 pub fn render(
     scene: Arc<Scene>,
@@ -14,46 +213,501 @@ pub fn render(
     samples_per_pixel: u32,
     max_depth: u32,
 ) -> Image {
+    let settings = RenderSettings { dimensions, samples: samples_per_pixel, max_depth, ..Default::default() };
+    render_with_stats(scene, camera, &settings).0
+}
+
+pub fn render_with_stats(
+    scene: Arc<Scene>,
+    camera: Camera,
+    settings: &RenderSettings,
+) -> (Image, RenderStats) {
+
+    let RenderSettings { dimensions, samples: samples_per_pixel, max_depth, ref post, denoise, time_budget } = *settings;
+    let jitter = samples_per_pixel > 1;
+
+    println!();
+    let progress_bar = match time_budget {
+        Some(_) => progress_bar(samples_per_pixel, "Passes"),
+        None => progress_bar(dimensions.1, "Lines"),
+    };
+
+    let start = Instant::now();
+    let (mut hdr, aovs, stats, samples) = match time_budget {
+        None => {
+            let (sum, aovs, stats) = render_pass(&scene, &camera, dimensions, 0..dimensions.1, samples_per_pixel, jitter, max_depth, denoise.is_some(), Some(&progress_bar));
+            (sum, aovs, stats, samples_per_pixel)
+        },
+        // Progressive passes of one sample per pixel, checking the clock between passes.
+        Some(budget) => {
+            let mut sum = vec![vec![Colour::default(); dimensions.0 as usize]; dimensions.1 as usize];
+            let mut stats = RenderStats::default();
+            let mut aovs = None;
+            let mut passes = 0;
+            while passes < samples_per_pixel.max(1) {
+                // Guide buffers from the first pass only.
+                let (pass, pass_aovs, pass_stats) = render_pass(&scene, &camera, dimensions, 0..dimensions.1, 1, jitter, max_depth, denoise.is_some() && passes == 0, None);
+                aovs = aovs.or(pass_aovs);
+                for (row, pass_row) in sum.iter_mut().zip(pass) {
+                    for (pixel, sample) in row.iter_mut().zip(pass_row) {
+                        *pixel += sample;
+                    }
+                }
+                stats += pass_stats;
+                passes += 1;
+                progress_bar.inc(1);
+                if start.elapsed() >= budget {
+                    break;
+                }
+            }
+            (sum, aovs, stats, passes)
+        },
+    };
+    average(&mut hdr, samples);
+
+    let stats = RenderStats { shading_time: start.elapsed(), samples, ..stats };
+
+    if let (Some(denoise), Some(aovs)) = (denoise, aovs) {
+        denoise.apply(&mut hdr, &aovs);
+    }
+    post.apply(&mut hdr);
+    let pixels = hdr.into_iter().map(to_bytes).collect();
+
+    let time_taken = progress_bar.elapsed();
+    progress_bar.finish_with_message("Done");
+    println!("Finished rendering in {} seconds.", time_taken.as_secs_f64());
+    if time_budget.is_some() {
+        println!("Time budget allowed {} of {} samples per pixel.", samples, samples_per_pixel);
+    }
+    (pixels, stats)
+}
+
+// Renders `band_height` rows at a time and passes each band to `sink` as soon as it
+// is done, so only one band is in memory at once. Denoising, post-processing and
+// time budgets need the whole image, so can't be used.
+pub fn render_streamed(
+    scene:       Arc<Scene>,
+    camera:      Camera,
+    settings:    &RenderSettings,
+    band_height: u32,
+    mut sink:    impl FnMut(&[Vec<u8>]) -> anyhow::Result<()>,
+) -> anyhow::Result<RenderStats> {
+
+    check_partial(settings, "Streamed output")?;
+    let RenderSettings { dimensions, samples: samples_per_pixel, max_depth, .. } = *settings;
+    if band_height == 0 {
+        anyhow::bail!("Band height must be at least 1.");
+    }
+    let jitter = samples_per_pixel > 1;
 
     println!();
-    let progress_bar = ProgressBar::new(dimensions.1 as u64)
+    let progress_bar = progress_bar(dimensions.1, "Lines");
+    let start = Instant::now();
+    let mut stats = RenderStats::default();
+    for band_start in (0..dimensions.1).step_by(band_height as usize) {
+        let rows = band_start..(band_start + band_height).min(dimensions.1);
+        let (mut band, _, band_stats) = render_pass(&scene, &camera, dimensions, rows, samples_per_pixel, jitter, max_depth, false, Some(&progress_bar));
+        average(&mut band, samples_per_pixel);
+        let band: Image = band.into_iter().map(to_bytes).collect();
+        sink(&band)?;
+        stats += band_stats;
+    }
+    let stats = RenderStats { shading_time: start.elapsed(), samples: samples_per_pixel, ..stats };
+
+    progress_bar.finish_with_message("Done");
+    println!("Finished rendering in {} seconds.", progress_bar.elapsed().as_secs_f64());
+    Ok(stats)
+}
+
+// Renders only the given rows, in full image pixel coordinates so they line up with
+// the rest of the image. Like render_streamed, can't use whole image passes.
+pub fn render_rows(
+    scene:    Arc<Scene>,
+    camera:   Camera,
+    settings: &RenderSettings,
+    rows:     Range<u32>,
+) -> anyhow::Result<(Image, RenderStats)> {
+
+    check_partial(settings, "Rendering part of an image")?;
+    let RenderSettings { dimensions, samples: samples_per_pixel, max_depth, .. } = *settings;
+    if rows.end > dimensions.1 || rows.is_empty() {
+        anyhow::bail!("Rows {}..{} are outside the image, which is {} rows high.", rows.start, rows.end, dimensions.1);
+    }
+
+    println!();
+    let progress_bar = progress_bar(rows.len() as u32, "Lines");
+    let start = Instant::now();
+    let (mut hdr, _, stats) = render_pass(&scene, &camera, dimensions, rows, samples_per_pixel, samples_per_pixel > 1, max_depth, false, Some(&progress_bar));
+    average(&mut hdr, samples_per_pixel);
+    let stats = RenderStats { shading_time: start.elapsed(), samples: samples_per_pixel, ..stats };
+
+    progress_bar.finish_with_message("Done");
+    println!("Finished rendering in {} seconds.", progress_bar.elapsed().as_secs_f64());
+    Ok((hdr.into_iter().map(to_bytes).collect(), stats))
+}
+
+fn check_partial(settings: &RenderSettings, what: &str) -> anyhow::Result<()> {
+    if !settings.post.0.is_empty() || settings.denoise.is_some() || settings.time_budget.is_some() {
+        anyhow::bail!("{} can't be used with denoising, post-processing or a time budget, they need the whole image.", what);
+    }
+    Ok(())
+}
+
+fn progress_bar(length: u32, unit: &str) -> ProgressBar {
+    let progress_bar = ProgressBar::new(length as u64)
         .with_message("Progress");
 
     progress_bar
-        .set_style(ProgressStyle::with_template("{spinner:.green} {msg} [{elapsed_precise}] [{bar:100.cyan/blue}] {pos}/{len} Lines rendered (ETA: {eta})")
+        .set_style(ProgressStyle::with_template(&format!("{{spinner:.green}} {{msg}} [{{elapsed_precise}}] [{{bar:100.cyan/blue}}] {{pos}}/{{len}} {} rendered (ETA: {{eta}})", unit))
         .unwrap()
         .progress_chars("#>-")
     );
-    
-    let pixels = (0..dimensions.1)
+    progress_bar
+}
+
+fn average(image: &mut HdrImage, samples: u32) {
+    let scale = 1.0 / samples.max(1) as f64;
+    for pixel in image.iter_mut().flatten() {
+        *pixel = *pixel * scale;
+    }
+}
+
+fn to_bytes(row: Vec<Colour>) -> Vec<u8> {
+    row.into_iter().flat_map(|mut c| {
+        c.gamma_correct(1);
+        Vec::<u8>::from(c)
+    }).collect()
+}
+
+// Sum of `samples` samples for every pixel in the given rows, not yet averaged.
+// With aovs, also the guide buffers from each pixel's first sample.
+#[allow(clippy::too_many_arguments)]
+fn render_pass(
+    scene:      &Arc<Scene>,
+    camera:     &Camera,
+    dimensions: (u32, u32),
+    rows:       Range<u32>,
+    samples:    u32,
+    jitter:     bool,
+    max_depth:  u32,
+    aovs:       bool,
+    progress:   Option<&ProgressBar>,
+) -> (HdrImage, Option<Aovs>, RenderStats) {
+    let bands: Vec<_> = rows
     .into_par_iter()
     .map(|j| {
 
-        let mut rng = if samples_per_pixel > 1 {
+        let mut rng = if jitter {
             Some(rand::thread_rng())
         } else {
             None
         };
-        let scene = Arc::clone(&scene);
-        let mut row = vec![0; 3 * dimensions.0 as usize];
-        for i in 0..dimensions.0 {
+        let scene = Arc::clone(scene);
+        let mut ctx = TraceContext::default();
+        let mut guides = Vec::with_capacity(if aovs { dimensions.0 as usize } else { 0 });
+        let row = (0..dimensions.0).map(|i| {
             let mut pixel_colour = Colour::default();
-            for _ in 0..samples_per_pixel {
+            for sample in 0..samples {
                 let ray = camera.get_ray(i, j, rng.as_mut());
-                pixel_colour += scene.colour_at(&ray, max_depth as usize);
+                ctx.stats.primary_rays += 1;
+                ctx.primary_hit = None;
+                pixel_colour += scene.colour_at_traced(&ray, max_depth as usize, &mut ctx);
+                if aovs && sample == 0 {
+                    guides.push(ctx.primary_hit.unwrap_or((Vec3::zeros(), f64::INFINITY)));
+                }
             }
-            pixel_colour.gamma_correct(samples_per_pixel);
+            pixel_colour
+        }).collect();
 
-            let rgb: Vec<u8> = pixel_colour.into();
-            row[i as usize * 3..i as usize * 3 + 3].copy_from_slice(&rgb);
+        if let Some(progress) = progress {
+            progress.inc(1);
         }
+        (row, guides, ctx.stats)
+    }).collect();
 
-        progress_bar.inc(1);
-        row
-    }).collect::<Image>();
-    
-    let time_taken = progress_bar.elapsed();
-    progress_bar.finish_with_message("Done");
-    println!("Finished rendering in {} seconds.", time_taken.as_secs_f64());
-    pixels
+    let mut image = Vec::with_capacity(bands.len());
+    let mut guide_aovs = Aovs::default();
+    let mut stats = RenderStats::default();
+    for (row, guides, row_stats) in bands {
+        image.push(row);
+        let (normals, depths) = guides.into_iter().unzip();
+        guide_aovs.normals.push(normals);
+        guide_aovs.depths.push(depths);
+        stats += row_stats;
+    }
+    (image, aovs.then_some(guide_aovs), stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Material, Light, Point3, Vec3};
+    use crate::object::Sphere;
+
+    #[test]
+    fn test_render_stats() {
+        let mut scene = Scene::default();
+        scene.push(Box::new(Sphere::new(Material::default())));
+        scene.lights.push(Light::new(Point3::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0)));
+
+        let settings = RenderSettings { dimensions: (4, 4), samples: 1, max_depth: 5, ..Default::default() };
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, -5.0),
+            Point3::origin(),
+            Vec3::new(0.0, 1.0, 0.0),
+            30.0,
+            settings.dimensions,
+            0.0,
+            5.0,
+        );
+        let covered = (0..4)
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .filter(|&(x, y)| scene.trace(&camera.ray_for_pixel(x, y)).is_some())
+            .count() as u64;
+
+        let (_, stats) = render_with_stats(Arc::new(scene), camera, &settings);
+        assert_eq!(stats.primary_rays, 16);
+        // One shadow ray per primary ray that hits the (opaque, non-reflective) sphere.
+        assert_eq!(stats.shadow_rays, covered);
+        assert_eq!(stats.hits, covered);
+        assert_eq!(stats.misses, 16 - covered);
+        assert_eq!(stats.reflection_rays + stats.refraction_rays, 0);
+    }
+
+    #[test]
+    fn test_render_pass_aovs() {
+        let mut scene = Scene::default();
+        scene.push(Box::new(Sphere::new(Material::default())));
+        let scene = Arc::new(scene);
+        let camera = Camera::new(Point3::new(0.0, 0.0, -5.0), Point3::origin(), Vec3::new(0.0, 1.0, 0.0), 30.0, (6, 6), 0.0, 5.0);
+
+        // Taken from the render's own camera rays, the same as tracing them again.
+        let (_, aovs, _) = render_pass(&scene, &camera, (6, 6), 0..6, 1, false, 5, true, None);
+        let aovs = aovs.unwrap();
+        for (y, (normals, depths)) in aovs.normals.iter().zip(&aovs.depths).enumerate() {
+            for (x, (normal, depth)) in normals.iter().zip(depths).enumerate() {
+                match scene.trace(&camera.ray_for_pixel(x as u32, y as u32)) {
+                    Some(hit) => assert_eq!((*normal, *depth), (hit.normal, hit.t)),
+                    None => assert_eq!((*normal, *depth), (Vec3::zeros(), f64::INFINITY)),
+                }
+            }
+        }
+        assert!(aovs.depths.iter().flatten().any(|d| d.is_finite()));
+        assert!(aovs.depths.iter().flatten().any(|d| d.is_infinite()));
+        assert!(render_pass(&scene, &camera, (6, 6), 0..6, 1, false, 5, false, None).1.is_none());
+    }
+
+    #[test]
+    fn test_shadows_are_darker() {
+        use crate::object::Plane;
+        use crate::transform::Transformable;
+
+        // A ball on a floor, lit from the left so its shadow falls to the right.
+        let mut scene = Scene::default();
+        scene.push(Box::new(Sphere::new(Material::default())));
+        let mut floor = Plane::new(Material::default());
+        floor.translate(0.0, -1.0, 0.0);
+        scene.push(Box::new(floor));
+        scene.lights.push(Light::new(Point3::new(-10.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+        let floor_id = scene.objects[1].id();
+
+        let dimensions = (24, 24);
+        let camera = Camera::new(Point3::new(0.0, 6.0, -8.0), Point3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 50.0, dimensions, 0.0, 10.0);
+        let scene = Arc::new(scene);
+        let image = render(Arc::clone(&scene), camera, dimensions, 1, 5);
+
+        let (mut shadowed, mut lit) = (vec![], vec![]);
+        for y in 0..dimensions.1 {
+            for x in 0..dimensions.0 {
+                let Some(hit) = scene.trace(&camera.ray_for_pixel(x, y)) else { continue };
+                if hit.obj_id != floor_id {
+                    continue;
+                }
+                let pixel = &image[y as usize][x as usize * 3..x as usize * 3 + 3];
+                let brightness = pixel.iter().map(|&c| c as u32).sum::<u32>();
+                if scene.is_shadowed(&(hit.point + Vec3::new(0.0, 1e-4, 0.0)), &mut rand::thread_rng()) { shadowed.push(brightness) } else { lit.push(brightness) }
+            }
+        }
+        assert!(!shadowed.is_empty() && !lit.is_empty());
+        let brightest_shadow = *shadowed.iter().max().unwrap();
+        let darkest_lit = *lit.iter().min().unwrap();
+        assert!(brightest_shadow < darkest_lit, "{} {}", brightest_shadow, darkest_lit);
+    }
+
+    #[test]
+    fn test_depth_of_field() {
+        use crate::transform::Transformable;
+
+        // Glowing balls left, middle and right, each twice as far away as the
+        // last and as big on screen. Only the middle one is in focus.
+        let mut scene = Scene::default();
+        for (i, depth) in [4.0, 8.0, 16.0].into_iter().enumerate() {
+            let mut ball = Sphere::new(Material { ambient: 1.0, diffuse: 0.0, specular: 0.0, ..Default::default() });
+            ball.translate(0.179 * depth * (i as f64 - 1.0), 0.0, -depth);
+            ball.scale_uniform(0.06 * depth);
+            scene.push(Box::new(ball));
+        }
+        let scene = Arc::new(scene);
+        let dimensions = (60, 20);
+        let camera = |aperture| Camera::new(Point3::origin(), Point3::new(0.0, 0.0, -8.0), Vec3::y(), 30.0, dimensions, aperture, 8.0);
+
+        // Pixels neither black nor white, in each third of the image.
+        let blurred = |aperture| {
+            let image = render(Arc::clone(&scene), camera(aperture), dimensions, 40, 1);
+            let mut counts = [0; 3];
+            for row in &image {
+                for (x, pixel) in row.chunks(3).enumerate() {
+                    if (20..235).contains(&pixel[0]) {
+                        counts[x / 20] += 1;
+                    }
+                }
+            }
+            counts
+        };
+        let pinhole = blurred(0.0);
+        let [near, focused, far] = blurred(0.6);
+        // About as sharp as through a pinhole, give or take the noise.
+        assert!(focused * 2 < pinhole[1] * 3, "{:?} {}", pinhole, focused);
+        assert!(near > focused * 2 && far > focused * 2, "{} {} {}", near, focused, far);
+    }
+
+    #[test]
+    fn test_anti_aliasing() {
+        // A glowing ball on black, its edge cutting pixels at every angle.
+        let mut scene = Scene::default();
+        scene.push(Box::new(Sphere::new(Material { ambient: 1.0, diffuse: 0.0, specular: 0.0, ..Default::default() })));
+        let scene = Arc::new(scene);
+        let camera = |dimensions| Camera::new(Point3::new(0.3, 0.1, -5.0), Point3::origin(), Vec3::y(), 30.0, dimensions, 0.0, 5.0);
+        // Back to linear, undoing the gamma of 2.
+        let brightness = |image: &Image, x: usize, y: usize| (image[y][x * 3] as f64 / 255.0).powi(2);
+
+        // Share of each pixel covered, from 8 by 8 centres across it.
+        let fine = render(Arc::clone(&scene), camera((192, 192)), (192, 192), 1, 1);
+        let coverage = |x: usize, y: usize| {
+            (0..64).map(|i| brightness(&fine, x * 8 + i % 8, y * 8 + i / 8)).sum::<f64>() / 64.0
+        };
+        // Mean squared error against it over the pixels the edge crosses.
+        let error = |samples| {
+            let image = render(Arc::clone(&scene), camera((24, 24)), (24, 24), samples, 1);
+            let edge: Vec<(usize, usize)> = (0..24).flat_map(|x| (0..24).map(move |y| (x, y)))
+                .filter(|&(x, y)| coverage(x, y) > 0.0 && coverage(x, y) < 1.0)
+                .collect();
+            assert!(edge.len() > 20);
+            let error = edge.iter().map(|&(x, y)| (brightness(&image, x, y) - coverage(x, y)).powi(2)).sum::<f64>();
+            (image, error / edge.len() as f64)
+        };
+
+        // One sample is the pixel centre, all or nothing.
+        let (single, aliased) = error(1);
+        for (x, y) in (0..24).flat_map(|x| (0..24).map(move |y| (x, y))) {
+            let centre = scene.colour_at(&camera((24, 24)).ray_for_pixel(x, y), 1);
+            assert_eq!(brightness(&single, x as usize, y as usize), Vec3::from(centre).x);
+        }
+        let (_, smoothed) = error(16);
+        assert!(smoothed * 4.0 < aliased, "{} {}", smoothed, aliased);
+    }
+
+    #[test]
+    fn test_quality_presets() {
+        let dims = (10, 10);
+        assert_eq!(RenderSettings::preset(Quality::Draft, dims), RenderSettings { dimensions: dims, samples: 4, max_depth: 4, ..Default::default() });
+        assert_eq!(RenderSettings::preset(Quality::Medium, dims), RenderSettings { dimensions: dims, samples: 64, max_depth: 10, ..Default::default() });
+        assert_eq!(RenderSettings::preset(Quality::Final, dims), RenderSettings { dimensions: dims, samples: 500, max_depth: 50, ..Default::default() });
+    }
+
+    #[test]
+    fn test_settings_precedence() {
+        let dims = (10, 10);
+        let none = RenderOverrides::default();
+        assert_eq!(RenderSettings::resolve(dims, &none, &none), RenderSettings { dimensions: dims, ..Default::default() });
+
+        // Scene file beats its own preset.
+        let file = RenderOverrides { quality: Some(Quality::Medium), samples: Some(32), ..Default::default() };
+        assert_eq!(RenderSettings::resolve(dims, &file, &none), RenderSettings { dimensions: dims, samples: 32, max_depth: 10, ..Default::default() });
+
+        // Command line preset beats the file's preset and its explicit values,
+        // but not its other settings.
+        let file = RenderOverrides { time_budget: Some(Duration::from_secs(5)), ..file };
+        let cli = RenderOverrides { quality: Some(Quality::Draft), ..Default::default() };
+        let expected = RenderSettings { dimensions: dims, samples: 4, max_depth: 4, time_budget: Some(Duration::from_secs(5)), ..Default::default() };
+        assert_eq!(RenderSettings::resolve(dims, &file, &cli), expected);
+
+        // Explicit command line values beat everything.
+        let cli = RenderOverrides { quality: Some(Quality::Final), samples: Some(8), max_depth: Some(2), ..Default::default() };
+        assert_eq!(RenderSettings::resolve(dims, &file, &cli), RenderSettings { samples: 8, max_depth: 2, ..expected });
+    }
+
+    #[test]
+    fn test_time_budget() {
+        // Only background, so every sample of a pixel is the same whatever the jitter.
+        let mut scene = Scene::default();
+        scene.background = Colour::new(0.25, 0.25, 0.25).into();
+        let scene = Arc::new(scene);
+        let camera = |dimensions| Camera::new(Point3::new(0.0, 0.0, -5.0), Point3::origin(), Vec3::new(0.0, 1.0, 0.0), 30.0, dimensions, 0.0, 5.0);
+
+        let settings = RenderSettings {
+            dimensions: (8, 8),
+            samples: 1000,
+            max_depth: 5,
+            time_budget: Some(Duration::from_nanos(1)),
+            ..Default::default()
+        };
+        let (image, stats) = render_with_stats(Arc::clone(&scene), camera(settings.dimensions), &settings);
+        assert_eq!(stats.samples, 1);
+        assert_eq!(stats.primary_rays, 64);
+
+        // Averaged over the samples taken, not the samples asked for.
+        let single = RenderSettings { samples: 1, time_budget: None, ..settings.clone() };
+        let (expected, _) = render_with_stats(scene, camera(single.dimensions), &single);
+        assert_eq!(image, expected);
+        assert_eq!(image[0][0], 128);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("60s"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("soon").is_err());
+        for rejected in ["-1s", "NaN", "inf", "1e300h", "-0.5ms"] {
+            assert_eq!(parse_duration(rejected), Err(format!("invalid duration {}", rejected)));
+        }
+    }
+
+    #[test]
+    fn test_streamed_matches_in_memory() {
+        use crate::io::{OutputFormat, RowWriter, write_to_file};
+
+        let mut scene = Scene::default();
+        scene.push(Box::new(Sphere::new(Material::default())));
+        scene.lights.push(Light::new(Point3::new(-10.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0)));
+        let scene = Arc::new(scene);
+        // Tall and skinny, with a band height that doesn't divide it.
+        let settings = RenderSettings { dimensions: (3, 50), samples: 1, max_depth: 5, ..Default::default() };
+        let camera = Camera::new(Point3::new(0.0, 0.0, -5.0), Point3::origin(), Vec3::new(0.0, 1.0, 0.0), 60.0, settings.dimensions, 0.0, 5.0);
+        let dir = std::env::temp_dir();
+        let name = |s: &str| dir.join(format!("ray-tracer-{}-{}", std::process::id(), s)).to_str().unwrap().to_string();
+
+        for (format, ext) in [(OutputFormat::PNG, "png"), (OutputFormat::PPM, "ppm")] {
+            let (image, _) = render_with_stats(Arc::clone(&scene), camera, &settings);
+            write_to_file(&name("memory"), image, format.clone(), settings.dimensions).unwrap();
+
+            let mut writer = RowWriter::create(&name("stream"), format, settings.dimensions).unwrap();
+            let mut bands = 0;
+            render_streamed(Arc::clone(&scene), camera, &settings, 8, |rows| {
+                bands += 1;
+                writer.write_rows(rows)
+            }).unwrap();
+            writer.finish().unwrap();
+            assert_eq!(bands, 7);
+
+            let memory = std::fs::read(format!("{}.{}", name("memory"), ext)).unwrap();
+            let stream = std::fs::read(format!("{}.{}", name("stream"), ext)).unwrap();
+            assert_eq!(memory, stream);
+        }
+
+        let post = RenderSettings { denoise: Some(Denoise::default()), ..settings };
+        assert!(render_streamed(scene, camera, &post, 8, |_| Ok(())).is_err());
+    }
 }
@@ -1,78 +1,345 @@
-use crate::colour::BLACK;
-use crate::{Colour, Point3, Material};
-use crate::object::Object;
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::colour::{BLACK, WHITE};
+use crate::{Colour, Point3, Vec3, Material, Translation};
+use crate::object::{Object, ObjectId, EPSILON};
 use crate::intersection::{Intersection, compute_intersections};
 use crate::ray::Ray;
-use crate::light::Light;
+use crate::light::{Light, LightKind};
+use crate::debug::{TraceKind, RayKind};
+use crate::render::TraceContext;
+use crate::math::rand_in_unit_sphere;
+use rand::Rng;
 
-#[derive(Default, Debug)]
+pub mod generators;
+pub mod background;
+
+pub use background::Background;
+
+// Summary of the nearest surface a ray hits, for picking and tooling.
+#[derive(Debug, Clone)]
+pub struct HitInfo {
+    // The ID of the object that was hit.
+    pub obj_id:   ObjectId,
+    // Distance along the ray.
+    pub t:        f64,
+    // World space point of incidence.
+    pub point:    Point3,
+    // World space normal, facing the ray.
+    pub normal:   Vec3,
+    // True if the ray hit the front of the object.
+    pub front_face: bool,
+    // Colour of material/pattern at the point.
+    pub colour:   Colour,
+    pub material: Arc<Material>,
+}
+
+impl From<Intersection> for HitInfo {
+    fn from(hit: Intersection) -> Self {
+        Self {
+            obj_id:     hit.obj_id,
+            t:          hit.t,
+            point:      hit.point,
+            normal:     hit.normal,
+            front_face: hit.front_face,
+            colour:     hit.colour,
+            material:   hit.material,
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct Scene {
     pub objects:    Vec<Box<dyn Object>>,
     pub lights:     Vec<Light>,
-    pub background: Colour,
-    pub id_counter: usize,
+    pub background: Background,
+    // Scales every material's ambient term, white leaves them as they are.
+    pub ambient_light: Colour,
+    // Where the scene's origin was in the coordinates it was written in, see recentre.
+    pub origin:     Vec3,
+    default_material: Arc<Material>,
+    // Object IDs by name, see set_name.
+    names:          HashMap<String, ObjectId>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new(Vec::new(), Vec::new(), Background::default())
+    }
 }
 
 impl Scene {
 
-    pub fn new(mut objects: Vec<Box<dyn Object>>, lights: Vec<Light>, bg: Colour) -> Self {
-        let mut id_counter = 0;
-        for obj in &mut objects {
-            obj.set_id(id_counter);
-            id_counter += 1;
+    pub fn new(objects: Vec<Box<dyn Object>>, lights: Vec<Light>, bg: impl Into<Background>) -> Self {
+        Self {
+            objects,
+            lights,
+            background: bg.into(),
+            ambient_light: WHITE,
+            origin: Vec3::zeros(),
+            default_material: Arc::default(),
+            names: HashMap::new(),
+        }
+    }
+
+    // Material for objects that weren't given one.
+    pub fn default_material(&self) -> &Arc<Material> {
+        &self.default_material
+    }
+
+    pub fn set_default_material(&mut self, material: impl Into<Arc<Material>>) {
+        self.default_material = material.into();
+    }
+
+    // Moves everything so the given point becomes the origin. Scenes far from the
+    // origin lose precision, so recentring on the camera keeps shading accurate.
+    pub fn recentre(&mut self, origin: Point3) {
+        let offset = origin.coords;
+        let to_origin = Translation::from(-offset).to_homogeneous();
+        let from_origin = Translation::from(offset).to_homogeneous();
+
+        for obj in &mut self.objects {
+            let transform = to_origin * obj.transform();
+            let inverse = obj.inverse() * from_origin;
+            obj.set_transform(transform);
+            obj.set_inverse(inverse);
+        }
+        for light in &mut self.lights {
+            light.position -= offset;
+            if let LightKind::Area { corner, .. } = &mut light.kind {
+                *corner -= offset;
+            }
         }
-        Self { objects, lights, id_counter, background: bg }
+        self.origin += offset;
     }
 
-    pub fn push(&mut self, mut object: Box<dyn Object>) {
-        object.set_id(self.id_counter);
-        self.id_counter += 1;
+    // Converts a point back to the coordinates the scene was written in.
+    pub fn to_original(&self, point: &Point3) -> Point3 {
+        point + self.origin
+    }
+
+    pub fn push(&mut self, object: Box<dyn Object>) {
         self.objects.push(object);
     }
 
     pub fn pop(&mut self) -> Option<Box<dyn Object>> {
-        self.objects.pop()
+        let object = self.objects.pop()?;
+        self.names.retain(|_, id| *id != object.id());
+        Some(object)
+    }
+
+    // Objects are hit by walking the list, so there is nothing to rebuild
+    // after removing or replacing one. Finds the object a hit's ID belongs to,
+    // so a hit on a group's child gives the group.
+    pub fn get(&self, id: ObjectId) -> Option<&dyn Object> {
+        self.objects.iter().find(|obj| obj.owns(id)).map(|obj| &**obj)
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut dyn Object> {
+        match self.objects.iter_mut().find(|obj| obj.owns(id)) {
+            Some(obj) => Some(&mut **obj),
+            None => None,
+        }
+    }
+
+    // Takes the object out, along with its name.
+    pub fn remove(&mut self, id: ObjectId) -> Option<Box<dyn Object>> {
+        let index = self.objects.iter().position(|obj| obj.id() == id)?;
+        self.names.retain(|_, named| *named != id);
+        Some(self.objects.remove(index))
+    }
+
+    // Swaps in a new object in the same place, giving it the old one's ID and
+    // name. Returns the old object with a fresh ID so the two never share one,
+    // or gives back the new one if the ID isn't found.
+    pub fn replace(&mut self, id: ObjectId, mut object: Box<dyn Object>) -> Result<Box<dyn Object>, Box<dyn Object>> {
+        let Some(slot) = self.objects.iter_mut().find(|obj| obj.id() == id) else {
+            return Err(object);
+        };
+        object.set_id(id);
+        let mut old = std::mem::replace(slot, object);
+        old.set_id(ObjectId::new());
+        Ok(old)
+    }
+
+    // Names the object with the given ID, taking the name from any object that had it.
+    pub fn set_name(&mut self, id: ObjectId, name: impl Into<String>) {
+        self.names.insert(name.into(), id);
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&dyn Object> {
+        self.get(*self.names.get(name)?)
+    }
+
+    pub fn get_mut_by_name(&mut self, name: &str) -> Option<&mut dyn Object> {
+        self.get_mut(*self.names.get(name)?)
+    }
+
+    pub fn name_of(&self, obj_id: ObjectId) -> Option<&str> {
+        self.names.iter()
+            .find(|(_, &id)| id == obj_id || self.get(id).is_some_and(|obj| obj.owns(obj_id)))
+            .map(|(name, _)| name.as_str())
     }
 
     pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<Intersection> {
         self.objects.iter()
+            .filter(|obj| !ray.primary || obj.visible_to_camera())
             .filter_map(|obj| obj.hit(ray, t_min, t_max))
             .flatten()
             .collect()
     }
 
+    // Whether anything casting shadows is hit between t_min and t_max. Stops at
+    // the first object hit, without working out the hits, so is quicker than hit.
+    pub fn hit_any(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.objects.iter()
+            .filter(|obj| obj.cast_shadow())
+            .any(|obj| obj.hit_any(ray, t_min, t_max))
+    }
+
+    // Nearest hit along the ray, without shading. The point is given in the
+    // scene's original coordinates, even if it has been recentred.
+    pub fn trace(&self, ray: &Ray) -> Option<HitInfo> {
+        self.hit(ray, EPSILON, f64::INFINITY)
+            .into_iter()
+            .min_by(|a, b| a.t.partial_cmp(&b.t).unwrap())
+            .map(HitInfo::from)
+            .map(|hit| HitInfo { point: self.to_original(&hit.point), ..hit })
+    }
+
     pub fn colour_at(&self, ray: &Ray, depth: usize) -> Colour {
+        self.colour_at_traced(ray, depth, &mut TraceContext::default())
+    }
 
-        let mut hits = self.hit(&ray, -0.0001, f64::INFINITY);
-        if hits.is_empty() { return self.background; }
+    // Same as colour_at, but counts rays and reports each step to the recorder if one is given.
+    pub(crate) fn colour_at_traced(&self, ray: &Ray, depth: usize, ctx: &mut TraceContext) -> Colour {
 
+        let mut hits = self.hit(ray, -EPSILON, f64::INFINITY);
+        if hits.is_empty() {
+            ctx.stats.misses += 1;
+            let background = self.background.colour(&ray.direction);
+            if let Some(rec) = &mut ctx.recorder { rec.record(depth, TraceKind::Miss { background }) }
+            return background;
+        }
+
+        ctx.stats.hits += 1;
         compute_intersections(&mut hits);
-        if let Some(hit) = hits.first() {
-            let in_shadow = self.is_shadowed(&hit.over_point);
+        // Hits just behind the origin, like the surface a refracted ray starts
+        // under, only count towards refractive indices.
+        if let Some(hit) = hits.iter().find(|hit| hit.t >= 0.0) {
+            if ray.primary && ctx.primary_hit.is_none() {
+                ctx.primary_hit = Some((hit.normal, hit.t));
+            }
+            if let Some(rec) = &mut ctx.recorder {
+                rec.record(depth, TraceKind::Hit {
+                    obj_id: hit.obj_id,
+                    name:   self.name_of(hit.obj_id).map(String::from),
+                    t:      hit.t,
+                    point:  hit.point,
+                    normal: hit.normal,
+                });
+            }
+
+            // Disabled lights are skipped, shadow rays and all.
+            let mut shadows = Vec::with_capacity(self.lights.len());
+            for (i, light) in self.lights.iter().enumerate().filter(|(_, light)| light.enabled) {
+                ctx.stats.shadow_rays += light.shadow_samples() as u64;
+                let transmission = self.transmission_from(light, &hit.over_point, ray.time, &mut ctx.rng);
+                if let Some(rec) = &mut ctx.recorder {
+                    rec.record(depth, TraceKind::Shadow {
+                        light:    i,
+                        origin:   hit.over_point,
+                        target:   light.target_from(&hit.over_point),
+                        shadowed: transmission == BLACK,
+                    });
+                }
+                shadows.push((light, transmission));
+            }
 
-            let surface_colour = hit.material.light(&self.lights[0], &hit, in_shadow);
-            let reflected_colour = self.reflected_colour_at(&hit.material, &hit, depth);
-            let refracted_colour = self.refracted_colour_at(&hit.material, &hit, depth);
-            if hit.material.reflect > 0.0 && hit.material.transparency > 0.0 {
+            // Catchers only show the background, darkened by the share of light they're shadowed from.
+            if hit.material.shadow_catcher {
+                let lit = shadows.iter().fold(BLACK, |total, &(_, transmission)| total + transmission) * (1.0 / shadows.len().max(1) as f64);
+                let colour = self.background.colour(&ray.direction) * (WHITE - (WHITE - lit) * (1.0 - hit.material.ambient));
+                if let Some(rec) = &mut ctx.recorder {
+                    rec.record(depth, TraceKind::Shade {
+                        surface:   colour,
+                        reflected: BLACK,
+                        refracted: BLACK,
+                        schlick:   None,
+                        total:     colour,
+                    });
+                }
+                return colour;
+            }
+
+            let surface_colour = if shadows.is_empty() {
+                // Only ambient, as if under a white light.
+                hit.colour * self.ambient_light * hit.material.ambient
+            } else {
+                shadows.into_iter()
+                    .map(|(light, transmission)| hit.material.light(light, hit, transmission, self.ambient_light))
+                    .fold(BLACK, |total, colour| total + colour)
+            };
+            let reflected_colour = self.reflected_colour_at(&hit.material, hit, depth, ctx);
+            let refracted_colour = self.refracted_colour_at(&hit.material, hit, depth, ctx);
+            let weighted = hit.material.transparency > 0.0 || hit.material.fresnel;
+            let (schlick, mut colour) = if hit.material.reflect > 0.0 && weighted {
                 let reflectance = hit.schlick();
-                return surface_colour + reflected_colour * reflectance + refracted_colour * (1.0 - reflectance);
+                (Some(reflectance), surface_colour + reflected_colour * reflectance + refracted_colour * (1.0 - reflectance))
             } else {
-                return surface_colour + reflected_colour + refracted_colour;
+                (None, surface_colour + reflected_colour + refracted_colour)
+            };
+            // Hitting the inside of an object, the ray has come from where it
+            // went in, and its light is absorbed along the way.
+            if !hit.front_face && hit.material.absorption != BLACK {
+                colour = colour * hit.material.transmittance(hit.t * ray.direction.norm());
             }
+
+            if let Some(rec) = &mut ctx.recorder {
+                rec.record(depth, TraceKind::Shade {
+                    surface:   surface_colour,
+                    reflected: reflected_colour,
+                    refracted: refracted_colour,
+                    schlick,
+                    total:     colour,
+                });
+            }
+            return colour;
         }
         
-        self.background
+        self.background.colour(&ray.direction)
     }
 
-    fn reflected_colour_at(&self, material: &Material, hit: &Intersection, depth: usize) -> Colour {
+    fn reflected_colour_at(&self, material: &Material, hit: &Intersection, depth: usize, ctx: &mut TraceContext) -> Colour {
         if depth == 0 || material.reflect == 0.0 {
             return BLACK;
         }
-        let reflected = Ray::new(hit.over_point, hit.reflect);
-        self.colour_at(&reflected, depth - 1) * material.reflect      
+        if material.roughness == 0.0 {
+            let reflected = Ray::new(hit.over_point, hit.reflect).with_time(hit.time);
+            ctx.stats.reflection_rays += 1;
+            if let Some(rec) = &mut ctx.recorder { rec.record_ray(depth - 1, RayKind::Reflect, &reflected) }
+            return self.colour_at_traced(&reflected, depth - 1, ctx) * material.reflect;
+        }
+
+        // Rough surfaces average reflections scattered around the perfect one.
+        let samples = material.glossy_samples.max(1);
+        let mut total = BLACK;
+        for _ in 0..samples {
+            let mut direction = (hit.reflect + rand_in_unit_sphere(&mut ctx.rng) * material.roughness).normalize();
+            // Mirrored back out if scattered into the surface.
+            let into = direction.dot(&hit.normal);
+            if into < 0.0 {
+                direction -= hit.normal * (2.0 * into);
+            }
+            let reflected = Ray::new(hit.over_point, direction).with_time(hit.time);
+            ctx.stats.reflection_rays += 1;
+            if let Some(rec) = &mut ctx.recorder { rec.record_ray(depth - 1, RayKind::Reflect, &reflected) }
+            total += self.colour_at_traced(&reflected, depth - 1, ctx);
+        }
+        total * (material.reflect / samples as f64)
     }
 
-    fn refracted_colour_at(&self, material: &Material, hit: &Intersection, depth: usize) -> Colour {
+    fn refracted_colour_at(&self, material: &Material, hit: &Intersection, depth: usize, ctx: &mut TraceContext) -> Colour {
         // Material is opaque/max depth.
         if material.transparency == 0.0 || depth == 0 {
             return BLACK;
@@ -85,42 +352,85 @@ impl Scene {
 
         // Total internal reflection.
         if sin2_t > 1.0 {
+            if let Some(rec) = &mut ctx.recorder { rec.record(depth, TraceKind::TotalInternalReflection) }
             return BLACK;
         }
         
         let cost_t = (1.0 - sin2_t).sqrt();
         let direction = hit.normal * (idx_ratio * cos_i - cost_t) - hit.eye * idx_ratio;
-        let refracted = Ray::new(hit.under_point, direction);
+        let refracted = Ray::new(hit.under_point, direction).with_time(hit.time);
+        ctx.stats.refraction_rays += 1;
+        if let Some(rec) = &mut ctx.recorder { rec.record_ray(depth - 1, RayKind::Refract, &refracted) }
 
-        self.colour_at(&refracted, depth - 1) * material.transparency
+        self.colour_at_traced(&refracted, depth - 1, ctx) * material.transparency
     }
 
-    fn is_shadowed(&self, point: &Point3) -> bool {
-        let shadow_vec = self.lights[0].position - point;
-        
-        let distance = shadow_vec.magnitude();
-        let direction = shadow_vec.normalize();
+    // Whether no light reaches the point.
+    pub(crate) fn is_shadowed(&self, point: &Point3, rng: &mut impl Rng) -> bool {
+        self.lights.iter()
+            .filter(|light| light.enabled)
+            .all(|light| self.transmission_from(light, point, 0.0, rng) == BLACK)
+    }
 
-        let shadow_ray = Ray::new(*point, direction);
-        let hits = self.hit(&shadow_ray, 0.0001, f64::INFINITY);
-        
-        if let Some(hit) = hits.first() {
-            hit.t < distance
-        } else {
-            false
+    // Light from the light reaching the point, white with nothing in the way
+    // and black when it is all blocked, at a time within the shutter for moving
+    // objects. Area and sphere lights are averaged over jittered points across
+    // them, giving soft edged shadows.
+    pub(crate) fn transmission_from(&self, light: &Light, point: &Point3, time: f64, rng: &mut impl Rng) -> Colour {
+        if matches!(light.kind, LightKind::Area { .. } | LightKind::Sphere { .. }) {
+            let targets = light.sample_points(point, rng);
+            let total = targets.iter()
+                .map(|target| {
+                    let to_target = target - point;
+                    self.transmission(point, &to_target.normalize(), to_target.norm(), time)
+                })
+                .fold(BLACK, |total, transmission| total + transmission);
+            return total * (1.0 / targets.len() as f64);
+        }
+        self.transmission(point, &light.direction_from(point), light.distance_from(point), time)
+    }
+
+    // Light getting through everything casting shadows within distance along
+    // direction. Anything opaque stops it, and each transparent object crossed
+    // lets through its colour times its transparency.
+    fn transmission(&self, point: &Point3, direction: &Vec3, distance: f64, time: f64) -> Colour {
+        // The point is already offset from the surface by the object's epsilon.
+        let shadow_ray = Ray::new(*point, *direction).with_time(time);
+        // Most shadow rays either get through untouched, or are stopped by
+        // something opaque on the way, which the loop below returns at.
+        if !self.hit_any(&shadow_ray, 0.0, distance) {
+            return WHITE;
+        }
+        let hits = self.objects.iter()
+            .filter(|obj| obj.cast_shadow())
+            .filter_map(|obj| obj.hit(&shadow_ray, 0.0, distance))
+            .flatten();
+
+        let mut transmission = WHITE;
+        let mut crossed = Vec::new();
+        for hit in hits {
+            if hit.material.transparency == 0.0 {
+                return BLACK;
+            }
+            // Objects only filter once, going in and out.
+            if !crossed.contains(&hit.obj_id) {
+                crossed.push(hit.obj_id);
+                transmission = transmission * hit.colour * hit.material.transparency;
+            }
         }
+        transmission
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Arc;
-    use crate::pattern::MockPattern;
-    use crate::{ray::Ray, Vec3, colour::fuzzy_eq_colour};
-    use crate::object::{Sphere, Plane};
+    use crate::pattern::TestPattern;
+    use crate::{ray::Ray, colour::fuzzy_eq_colour, math::{fuzzy_eq_f64, fuzzy_eq_vec}};
+    use crate::object::{Sphere, Plane, AxisAlignedBoundingBox, Cylinder};
     use crate::material::Material;
     use crate::transform::Transformable;
+    use rand::{SeedableRng, rngs::StdRng};
 
     fn default_sphere() -> Sphere {
         Sphere::new(
@@ -138,6 +448,11 @@ mod tests {
         )
     }
 
+    // Share of a white light blocked from the point, sampled the same way each time.
+    fn shadow_from(scene: &Scene, light: &Light, point: &Point3) -> f64 {
+        1.0 - Vec3::from(scene.transmission_from(light, point, 0.0, &mut StdRng::seed_from_u64(3))).x
+    }
+
     fn default_light() -> Light {
         Light::new(
             Point3::new(-10.0, 10.0, -10.0),
@@ -145,6 +460,327 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_every_light() {
+        let light = |x: f64| Light::new(Point3::new(x, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        // Highlights are where the normal is halfway between the eye and light.
+        let highlight = |x: f64| Ray::new(Point3::new(x * 22.5f64.to_radians().sin(), 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        let mut one = Scene::default();
+        one.push(Box::new(default_sphere()));
+        one.lights.push(light(-10.0));
+        let mut two = Scene::default();
+        two.push(Box::new(default_sphere()));
+        two.lights.extend([light(-10.0), light(10.0)]);
+
+        let (left, right) = (two.colour_at(&highlight(-1.0), 0), two.colour_at(&highlight(1.0), 0));
+        assert!(fuzzy_eq_colour(left, right));
+        let lit = one.colour_at(&highlight(-1.0), 0);
+        let unlit = one.colour_at(&highlight(1.0), 0);
+        let red = |colour: Colour| Vec3::from(colour).x;
+        assert!(red(right) > red(lit) && red(right) > red(unlit) + 0.2);
+
+        // With no lights only ambient is left.
+        let mut dark = Scene::default();
+        dark.push(Box::new(default_sphere()));
+        let colour = dark.colour_at(&highlight(1.0), 0);
+        assert!(fuzzy_eq_colour(colour, Colour::new(0.8, 1.0, 0.6) * 0.1));
+    }
+
+    #[test]
+    fn test_nearest_occluder() {
+        // One sphere between the point and the light, and one past the light.
+        let sphere_at = |y: f64| {
+            let mut sphere = Sphere::new(Material::default());
+            sphere.translate(0.0, y, 0.0);
+            Box::new(sphere)
+        };
+        for order in [[5.0, 20.0], [20.0, 5.0]] {
+            let mut scene = Scene::default();
+            scene.lights.push(Light::new(Point3::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+            for y in order {
+                scene.push(sphere_at(y));
+            }
+            assert!(scene.is_shadowed(&Point3::origin(), &mut rand::thread_rng()), "{:?}", order);
+            assert!(!scene.is_shadowed(&Point3::new(3.0, 0.0, 0.0), &mut rand::thread_rng()), "{:?}", order);
+        }
+
+        let mut scene = Scene::default();
+        scene.push(sphere_at(20.0));
+        let up = Ray::new(Point3::origin(), Vec3::y());
+        assert!(!scene.hit_any(&up, 0.0, 10.0));
+        assert!(scene.hit_any(&up, 0.0, 30.0));
+        scene.objects[0].set_cast_shadow(false);
+        assert!(!scene.hit_any(&up, 0.0, 30.0));
+    }
+
+    #[test]
+    fn test_disabled_light() {
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut one = Scene::default();
+        one.push(Box::new(default_sphere()));
+        one.lights.push(default_light());
+        let mut two = Scene::default();
+        two.push(Box::new(default_sphere()));
+        two.lights.extend([default_light(), default_light().with_intensity(3.0).with_enabled(false)]);
+
+        let mut ctx = TraceContext::default();
+        assert_eq!(two.colour_at_traced(&ray, 0, &mut ctx), one.colour_at(&ray, 0));
+        assert_eq!(ctx.stats.shadow_rays, 1);
+
+        // With every light off only ambient is left, as with none.
+        two.lights[0].enabled = false;
+        assert!(fuzzy_eq_colour(two.colour_at(&ray, 0), Colour::new(0.8, 1.0, 0.6) * 0.1));
+    }
+
+    #[test]
+    fn test_ambient_light() {
+        let mut scene = Scene::default();
+        scene.push(Box::new(default_sphere()));
+        scene.lights.push(default_light());
+        // The side facing the light, and the side away from it.
+        let lit = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::z());
+        let unlit = Ray::new(Point3::new(0.0, 0.0, 5.0), -Vec3::z());
+        let ambient = Colour::new(0.8, 1.0, 0.6) * 0.1;
+        assert!(fuzzy_eq_colour(scene.colour_at(&unlit, 0), ambient));
+        let full = scene.colour_at(&lit, 0);
+
+        scene.ambient_light = Colour::new(0.5, 0.5, 2.0);
+        assert!(fuzzy_eq_colour(scene.colour_at(&unlit, 0), ambient * Colour::new(0.5, 0.5, 2.0)));
+
+        // Black leaves only the light that reaches the surface.
+        scene.ambient_light = BLACK;
+        assert_eq!(scene.colour_at(&unlit, 0), BLACK);
+        assert!(fuzzy_eq_colour(scene.colour_at(&lit, 0) + ambient, full));
+        scene.lights.clear();
+        assert_eq!(scene.colour_at(&lit, 0), BLACK);
+    }
+
+    #[test]
+    fn test_directional_shadow() {
+        let direction = Vec3::new(1.0, -2.0, 0.0);
+        // Sphere centres, and where straight down the light they meet the floor.
+        for centre in [Point3::new(0.0, 2.0, 0.0), Point3::new(-7.0, 9.0, 4.0)] {
+            let mut scene = Scene::default();
+            scene.push(Box::new(Plane::new(Material::default())));
+            let mut sphere = Sphere::new(Material::default());
+            sphere.translate(centre.x, centre.y, centre.z);
+            scene.push(Box::new(sphere));
+            scene.lights.push(Light::directional(direction, Colour::new(1.0, 1.0, 1.0)));
+
+            // Across the light the shadow is exactly as wide as the sphere.
+            // Just above it, as hits' over points are.
+            let floor = centre + direction * (centre.y / 2.0) + Vec3::new(0.0, 1e-6, 0.0);
+            assert!(scene.is_shadowed(&(floor + Vec3::new(0.0, 0.0, 0.99)), &mut rand::thread_rng()));
+            assert!(scene.is_shadowed(&(floor - Vec3::new(0.0, 0.0, 0.99)), &mut rand::thread_rng()));
+            assert!(!scene.is_shadowed(&(floor + Vec3::new(0.0, 0.0, 1.01)), &mut rand::thread_rng()));
+            assert!(!scene.is_shadowed(&(floor - Vec3::new(0.0, 0.0, 1.01)), &mut rand::thread_rng()));
+        }
+
+        // No falloff, however far away the light is.
+        let light = Light::directional(direction, Colour::new(1.0, 1.0, 1.0)).with_attenuation(0.0, 0.0, 1.0);
+        assert!(fuzzy_eq_vec(&light.direction_from(&Point3::new(100.0, 0.0, 3.0)), &-direction.normalize()));
+        assert_eq!(light.falloff(light.distance_from(&Point3::origin())), 1.0);
+    }
+
+    #[test]
+    fn test_area_light_penumbra() {
+        // Width of floor, along x, that is neither fully lit nor fully shadowed.
+        let penumbra = |size: f64| {
+            let mut scene = Scene::default();
+            scene.push(Box::new(Plane::new(Material::default())));
+            let mut sphere = Sphere::new(Material::default());
+            sphere.translate(0.0, 2.0, 0.0);
+            scene.push(Box::new(sphere));
+            let corner = Point3::new(-size / 2.0, 10.0, -size / 2.0);
+            scene.lights.push(Light::area(corner, Vec3::x() * size, Vec3::z() * size, 64, Colour::new(1.0, 1.0, 1.0)));
+
+            let partial = (-300..=300)
+                .map(|x| shadow_from(&scene, &scene.lights[0], &Point3::new(x as f64 / 100.0, 1e-6, 0.0)))
+                .filter(|&shadow| shadow > 0.0 && shadow < 1.0)
+                .count();
+            partial as f64 / 100.0
+        };
+        let (small, large) = (penumbra(0.5), penumbra(4.0));
+        assert!(small > 0.0 && small < 0.4, "{}", small);
+        assert!(large > small * 4.0, "{} {}", small, large);
+
+        // Under the middle of the sphere no sample gets through, and far away all do.
+        let mut scene = Scene::default();
+        let mut sphere = Sphere::new(Material::default());
+        sphere.translate(0.0, 2.0, 0.0);
+        scene.push(Box::new(sphere));
+        scene.lights.push(Light::area(Point3::new(-0.5, 10.0, -0.5), Vec3::x(), Vec3::z(), 16, Colour::new(1.0, 1.0, 1.0)));
+        assert_eq!(shadow_from(&scene, &scene.lights[0], &Point3::origin()), 1.0);
+        assert_eq!(shadow_from(&scene, &scene.lights[0], &Point3::new(5.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_sphere_light_shadow() {
+        let white = Colour::new(1.0, 1.0, 1.0);
+        // A slab over every x below 0, halfway between the floor and the light.
+        let mut scene = Scene::default();
+        scene.push(Box::new(AxisAlignedBoundingBox::from_corners(
+            Material::default(),
+            Point3::new(-100.0, 4.9, -100.0),
+            Point3::new(0.0, 5.0, 100.0),
+        )));
+        let shadow = |light: &Light, x: f64| shadow_from(&scene, light, &Point3::new(x, 0.0, 0.0));
+
+        let light = Light::sphere(Point3::new(0.0, 10.0, 0.0), 1.0, 64, white);
+        assert_eq!(shadow(&light, -5.0), 1.0);
+        assert_eq!(shadow(&light, 5.0), 0.0);
+        // Right under the slab's edge it covers half of the light.
+        assert!((shadow(&light, 0.0) - 0.5).abs() < 0.1, "{}", shadow(&light, 0.0));
+        // The samples come from the rng passed in.
+        let seeded = |seed| scene.transmission_from(&light, &Point3::origin(), 0.0, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(seeded(1), seeded(1));
+
+        // Shrinking the light sharpens the shadow to the point light's.
+        let tiny = Light::sphere(Point3::new(0.0, 10.0, 0.0), 1e-6, 64, white);
+        let point = Light::new(Point3::new(0.0, 10.0, 0.0), white);
+        for x in [-0.5, -0.01, 0.01, 0.5] {
+            assert_eq!(shadow(&tiny, x), shadow(&point, x));
+        }
+    }
+
+    #[test]
+    fn test_transparent_shadows() {
+        let light = Light::new(Point3::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0));
+        let pane = |y: f64, colour: Colour, transparency: f64| {
+            Box::new(AxisAlignedBoundingBox::from_corners(
+                Material { colour, transparency, ..Default::default() },
+                Point3::new(-1.0, y, -1.0),
+                Point3::new(1.0, y + 0.1, 1.0),
+            ))
+        };
+        let under = Point3::origin();
+
+        let mut scene = Scene::default();
+        scene.lights.push(light);
+        scene.push(pane(2.0, Colour::new(1.0, 0.5, 0.0), 0.8));
+        let one = scene.transmission_from(&light, &under, 0.0, &mut rand::thread_rng());
+        assert!(fuzzy_eq_colour(one, Colour::new(0.8, 0.4, 0.0)));
+
+        // Panes stack up multiplicatively.
+        scene.push(pane(4.0, Colour::new(1.0, 1.0, 1.0), 0.5));
+        let two = scene.transmission_from(&light, &under, 0.0, &mut rand::thread_rng());
+        assert!(fuzzy_eq_colour(two, Colour::new(0.4, 0.2, 0.0)));
+        assert!(!scene.is_shadowed(&under, &mut rand::thread_rng()));
+
+        // Anything opaque still blocks everything.
+        scene.push(pane(6.0, Colour::new(1.0, 1.0, 1.0), 0.0));
+        assert_eq!(scene.transmission_from(&light, &under, 0.0, &mut rand::thread_rng()), BLACK);
+        assert!(scene.is_shadowed(&under, &mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn test_absorption() {
+        // A rod 10 long and 1 across, of glass that doesn't bend light.
+        let glass = Material {
+            absorption: Colour::new(0.2, 0.05, 0.05),
+            ..Material::dielectric(1.0)
+        };
+        let mut scene = Scene { background: WHITE.into(), ..Default::default() };
+        scene.push(Box::new(Cylinder::new(glass, 0.5, -5.0, 5.0, true)));
+
+        let end_on = scene.colour_at(&Ray::new(Point3::new(0.0, 10.0, 0.0), -Vec3::y()), 5);
+        let side_on = scene.colour_at(&Ray::new(Point3::new(0.0, 0.0, -10.0), Vec3::z()), 5);
+        assert!(fuzzy_eq_colour(end_on, Colour::new((-2.0f64).exp(), (-0.5f64).exp(), (-0.5f64).exp())));
+        assert!(fuzzy_eq_colour(side_on, Colour::new((-0.2f64).exp(), (-0.05f64).exp(), (-0.05f64).exp())));
+
+        // Without absorption it is see through either way.
+        scene.objects[0] = Box::new(Cylinder::new(Material::dielectric(1.0), 0.5, -5.0, 5.0, true));
+        assert!(fuzzy_eq_colour(scene.colour_at(&Ray::new(Point3::new(0.0, 10.0, 0.0), -Vec3::y()), 5), WHITE));
+    }
+
+    #[test]
+    fn test_glossy_reflection() {
+        // A glowing ball over a mirror floor, in the dark.
+        let scene_with = |roughness: f64| {
+            let mut scene = Scene::default();
+            scene.push(Box::new(Plane::new(Material {
+                colour: BLACK,
+                roughness,
+                glossy_samples: 64,
+                ..Material::mirror()
+            })));
+            let mut ball = Sphere::new(Material { ambient: 1.0, ..Default::default() });
+            ball.translate(0.0, 3.0, 0.0);
+            scene.push(Box::new(ball));
+            scene
+        };
+        // Looking straight down, the reflection is straight up.
+        let down = |x: f64| Ray::new(Point3::new(x, 10.0, 0.0), -Vec3::y());
+        let red = |colour: Colour| Vec3::from(colour).x;
+
+        let sharp = scene_with(0.0);
+        let mut ctx = TraceContext::default();
+        assert_eq!(sharp.colour_at_traced(&down(1.2), 2, &mut ctx), BLACK);
+        assert_eq!(ctx.stats.reflection_rays, 1);
+        assert!(fuzzy_eq_colour(sharp.colour_at(&down(0.0), 2), Colour::new(1.0, 1.0, 1.0)));
+
+        // Rough, the ball's edge bleeds into its surroundings.
+        let rough = scene_with(0.3);
+        let mut ctx = TraceContext::default();
+        let beside = rough.colour_at_traced(&down(1.2), 2, &mut ctx);
+        assert!(red(beside) > 0.05 && red(beside) < 0.95, "{}", beside);
+        assert_eq!(ctx.stats.reflection_rays, 64);
+        assert!(red(rough.colour_at(&down(0.0), 2)) > 0.95);
+    }
+
+    #[test]
+    fn test_checker_floor() {
+        use crate::Camera;
+        use crate::pattern::{Pattern, Checkers, UvCheckers};
+
+        let (white, black) = (Colour::new(1.0, 1.0, 1.0), BLACK);
+        let patterns: [(Arc<dyn Pattern>, f64); 2] = [
+            (Arc::new(Checkers::new(white, black)), 1.0),
+            (Arc::new(UvCheckers::new(2.0, 2.0, white, black)), 0.5),
+        ];
+        let camera = Camera::new(Point3::new(0.3, 2.0, -5.0), Point3::new(0.0, 0.0, 3.0), Vec3::y(), 60.0, (64, 48), 0.0, 8.0);
+        for (pattern, size) in patterns {
+            let material = Material { pattern: Some(pattern), ambient: 1.0, diffuse: 0.0, specular: 0.0, ..Default::default() };
+            let mut scene = Scene::default();
+            scene.push(Box::new(Plane::new(material)));
+            // Every pixel is the colour of the square its centre lands in.
+            for (x, y) in (0..64).flat_map(|x| (0..48).map(move |y| (x, y))) {
+                let ray = camera.ray_for_pixel(x, y);
+                let Some(hit) = scene.trace(&ray) else { continue };
+                let (cx, cz) = (hit.point.x / size, hit.point.z / size);
+                // Too close to an edge to say.
+                if [cx, cz].iter().any(|v| (v - v.round()).abs() < 1e-3) {
+                    continue;
+                }
+                let expected = if (cx.floor() + cz.floor()) as i64 % 2 == 0 { white } else { black };
+                assert_eq!(scene.colour_at(&ray, 1), expected, "pixel {} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fresnel_reflection() {
+        // A black floor under a white sky, only lit by what it reflects.
+        let scene_with = |fresnel: bool| {
+            let floor = Plane::new(Material { colour: BLACK, reflect: 0.2, fresnel, ..Default::default() });
+            let mut scene = Scene { background: WHITE.into(), ..Default::default() };
+            scene.push(Box::new(floor));
+            scene
+        };
+        let down = Ray::new(Point3::new(0.0, 1.0, 0.0), -Vec3::y());
+        let grazing = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -0.05, 1.0).normalize());
+        let red = |colour: Colour| Vec3::from(colour).x;
+
+        let constant = scene_with(false);
+        assert!(fuzzy_eq_colour(constant.colour_at(&down, 2), Colour::new(0.2, 0.2, 0.2)));
+        assert!(fuzzy_eq_colour(constant.colour_at(&grazing, 2), Colour::new(0.2, 0.2, 0.2)));
+
+        let fresnel = scene_with(true);
+        assert!(red(fresnel.colour_at(&down, 2)) < 1e-6);
+        assert!(red(fresnel.colour_at(&grazing, 2)) > 0.15);
+    }
+
     #[test]
     fn test_nonreflective_colour() {
         let mut scene = Scene::default();
@@ -169,7 +805,7 @@ mod tests {
 
         let ray = Ray::new(Point3::origin(), Vec3::new(0.0, 0.0, 1.0));
         let hit_rec = &scene.hit(&ray, 0.0001, f64::INFINITY)[0];
-        let colour = scene.reflected_colour_at(scene.objects[1].material(), hit_rec, 1);
+        let colour = scene.reflected_colour_at(scene.objects[1].material(), hit_rec, 1, &mut TraceContext::default());
         assert_eq!(colour, Colour::new(0.0, 0.0, 0.0));
     }
 
@@ -196,7 +832,7 @@ mod tests {
         
         let ray = Ray::new(Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0));
         let hit_rec = &scene.hit(&ray, 0.0001, f64::INFINITY)[0];
-        let colour = scene.reflected_colour_at(scene.objects[1].material(), hit_rec, 1);
+        let colour = scene.reflected_colour_at(scene.objects[1].material(), hit_rec, 1, &mut TraceContext::default());
         // 0.5 reflectiveness so should be half the colour of the light.
         assert!(fuzzy_eq_colour(colour, Colour::new(0.19032, 0.2379, 0.14274)));
     }
@@ -210,7 +846,7 @@ mod tests {
         let mut intersections = scene.hit(&ray, 0.0001, f64::INFINITY);
         compute_intersections(&mut intersections);
         let hit = &intersections[0];
-        let colour = scene.refracted_colour_at(&hit.material, hit, 5);
+        let colour = scene.refracted_colour_at(&hit.material, hit, 5, &mut TraceContext::default());
         assert_eq!(colour, BLACK);
     }
 
@@ -223,7 +859,7 @@ mod tests {
         let mut intersections = scene.hit(&ray, 0.0001, f64::INFINITY);
         compute_intersections(&mut intersections);
         let hit = &intersections[0];
-        let colour = scene.refracted_colour_at(&hit.material, hit, 0);
+        let colour = scene.refracted_colour_at(&hit.material, hit, 0, &mut TraceContext::default());
         assert_eq!(colour, BLACK);
     }
 
@@ -235,7 +871,7 @@ mod tests {
             ambient: 1.0,
             diffuse: 0.7,
             specular: 0.2,
-            pattern: Some(Arc::new(MockPattern::new())),
+            pattern: Some(Arc::new(TestPattern::raw())),
             ..Default::default()
         });
         scene.push(Box::new(sphere));
@@ -246,6 +882,8 @@ mod tests {
             ..Default::default()
         });
         sphere2.scale_uniform(0.5);
+        // Expected colour assumes the unscaled offset.
+        sphere2.set_epsilon(EPSILON);
         scene.push(Box::new(sphere2));
 
         scene.lights.push(default_light());
@@ -254,7 +892,7 @@ mod tests {
         let mut intersections = scene.hit(&ray, -f64::INFINITY, f64::INFINITY);    
         compute_intersections(&mut intersections);
         let hit = &intersections[2];
-        let colour = scene.refracted_colour_at(&hit.material, hit, 5);
+        let colour = scene.refracted_colour_at(&hit.material, hit, 5, &mut TraceContext::default());
         assert!(fuzzy_eq_colour(colour, Colour::new(0.0, 0.99888, 0.04725)));
     }
 
@@ -287,6 +925,261 @@ mod tests {
 
         let ray = Ray::new(Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0));
         let colour = scene.colour_at(&ray, 5);
-        assert!(fuzzy_eq_colour(colour, Colour::new(0.93642, 0.68642, 0.68642)))
+        // Half the light gets through the glass floor to the red sphere, which
+        // would be 0.93642 red if the floor cast a full shadow.
+        assert!(fuzzy_eq_colour(colour, Colour::new(1.12547, 0.68642, 0.68642)))
+    }
+
+    #[test]
+    fn test_trace() {
+        let dimensions = (200, 100);
+        let (scene, camera) = crate::parse_scene("scenes/examples/3spheres.yaml", dimensions).unwrap();
+
+        // Centre pixel looks straight at the reflective ball.
+        let ray = camera.ray_for_pixel(100, 50);
+        let info = scene.trace(&ray).unwrap();
+        assert_eq!(info.obj_id, scene.objects[0].id());
+        assert!(info.front_face);
+        assert!(info.t > 55.0 && info.t < 57.0);
+        assert!(fuzzy_eq_f64((info.point - Point3::new(0.0, 4.0, 30.0)).magnitude(), 4.0));
+        assert_eq!(info.material.reflect, 0.5);
+
+        let miss = Ray::new(Point3::origin(), Vec3::new(0.0, 1.0, 0.0));
+        assert!(scene.trace(&miss).is_none());
+    }
+
+    #[test]
+    fn test_merged_scenes_keep_ids_apart() {
+        let glass = |ior: f64, radius: f64| {
+            let mut sphere = Sphere::new(Material { refractive_index: ior, ..Material::glass() });
+            sphere.scale_uniform(radius);
+            Box::new(sphere) as Box<dyn Object>
+        };
+        let mut scene = Scene::new(vec![glass(1.5, 2.0)], vec![], BLACK);
+        let other = Scene::new(vec![glass(2.0, 1.0)], vec![], BLACK);
+        for obj in other.objects {
+            scene.push(obj);
+        }
+        assert_ne!(scene.objects[0].id(), scene.objects[1].id());
+
+        // The inner sphere is entered from the outer one, not from air.
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut hits = scene.hit(&ray, 0.0, f64::INFINITY);
+        compute_intersections(&mut hits);
+        let idx: Vec<_> = hits.iter().map(|hit| (hit.exit_idx, hit.enter_idx)).collect();
+        assert_eq!(idx, vec![(1.0, 1.5), (1.5, 2.0), (2.0, 1.5), (1.5, 1.0)]);
+    }
+
+    #[test]
+    fn test_names() {
+        use crate::object::{Csg, CsgOp};
+        let mut scene = Scene::default();
+        scene.push(Box::new(default_sphere()));
+        let csg = Csg::new(Material::default(), CsgOp::Union, Box::new(default_sphere()), Box::new(default_sphere()));
+        scene.push(Box::new(csg));
+        let id = scene.objects[1].id();
+        scene.set_name(id, "pair");
+
+        scene.get_mut_by_name("pair").unwrap().translate(0.0, 2.0, 0.0);
+        assert!(fuzzy_eq_f64(scene.objects[1].transform()[(1, 3)], 2.0));
+        assert_eq!(scene.name_of(id), Some("pair"));
+        assert_eq!(scene.name_of(scene.objects[0].id()), None);
+
+        scene.pop();
+        assert!(scene.get_by_name("pair").is_none());
+    }
+
+    #[test]
+    fn test_named_group_hit() {
+        use crate::object::Group;
+        let mut inner = default_sphere();
+        inner.translate(0.0, 0.0, 3.0);
+        let nested = Group::new(Material::default(), vec![Box::new(inner)]);
+        let mut group = Group::new(Material::default(), vec![Box::new(default_sphere()), Box::new(nested)]);
+        group.translate(5.0, 0.0, 0.0);
+        let mut scene = Scene::default();
+        scene.push(Box::new(default_sphere()));
+        scene.push(Box::new(group));
+        let id = scene.objects[1].id();
+        scene.set_name(id, "hexagon");
+
+        // Hits keep the children's IDs but resolve to the group.
+        for z in [0.0, 3.0] {
+            let ray = Ray::new(Point3::new(5.0, 5.0, z), Vec3::new(0.0, -1.0, 0.0));
+            let hit = scene.trace(&ray).unwrap();
+            assert_ne!(hit.obj_id, id);
+            assert_eq!(scene.get(hit.obj_id).unwrap().id(), id);
+            assert_eq!(scene.name_of(hit.obj_id), Some("hexagon"));
+        }
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(scene.name_of(scene.trace(&ray).unwrap().obj_id), None);
+
+        // Normals outside of hit come from the nearest child.
+        let normal = scene.objects[1].normal_at(&Point3::new(5.0, 0.0, 4.0));
+        assert!(fuzzy_eq_vec(&normal, &Vec3::new(0.0, 0.0, 1.0)));
+        let normal = scene.objects[1].normal_at(&Point3::new(4.0, 0.0, 0.0));
+        assert!(fuzzy_eq_vec(&normal, &Vec3::new(-1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_remove_and_replace() {
+        let mut scene = Scene::default();
+        scene.push(Box::new(default_sphere()));
+        let id = scene.objects[0].id();
+        scene.set_name(id, "ball");
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        // The replacement takes the old one's place, ID and name.
+        let mut plane = Plane::new(Material::default());
+        plane.rotate(crate::Axis::X, 90.0);
+        let old = scene.replace(id, Box::new(plane)).unwrap();
+        assert!(old.transform().is_identity(0.0));
+        assert_ne!(old.id(), id);
+        assert_eq!(scene.get(id).unwrap().id(), id);
+        assert_eq!(scene.get_by_name("ball").unwrap().id(), id);
+        assert!(fuzzy_eq_f64(scene.trace(&ray).unwrap().t, 5.0));
+        assert_eq!(scene.trace(&ray).unwrap().obj_id, id);
+
+        // Removing the only object leaves the scene empty.
+        assert_eq!(scene.remove(id).unwrap().id(), id);
+        assert!(scene.objects.is_empty());
+        assert!(scene.get(id).is_none());
+        assert!(scene.get_by_name("ball").is_none());
+        assert!(scene.trace(&ray).is_none());
+
+        // Missing IDs change nothing, and the object is given back.
+        scene.push(Box::new(default_sphere()));
+        assert!(scene.remove(id).is_none());
+        assert!(scene.replace(id, Box::new(default_sphere())).is_err());
+        assert_eq!(scene.objects.len(), 1);
+    }
+
+    #[test]
+    fn test_no_acne_at_large_scale() {
+        // A sphere resting on a plane, everything 1000 times unit size and far from the origin.
+        let offset = Vec3::new(2.0e6, 0.0, -3.0e6);
+        let mut scene = Scene::default();
+        let mut floor = Plane::new(Material::default());
+        floor.translate(offset.x, offset.y, offset.z);
+        scene.push(Box::new(floor));
+        let mut ball = Sphere::new(Material::default());
+        ball.translate(offset.x, offset.y + 1000.0, offset.z);
+        ball.scale_uniform(1000.0);
+        scene.push(Box::new(ball));
+        let light_pos = Point3::from(offset + Vec3::new(-10000.0, 10000.0, -10000.0));
+        scene.lights.push(Light::new(light_pos, Colour::new(1.0, 1.0, 1.0)));
+
+        // Every point facing the light must see it, apart from the floor in the ball's shadow.
+        let eye = Point3::from(offset + Vec3::new(0.0, 1500.0, -5000.0));
+        let mut lit = 0;
+        for i in 0..40 {
+            for j in 0..40 {
+                let target = Point3::from(offset + Vec3::new(i as f64 * 100.0 - 2000.0, j as f64 * 75.0 - 1000.0, 0.0));
+                let ray = Ray::new(eye, (target - eye).normalize());
+                let mut hits = scene.hit(&ray, EPSILON, f64::INFINITY);
+                if hits.is_empty() {
+                    continue;
+                }
+                compute_intersections(&mut hits);
+                let hit = &hits[0];
+                let to_light = (light_pos - hit.point).normalize();
+                // Whether the ball is between the point and the light, with some room around its edge.
+                let (to_point, to_ball) = (hit.point - light_pos, offset + Vec3::new(0.0, 1000.0, 0.0) - light_pos.coords);
+                let along = to_ball.dot(&to_point.normalize());
+                let behind_ball = along < to_point.norm() && (to_ball - to_point.normalize() * along).norm() < 1050.0;
+                if hit.normal.dot(&to_light) > 0.05 && !behind_ball {
+                    assert!(!scene.is_shadowed(&hit.over_point, &mut rand::thread_rng()), "acne at {:?}", hit.point);
+                    lit += 1;
+                }
+            }
+        }
+        assert!(lit > 500, "{}", lit);
+    }
+
+    #[test]
+    fn test_shadow_catcher() {
+        let mut scene = Scene {
+            background: Background::VerticalGradient { top: Colour::new(0.2, 0.4, 0.6), bottom: Colour::new(0.9, 0.6, 0.1) },
+            ..Default::default()
+        };
+        scene.push(Box::new(Plane::new(Material {
+            ambient: 0.25,
+            shadow_catcher: true,
+            ..Default::default()
+        })));
+        let mut sphere = default_sphere();
+        sphere.translate(0.0, 2.0, 0.0);
+        scene.push(Box::new(sphere));
+        scene.lights.push(Light::new(Point3::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+
+        // Lit, the catcher shows the background behind it in the ray's direction.
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let slanted = Vec3::new(0.0, -1.0, 1.0).normalize();
+        for (origin, dir) in [(Point3::new(5.0, 5.0, 0.0), down), (Point3::new(6.0, 3.0, 0.0), slanted)] {
+            let lit = scene.colour_at(&Ray::new(origin, dir), 5);
+            assert_eq!(lit, scene.background.colour(&dir));
+        }
+        assert_ne!(scene.background.colour(&down), scene.background.colour(&slanted));
+
+        // Directly beneath the sphere.
+        let shadowed = scene.colour_at(&Ray::new(Point3::new(0.0, 0.5, 0.0), down), 5);
+        assert_eq!(shadowed, scene.background.colour(&down) * 0.25);
+    }
+
+    #[test]
+    fn test_huge_floor_acne() {
+        let mut scene = Scene::default();
+        let mut floor = Plane::new(Material::default());
+        floor.scale_uniform(10_000.0);
+        scene.push(Box::new(floor));
+        scene.lights.push(Light::new(Point3::new(0.0, 1000.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+
+        // Grazing rays from just above the floor out to a few thousand units.
+        let eye = Point3::new(0.0, 2.0, 0.0);
+        for i in 0..50 {
+            for j in 0..50 {
+                let target = Point3::new(i as f64 * 73.1 - 1800.0, 0.0, j as f64 * 91.7 + 10.0);
+                let ray = Ray::new(eye, (target - eye).normalize());
+                let hit = &scene.hit(&ray, 0.0001, f64::INFINITY)[0];
+                assert!(!scene.is_shadowed(&hit.over_point, &mut rand::thread_rng()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_tiny_contact_shadow() {
+        let mut scene = Scene::default();
+        let mut bead = Sphere::new(Material::default());
+        bead.scale_uniform(0.001);
+        // Sits just above the bead, between it and the light.
+        let mut grain = Sphere::new(Material::default());
+        grain.translate(0.0, 0.00105, 0.0);
+        grain.scale_uniform(0.00003);
+        scene.lights.push(Light::new(Point3::new(0.0, 10.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+
+        let top = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = &bead.hit(&top, 0.0, f64::INFINITY).unwrap()[0];
+        assert!(fuzzy_eq_f64(bead.epsilon(), 1e-7));
+        scene.push(Box::new(grain));
+        assert!(scene.is_shadowed(&hit.over_point, &mut rand::thread_rng()));
+
+        // The unscaled offset jumps right over the grain, letting light leak in.
+        bead.set_epsilon(EPSILON);
+        let hit = &bead.hit(&top, 0.0, f64::INFINITY).unwrap()[0];
+        assert!(!scene.is_shadowed(&hit.over_point, &mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn test_recentre() {
+        let mut sphere = default_sphere();
+        sphere.translate(100.0, 0.0, 0.0);
+        let mut scene = Scene::new(vec![Box::new(sphere)], vec![default_light()], Colour::default());
+        scene.recentre(Point3::new(100.0, 0.0, -5.0));
+
+        assert_eq!(scene.lights[0].position, Point3::new(-110.0, 10.0, -5.0));
+        let ray = Ray::new(Point3::origin(), Vec3::new(0.0, 0.0, 1.0));
+        let hit = scene.trace(&ray).unwrap();
+        assert!(fuzzy_eq_f64(hit.t, 4.0));
+        assert!(fuzzy_eq_vec(&(hit.point - Point3::new(100.0, 0.0, -1.0)), &Vec3::zeros()));
     }
 }
@@ -0,0 +1,110 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+use crate::{Colour, Vec3};
+use crate::colour::BLACK;
+use crate::pattern::Texture;
+
+// What rays that miss everything see.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Flat(Colour),
+    // From bottom straight down to top straight up, by the ray's height, like
+    // the sky in Ray Tracing in One Weekend.
+    VerticalGradient { top: Colour, bottom: Colour },
+    // An equirectangular image all around, its middle straight ahead along z
+    // and the top straight up. Turned rotation degrees about y.
+    Environment { map: Arc<Texture>, rotation: f64 },
+}
+
+impl Background {
+    pub fn colour(&self, direction: &Vec3) -> Colour {
+        match self {
+            Background::Flat(colour) => *colour,
+            Background::VerticalGradient { top, bottom } => {
+                let t = 0.5 * (direction.normalize().y + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            },
+            Background::Environment { map, rotation } => {
+                let direction = direction.normalize();
+                // Turning right from z towards x moves right across the image.
+                let longitude = direction.x.atan2(direction.z) - rotation.to_radians();
+                let latitude = direction.y.clamp(-1.0, 1.0).acos();
+                map.sample(0.5 + longitude / (2.0 * PI), 1.0 - latitude / PI)
+            },
+        }
+    }
+}
+
+// Maps are compared by identity.
+impl PartialEq for Background {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Background::Flat(a), Background::Flat(b)) => a == b,
+            (Background::VerticalGradient { top: a, bottom: b }, Background::VerticalGradient { top: c, bottom: d }) => a == c && b == d,
+            (Background::Environment { map: a, rotation: b }, Background::Environment { map: c, rotation: d }) => Arc::ptr_eq(a, c) && b == d,
+            _ => false,
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Flat(BLACK)
+    }
+}
+
+impl From<Colour> for Background {
+    fn from(colour: Colour) -> Self {
+        Background::Flat(colour)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::fuzzy_eq_colour;
+
+    #[test]
+    fn test_vertical_gradient() {
+        let (top, bottom) = (Colour::new(0.5, 0.7, 1.0), Colour::new(1.0, 1.0, 1.0));
+        let sky = Background::VerticalGradient { top, bottom };
+        assert!(fuzzy_eq_colour(sky.colour(&Vec3::new(0.0, 3.0, 0.0)), top));
+        assert!(fuzzy_eq_colour(sky.colour(&-Vec3::y()), bottom));
+        assert!(fuzzy_eq_colour(sky.colour(&Vec3::new(2.0, 0.0, -1.0)), Colour::new(0.75, 0.85, 1.0)));
+
+        let flat = Background::from(top);
+        assert_eq!(flat.colour(&Vec3::y()), flat.colour(&-Vec3::x()));
+    }
+
+    fn environment(rotation: f64) -> Background {
+        // Longitude bands a quarter round each, green then red at the middle,
+        // and a float image so values above 1 survive.
+        let colours = [[0.0, 0.0, 4.0], [0.0, 2.0, 0.0], [3.0, 0.0, 0.0], [1.0, 1.0, 1.0]];
+        let image = image::Rgb32FImage::from_fn(64, 32, |x, _| image::Rgb(colours[x as usize / 16]));
+        Background::Environment { map: Arc::new(Texture::from_linear(&image, Default::default())), rotation }
+    }
+
+    #[test]
+    fn test_environment() {
+        let sky = environment(0.0);
+        let turned = |degrees: f64| {
+            let angle = degrees.to_radians();
+            Vec3::new(angle.sin(), 0.1, angle.cos())
+        };
+        // Straight ahead is the middle of the image, right is further along.
+        assert!(fuzzy_eq_colour(sky.colour(&turned(-45.0)), Colour::new(0.0, 2.0, 0.0)));
+        assert!(fuzzy_eq_colour(sky.colour(&turned(45.0)), Colour::new(3.0, 0.0, 0.0)));
+        assert!(fuzzy_eq_colour(sky.colour(&turned(135.0)), Colour::new(1.0, 1.0, 1.0)));
+        assert!(fuzzy_eq_colour(sky.colour(&turned(-135.0)), Colour::new(0.0, 0.0, 4.0)));
+
+        // No seam behind, where the image's edges meet, just a blend of the two.
+        let left = sky.colour(&turned(180.0 - 1e-9));
+        let right = sky.colour(&turned(-180.0 + 1e-9));
+        assert!(fuzzy_eq_colour(left, right));
+        assert!(fuzzy_eq_colour(left, Colour::new(0.5, 0.5, 2.5)));
+
+        // Turning the environment 90 degrees moves everything a quarter round to the right.
+        assert!(fuzzy_eq_colour(environment(90.0).colour(&turned(45.0)), Colour::new(0.0, 2.0, 0.0)));
+        assert!(fuzzy_eq_colour(environment(-90.0).colour(&turned(-45.0)), Colour::new(3.0, 0.0, 0.0)));
+    }
+}
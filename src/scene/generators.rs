@@ -0,0 +1,104 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use crate::{Camera, Colour, Light, Material, Point3, Vec3};
+use crate::object::{Plane, Sphere};
+use crate::scene::Scene;
+use crate::transform::Transformable;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RandomSceneConfig {
+    pub seed:          u64,
+    // Small spheres are placed on a grid from -grid_extent to grid_extent in x and z.
+    pub grid_extent:   i32,
+    // Chance of each small sphere being glass or metal, the rest are plastic.
+    pub glass_ratio:   f64,
+    pub metal_ratio:   f64,
+    pub ground_colour: Colour,
+    pub dimensions:    (u32, u32),
+}
+
+impl Default for RandomSceneConfig {
+    fn default() -> Self {
+        Self {
+            seed:          0,
+            grid_extent:   11,
+            glass_ratio:   0.05,
+            metal_ratio:   0.15,
+            ground_colour: Colour::new(0.5, 0.5, 0.5),
+            dimensions:    crate::default_dims(),
+        }
+    }
+}
+
+fn sphere(material: Material, centre: Point3, radius: f64) -> Box<Sphere> {
+    let mut sphere = Sphere::new(material);
+    sphere.translate(centre.x, centre.y, centre.z);
+    sphere.scale_uniform(radius);
+    Box::new(sphere)
+}
+
+// The book cover scene: a grid of small random spheres around three big ones,
+// the same for the same seed.
+pub fn random_spheres(cfg: RandomSceneConfig) -> (Scene, Camera) {
+    let mut rng = StdRng::seed_from_u64(cfg.seed);
+    let mut scene = Scene::new(vec![], vec![], Colour::new(0.7, 0.8, 1.0));
+    scene.push(Box::new(Plane::new(Material::matte(cfg.ground_colour))));
+
+    for a in -cfg.grid_extent..cfg.grid_extent {
+        for b in -cfg.grid_extent..cfg.grid_extent {
+            let centre = Point3::new(a as f64 + 0.9 * rng.gen::<f64>(), 0.2, b as f64 + 0.9 * rng.gen::<f64>());
+            let choice = rng.gen::<f64>();
+            let colour = Colour::new_random(&mut rng);
+            // Keep clear of the big metal sphere.
+            if (centre - Point3::new(4.0, 0.2, 0.0)).norm() <= 0.9 {
+                continue;
+            }
+
+            let material = if choice < cfg.glass_ratio {
+                Material::glass()
+            } else if choice < cfg.glass_ratio + cfg.metal_ratio {
+                Material::metal(colour, None)
+            } else {
+                Material::plastic(colour, None)
+            };
+            scene.push(sphere(material, centre, 0.2));
+        }
+    }
+
+    scene.push(sphere(Material::glass(), Point3::new(0.0, 1.0, 0.0), 1.0));
+    scene.push(sphere(Material::matte(Colour::new(0.4, 0.2, 0.1)), Point3::new(-4.0, 1.0, 0.0), 1.0));
+    scene.push(sphere(Material::metal(Colour::new(0.7, 0.6, 0.5), None), Point3::new(4.0, 1.0, 0.0), 1.0));
+    scene.lights.push(Light::new(Point3::new(10.0, 20.0, 10.0), Colour::new(1.0, 1.0, 1.0)));
+
+    let camera = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::origin(),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        cfg.dimensions,
+        0.0,
+        10.0,
+    );
+    (scene, camera)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_spheres_seed() {
+        let cfg = RandomSceneConfig { seed: 3, grid_extent: 4, ..Default::default() };
+        let (a, _) = random_spheres(cfg);
+        let (b, _) = random_spheres(cfg);
+        let (c, _) = random_spheres(RandomSceneConfig { seed: 4, ..cfg });
+
+        assert_eq!(a.objects.len(), b.objects.len());
+        // Ground, up to 8 x 8 small spheres and the 3 big ones.
+        assert!(a.objects.len() > 50 && a.objects.len() <= 68);
+        assert_eq!(a.objects[1].transform(), b.objects[1].transform());
+        let last = a.objects.len() - 4;
+        assert_eq!(a.objects[last].transform(), b.objects[last].transform());
+
+        assert_ne!(a.objects[1].transform(), c.objects[1].transform());
+    }
+}
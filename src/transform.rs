@@ -1,4 +1,5 @@
-use crate::{Matrix4, Axis, Vec3, Rotation, Translation, Scale};
+use nalgebra::Unit;
+use crate::{Matrix4, Axis, Point3, Vec3, Rotation, Translation, Scale, Camera};
 
 pub trait Transformable {
     
@@ -10,6 +11,14 @@ pub trait Transformable {
 
     fn set_inverse(&mut self, inverse: Matrix4);
 
+    // Largest factor the transform stretches lengths by along an axis.
+    fn max_scale(&self) -> f64 {
+        let linear = self.transform().fixed_view::<3, 3>(0, 0);
+        linear.column_iter()
+            .map(|c| c.norm())
+            .fold(0.0, f64::max)
+    }
+
     fn rotate(&mut self, axis: Axis, angle: f64) {
         let angle = angle.to_radians();
         let rotation = match axis {
@@ -23,6 +32,30 @@ pub trait Transformable {
         self.set_inverse(inv * self.inverse());
     }
 
+    // Right handed about any axis, which needn't be unit length.
+    fn rotate_about(&mut self, axis: Vec3, angle: f64) {
+        assert!(axis.norm() > 0.0 && axis.iter().all(|v| v.is_finite()), "rotation axis must have a length");
+        let rotation = Rotation::from_axis_angle(&Unit::new_normalize(axis), angle.to_radians()).to_homogeneous();
+
+        let inv = rotation.try_inverse().expect("Rotation matrix is not invertible.");
+        self.set_transform(self.transform() * rotation);
+        self.set_inverse(inv * self.inverse());
+    }
+
+    // As rotate_about, but turning around pivot instead of the origin.
+    fn rotate_about_point(&mut self, axis: Vec3, angle: f64, pivot: Point3) {
+        self.translate(pivot.x, pivot.y, pivot.z);
+        self.rotate_about(axis, angle);
+        self.translate(-pivot.x, -pivot.y, -pivot.z);
+    }
+
+    // As scale, but towards pivot instead of the origin.
+    fn scale_about_point(&mut self, x: f64, y: f64, z: f64, pivot: Point3) {
+        self.translate(pivot.x, pivot.y, pivot.z);
+        self.scale(x, y, z);
+        self.translate(-pivot.x, -pivot.y, -pivot.z);
+    }
+
     fn translate(&mut self, x: f64, y: f64, z: f64) {
         let translation = Translation::new(x, y, z).to_homogeneous();
         self.set_transform(self.transform() * translation);
@@ -55,4 +88,125 @@ pub trait Transformable {
         self.set_transform(self.transform() * shear);
         self.set_inverse(inv * self.inverse());
     }
-}
\ No newline at end of file
+
+    // Any invertible matrix, such as one read from a model file.
+    fn apply_matrix(&mut self, matrix: Matrix4) {
+        let inv = matrix.try_inverse().expect("Matrix is not invertible.");
+        self.set_transform(self.transform() * matrix);
+        self.set_inverse(inv * self.inverse());
+    }
+
+    // Moves the origin to eye and turns the y axis to point at target, so a
+    // cylinder or cone runs from one to the other. The z axis leans towards up,
+    // or towards z, or x, when up is along the line, such as looking straight up.
+    fn look_at(&mut self, eye: Point3, target: Point3, up: Vec3) {
+        let forward = (target - eye).normalize();
+        assert!(forward.iter().all(|v| v.is_finite()), "look_at needs eye and target to be different");
+        let up = if forward.cross(&up).norm() <= 1e-6 * up.norm() {
+            if forward.z.abs() < 0.9 { Vec3::z() } else { Vec3::x() }
+        } else {
+            up
+        };
+        // The camera's view matrix points -z at target, then -z is turned onto y.
+        let view = Camera::view_matrix(eye, target, up).try_inverse().expect("View matrix is not invertible.");
+        let y_to_back = Rotation::from_axis_angle(&Vec3::x_axis(), -std::f64::consts::FRAC_PI_2).to_homogeneous();
+        self.apply_matrix(view * y_to_back);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Material;
+    use crate::object::{Object, Cylinder};
+    use crate::ray::Ray;
+    use crate::math::*;
+
+    #[test]
+    fn test_rotate_about() {
+        for (axis, principal) in [(Vec3::new(0.0, 1.0, 0.0), Axis::Y), (Vec3::new(3.0, 0.0, 0.0), Axis::X), (Vec3::new(0.0, 0.0, -2.0), Axis::Z)] {
+            let mut about = Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true);
+            let mut rotated = Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true);
+            about.translate(1.0, 2.0, 3.0);
+            rotated.translate(1.0, 2.0, 3.0);
+            about.rotate_about(axis, 90.0);
+            // Turning about -z is turning the other way about z.
+            rotated.rotate(principal, if axis.z < 0.0 { -90.0 } else { 90.0 });
+            assert!(about.transform().relative_eq(rotated.transform(), 1e-12, 1e-12));
+            assert!(about.inverse().relative_eq(rotated.inverse(), 1e-12, 1e-12));
+        }
+
+        // A third of a turn about the diagonal swaps the axes round.
+        let mut cylinder = Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true);
+        cylinder.rotate_about(Vec3::new(1.0, 1.0, 1.0), 120.0);
+        assert!(fuzzy_eq_vec(&cylinder.transform().transform_vector(&Vec3::x()), &Vec3::y()));
+        assert!(fuzzy_eq_vec(&cylinder.transform().transform_vector(&Vec3::y()), &Vec3::z()));
+    }
+
+    #[test]
+    #[should_panic(expected = "rotation axis")]
+    fn test_rotate_about_zero_axis() {
+        Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true).rotate_about(Vec3::zeros(), 90.0);
+    }
+
+    #[test]
+    fn test_about_point() {
+        use crate::object::Sphere;
+        // Placed at (5, 0, 0), as later entries in a transform list are.
+        let centre = Point3::new(5.0, 0.0, 0.0);
+        let mut sphere = Sphere::new(Material::default());
+        sphere.rotate_about_point(Vec3::y(), 90.0, centre);
+        sphere.translate(5.0, 0.0, 0.0);
+        assert!(fuzzy_eq_vec(&sphere.transform().transform_point(&Point3::origin()).coords, &centre.coords));
+        // Turned in place, not swung round the origin.
+        assert!(fuzzy_eq_vec(&sphere.transform().transform_point(&Point3::new(1.0, 0.0, 0.0)).coords, &Vec3::new(5.0, 0.0, -1.0)));
+
+        let mut swung = Sphere::new(Material::default());
+        swung.rotate(Axis::Y, 90.0);
+        swung.translate(5.0, 0.0, 0.0);
+        assert!(fuzzy_eq_vec(&swung.transform().transform_point(&Point3::origin()).coords, &Vec3::new(0.0, 0.0, -5.0)));
+
+        let mut sphere = Sphere::new(Material::default());
+        sphere.scale_about_point(2.0, 2.0, 2.0, Point3::new(1.0, 0.0, 0.0));
+        let bounds = sphere.bounds();
+        assert!(fuzzy_eq_vec(&bounds.min.coords, &Vec3::new(-3.0, -2.0, -2.0)));
+        assert!(fuzzy_eq_vec(&bounds.max.coords, &Vec3::new(1.0, 2.0, 2.0)));
+        assert!(sphere.inverse().relative_eq(&sphere.transform().try_inverse().unwrap(), 1e-12, 1e-12));
+    }
+
+    #[test]
+    fn test_look_at() {
+        let (eye, target) = (Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, -2.0, 3.0));
+        let mut cylinder = Cylinder::new(Material::default(), 0.5, 0.0, 5.0, true);
+        cylinder.look_at(eye, target, Vec3::y());
+        let transform = cylinder.transform();
+        assert!(fuzzy_eq_vec(&transform.transform_point(&Point3::origin()).coords, &eye.coords));
+        assert!(fuzzy_eq_vec(&transform.transform_vector(&Vec3::y()), &Vec3::new(0.6, -0.8, 0.0)));
+        assert!(transform.transform_vector(&Vec3::z()).y > 0.0);
+
+        // Runs from eye to target, 5 long, through its end caps.
+        let ray = Ray::new(Point3::new(-2.0, 6.0, 3.0), Vec3::new(0.6, -0.8, 0.0));
+        let hits = cylinder.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(fuzzy_eq_f64(hits[0].t, 5.0) && fuzzy_eq_f64(hits[1].t, 10.0));
+    }
+
+    #[test]
+    fn test_look_at_along_up() {
+        for (target, up) in [
+            (Point3::new(0.0, 5.0, 0.0), Vec3::y()),
+            (Point3::new(0.0, -5.0, 0.0), Vec3::y()),
+            (Point3::new(0.0, 0.0, 5.0), Vec3::z()),
+            (Point3::new(2.0, 0.0, 0.0), Vec3::zeros()),
+        ] {
+            let mut cylinder = Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true);
+            cylinder.look_at(Point3::origin(), target, up);
+            assert!(cylinder.transform().iter().chain(cylinder.inverse().iter()).all(|v| v.is_finite()));
+            let axis = cylinder.transform().transform_vector(&Vec3::y());
+            assert!(fuzzy_eq_vec(&axis, &(target.coords.normalize())));
+        }
+        // Straight up leaves it as it was.
+        let mut cylinder = Cylinder::new(Material::default(), 1.0, 0.0, 1.0, true);
+        cylinder.look_at(Point3::origin(), Point3::new(0.0, 1.0, 0.0), Vec3::y());
+        assert!(cylinder.transform().relative_eq(&Matrix4::identity(), 1e-12, 1e-12));
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use crate::io::scene_files;
+
+// Files a scene is built from, the scene file and anything it loads. Only the
+// scene file if it doesn't parse, the next save may fix it.
+pub fn scene_dependencies<P: AsRef<Path>>(scene: P, params: &[(String, String)]) -> Vec<PathBuf> {
+    let mut files = vec![scene.as_ref().to_path_buf()];
+    for file in scene_files(&scene, params).unwrap_or_default() {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Polls modification times of a set of files. Editors often write a file in
+// several steps, so a change is only reported once nothing has changed for
+// the debounce period.
+#[derive(Debug)]
+pub struct FileWatcher {
+    files:    Vec<PathBuf>,
+    stamps:   Vec<Option<SystemTime>>,
+    debounce: Duration,
+    // When the last unreported change was seen.
+    pending:  Option<Instant>,
+}
+
+impl FileWatcher {
+    pub fn new(files: Vec<PathBuf>, debounce: Duration) -> Self {
+        let stamps = files.iter().map(|f| modified(f)).collect();
+        Self { files, stamps, debounce, pending: None }
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    // True once the files have changed and then settled.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let stamps: Vec<_> = self.files.iter().map(|f| modified(f)).collect();
+        if stamps != self.stamps {
+            self.stamps = stamps;
+            self.pending = Some(now);
+            return false;
+        }
+        match self.pending {
+            Some(changed) if now.duration_since(changed) >= self.debounce => {
+                self.pending = None;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ray-tracer-{}-{}", std::process::id(), name));
+        fs::write(&path, "objects: []").unwrap();
+        path
+    }
+
+    fn touch(path: &Path, secs: u64) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+    }
+
+    #[test]
+    fn test_dependencies() {
+        assert_eq!(scene_dependencies("scenes/missing.yaml", &[]), vec![PathBuf::from("scenes/missing.yaml")]);
+
+        // Everything the scene loads, relative to the scene's directory.
+        let dir = std::env::temp_dir().join(format!("ray-tracer-{}-deps", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let scene = dir.join("scene.yaml");
+        fs::write(&scene, "
+            background: !Environment { file: sky.hdr }
+            materials:
+                wood: !Matte { colour: white, pattern: { type: !Texture, file: wood.png } }
+            prototypes:
+                bunny: { type: !Mesh { file: bunny.ply } }
+            objects:
+                - type: !Mesh { file: teapot.obj }
+                  material: wood
+                - type: !Heightfield { file: hills.png }
+                - type: !Mesh { file: /models/car.glb }
+                - type: !Csg
+                    op: Union
+                    left: { type: !Sphere }
+                    right: { type: !Mesh { file: teapot.obj } }
+                - type: !Plane
+                  material: !Matte
+                    colour: white
+                    pattern: { type: !Blend, pattern_a: { type: !Texture, file: textures/a.png }, pattern_b: { type: !Debug } }
+                - type: !Instance bunny
+        ").unwrap();
+        let mut files = scene_dependencies(&scene, &[]);
+        files.sort();
+        let mut expected = vec![
+            scene.clone(),
+            dir.join("sky.hdr"),
+            dir.join("wood.png"),
+            dir.join("bunny.ply"),
+            dir.join("teapot.obj"),
+            dir.join("hills.png"),
+            PathBuf::from("/models/car.glb"),
+            dir.join("textures/a.png"),
+        ];
+        expected.sort();
+        assert_eq!(files, expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watcher_debounce() {
+        let path = temp_file("debounce.yaml");
+        touch(&path, 1000);
+        let debounce = Duration::from_millis(100);
+        let mut watcher = FileWatcher::new(vec![path.clone()], debounce);
+        let start = Instant::now();
+
+        assert!(!watcher.poll(start));
+        assert!(!watcher.poll(start + debounce * 5));
+
+        // Two quick writes only report once, after the last settles.
+        touch(&path, 2000);
+        assert!(!watcher.poll(start));
+        touch(&path, 3000);
+        assert!(!watcher.poll(start + debounce / 2));
+        assert!(!watcher.poll(start + debounce));
+        assert!(watcher.poll(start + debounce * 2));
+        assert!(!watcher.poll(start + debounce * 3));
+
+        // Deleting counts as a change too.
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll(start));
+        assert!(watcher.poll(start + debounce));
+    }
+}